@@ -0,0 +1,32 @@
+//! Image hash throughput: SHA256 vs BLAKE3
+//!
+//! Run with `cargo bench --bench image_hash`.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use sha2::{Digest, Sha256};
+
+/// Typical captcha PNG, large screenshot, and full-size photo
+const SIZES: &[usize] = &[8 * 1024, 256 * 1024, 4 * 1024 * 1024];
+
+fn image_hash(c: &mut Criterion) {
+    let mut group = c.benchmark_group("image_hash");
+
+    for &size in SIZES {
+        let data: Vec<u8> = (0..size).map(|i| (i * 31 % 251) as u8).collect();
+        group.throughput(Throughput::Bytes(size as u64));
+
+        group.bench_with_input(BenchmarkId::new("sha256", size), &data, |b, data| {
+            b.iter(|| hex::encode(Sha256::digest(black_box(data))))
+        });
+        group.bench_with_input(BenchmarkId::new("blake3", size), &data, |b, data| {
+            b.iter(|| blake3::hash(black_box(data)).to_hex().to_string())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, image_hash);
+criterion_main!(benches);
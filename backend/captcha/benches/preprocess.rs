@@ -0,0 +1,116 @@
+//! Preprocessing operation latency
+//!
+//! Run with `cargo bench --bench preprocess`. Every step accepted in
+//! `PreprocessOptions::steps` runs alone on a captcha from the regression dataset,
+//! followed by the named variants and the analysis helpers (segmentation,
+//! difficulty estimate, histogram). The vectorized pixel loops are also timed
+//! against their scalar reference on a 300x100 captcha.
+
+// The service is a binary crate, so the preprocessing modules are compiled into the bench
+#[allow(dead_code, unused_imports)]
+#[path = "../src/error.rs"]
+mod error;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/models.rs"]
+mod models;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/validation.rs"]
+mod validation;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/solvers"]
+mod solvers {
+    pub mod preprocessor;
+    pub mod preprocessor_scalar;
+}
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use image::DynamicImage;
+
+use models::{ColorFilterOptions, PreprocessOptions};
+use solvers::preprocessor_scalar;
+use solvers::preprocessor::{
+    DifficultyThresholds, ImagePreprocessor, Segmentation, DEFAULT_AUTO_PREPROCESS_VARIANTS, PREPROCESS_STEPS,
+};
+
+/// Noisy 160x50 captcha with a strike line
+const CAPTCHA: &[u8] = include_bytes!("../assets/regression/images/002.png");
+
+fn captcha() -> DynamicImage {
+    image::load_from_memory(CAPTCHA).expect("regression fixture decodes")
+}
+
+fn steps(c: &mut Criterion) {
+    let image = captcha();
+    let mut group = c.benchmark_group("preprocess_step");
+
+    for &step in PREPROCESS_STEPS {
+        let options = PreprocessOptions {
+            steps: Some(vec![step.to_string()]),
+            color_filter: Some(ColorFilterOptions { target: [40, 40, 40], tolerance: None, space: None }),
+            ..Default::default()
+        };
+        group.bench_with_input(BenchmarkId::from_parameter(step), &options, |b, options| {
+            b.iter(|| ImagePreprocessor::preprocess(black_box(&image), options).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+fn variants(c: &mut Criterion) {
+    let image = captcha();
+    let mut group = c.benchmark_group("preprocess_variant");
+
+    for name in DEFAULT_AUTO_PREPROCESS_VARIANTS.split(',') {
+        let options = ImagePreprocessor::variant(name).unwrap();
+        group.bench_with_input(BenchmarkId::from_parameter(name), &options, |b, options| {
+            b.iter(|| ImagePreprocessor::preprocess(black_box(&image), options).unwrap())
+        });
+    }
+    group.bench_function("full_pipeline", |b| {
+        b.iter(|| ImagePreprocessor::full_pipeline(black_box(&image)).unwrap())
+    });
+
+    group.finish();
+}
+
+fn analysis(c: &mut Criterion) {
+    let image = captcha();
+    let mut group = c.benchmark_group("preprocess_analysis");
+
+    group.bench_function("segment_boxes", |b| {
+        b.iter(|| ImagePreprocessor::segment_boxes(black_box(&image), &Segmentation::default()).unwrap())
+    });
+    group.bench_function("estimate_difficulty", |b| {
+        b.iter(|| ImagePreprocessor::estimate_difficulty(black_box(&image), &DifficultyThresholds::default()).unwrap())
+    });
+    group.bench_function("histogram", |b| b.iter(|| ImagePreprocessor::histogram(black_box(&image))));
+
+    group.finish();
+}
+
+/// Vectorized loops next to the per-pixel versions they replaced
+fn fast_paths(c: &mut Criterion) {
+    let image = captcha().resize_exact(300, 100, image::imageops::FilterType::Triangle);
+    let mut group = c.benchmark_group("preprocess_fast_path_300x100");
+
+    group.bench_function("remove_lines", |b| {
+        b.iter(|| ImagePreprocessor::remove_lines(black_box(&image)).unwrap())
+    });
+    group.bench_function("remove_lines_scalar", |b| {
+        b.iter(|| preprocessor_scalar::remove_lines(black_box(&image)))
+    });
+    group.bench_function("enhance_contrast", |b| {
+        b.iter(|| ImagePreprocessor::enhance_contrast(black_box(&image)).unwrap())
+    });
+    group.bench_function("enhance_contrast_scalar", |b| {
+        b.iter(|| preprocessor_scalar::enhance_contrast(black_box(&image)))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, steps, variants, analysis, fast_paths);
+criterion_main!(benches);
@@ -0,0 +1,48 @@
+//! Build script: embeds the git commit and build time reported by `GET /version`,
+//! rebuilds when the embedded database migrations change, and with the `grpc`
+//! feature generates the gRPC server from `proto/captcha.proto`.
+//!
+//! `GIT_COMMIT` overrides the commit for builds without a git checkout (e.g. Docker).
+
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    let commit = std::env::var("GIT_COMMIT")
+        .ok()
+        .filter(|c| !c.is_empty())
+        .or_else(|| git(&["rev-parse", "--short=12", "HEAD"]))
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    println!("cargo:rustc-env=BUILD_GIT_COMMIT={}", commit);
+    println!("cargo:rustc-env=BUILD_TIMESTAMP={}", timestamp);
+    println!("cargo:rerun-if-env-changed=GIT_COMMIT");
+
+    // Migrations are embedded by `sqlx::migrate!`
+    println!("cargo:rerun-if-changed=../../database/migrations");
+
+    #[cfg(feature = "grpc")]
+    tonic_build::configure()
+        .build_client(false)
+        .compile(&["proto/captcha.proto"], &["proto"])
+        .expect("Failed to compile proto/captcha.proto");
+
+    // Rebuild when the checked-out commit changes
+    if let Some(git_dir) = git(&["rev-parse", "--absolute-git-dir"]) {
+        println!("cargo:rerun-if-changed={}/HEAD", git_dir);
+        println!("cargo:rerun-if-changed={}/logs/HEAD", git_dir);
+    }
+}
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
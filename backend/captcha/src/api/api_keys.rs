@@ -0,0 +1,196 @@
+//! API Key Management Handlers (admin)
+
+use actix_web::{web, HttpResponse, HttpRequest};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::AppState;
+use crate::api::require_admin;
+use crate::auth;
+use crate::error::CaptchaError;
+use crate::models::{ApiKey, Page};
+
+/// Default requests-per-minute limit for new keys
+const DEFAULT_RATE_LIMIT: u32 = 100;
+
+/// Create an API key for a user. The plaintext key is only returned here.
+pub async fn create_api_key(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    body: web::Json<CreateApiKeyRequest>,
+) -> Result<HttpResponse, CaptchaError> {
+    require_admin(&req)?;
+
+    if body.name.trim().is_empty() {
+        return Err(CaptchaError::BadRequest("Key name is required".to_string()));
+    }
+
+    if let Some(unknown) = body.scopes.iter().flatten().find(|s| !auth::ALL_SCOPES.contains(&s.as_str())) {
+        return Err(CaptchaError::BadRequest(format!(
+            "Unknown scope '{}'; valid scopes are: {}", unknown, auth::ALL_SCOPES.join(", ")
+        )));
+    }
+
+    let generated = auth::generate_key();
+    let scopes = body.scopes.as_ref().map(|s| serde_json::json!(s));
+    let expires_at = body.expires_in_days.map(|days| Utc::now() + Duration::days(days as i64));
+
+    let key_id = state.db.create_api_key(
+        body.user_id,
+        body.name.trim(),
+        &generated.prefix,
+        &generated.hash,
+        scopes.as_ref(),
+        body.rate_limit.unwrap_or(DEFAULT_RATE_LIMIT),
+        expires_at,
+    ).await?;
+
+    let key = state.db.get_api_key(key_id).await?
+        .ok_or(CaptchaError::NotFound(format!("API key {} not found", key_id)))?;
+
+    tracing::info!("API key {} created for user {}", key_id, body.user_id);
+
+    Ok(HttpResponse::Created().json(ApiKeyWithSecret {
+        key: generated.plaintext,
+        api_key: ApiKeyResponse::from(key),
+    }))
+}
+
+/// List a user's API keys (prefixes only) with their usage
+pub async fn list_api_keys(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    query: web::Query<ListApiKeysQuery>,
+) -> Result<HttpResponse, CaptchaError> {
+    require_admin(&req)?;
+
+    let limit = query.limit.unwrap_or(50);
+    let offset = query.offset.unwrap_or(0);
+
+    let keys = state.db.list_api_keys(query.user_id, limit, offset).await?;
+    let total = state.db.count_api_keys(query.user_id).await?;
+    let items: Vec<ApiKeyResponse> = keys.into_iter()
+        .map(|key| {
+            // Include usage not yet flushed to the database
+            let pending = state.usage.pending(key.id);
+            let mut response = ApiKeyResponse::from(key);
+            if let Some(usage) = pending {
+                response.total_requests += usage.requests;
+                response.last_used_at = Some(usage.last_used_at.to_rfc3339());
+            }
+            response
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(Page { items, total, limit, offset }))
+}
+
+/// Replace an API key's secret. The old key stops working immediately.
+pub async fn rotate_api_key(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<u64>,
+) -> Result<HttpResponse, CaptchaError> {
+    require_admin(&req)?;
+    let key_id = path.into_inner();
+
+    let existing = state.db.get_api_key(key_id).await?
+        .ok_or(CaptchaError::NotFound(format!("API key {} not found", key_id)))?;
+
+    if !existing.is_active {
+        return Err(CaptchaError::BadRequest("Cannot rotate a revoked key".to_string()));
+    }
+
+    let generated = auth::generate_key();
+    state.db.rotate_api_key(key_id, &generated.prefix, &generated.hash).await?;
+
+    let key = state.db.get_api_key(key_id).await?
+        .ok_or(CaptchaError::NotFound(format!("API key {} not found", key_id)))?;
+
+    tracing::info!("API key {} rotated", key_id);
+
+    Ok(HttpResponse::Ok().json(ApiKeyWithSecret {
+        key: generated.plaintext,
+        api_key: ApiKeyResponse::from(key),
+    }))
+}
+
+/// Revoke an API key
+pub async fn revoke_api_key(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<u64>,
+) -> Result<HttpResponse, CaptchaError> {
+    require_admin(&req)?;
+    let key_id = path.into_inner();
+
+    state.db.get_api_key(key_id).await?
+        .ok_or(CaptchaError::NotFound(format!("API key {} not found", key_id)))?;
+
+    state.db.revoke_api_key(key_id).await?;
+
+    tracing::info!("API key {} revoked", key_id);
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "API key revoked"
+    })))
+}
+
+// Request/Response types
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiKeyRequest {
+    pub user_id: u64,
+    pub name: String,
+    pub scopes: Option<Vec<String>>,
+    pub rate_limit: Option<u32>,
+    pub expires_in_days: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListApiKeysQuery {
+    pub user_id: u64,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiKeyResponse {
+    pub id: u64,
+    pub user_id: u64,
+    pub name: String,
+    pub key_prefix: String,
+    pub scopes: Option<serde_json::Value>,
+    pub rate_limit: u32,
+    pub total_requests: u64,
+    pub last_used_at: Option<String>,
+    pub is_active: bool,
+    pub expires_at: Option<String>,
+    pub created_at: String,
+}
+
+/// Returned on creation and rotation only
+#[derive(Debug, Serialize)]
+pub struct ApiKeyWithSecret {
+    pub key: String,
+    #[serde(flatten)]
+    pub api_key: ApiKeyResponse,
+}
+
+impl From<ApiKey> for ApiKeyResponse {
+    fn from(key: ApiKey) -> Self {
+        Self {
+            id: key.id,
+            user_id: key.user_id,
+            name: key.name,
+            key_prefix: key.key_prefix,
+            scopes: key.scopes,
+            rate_limit: key.rate_limit,
+            total_requests: key.total_requests,
+            last_used_at: key.last_used_at.map(|t| t.to_rfc3339()),
+            is_active: key.is_active,
+            expires_at: key.expires_at.map(|t| t.to_rfc3339()),
+            created_at: key.created_at.to_rfc3339(),
+        }
+    }
+}
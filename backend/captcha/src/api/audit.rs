@@ -0,0 +1,136 @@
+//! Audit Log
+//!
+//! Records who changed what, and when, for mutating admin actions (models,
+//! training jobs), with before/after snapshots, and exposes the trail to admins.
+
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use serde::Serialize;
+
+use crate::AppState;
+use crate::api::require_admin;
+use crate::error::CaptchaError;
+use crate::models::{ApiKey, AuditLogQuery, Page};
+
+// Audited actions
+pub const MODEL_CREATE: &str = "model.create";
+pub const MODEL_UPDATE: &str = "model.update";
+pub const MODEL_DELETE: &str = "model.delete";
+pub const MODEL_SET_DEFAULT: &str = "model.set_default";
+pub const MODEL_RECOMPUTE_ACCURACY: &str = "model.recompute_accuracy";
+pub const TRAINING_CANCEL: &str = "training.cancel";
+
+// Audited resource types
+pub const RESOURCE_MODEL: &str = "model";
+pub const RESOURCE_TRAINING_JOB: &str = "training_job";
+
+/// Who performed a request
+#[derive(Debug, Default, PartialEq)]
+pub struct Actor {
+    pub user_id: Option<u64>,
+    pub api_key_id: Option<u64>,
+    pub request_ip: Option<String>,
+}
+
+impl Actor {
+    /// The API key owner when authenticated by key, otherwise the gateway-forwarded user
+    pub fn from_request(req: &HttpRequest) -> Self {
+        let (user_id, api_key_id) = match req.extensions().get::<ApiKey>() {
+            Some(key) => (Some(key.user_id), Some(key.id)),
+            None => {
+                let user_id = req.headers()
+                    .get("X-User-ID")
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|s| s.parse::<u64>().ok());
+                (user_id, None)
+            }
+        };
+
+        let request_ip = req.headers()
+            .get("X-Forwarded-For")
+            .and_then(|v| v.to_str().ok())
+            .map(|s| s.to_string())
+            .or_else(|| req.peer_addr().map(|a| a.ip().to_string()));
+
+        Self { user_id, api_key_id, request_ip }
+    }
+}
+
+/// Serialize a resource for the before/after columns
+pub fn snapshot<T: Serialize>(value: &T) -> Option<serde_json::Value> {
+    serde_json::to_value(value).ok()
+}
+
+/// Record a mutation. Failures are logged, not returned: the change has already been applied.
+pub async fn record(
+    state: &web::Data<AppState>,
+    req: &HttpRequest,
+    action: &str,
+    resource_type: &str,
+    resource_id: Option<u64>,
+    before: Option<serde_json::Value>,
+    after: Option<serde_json::Value>,
+) {
+    let actor = Actor::from_request(req);
+
+    let result = state.db.create_audit_log(
+        actor.user_id,
+        actor.api_key_id,
+        action,
+        resource_type,
+        resource_id,
+        before.as_ref(),
+        after.as_ref(),
+        actor.request_ip.as_deref(),
+    ).await;
+
+    if let Err(e) = result {
+        tracing::error!(
+            "Failed to write audit log for {} on {} {:?} by user {:?}: {}",
+            action, resource_type, resource_id, actor.user_id, e
+        );
+    }
+}
+
+/// Query the audit trail (admin)
+pub async fn list_audit_logs(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    query: web::Query<AuditLogQuery>,
+) -> Result<HttpResponse, CaptchaError> {
+    require_admin(&req)?;
+
+    let limit = query.limit.unwrap_or(50);
+    let offset = query.offset.unwrap_or(0);
+    let (items, total) = state.db.list_audit_logs(&query, limit, offset).await?;
+
+    Ok(HttpResponse::Ok().json(Page { items, total, limit, offset }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn test_actor_from_forwarded_user() {
+        let req = TestRequest::default()
+            .insert_header(("X-User-ID", "42"))
+            .peer_addr("10.0.0.1:1234".parse().unwrap())
+            .to_http_request();
+
+        assert_eq!(Actor::from_request(&req), Actor {
+            user_id: Some(42),
+            api_key_id: None,
+            request_ip: Some("10.0.0.1".to_string()),
+        });
+    }
+
+    #[test]
+    fn test_actor_without_identity() {
+        let req = TestRequest::default().to_http_request();
+        let actor = Actor::from_request(&req);
+
+        assert_eq!(actor.user_id, None);
+        assert_eq!(actor.api_key_id, None);
+    }
+}
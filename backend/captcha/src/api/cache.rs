@@ -0,0 +1,62 @@
+//! Solve Cache Handlers
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::Serialize;
+
+use crate::AppState;
+use crate::api::require_admin;
+use crate::error::CaptchaError;
+
+/// Hit rate and contents of the solve memo (admin only)
+pub async fn cache_stats(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+) -> Result<HttpResponse, CaptchaError> {
+    require_admin(&req)?;
+
+    let memo = &state.solve_memo;
+    let stats = memo.stats();
+    let lookups = stats.hits + stats.misses;
+
+    Ok(HttpResponse::Ok().json(CacheStatsResponse {
+        enabled: memo.enabled(),
+        ttl_seconds: memo.ttl().as_secs(),
+        hits: stats.hits,
+        misses: stats.misses,
+        hit_rate: (lookups > 0).then(|| stats.hits as f64 / lookups as f64),
+        size: stats.size,
+        evictions: stats.evictions,
+    }))
+}
+
+/// Drop every memoized solve result (admin only)
+pub async fn clear_cache(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+) -> Result<HttpResponse, CaptchaError> {
+    require_admin(&req)?;
+
+    let cleared = state.solve_memo.clear();
+    tracing::info!("Cleared {} memoized solve results", cleared);
+
+    Ok(HttpResponse::Ok().json(ClearCacheResponse { cleared }))
+}
+
+// Response types
+
+#[derive(Debug, Serialize)]
+pub struct CacheStatsResponse {
+    pub enabled: bool,
+    pub ttl_seconds: u64,
+    pub hits: u64,
+    pub misses: u64,
+    /// Share of lookups answered from the cache, `null` before the first lookup
+    pub hit_rate: Option<f64>,
+    pub size: usize,
+    pub evictions: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClearCacheResponse {
+    pub cleared: usize,
+}
@@ -1,90 +1,297 @@
 //! Captcha Solving Handlers
 
-use actix_web::{web, HttpResponse, HttpRequest};
+use actix_web::http::header::{self, Header};
+use actix_web::error::JsonPayloadError;
+use actix_web::{web, HttpResponse, HttpResponseBuilder, HttpRequest};
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
 use image::io::Reader as ImageReader;
-use sha2::{Sha256, Digest};
-use std::io::Cursor;
+use std::io::{Cursor, Read};
 use std::time::Instant;
+use tracing::Instrument;
 
 use crate::AppState;
 use crate::error::{CaptchaError, CaptchaResult};
+use crate::idempotency::{self, Begin};
+use crate::solve_log::SolveLogEntry;
+use crate::solvers::SolveHints;
+use crate::solvers::preprocessor::{image_format_name, ImagePreprocessor, InputOptions};
+use crate::validation::Validator;
 use crate::models::{
     SolveRequest, SolveResponse, BatchSolveRequest, 
-    BatchSolveResponse, BatchResult, PreprocessOptions
+    BatchSolveResponse, BatchResult, PreprocessOptions,
+    ArchiveSolveQuery, ArchiveSolveResponse, ArchiveEntryResult, ProcessedImageQuery,
+    CompareRequest, CompareResponse, CompareResult, CropRegion, SOLVER_NAMES
 };
 
-/// Solve a single captcha
+/// Solve a single captcha. With an `Idempotency-Key` header, a repeated request
+/// returns the stored response of the first one instead of solving again.
+/// Clients preferring `text/plain` receive only the predicted text (see [`solve_reply`]).
 pub async fn solve(
     state: web::Data<AppState>,
     req: HttpRequest,
+    query: web::Query<ProcessedImageQuery>,
     body: web::Json<SolveRequest>,
 ) -> Result<HttpResponse, CaptchaError> {
+    let bit_depth = processed_bit_depth(&query)?;
+    let mut body = body.into_inner();
+    apply_profile(&state, &mut body)?;
+    body.no_cache |= requests_no_cache(&req);
+
+    let Some(key) = idempotency::idempotency_key(&req)? else {
+        let response = solve_request(&state, &Caller::from_request(&req), &body, bit_depth).await?;
+        return solve_reply(&state, &req, HttpResponse::Ok(), serde_json::to_value(&response).unwrap_or_default());
+    };
+
+    let scope = idempotency::caller_scope(&req);
+    match state.idempotency.begin(&scope, &key, &idempotency::fingerprint(&body))? {
+        Begin::Replay(response) => {
+            let mut builder = HttpResponse::Ok();
+            builder.insert_header((idempotency::IDEMPOTENT_REPLAYED_HEADER, "true"));
+            solve_reply(&state, &req, builder, response)
+        }
+        Begin::New(claim) => {
+            let response = serde_json::to_value(&solve_request(&state, &Caller::from_request(&req), &body, bit_depth).await?).unwrap_or_default();
+            claim.complete(response.clone());
+            solve_reply(&state, &req, HttpResponse::Ok(), response)
+        }
+    }
+}
+
+/// Send a solve response as JSON, or as just the predicted text when the client's
+/// `Accept` header prefers `text/plain`. A plain-text client cannot tell a guess from
+/// an answer, so an empty prediction or one below `processing.success_confidence_floor`
+/// is sent as a `low_confidence` JSON error instead.
+fn solve_reply(
+    state: &AppState,
+    req: &HttpRequest,
+    mut builder: HttpResponseBuilder,
+    response: serde_json::Value,
+) -> Result<HttpResponse, CaptchaError> {
+    if !prefers_plain_text(req) {
+        return Ok(builder.json(response));
+    }
+
+    let text = response["text"].as_str().unwrap_or_default();
+    let confidence = response["confidence"].as_f64().unwrap_or_default();
+    if text.is_empty() || confidence < state.config.processing.success_confidence_floor {
+        return Err(CaptchaError::LowConfidence(confidence as f32));
+    }
+
+    Ok(builder.insert_header(header::ContentType::plaintext()).body(text.to_string()))
+}
+
+/// Whether the most preferred media type of the `Accept` header is `text/plain`.
+/// A missing or unparsable header, or `*/*`, keeps the JSON default.
+fn prefers_plain_text(req: &HttpRequest) -> bool {
+    header::Accept::parse(req)
+        .ok()
+        .and_then(|accept| accept.ranked().into_iter().next())
+        .is_some_and(|mime| mime.essence_str() == "text/plain")
+}
+
+/// Whether the request's `Cache-Control` header asks for a fresh answer (`no-cache`)
+fn requests_no_cache(req: &HttpRequest) -> bool {
+    header::CacheControl::parse(req).is_ok_and(|cache_control| cache_control.0.contains(&header::CacheDirective::NoCache))
+}
+
+/// JSON body settings of the solve endpoints: a body sent as anything but `application/json`
+/// is a `bad_request` naming the expected type, instead of actix's bare 400
+pub fn solve_json_config() -> web::JsonConfig {
+    web::JsonConfig::default().error_handler(|err, req| match err {
+        JsonPayloadError::ContentType => CaptchaError::BadRequest(content_type_error(req)).into(),
+        err => err.into(),
+    })
+}
+
+/// Why a solve body with the wrong `Content-Type` was rejected. Clients uploading the
+/// image itself (as a form or raw bytes) are told how to send it instead.
+fn content_type_error(req: &HttpRequest) -> String {
+    let Ok(content_type) = header::ContentType::parse(req) else {
+        return "expected application/json, but the request has no valid Content-Type".to_string();
+    };
+
+    let mime = content_type.0;
+    let upload = matches!(mime.type_().as_str(), "multipart" | "image")
+        || mime.essence_str() == "application/x-www-form-urlencoded";
+    if upload {
+        format!(
+            "expected application/json, got {}: send the image base64-encoded in the `image_base64` \
+             field of a JSON body, or zip images and POST them to /captcha/solve/archive",
+            mime.essence_str()
+        )
+    } else {
+        format!("expected application/json, got {}", mime.essence_str())
+    }
+}
+
+/// Fill unset request fields from the provider profile the request selects, if any
+pub fn apply_profile(state: &AppState, request: &mut SolveRequest) -> CaptchaResult<()> {
+    let Some(name) = &request.profile else {
+        return Ok(());
+    };
+
+    let profile = state.config.profiles.get(name).ok_or_else(|| {
+        let mut available: Vec<&str> = state.config.profiles.keys().map(String::as_str).collect();
+        available.sort_unstable();
+        CaptchaError::BadRequest(format!(
+            "Unknown profile '{}' (available: {})", name, available.join(", ")
+        ))
+    })?;
+
+    profile.apply(request);
+    Ok(())
+}
+
+/// Who a solve is for: scopes memoized results and is recorded in the solve log
+pub struct Caller {
+    /// See [`idempotency::caller_scope`]
+    pub scope: String,
+    pub user_id: Option<u64>,
+    pub request_ip: Option<String>,
+}
+
+impl Caller {
+    /// Caller of a REST request, from the headers forwarded by the gateway
+    fn from_request(req: &HttpRequest) -> Self {
+        let user_id = req.headers()
+            .get("X-User-ID")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|s| s.parse::<u64>().ok());
+
+        let request_ip = req.headers()
+            .get("X-Forwarded-For")
+            .and_then(|v| v.to_str().ok())
+            .map(|s| s.to_string())
+            .or_else(|| req.peer_addr().map(|a| a.ip().to_string()));
+
+        Self { scope: idempotency::caller_scope(req), user_id, request_ip }
+    }
+}
+
+/// Solve and log a single captcha request
+async fn solve_request(
+    state: &AppState,
+    caller: &Caller,
+    body: &SolveRequest,
+    bit_depth: u8,
+) -> CaptchaResult<SolveResponse> {
     let start = Instant::now();
+    validate_request(body, state.solver_manager.max_output_length())?;
 
     // Decode base64 image
     let image_data = decode_base64_image(&body.image_base64)?;
-    
-    // Calculate image hash for logging
-    let image_hash = calculate_hash(&image_data);
 
-    // Load image
-    let image = load_image(&image_data)?;
+    solve_validated_request(state, caller, body, &image_data, bit_depth, start).await
+}
+
+/// Solve and log a single captcha request whose image arrived as raw bytes instead of
+/// `image_base64` (gRPC). The profile must already be applied.
+pub async fn solve_request_bytes(
+    state: &AppState,
+    caller: &Caller,
+    body: &SolveRequest,
+    image_data: &[u8],
+) -> CaptchaResult<SolveResponse> {
+    let start = Instant::now();
+    validate_request(body, state.solver_manager.max_output_length())?;
+    if image_data.is_empty() {
+        return Err(CaptchaError::InvalidImage("empty image".to_string()));
+    }
+
+    solve_validated_request(state, caller, body, image_data, 8, start).await
+}
+
+/// Solve and log a validated request for `image_data`, encoding its processed image with
+/// `bit_depth` bits per pixel; `start` marks when the request began
+async fn solve_validated_request(
+    state: &AppState,
+    caller: &Caller,
+    body: &SolveRequest,
+    image_data: &[u8],
+    bit_depth: u8,
+    start: Instant,
+) -> CaptchaResult<SolveResponse> {
+    // Load image, keeping only the requested region
+    let image = load_image_region(image_data, &state.config.processing.input_options(), body.crop.as_ref())?;
+
+    // Calculate image hash for logging: of the upload, or of the pixels solved when cropped
+    let hash_algorithm = state.config.processing.image_hash_algorithm;
+    let image_hash = match &body.crop {
+        Some(_) => hash_algorithm.hash(image.as_bytes()),
+        None => hash_algorithm.hash(image_data),
+    };
 
     // Get preprocessing options
     let preprocess_opts = body.preprocess.clone();
 
-    // Solve captcha
-    let result = state.solver_manager.solve(
-        &image,
-        body.model.as_deref(),
-        preprocess_opts.as_ref(),
-    ).await?;
+    // Solve captcha, reusing the result of a near-identical resubmit unless asked not to.
+    // Only a fresh solve is recorded as a failure; a memoized one already was.
+    let hints = SolveHints::from_request(body);
+    let solve = async {
+        let result = state.solver_manager.solve(&image, requested_solver(body), preprocess_opts.as_ref(), &hints).await?;
+        if let Some(failures) = &state.failures {
+            failures.record(&image, &result.text, result.confidence, &result.solver_name);
+        }
+        Ok(result)
+    };
+    let result = state.solve_memo.get_or_solve(&caller.scope, &image, body, solve).await?;
 
     let processing_time = start.elapsed().as_millis() as u64;
 
-    // Get user info from headers (forwarded by gateway)
-    let user_id = req.headers()
-        .get("X-User-ID")
-        .and_then(|v| v.to_str().ok())
-        .and_then(|s| s.parse::<u64>().ok());
-
-    let request_ip = req.headers()
-        .get("X-Forwarded-For")
-        .and_then(|v| v.to_str().ok())
-        .map(|s| s.to_string())
-        .or_else(|| req.peer_addr().map(|a| a.ip().to_string()));
-
-    // Log the request
-    let model_id = state.db.get_model_by_name(&result.solver_name).await?
-        .map(|m| m.id);
-
-    state.db.create_log(
-        user_id,
-        model_id,
-        &image_hash,
-        Some(&result.text),
-        Some(result.confidence as f64),
-        processing_time as u32,
-        request_ip.as_deref(),
-    ).await?;
+    // Keep what was solved for replaying: the upload, or the pixels solved when cropped
+    let stored_image = match (state.config.processing.store_log_images, &body.crop) {
+        (false, _) => None,
+        (true, Some(_)) => Some(encode_png_base64(&image, 8)?),
+        (true, None) => Some(BASE64.encode(image_data)),
+    };
+
+    // Log the request in the background; a database outage must not fail the solve
+    state.solve_log.push(SolveLogEntry {
+        user_id: caller.user_id,
+        solver_name: result.solver_name.clone(),
+        image_hash,
+        image_hash_algorithm: hash_algorithm.as_str(),
+        predicted_text: result.text.clone(),
+        confidence: result.confidence as f64,
+        processing_time_ms: processing_time as u32,
+        request_ip: caller.request_ip.clone(),
+        image_base64: stored_image,
+    });
 
-    Ok(HttpResponse::Ok().json(SolveResponse {
+    let probability = match body.include_probability {
+        true => Some(state.solver_manager.probability(&result.solver_name, result.confidence).await),
+        false => None,
+    };
+
+    Ok(SolveResponse {
         text: result.text,
         confidence: result.confidence,
         model: result.solver_name,
         processing_time_ms: processing_time,
-    }))
+        preprocess_ms: result.preprocess_ms,
+        inference_ms: result.inference_ms,
+        length_mismatch: result.length_mismatch,
+        candidates: result.candidates,
+        preprocess_variant: result.preprocess_variant,
+        processed_image_base64: result.processed_image.as_ref().map(|image| encode_png_base64(image, bit_depth)).transpose()?,
+        char_positions: result.char_positions,
+        probability,
+        image_quality: ImagePreprocessor::quality(&image).ok(),
+    })
 }
 
 /// Solve multiple captchas in batch
 pub async fn solve_batch(
     state: web::Data<AppState>,
     req: HttpRequest,
+    query: web::Query<ProcessedImageQuery>,
     body: web::Json<BatchSolveRequest>,
 ) -> Result<HttpResponse, CaptchaError> {
     let start = Instant::now();
+    let bit_depth = processed_bit_depth(&query)?;
+    let mut body = body.into_inner();
     let batch_size = state.config.processing.batch_size;
+    let max_total_pixels = state.config.processing.batch_max_total_pixels;
 
     // Limit batch size
     if body.images.len() > batch_size {
@@ -93,10 +300,62 @@ pub async fn solve_batch(
         ));
     }
 
+    for solve_req in &mut body.images {
+        apply_profile(&state, solve_req)?;
+    }
+
+    let input = state.config.processing.input_options();
     let mut results: Vec<BatchResult> = Vec::with_capacity(body.images.len());
+    let mut total_pixels: u64 = 0;
 
     for (index, solve_req) in body.images.iter().enumerate() {
-        let result = process_single_image(&state, solve_req).await;
+        let item_start = Instant::now();
+        let span = tracing::info_span!("batch_image", index, time_ms = tracing::field::Empty);
+        let data = span.in_scope(|| {
+            validate_request(solve_req, state.solver_manager.max_output_length())
+                .and_then(|_| decode_base64_image(&solve_req.image_base64))
+        });
+
+        // Charge each upload's header dimensions against the batch pixel budget before
+        // decoding it, so an image that would blow the budget is never decoded
+        if let Some(pixels) = data.as_deref().ok().and_then(image_pixels) {
+            total_pixels += pixels;
+            if total_pixels > max_total_pixels {
+                return Err(CaptchaError::BadRequest(format!(
+                    "Batch exceeds total pixel budget of {} (reached at image {})",
+                    max_total_pixels, index
+                )));
+            }
+        }
+
+        let image = span.in_scope(|| {
+            data.and_then(|data| load_image_region(&data, &input, solve_req.crop.as_ref()))
+        });
+        let decode_ms = item_start.elapsed().as_millis() as u64;
+
+        let result = match image {
+            Ok(image) => solve_decoded_image(
+                &state,
+                &image,
+                requested_solver(solve_req),
+                solve_req.preprocess.as_ref(),
+                &SolveHints::from_request(solve_req),
+                bit_depth,
+                item_start,
+            ).instrument(span.clone()).await,
+            Err(e) => Err(e),
+        };
+        let result = match result {
+            Ok(mut response) if solve_req.include_probability => {
+                response.probability = Some(state.solver_manager.probability(&response.model, response.confidence).await);
+                Ok(response)
+            }
+            result => result,
+        };
+
+        let time_ms = item_start.elapsed().as_millis() as u64;
+        span.record("time_ms", time_ms);
+        span.in_scope(|| tracing::debug!(decode_ms, time_ms, success = result.is_ok(), "Batch image done"));
 
         match result {
             Ok(response) => {
@@ -105,6 +364,9 @@ pub async fn solve_batch(
                     success: true,
                     result: Some(response),
                     error: None,
+                    error_code: None,
+                    decode_ms,
+                    time_ms,
                 });
             }
             Err(e) => {
@@ -113,6 +375,9 @@ pub async fn solve_batch(
                     success: false,
                     result: None,
                     error: Some(e.to_string()),
+                    error_code: Some(e.error_code().to_string()),
+                    decode_ms,
+                    time_ms,
                 });
             }
         }
@@ -126,36 +391,360 @@ pub async fn solve_batch(
     }))
 }
 
-/// Process a single image in batch
-async fn process_single_image(
+/// Solve one image with each requested solver and return the raw answers side by side.
+/// Unlike the ensemble nothing is blended, and a failing solver does not fall back to another.
+pub async fn compare(
+    state: web::Data<AppState>,
+    body: web::Json<CompareRequest>,
+) -> Result<HttpResponse, CaptchaError> {
+    let start = Instant::now();
+
+    if body.models.is_empty() {
+        return Err(CaptchaError::BadRequest("models must name at least one solver".to_string()));
+    }
+    if let Some((i, model)) = body.models.iter().enumerate().find(|(i, model)| body.models[..*i].contains(*model)) {
+        return Err(CaptchaError::BadRequest(format!("Duplicate model '{}' at index {}", model, i)));
+    }
+
+    // Every solver is run as a solve request with these settings would be
+    let template = compare_template(&body);
+    validate_request(&template, state.solver_manager.max_output_length())?;
+    let hints = SolveHints::from_request(&template);
+
+    let image = load_image(&decode_base64_image(&body.image_base64)?, &state.config.processing.input_options())?;
+
+    let mut results = Vec::with_capacity(body.models.len());
+    for model in &body.models {
+        let outcome = solve_decoded_image(
+            &state,
+            &image,
+            Some(model),
+            body.preprocess.as_ref(),
+            &hints,
+            8,
+            Instant::now(),
+        ).await;
+
+        results.push(match outcome {
+            Ok(response) => CompareResult {
+                model: model.clone(),
+                success: true,
+                result: Some(response),
+                error: None,
+                error_code: None,
+            },
+            Err(e) => CompareResult {
+                model: model.clone(),
+                success: false,
+                result: None,
+                error: Some(e.to_string()),
+                error_code: Some(e.error_code().to_string()),
+            },
+        });
+    }
+
+    Ok(HttpResponse::Ok().json(CompareResponse {
+        agree: answers_agree(&results),
+        results,
+        total_time_ms: start.elapsed().as_millis() as u64,
+    }))
+}
+
+/// The solve request each solver of a comparison answers, without its image and model.
+/// Fallback is off: a failing solver must not answer with another one's text.
+fn compare_template(body: &CompareRequest) -> SolveRequest {
+    SolveRequest {
+        image_base64: String::new(),
+        model: None,
+        model_type: None,
+        preprocess: body.preprocess.clone(),
+        normalize: body.normalize,
+        expected_length: body.expected_length,
+        top_k: body.top_k,
+        strategy: body.strategy,
+        auto_preprocess: body.auto_preprocess.clone(),
+        language: body.language.clone(),
+        case: body.case,
+        whitespace: body.whitespace,
+        charset: body.charset.clone(),
+        profile: None,
+        include_processed_image: body.include_processed_image,
+        include_char_positions: body.include_char_positions,
+        include_probability: false,
+        fallback: Some(false),
+        crop: None,
+        raw_output: body.raw_output,
+        no_cache: false,
+    }
+}
+
+/// Whether at least two solvers answered and all gave the same text
+fn answers_agree(results: &[CompareResult]) -> bool {
+    let texts: Vec<&str> = results
+        .iter()
+        .filter_map(|r| r.result.as_ref())
+        .map(|response| response.text.as_str())
+        .collect();
+    texts.len() >= 2 && texts.windows(2).all(|pair| pair[0] == pair[1])
+}
+
+/// Solve every image in an uploaded zip archive
+pub async fn solve_archive(
+    state: web::Data<AppState>,
+    query: web::Query<ArchiveSolveQuery>,
+    body: web::Bytes,
+) -> Result<HttpResponse, CaptchaError> {
+    let start = Instant::now();
+    let max_entries = state.config.processing.archive_max_entries;
+    let max_entry_size = (state.config.processing.archive_max_entry_size_mb * 1024 * 1024) as u64;
+
+    let mut archive = zip::ZipArchive::new(Cursor::new(body))
+        .map_err(|e| CaptchaError::BadRequest(format!("Invalid zip archive: {}", e)))?;
+
+    if archive.len() > max_entries {
+        return Err(CaptchaError::BadRequest(
+            format!("Archive exceeds limit of {} entries", max_entries)
+        ));
+    }
+
+    let hints = SolveHints {
+        expected_length: query.expected_length,
+        normalize: query.normalize,
+        language: query.language.clone(),
+        case: query.case,
+        whitespace: query.whitespace,
+        ..Default::default()
+    };
+    hints.validate(state.solver_manager.max_output_length())?;
+
+    let mut results: Vec<ArchiveEntryResult> = Vec::new();
+    let mut skipped: Vec<String> = Vec::new();
+
+    for i in 0..archive.len() {
+        // Read the entry fully so the archive borrow ends before solving
+        let (filename, data) = {
+            let entry = match archive.by_index(i) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    results.push(ArchiveEntryResult {
+                        filename: format!("#{}", i),
+                        success: false,
+                        result: None,
+                        error: Some(format!("Cannot read entry: {}", e)),
+                        error_code: Some("invalid_image".to_string()),
+                    });
+                    continue;
+                }
+            };
+
+            let filename = entry.name().to_string();
+            if entry.is_dir() {
+                skipped.push(filename);
+                continue;
+            }
+            if entry.size() > max_entry_size {
+                (filename, Err(CaptchaError::ImageTooLarge))
+            } else {
+                // Guard against entries whose header under-reports their size
+                let mut data = Vec::with_capacity(entry.size() as usize);
+                let read = entry.take(max_entry_size + 1).read_to_end(&mut data);
+                match read {
+                    Ok(n) if n as u64 > max_entry_size => (filename, Err(CaptchaError::ImageTooLarge)),
+                    Ok(_) => (filename, Ok(data)),
+                    Err(e) => (filename, Err(CaptchaError::InvalidImage(format!("Cannot read entry: {}", e)))),
+                }
+            }
+        };
+
+        let outcome = match data {
+            Ok(data) => {
+                if image::guess_format(&data).is_err() {
+                    skipped.push(filename);
+                    continue;
+                }
+                solve_image_data(&state, &data, query.model.as_deref(), None, &hints).await
+            }
+            Err(e) => Err(e),
+        };
+
+        results.push(match outcome {
+            Ok(response) => ArchiveEntryResult {
+                filename,
+                success: true,
+                result: Some(response),
+                error: None,
+                error_code: None,
+            },
+            Err(e) => ArchiveEntryResult {
+                filename,
+                success: false,
+                result: None,
+                error: Some(e.to_string()),
+                error_code: Some(e.error_code().to_string()),
+            },
+        });
+    }
+
+    let total_time = start.elapsed().as_millis() as u64;
+
+    Ok(HttpResponse::Ok().json(ArchiveSolveResponse {
+        results,
+        skipped,
+        total_time_ms: total_time,
+    }))
+}
+
+/// Load and solve raw image bytes
+async fn solve_image_data(
     state: &web::Data<AppState>,
-    request: &SolveRequest,
+    image_data: &[u8],
+    model: Option<&str>,
+    preprocess: Option<&PreprocessOptions>,
+    hints: &SolveHints,
 ) -> CaptchaResult<SolveResponse> {
     let start = Instant::now();
+    let image = load_image(image_data, &state.config.processing.input_options())?;
 
-    // Decode and load image
-    let image_data = decode_base64_image(&request.image_base64)?;
-    let image = load_image(&image_data)?;
+    solve_decoded_image(state, &image, model, preprocess, hints, 8, start).await
+}
 
+/// Solve an already decoded image, encoding its processed image with `bit_depth` bits
+/// per pixel; `start` marks when the request for it began. `probability` is left for
+/// callers honoring `include_probability` to fill in.
+async fn solve_decoded_image(
+    state: &web::Data<AppState>,
+    image: &image::DynamicImage,
+    model: Option<&str>,
+    preprocess: Option<&PreprocessOptions>,
+    hints: &SolveHints,
+    bit_depth: u8,
+    start: Instant,
+) -> CaptchaResult<SolveResponse> {
     // Solve
     let result = state.solver_manager.solve(
-        &image,
-        request.model.as_deref(),
-        request.preprocess.as_ref(),
+        image,
+        model,
+        preprocess,
+        hints,
     ).await?;
 
     let processing_time = start.elapsed().as_millis() as u64;
 
+    if let Some(failures) = &state.failures {
+        failures.record(image, &result.text, result.confidence, &result.solver_name);
+    }
+
     Ok(SolveResponse {
         text: result.text,
         confidence: result.confidence,
         model: result.solver_name,
         processing_time_ms: processing_time,
+        preprocess_ms: result.preprocess_ms,
+        inference_ms: result.inference_ms,
+        length_mismatch: result.length_mismatch,
+        candidates: result.candidates,
+        preprocess_variant: result.preprocess_variant,
+        processed_image_base64: result.processed_image.as_ref().map(|image| encode_png_base64(image, bit_depth)).transpose()?,
+        char_positions: result.char_positions,
+        probability: None,
+        image_quality: ImagePreprocessor::quality(image).ok(),
     })
 }
 
+/// Check the fields of a solve request that don't depend on the image, reporting every invalid one
+fn validate_request(request: &SolveRequest, max_output_length: usize) -> CaptchaResult<()> {
+    let mut v = Validator::new();
+
+    if let Some(model_type) = &request.model_type {
+        if request.model.is_some() {
+            v.add("model_type", "cannot be set together with model");
+        } else if !SOLVER_NAMES.contains(&model_type.as_str()) {
+            v.add("model_type", format!("must be one of: {}", SOLVER_NAMES.join(", ")));
+        }
+    }
+    if let Some(crop) = &request.crop {
+        v.nested("crop", |v| {
+            v.check(crop.w > 0, "w", "must be >= 1");
+            v.check(crop.h > 0, "h", "must be >= 1");
+        });
+    }
+    if let Some(preprocess) = &request.preprocess {
+        v.nested("preprocess", |v| ImagePreprocessor::check(preprocess, v));
+    }
+    SolveHints::from_request(request).check(&mut v, max_output_length);
+
+    v.finish()
+}
+
+/// Solver a validated request asks for: its `model`, else the solver serving the default
+/// model of its `model_type`. Solvers are named after the model type they serve.
+pub fn requested_solver(request: &SolveRequest) -> Option<&str> {
+    request.model.as_deref().or(request.model_type.as_deref())
+}
+
+/// The `region` of an image, rejecting empty regions and regions extending past its edges
+fn crop_image(image: &image::DynamicImage, region: &CropRegion) -> CaptchaResult<image::DynamicImage> {
+    if region.w == 0 || region.h == 0 {
+        return Err(CaptchaError::BadRequest("crop width and height must be positive".to_string()));
+    }
+
+    let right = region.x as u64 + region.w as u64;
+    let bottom = region.y as u64 + region.h as u64;
+    if right > image.width() as u64 || bottom > image.height() as u64 {
+        return Err(CaptchaError::BadRequest(format!(
+            "crop {}x{} at ({}, {}) is outside the {}x{} image",
+            region.w, region.h, region.x, region.y, image.width(), image.height()
+        )));
+    }
+
+    Ok(image.crop_imm(region.x, region.y, region.w, region.h))
+}
+
+/// Bits per pixel requested for processed images by `?bitdepth=`: 8 (default) or 1
+fn processed_bit_depth(query: &ProcessedImageQuery) -> CaptchaResult<u8> {
+    match query.bitdepth.unwrap_or(8) {
+        depth @ (1 | 8) => Ok(depth),
+        depth => Err(CaptchaError::BadRequest(format!("bitdepth must be 1 or 8, got {}", depth))),
+    }
+}
+
+/// Encode an image as base64 PNG. With `bit_depth` 1 an image that is binary (see
+/// [`ImagePreprocessor::is_binary`]) is packed 1 bit per pixel; any other stays 8-bit.
+fn encode_png_base64(image: &image::DynamicImage, bit_depth: u8) -> CaptchaResult<String> {
+    if bit_depth == 1 && ImagePreprocessor::is_binary(image) {
+        return encode_png_1bit(&image.to_luma8()).map(|png| BASE64.encode(png));
+    }
+
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), image::ImageOutputFormat::Png)
+        .map_err(|e| CaptchaError::ProcessingError(format!("Cannot encode processed image: {}", e)))?;
+    Ok(BASE64.encode(png))
+}
+
+/// Encode a black and white image as a 1-bit grayscale PNG, white pixels set
+fn encode_png_1bit(image: &image::GrayImage) -> CaptchaResult<Vec<u8>> {
+    let row_bytes = (image.width() as usize).div_ceil(8);
+    let mut packed = vec![0u8; row_bytes * image.height() as usize];
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if pixel[0] == 255 {
+            packed[y as usize * row_bytes + x as usize / 8] |= 0x80 >> (x % 8);
+        }
+    }
+
+    let error = |e: png::EncodingError| CaptchaError::ProcessingError(format!("Cannot encode processed image: {}", e));
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, image.width(), image.height());
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::One);
+    let mut writer = encoder.write_header().map_err(error)?;
+    writer.write_image_data(&packed).map_err(error)?;
+    writer.finish().map_err(error)?;
+    Ok(png)
+}
+
 /// Decode base64 image data
-fn decode_base64_image(base64_str: &str) -> CaptchaResult<Vec<u8>> {
+pub fn decode_base64_image(base64_str: &str) -> CaptchaResult<Vec<u8>> {
     // Handle data URL format
     let data = if base64_str.contains(",") {
         base64_str.split(",").last().unwrap_or(base64_str)
@@ -163,22 +752,576 @@ fn decode_base64_image(base64_str: &str) -> CaptchaResult<Vec<u8>> {
         base64_str
     };
 
+    // Pasted base64 often carries surrounding whitespace or line wrapping
+    let data: String = data.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    if data.is_empty() {
+        return Err(CaptchaError::InvalidImage("empty image".to_string()));
+    }
+
     BASE64.decode(data)
         .map_err(|e| CaptchaError::InvalidImage(format!("Invalid base64: {}", e)))
 }
 
-/// Load image from bytes
-fn load_image(data: &[u8]) -> CaptchaResult<image::DynamicImage> {
-    ImageReader::new(Cursor::new(data))
+/// Load image from bytes, applying any EXIF orientation. High-bit-depth images
+/// are converted to 8 bits per channel (see [`ImagePreprocessor::to_eight_bit`]),
+/// then the [`InputOptions`] apply (transparency, size cap). Images in a format
+/// outside `input.allowed_formats` are rejected before decoding.
+pub fn load_image(data: &[u8], input: &InputOptions) -> CaptchaResult<image::DynamicImage> {
+    load_image_region(data, input, None)
+}
+
+/// Load image from bytes like [`load_image`], keeping only `crop` if set. The region is
+/// cut before the size cap applies, so it is given in pixels of the uploaded image.
+pub fn load_image_region(
+    data: &[u8],
+    input: &InputOptions,
+    crop: Option<&CropRegion>,
+) -> CaptchaResult<image::DynamicImage> {
+    let reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|e| CaptchaError::InvalidImage(format!("Cannot detect image format: {}", e)))?;
+
+    let format = reader.format();
+    if let Some(format) = format.filter(|format| !input.allowed_formats.contains(format)) {
+        let allowed: Vec<String> = input.allowed_formats.iter().map(|f| image_format_name(*f)).collect();
+        return Err(CaptchaError::FormatNotAllowed(format!(
+            "detected {} image; allowed formats: {}", image_format_name(format), allowed.join(", ")
+        )));
+    }
+
+    let image = ImagePreprocessor::to_eight_bit(reader.decode().map_err(|e| decode_error(format, data.len(), e))?);
+    let image = match exif_orientation(data) {
+        Some(orientation) => apply_orientation(image, orientation),
+        None => image,
+    };
+    let image = match crop {
+        Some(region) => crop_image(&image, region)?,
+        None => image,
+    };
+
+    Ok(ImagePreprocessor::prepare_input(image, input))
+}
+
+/// Pixel count an upload declares in its header, read without decoding it; `None`
+/// when the header can't be read, which [`load_image_region`] then reports
+pub fn image_pixels(data: &[u8]) -> Option<u64> {
+    let (width, height) = ImageReader::new(Cursor::new(data))
         .with_guessed_format()
-        .map_err(|e| CaptchaError::InvalidImage(format!("Cannot detect image format: {}", e)))?
-        .decode()
-        .map_err(|e| CaptchaError::InvalidImage(format!("Cannot decode image: {}", e)))
+        .ok()?
+        .into_dimensions()
+        .ok()?;
+    Some(width as u64 * height as u64)
+}
+
+/// Map a decode failure to an error telling the client what to fix: a truncated or
+/// corrupt upload should be resent, an unrecognized or unsupported format converted
+fn decode_error(format: Option<image::ImageFormat>, len: usize, err: image::ImageError) -> CaptchaError {
+    use image::ImageError;
+
+    match (format, err) {
+        (None, _) => CaptchaError::InvalidImage(
+            "Unrecognized image format; convert to PNG or JPEG before submitting".to_string(),
+        ),
+        (Some(format), ImageError::Unsupported(_)) => CaptchaError::InvalidImage(format!(
+            "Unsupported image format {:?}; convert to PNG or JPEG before submitting", format
+        )),
+        (Some(format), ImageError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+            CaptchaError::CorruptImage(format!(
+                "{:?} image is truncated after {} bytes; resend the complete file", format, len
+            ))
+        }
+        (Some(format), ImageError::Decoding(e)) => CaptchaError::CorruptImage(format!(
+            "{:?} image data is corrupt ({}); resend the file", format, e
+        )),
+        (Some(format), e) => CaptchaError::InvalidImage(format!("Cannot decode {:?} image: {}", format, e)),
+    }
 }
 
-/// Calculate SHA256 hash of data
-fn calculate_hash(data: &[u8]) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(data);
-    hex::encode(hasher.finalize())
-}
\ No newline at end of file
+/// Rotate/flip an image so it displays upright for the given EXIF orientation (1-8)
+fn apply_orientation(image: image::DynamicImage, orientation: u16) -> image::DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+/// Read the EXIF orientation tag from JPEG (APP1) or PNG (eXIf) data
+fn exif_orientation(data: &[u8]) -> Option<u16> {
+    const JPEG_SOI: &[u8] = &[0xFF, 0xD8];
+    const PNG_SIGNATURE: &[u8] = &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+    if data.starts_with(JPEG_SOI) {
+        let mut pos = 2;
+        while pos + 4 <= data.len() && data[pos] == 0xFF {
+            let marker = data[pos + 1];
+            let len = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+            let segment = data.get(pos + 4..pos + 2 + len)?;
+            if marker == 0xE1 && segment.starts_with(b"Exif\0\0") {
+                return tiff_orientation(&segment[6..]);
+            }
+            // Metadata segments precede the scan data
+            if marker == 0xDA {
+                return None;
+            }
+            pos += 2 + len;
+        }
+    } else if data.starts_with(PNG_SIGNATURE) {
+        let mut pos = PNG_SIGNATURE.len();
+        while pos + 8 <= data.len() {
+            let len = u32::from_be_bytes(data[pos..pos + 4].try_into().ok()?) as usize;
+            let chunk_type = &data[pos + 4..pos + 8];
+            if chunk_type == b"eXIf" {
+                return tiff_orientation(data.get(pos + 8..pos + 8 + len)?);
+            }
+            if chunk_type == b"IDAT" {
+                return None;
+            }
+            pos += 12 + len;
+        }
+    }
+
+    None
+}
+
+/// Find the orientation tag (0x0112) in the first IFD of a TIFF structure
+fn tiff_orientation(tiff: &[u8]) -> Option<u16> {
+    let little_endian = match tiff.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let read_u16 = |at: usize| -> Option<u16> {
+        let bytes: [u8; 2] = tiff.get(at..at + 2)?.try_into().ok()?;
+        Some(if little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+    };
+    let read_u32 = |at: usize| -> Option<u32> {
+        let bytes: [u8; 4] = tiff.get(at..at + 4)?.try_into().ok()?;
+        Some(if little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+    };
+
+    let ifd = read_u32(4)? as usize;
+    let entries = read_u16(ifd)? as usize;
+    (0..entries)
+        .map(|i| ifd + 2 + i * 12)
+        .find(|&entry| read_u16(entry) == Some(0x0112))
+        .and_then(|entry| read_u16(entry + 8))
+        .filter(|orientation| (1..=8).contains(orientation))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 1x1 lossless WebP
+    const WEBP_SAMPLE: &str = "UklGRhoAAABXRUJQVlA4TA0AAAAvAAAAEAcQERGIiP4HAA==";
+
+    #[test]
+    fn test_load_webp() {
+        let data = decode_base64_image(WEBP_SAMPLE).unwrap();
+        let image = load_image(&data, &InputOptions::default()).unwrap();
+
+        assert_eq!((image.width(), image.height()), (1, 1));
+    }
+
+    #[test]
+    fn test_prefers_plain_text() {
+        use actix_web::test::TestRequest;
+
+        let accepts = |value: &str| {
+            prefers_plain_text(&TestRequest::default().insert_header((header::ACCEPT, value)).to_http_request())
+        };
+        assert!(accepts("text/plain"));
+        assert!(accepts("text/plain; charset=utf-8"));
+        assert!(accepts("application/json;q=0.5, text/plain"));
+        assert!(!accepts("application/json"));
+        assert!(!accepts("*/*"));
+        assert!(!accepts("text/plain;q=0.2, application/json"));
+        assert!(!prefers_plain_text(&TestRequest::default().to_http_request()));
+    }
+
+    #[test]
+    fn test_requests_no_cache() {
+        use actix_web::test::TestRequest;
+
+        let no_cache = |value: &str| {
+            requests_no_cache(&TestRequest::default().insert_header((header::CACHE_CONTROL, value)).to_http_request())
+        };
+        assert!(no_cache("no-cache"));
+        assert!(no_cache("max-age=0, no-cache"));
+        assert!(!no_cache("max-age=60"));
+        assert!(!requests_no_cache(&TestRequest::default().to_http_request()));
+    }
+
+    #[actix_web::test]
+    async fn test_solve_rejects_wrong_content_type() {
+        use actix_web::test::{self, TestRequest};
+
+        let app = test::init_service(
+            actix_web::App::new()
+                .app_data(solve_json_config())
+                .route("/solve", web::post().to(|_: web::Json<SolveRequest>| async { HttpResponse::Ok().finish() })),
+        ).await;
+        let message = |content_type: Option<&str>| {
+            let mut req = TestRequest::post().uri("/solve").set_payload("image_base64=abc");
+            if let Some(content_type) = content_type {
+                req = req.insert_header((header::CONTENT_TYPE, content_type));
+            }
+            let app = &app;
+            async move {
+                let response = test::call_service(app, req.to_request()).await;
+                assert_eq!(response.status(), actix_web::http::StatusCode::BAD_REQUEST);
+                let body: serde_json::Value = test::read_body_json(response).await;
+                assert_eq!(body["error"], "bad_request");
+                body["message"].as_str().unwrap().to_string()
+            }
+        };
+
+        let form = message(Some("multipart/form-data; boundary=x")).await;
+        assert!(form.contains("expected application/json, got multipart/form-data"));
+        assert!(form.contains("image_base64"));
+        assert_eq!(message(Some("text/plain")).await, "expected application/json, got text/plain");
+        assert!(message(None).await.contains("expected application/json"));
+    }
+
+    #[test]
+    fn test_decode_base64_rejects_empty() {
+        for input in ["", "   ", "\n\t", "data:image/png;base64,", "data:image/png;base64, \n"] {
+            let err = decode_base64_image(input).unwrap_err();
+            assert!(matches!(err, CaptchaError::InvalidImage(ref msg) if msg == "empty image"), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_decode_base64_strips_whitespace() {
+        assert_eq!(decode_base64_image("  aGVs\nbG8=\r\n").unwrap(), b"hello");
+        assert_eq!(decode_base64_image("data:text/plain;base64, aGVsbG8=\n").unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_load_image_names_detected_format() {
+        // Valid PNG signature followed by garbage
+        let mut data = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        data.extend_from_slice(&[0u8; 16]);
+
+        let err = load_image(&data, &InputOptions::default()).unwrap_err();
+        assert!(matches!(err, CaptchaError::CorruptImage(_)), "unexpected error: {:?}", err);
+        assert!(err.to_string().contains("Png"), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn test_load_image_checks_allowed_formats() {
+        let mut png = Vec::new();
+        image::DynamicImage::new_luma8(4, 4)
+            .write_to(&mut Cursor::new(&mut png), image::ImageOutputFormat::Png)
+            .unwrap();
+        let webp = BASE64.decode(WEBP_SAMPLE).unwrap();
+        let input = InputOptions {
+            allowed_formats: vec![image::ImageFormat::Png, image::ImageFormat::Jpeg],
+            ..Default::default()
+        };
+
+        assert!(load_image(&png, &input).is_ok());
+        let err = load_image(&webp, &input).unwrap_err();
+        assert!(matches!(err, CaptchaError::FormatNotAllowed(_)), "unexpected error: {:?}", err);
+        assert_eq!(err.message(), "detected webp image; allowed formats: png, jpeg");
+        assert!(load_image(&webp, &InputOptions::default()).is_ok());
+    }
+
+    #[test]
+    fn test_load_image_distinguishes_truncated_from_unrecognized() {
+        let mut png = Vec::new();
+        image::DynamicImage::new_rgb8(40, 20)
+            .write_to(&mut Cursor::new(&mut png), image::ImageOutputFormat::Png)
+            .unwrap();
+
+        let err = load_image(&png[..png.len() / 2], &InputOptions::default()).unwrap_err();
+        assert!(matches!(err, CaptchaError::CorruptImage(ref msg) if msg.contains("truncated")), "{:?}", err);
+
+        let err = load_image(b"definitely not an image", &InputOptions::default()).unwrap_err();
+        assert!(matches!(err, CaptchaError::InvalidImage(ref msg) if msg.starts_with("Unrecognized")), "{:?}", err);
+    }
+
+    #[test]
+    fn test_load_image_flattens_transparent_png() {
+        // Transparent background stored as black, opaque dark text pixel in the middle
+        let mut rgba = image::RgbaImage::from_pixel(3, 1, image::Rgba([0, 0, 0, 0]));
+        rgba.put_pixel(1, 0, image::Rgba([30, 30, 30, 255]));
+        let mut png = Vec::new();
+        image::DynamicImage::ImageRgba8(rgba)
+            .write_to(&mut Cursor::new(&mut png), image::ImageOutputFormat::Png)
+            .unwrap();
+
+        let gray = load_image(&png, &InputOptions::default()).unwrap().to_luma8();
+        assert_eq!(gray.get_pixel(0, 0).0[0], 255);
+        assert_eq!(gray.get_pixel(1, 0).0[0], 30);
+        assert_eq!(gray.get_pixel(2, 0).0[0], 255);
+
+        let gray_background = InputOptions { alpha_background: [128, 128, 128], ..Default::default() };
+        let on_gray = load_image(&png, &gray_background).unwrap().to_luma8();
+        assert_eq!(on_gray.get_pixel(0, 0).0[0], 128);
+    }
+
+    #[test]
+    fn test_load_image_region_crops_before_downscaling() {
+        // Dark 400x100 band at the bottom right of a 2000x1000 upload
+        let mut upload = image::GrayImage::from_pixel(2000, 1000, image::Luma([255]));
+        for x in 1600..2000 {
+            for y in 900..1000 {
+                upload.put_pixel(x, y, image::Luma([0]));
+            }
+        }
+        let mut png = Vec::new();
+        image::DynamicImage::ImageLuma8(upload)
+            .write_to(&mut Cursor::new(&mut png), image::ImageOutputFormat::Png)
+            .unwrap();
+        let input = InputOptions { max_dimension: 500, ..Default::default() };
+
+        let whole = load_image(&png, &input).unwrap();
+        assert_eq!((whole.width(), whole.height()), (500, 250));
+
+        // The crop is in upload coordinates, and the region is under the cap
+        let region = CropRegion { x: 1600, y: 900, w: 400, h: 100 };
+        let cropped = load_image_region(&png, &input, Some(&region)).unwrap();
+        assert_eq!((cropped.width(), cropped.height()), (400, 100));
+        assert_eq!(cropped.to_luma8().get_pixel(10, 10).0[0], 0);
+    }
+
+    #[test]
+    fn test_image_pixels_reads_header_only() {
+        let mut png = Vec::new();
+        image::DynamicImage::new_luma8(2000, 1000)
+            .write_to(&mut Cursor::new(&mut png), image::ImageOutputFormat::Png)
+            .unwrap();
+        assert_eq!(image_pixels(&png), Some(2_000_000));
+
+        // Header intact, pixel data cut off: the size is still known without decoding
+        assert_eq!(image_pixels(&png[..64]), Some(2_000_000));
+        assert_eq!(image_pixels(b"not an image"), None);
+    }
+
+    /// Encode a 2x1 JPEG carrying an EXIF orientation tag
+    fn jpeg_with_orientation(orientation: u16) -> Vec<u8> {
+        let mut jpeg = Vec::new();
+        image::DynamicImage::new_rgb8(2, 1)
+            .write_to(&mut Cursor::new(&mut jpeg), image::ImageOutputFormat::Jpeg(90))
+            .unwrap();
+
+        // Big-endian TIFF with a single IFD entry: orientation, SHORT, count 1
+        let mut tiff = b"MM\0\x2a\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01".to_vec();
+        tiff.extend_from_slice(&orientation.to_be_bytes());
+        tiff.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+
+        let mut app1 = vec![0xFF, 0xE1];
+        app1.extend_from_slice(&((tiff.len() + 8) as u16).to_be_bytes());
+        app1.extend_from_slice(b"Exif\0\0");
+        app1.extend_from_slice(&tiff);
+
+        jpeg.splice(2..2, app1);
+        jpeg
+    }
+
+    #[test]
+    fn test_load_image_applies_exif_orientation() {
+        let upright = load_image(&jpeg_with_orientation(1), &InputOptions::default()).unwrap();
+        assert_eq!((upright.width(), upright.height()), (2, 1));
+
+        let rotated = load_image(&jpeg_with_orientation(6), &InputOptions::default()).unwrap();
+        assert_eq!((rotated.width(), rotated.height()), (1, 2));
+    }
+
+    #[test]
+    fn test_apply_orientation() {
+        let mut image = image::RgbImage::new(2, 1);
+        image.put_pixel(0, 0, image::Rgb([255, 0, 0]));
+        let image = image::DynamicImage::ImageRgb8(image);
+
+        // Orientation 6: rotate 90° clockwise, so the left pixel ends up on top
+        let rotated = apply_orientation(image.clone(), 6).to_rgb8();
+        assert_eq!(rotated.get_pixel(0, 0).0, [255, 0, 0]);
+
+        // Orientation 2: mirror horizontally
+        let mirrored = apply_orientation(image.clone(), 2).to_rgb8();
+        assert_eq!(mirrored.get_pixel(1, 0).0, [255, 0, 0]);
+
+        assert_eq!(apply_orientation(image.clone(), 1).to_rgb8(), image.to_rgb8());
+    }
+
+    #[test]
+    fn test_exif_orientation_absent() {
+        let mut png = Vec::new();
+        image::DynamicImage::new_rgb8(2, 1)
+            .write_to(&mut Cursor::new(&mut png), image::ImageOutputFormat::Png)
+            .unwrap();
+
+        assert_eq!(exif_orientation(&png), None);
+        assert_eq!(exif_orientation(b"not an image"), None);
+    }
+
+    #[test]
+    fn test_crop_image() {
+        let image = image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(100, 40, |x, y| {
+            if x >= 20 && y >= 10 { image::Rgb([0, 0, 0]) } else { image::Rgb([255, 255, 255]) }
+        }));
+
+        let cropped = crop_image(&image, &CropRegion { x: 20, y: 10, w: 80, h: 30 }).unwrap();
+        assert_eq!((cropped.width(), cropped.height()), (80, 30));
+        assert_eq!(cropped.to_rgb8().get_pixel(0, 0).0, [0, 0, 0]);
+
+        let out_of_bounds = [
+            CropRegion { x: 21, y: 10, w: 80, h: 30 },
+            CropRegion { x: 0, y: 0, w: 100, h: 41 },
+            CropRegion { x: u32::MAX, y: 0, w: 2, h: 2 },
+            CropRegion { x: 0, y: 0, w: 0, h: 10 },
+        ];
+        for region in &out_of_bounds {
+            assert!(matches!(crop_image(&image, region), Err(CaptchaError::BadRequest(_))), "{:?}", region);
+        }
+    }
+
+    fn solve_request(fields: serde_json::Value) -> SolveRequest {
+        let mut body = serde_json::json!({ "image_base64": "" });
+        body.as_object_mut().unwrap().extend(fields.as_object().unwrap().clone());
+        serde_json::from_value(body).unwrap()
+    }
+
+    #[test]
+    fn test_encode_png_bit_depth() {
+        let binary = image::DynamicImage::ImageLuma8(image::GrayImage::from_fn(13, 5, |x, y| {
+            image::Luma([if (x + y) % 3 == 0 { 0 } else { 255 }])
+        }));
+        let decode = |encoded: String| {
+            let png = BASE64.decode(encoded).unwrap();
+            let depth = png::Decoder::new(png.as_slice()).read_info().unwrap().info().bit_depth;
+            (depth, image::load_from_memory(&png).unwrap().to_luma8())
+        };
+
+        let (depth, decoded) = decode(encode_png_base64(&binary, 1).unwrap());
+        assert_eq!(depth, png::BitDepth::One);
+        assert_eq!(decoded, binary.to_luma8());
+        assert_eq!(decode(encode_png_base64(&binary, 8).unwrap()).0, png::BitDepth::Eight);
+
+        // Gray levels fall back to 8-bit rather than being thresholded
+        let mut gray = binary.to_luma8();
+        gray.put_pixel(1, 1, image::Luma([128]));
+        let (depth, decoded) = decode(encode_png_base64(&image::DynamicImage::ImageLuma8(gray.clone()), 1).unwrap());
+        assert_eq!(depth, png::BitDepth::Eight);
+        assert_eq!(decoded, gray);
+
+        assert_eq!(processed_bit_depth(&ProcessedImageQuery::default()).unwrap(), 8);
+        assert_eq!(processed_bit_depth(&ProcessedImageQuery { bitdepth: Some(1) }).unwrap(), 1);
+        assert!(processed_bit_depth(&ProcessedImageQuery { bitdepth: Some(4) }).is_err());
+    }
+
+    #[test]
+    fn test_requested_solver() {
+        assert_eq!(requested_solver(&solve_request(serde_json::json!({}))), None);
+        assert_eq!(requested_solver(&solve_request(serde_json::json!({ "model": "ocr" }))), Some("ocr"));
+        assert_eq!(requested_solver(&solve_request(serde_json::json!({ "model_type": "cnn" }))), Some("cnn"));
+    }
+
+    #[test]
+    fn test_validate_request_reports_field_paths() {
+        assert!(validate_request(&solve_request(serde_json::json!({ "model_type": "cnn" })), 32).is_ok());
+
+        let invalid = solve_request(serde_json::json!({
+            "model": "ocr",
+            "model_type": "cnn",
+            "crop": { "x": 0, "y": 0, "w": 0, "h": 10 },
+            "preprocess": { "resize_width": 0, "resize_height": 50, "steps": ["sharpen"] },
+            "charset": "",
+            "expected_length": 40
+        }));
+        let Err(CaptchaError::Validation(errors)) = validate_request(&invalid, 32) else {
+            panic!("expected a validation error");
+        };
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, vec!["model_type", "crop.w", "preprocess.resize_width", "preprocess.steps[0]", "expected_length", "charset"]);
+
+        let unknown = solve_request(serde_json::json!({ "model_type": "lstm" }));
+        assert!(matches!(validate_request(&unknown, 32), Err(CaptchaError::Validation(_))));
+    }
+
+    fn compared(model: &str, text: Option<&str>) -> CompareResult {
+        CompareResult {
+            model: model.to_string(),
+            success: text.is_some(),
+            result: text.map(|text| SolveResponse {
+                text: text.to_string(),
+                confidence: 0.9,
+                model: model.to_string(),
+                processing_time_ms: 5,
+                preprocess_ms: 1,
+                inference_ms: 4,
+                length_mismatch: None,
+                candidates: Vec::new(),
+                preprocess_variant: None,
+                processed_image_base64: None,
+                char_positions: None,
+                probability: None,
+                image_quality: None,
+            }),
+            error: text.is_none().then(|| "Model not found: rnn".to_string()),
+            error_code: text.is_none().then(|| "model_not_found".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_compare_template_forwards_hints() {
+        let body: CompareRequest = serde_json::from_value(serde_json::json!({
+            "image_base64": "",
+            "models": ["ocr", "cnn"],
+            "expected_length": 4,
+            "top_k": 3,
+            "strategy": "digits",
+            "language": "eng+rus",
+            "charset": "0123456789",
+            "include_char_positions": true,
+        })).unwrap();
+
+        let hints = SolveHints::from_request(&compare_template(&body));
+        assert_eq!(hints.expected_length, Some(4));
+        assert_eq!(hints.top_k, Some(3));
+        assert!(hints.digits_only());
+        assert_eq!(hints.language.as_deref(), Some("eng+rus"));
+        assert_eq!(hints.charset.as_deref(), Some("0123456789"));
+        assert!(hints.include_char_positions);
+        assert!(hints.disable_fallback);
+    }
+
+    #[test]
+    fn test_compare_template_is_validated_like_solve() {
+        let body: CompareRequest = serde_json::from_value(serde_json::json!({
+            "image_base64": "",
+            "models": ["ocr"],
+            "expected_length": 40,
+            "top_k": 0,
+            "charset": "",
+        })).unwrap();
+
+        let Err(CaptchaError::Validation(errors)) = validate_request(&compare_template(&body), 32) else {
+            panic!("expected a validation error");
+        };
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, vec!["expected_length", "top_k", "charset"]);
+    }
+
+    #[test]
+    fn test_answers_agree() {
+        assert!(answers_agree(&[compared("ocr", Some("AB12")), compared("cnn", Some("AB12"))]));
+        assert!(!answers_agree(&[compared("ocr", Some("AB12")), compared("cnn", Some("A812"))]));
+
+        // Failed solvers are ignored, but a single answer is no agreement
+        assert!(answers_agree(&[
+            compared("ocr", Some("AB12")),
+            compared("rnn", None),
+            compared("cnn", Some("AB12")),
+        ]));
+        assert!(!answers_agree(&[compared("ocr", Some("AB12")), compared("rnn", None)]));
+    }
+}
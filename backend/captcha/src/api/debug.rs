@@ -0,0 +1,161 @@
+//! Debug Handlers
+//!
+//! Diagnostic endpoints for building tooling around the preprocessing pipeline.
+//! Available to admins and to API keys granted the `debug` scope.
+
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+use crate::api::captcha::{decode_base64_image, load_image};
+use crate::api::require_admin;
+use crate::error::{CaptchaError, CaptchaResult};
+use crate::models::{ApiKey, ComponentBox, PreprocessOptions};
+use crate::solvers::preprocessor::{ImagePreprocessor, Segmentation};
+use crate::AppState;
+
+/// Connected-component bounding boxes of an image after binarization
+pub async fn components(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    body: web::Json<ComponentsRequest>,
+) -> Result<HttpResponse, CaptchaError> {
+    require_debug_access(&req)?;
+
+    let image_data = decode_base64_image(&body.image_base64)?;
+    let mut image = load_image(&image_data, &state.config.processing.input_options())?;
+
+    if let Some(options) = &body.preprocess {
+        image = ImagePreprocessor::preprocess(&image, options)?;
+    }
+
+    let (threshold, components) = ImagePreprocessor::component_boxes(&image, body.threshold)?;
+
+    Ok(HttpResponse::Ok().json(ComponentsResponse {
+        width: image.width(),
+        height: image.height(),
+        threshold,
+        components,
+    }))
+}
+
+/// Character segments found by projection segmentation, with per-request size filters
+pub async fn segments(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    body: web::Json<SegmentsRequest>,
+) -> Result<HttpResponse, CaptchaError> {
+    require_debug_access(&req)?;
+
+    let image_data = decode_base64_image(&body.image_base64)?;
+    let mut image = load_image(&image_data, &state.config.processing.input_options())?;
+
+    if let Some(options) = &body.preprocess {
+        image = ImagePreprocessor::preprocess(&image, options)?;
+    }
+
+    let defaults = state.config.processing.segmentation();
+    let params = Segmentation {
+        min_width: body.min_width.unwrap_or(defaults.min_width),
+        min_height: body.min_height.unwrap_or(defaults.min_height),
+        min_pixels: body.min_pixels.unwrap_or(defaults.min_pixels),
+        split_width_ratio: body.split_width_ratio.unwrap_or(defaults.split_width_ratio),
+    };
+    let segments = ImagePreprocessor::segment_boxes(&image, &params)?;
+
+    Ok(HttpResponse::Ok().json(SegmentsResponse {
+        width: image.width(),
+        height: image.height(),
+        segments,
+    }))
+}
+
+/// Grayscale histogram of an image, with Otsu's level and intensity statistics, to help pick thresholds
+pub async fn histogram(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    body: web::Json<HistogramRequest>,
+) -> Result<HttpResponse, CaptchaError> {
+    require_debug_access(&req)?;
+
+    let image_data = decode_base64_image(&body.image_base64)?;
+    let mut image = load_image(&image_data, &state.config.processing.input_options())?;
+
+    if let Some(options) = &body.preprocess {
+        image = ImagePreprocessor::preprocess(&image, options)?;
+    }
+
+    let histogram = ImagePreprocessor::histogram(&image);
+
+    Ok(HttpResponse::Ok().json(HistogramResponse {
+        width: image.width(),
+        height: image.height(),
+        histogram: histogram.bins.to_vec(),
+        otsu_threshold: histogram.otsu_threshold,
+        mean: histogram.mean,
+        stddev: histogram.stddev,
+    }))
+}
+
+/// Keys reaching this point already passed the `debug` scope check in the auth middleware
+fn require_debug_access(req: &HttpRequest) -> CaptchaResult<()> {
+    if req.extensions().get::<ApiKey>().is_some() {
+        return Ok(());
+    }
+    require_admin(req)
+}
+
+// Request/Response types
+
+#[derive(Debug, Deserialize)]
+pub struct ComponentsRequest {
+    pub image_base64: String,
+    /// Applied before binarization (e.g. color filter, resize)
+    pub preprocess: Option<PreprocessOptions>,
+    /// Binarization level; Otsu's method when omitted
+    pub threshold: Option<u8>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ComponentsResponse {
+    pub width: u32,
+    pub height: u32,
+    pub threshold: u8,
+    pub components: Vec<ComponentBox>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SegmentsRequest {
+    pub image_base64: String,
+    /// Applied before segmentation (e.g. color filter, threshold)
+    pub preprocess: Option<PreprocessOptions>,
+    /// Overrides of the configured segmentation parameters
+    pub min_width: Option<u32>,
+    pub min_height: Option<u32>,
+    pub min_pixels: Option<u32>,
+    pub split_width_ratio: Option<f32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SegmentsResponse {
+    pub width: u32,
+    pub height: u32,
+    pub segments: Vec<ComponentBox>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistogramRequest {
+    pub image_base64: String,
+    /// Applied before the histogram is computed
+    pub preprocess: Option<PreprocessOptions>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistogramResponse {
+    pub width: u32,
+    pub height: u32,
+    /// Pixel count per grayscale intensity, 256 bins from 0 (black) to 255 (white)
+    pub histogram: Vec<u32>,
+    pub otsu_threshold: u8,
+    pub mean: f64,
+    pub stddev: f64,
+}
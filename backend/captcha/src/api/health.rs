@@ -3,6 +3,7 @@
 use actix_web::{HttpResponse, web};
 use serde::Serialize;
 use chrono::Utc;
+use std::path::Path;
 
 use crate::AppState;
 
@@ -20,6 +21,7 @@ pub struct HealthResponse {
 pub struct HealthChecks {
     pub database: HealthStatus,
     pub solvers: HealthStatus,
+    pub models: HealthStatus,
 }
 
 #[derive(Debug, Serialize)]
@@ -44,19 +46,30 @@ pub async fn health_check(
     };
 
     let solver_count = state.solver_manager.model_count();
-    let solver_status = if solver_count > 0 {
+    let mock_solvers = state.solver_manager.mock_solvers();
+    let solver_status = if solver_count == 0 {
         HealthStatus {
-            status: "healthy".to_string(),
-            message: Some(format!("{} models loaded", solver_count)),
+            status: "degraded".to_string(),
+            message: Some("No models loaded".to_string()),
         }
-    } else {
+    } else if !mock_solvers.is_empty() {
         HealthStatus {
             status: "degraded".to_string(),
-            message: Some("No models loaded".to_string()),
+            message: Some(format!("Solvers running in mock mode: {}", mock_solvers.join(", "))),
+        }
+    } else {
+        HealthStatus {
+            status: "healthy".to_string(),
+            message: Some(format!("{} models loaded", solver_count)),
         }
     };
 
-    let overall_status = if db_status.status == "healthy" && solver_status.status != "unhealthy" {
+    let model_status = check_model_files(&state);
+
+    let overall_status = if db_status.status == "healthy"
+        && solver_status.status == "healthy"
+        && model_status.status == "healthy"
+    {
         "healthy"
     } else if db_status.status == "unhealthy" {
         "unhealthy"
@@ -72,6 +85,7 @@ pub async fn health_check(
         checks: HealthChecks {
             database: db_status,
             solvers: solver_status,
+            models: model_status,
         },
     };
 
@@ -84,6 +98,54 @@ pub async fn health_check(
     }
 }
 
+/// Verify the model file each solver loads is present and no model file failed its
+/// checksum verification
+fn check_model_files(state: &AppState) -> HealthStatus {
+    let mut failures: Vec<String> = state.solver_manager
+        .checksum_failures()
+        .into_iter()
+        .map(|(solver, reason)| format!("{}: {}", solver, reason))
+        .collect();
+    if !failures.is_empty() {
+        failures.sort();
+        return HealthStatus {
+            status: "degraded".to_string(),
+            message: Some(format!("Model file verification failed, solvers unavailable: {}", failures.join("; "))),
+        };
+    }
+
+    let files = state.solver_manager.model_files();
+    let missing: Vec<String> = files
+        .iter()
+        .filter(|(_, path)| !model_file_ok(path))
+        .map(|(solver, path)| format!("{}: {}", solver, path.display()))
+        .collect();
+
+    if !missing.is_empty() {
+        HealthStatus {
+            status: "degraded".to_string(),
+            message: Some(format!("Model files missing or empty: {}", missing.join("; "))),
+        }
+    } else if files.is_empty() {
+        HealthStatus {
+            status: "healthy".to_string(),
+            message: Some("No solver loads a model file".to_string()),
+        }
+    } else {
+        HealthStatus {
+            status: "healthy".to_string(),
+            message: Some(format!("{} model file(s) found", files.len())),
+        }
+    }
+}
+
+/// Whether a model file exists and is non-empty
+fn model_file_ok(path: &Path) -> bool {
+    std::fs::metadata(path)
+        .map(|meta| meta.is_file() && meta.len() > 0)
+        .unwrap_or(false)
+}
+
 /// Readiness check (for Kubernetes)
 pub async fn ready_check(
     state: web::Data<AppState>,
@@ -4,7 +4,21 @@ use actix_web::{web, HttpResponse, HttpRequest};
 use serde::{Deserialize, Serialize};
 
 use crate::AppState;
+use crate::api::captcha::{apply_profile, decode_base64_image, load_image, requested_solver};
+use crate::api::require_admin;
 use crate::error::CaptchaError;
+use crate::models::{CaseMode, NormalizeMode, Page, PreprocessOptions, SolveRequest};
+use crate::solvers::SolveHints;
+
+/// Number of logs re-evaluated per database round trip
+const RECOMPUTE_BATCH_SIZE: u32 = 500;
+
+/// Labeled logs replayed when the request sets no `limit`, and the most it may set
+const REPLAY_DEFAULT_LIMIT: u32 = 200;
+const REPLAY_MAX_LIMIT: u32 = 5000;
+
+/// Logs, with their images, loaded per database round trip of a replay
+const REPLAY_BATCH_SIZE: u32 = 50;
 
 /// Get captcha processing logs
 pub async fn get_logs(
@@ -28,8 +42,8 @@ pub async fn get_logs(
 
     let total = state.db.count_logs(user_id, query.model_id, query.is_correct).await?;
 
-    let response = LogsResponse {
-        logs: logs.into_iter().map(|l| l.into()).collect(),
+    let response = Page::<LogResponse> {
+        items: logs.into_iter().map(|l| l.into()).collect(),
         total,
         limit: query.limit.unwrap_or(50),
         offset: query.offset.unwrap_or(0),
@@ -64,8 +78,12 @@ pub async fn update_log(
         .ok_or(CaptchaError::BadRequest(format!("Log {} not found", log_id)))?;
 
     // Calculate if correct
+    let normalizer = state.solver_manager.normalizer();
+    let case = state.solver_manager.output_case();
     let is_correct = body.actual_text.as_ref()
-        .map(|actual| existing.predicted_text.as_ref() == Some(actual));
+        .map(|actual| existing.predicted_text.as_deref()
+            .map(|predicted| normalizer.matches(predicted, actual, NormalizeMode::default(), case))
+            .unwrap_or(false));
 
     // Update the log
     state.db.update_log(log_id, body.actual_text.clone(), is_correct).await?;
@@ -77,6 +95,165 @@ pub async fn update_log(
     Ok(HttpResponse::Ok().json(LogResponse::from(log)))
 }
 
+/// Re-evaluate `is_correct` for all labeled logs under the current normalization rules
+pub async fn recompute_correctness(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    body: Option<web::Json<RecomputeRequest>>,
+) -> Result<HttpResponse, CaptchaError> {
+    require_admin(&req)?;
+
+    let mode = body.as_ref().and_then(|b| b.normalize).unwrap_or_default();
+    let case = body.as_ref().and_then(|b| b.case).unwrap_or(state.solver_manager.output_case());
+    let normalizer = state.solver_manager.normalizer();
+
+    let mut scanned: u64 = 0;
+    let mut updated: u64 = 0;
+    let mut after_id: u64 = 0;
+
+    loop {
+        let batch = state.db.get_labeled_logs_batch(after_id, RECOMPUTE_BATCH_SIZE).await?;
+        let Some(&(last_id, ..)) = batch.last() else {
+            break;
+        };
+
+        for (id, predicted, actual, is_correct) in &batch {
+            let correct = predicted.as_deref()
+                .map(|p| normalizer.matches(p, actual, mode, case))
+                .unwrap_or(false);
+
+            if *is_correct != Some(correct) {
+                state.db.set_log_correctness(*id, correct).await?;
+                updated += 1;
+            }
+        }
+
+        scanned += batch.len() as u64;
+        after_id = last_id;
+    }
+
+    tracing::info!("Recomputed is_correct for {} logs, {} changed", scanned, updated);
+
+    Ok(HttpResponse::Ok().json(RecomputeResponse { scanned, updated }))
+}
+
+/// Re-solve the newest labeled logs that kept their image (`processing.store_log_images`)
+/// with a profile, model or preprocessing, and compare the accuracy with what was logged
+pub async fn replay_logs(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    body: web::Json<ReplayRequest>,
+) -> Result<HttpResponse, CaptchaError> {
+    require_admin(&req)?;
+
+    let body = body.into_inner();
+    let limit = body.limit.unwrap_or(REPLAY_DEFAULT_LIMIT);
+    if limit == 0 || limit > REPLAY_MAX_LIMIT {
+        return Err(CaptchaError::BadRequest(format!("limit must be between 1 and {}", REPLAY_MAX_LIMIT)));
+    }
+
+    // Every log is solved as a request with these settings would be
+    let mut template = SolveRequest {
+        image_base64: String::new(),
+        model: body.model,
+        model_type: None,
+        preprocess: body.preprocess,
+        normalize: body.normalize,
+        expected_length: None,
+        top_k: None,
+        strategy: None,
+        auto_preprocess: None,
+        language: None,
+        case: body.case,
+        whitespace: None,
+        charset: None,
+        profile: body.profile,
+        include_processed_image: false,
+        include_char_positions: false,
+        include_probability: false,
+        fallback: Some(false),
+        crop: None,
+        raw_output: false,
+        no_cache: false,
+    };
+    apply_profile(&state, &mut template)?;
+
+    let hints = SolveHints::from_request(&template);
+    let mode = template.normalize.unwrap_or_default();
+    let case = template.case.unwrap_or(state.solver_manager.output_case());
+    let normalizer = state.solver_manager.normalizer();
+    let input = state.config.processing.input_options();
+
+    // Images are large: load one page at a time and solve it before loading the next
+    let mut response = ReplayResponse::default();
+    let mut remaining = limit;
+    let mut before_id = u64::MAX;
+
+    while remaining > 0 {
+        let logs = state.db.get_replay_logs(body.model_id, before_id, remaining.min(REPLAY_BATCH_SIZE)).await?;
+        let Some(&(last_id, ..)) = logs.last() else {
+            break;
+        };
+        remaining -= logs.len() as u32;
+        before_id = last_id;
+
+        for (id, predicted, actual, image_base64) in logs {
+            let image = match decode_base64_image(&image_base64).and_then(|data| load_image(&data, &input)) {
+                Ok(image) => image,
+                Err(e) => {
+                    tracing::warn!("Skipping log {} in replay: {}", id, e);
+                    response.skipped += 1;
+                    continue;
+                }
+            };
+
+            // A failed solve counts as a wrong answer, as it would have for the caller
+            let replayed = match state.solver_manager
+                .solve(&image, requested_solver(&template), template.preprocess.as_ref(), &hints)
+                .await
+            {
+                Ok(result) => Some(result.text),
+                Err(e @ CaptchaError::Overloaded(_)) => return Err(e),
+                Err(e) => {
+                    tracing::debug!("Replay of log {} failed: {}", id, e);
+                    None
+                }
+            };
+
+            let original_correct = predicted.as_deref()
+                .is_some_and(|p| normalizer.matches(p, &actual, mode, case));
+            let replayed_correct = replayed.as_deref()
+                .is_some_and(|r| normalizer.matches(r, &actual, mode, case));
+
+            response.replayed += 1;
+            response.original_correct += original_correct as u64;
+            response.replayed_correct += replayed_correct as u64;
+            if original_correct != replayed_correct {
+                response.changed.push(ReplayChange {
+                    id,
+                    actual_text: actual,
+                    original_text: predicted,
+                    replayed_text: replayed,
+                    replayed_correct,
+                });
+            }
+        }
+    }
+
+    if response.replayed > 0 {
+        response.original_accuracy = response.original_correct as f64 / response.replayed as f64;
+        response.replayed_accuracy = response.replayed_correct as f64 / response.replayed as f64;
+        response.accuracy_delta = response.replayed_accuracy - response.original_accuracy;
+    }
+
+    tracing::info!(
+        "Replayed {} labeled logs: accuracy {:.4} -> {:.4} ({} skipped)",
+        response.replayed, response.original_accuracy, response.replayed_accuracy, response.skipped
+    );
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
 /// Export logs as CSV
 pub async fn export_logs(
     state: web::Data<AppState>,
@@ -142,12 +319,52 @@ pub struct UpdateLogRequest {
     pub actual_text: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct RecomputeRequest {
+    pub normalize: Option<NormalizeMode>,
+    pub case: Option<CaseMode>,
+}
+
 #[derive(Debug, Serialize)]
-pub struct LogsResponse {
-    pub logs: Vec<LogResponse>,
-    pub total: u64,
-    pub limit: u32,
-    pub offset: u32,
+pub struct RecomputeResponse {
+    pub scanned: u64,
+    pub updated: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReplayRequest {
+    /// Provider profile filling the settings below that are unset
+    pub profile: Option<String>,
+    pub model: Option<String>,
+    pub preprocess: Option<PreprocessOptions>,
+    pub normalize: Option<NormalizeMode>,
+    pub case: Option<CaseMode>,
+    /// Only replay logs answered by this model
+    pub model_id: Option<u64>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ReplayResponse {
+    pub replayed: u64,
+    /// Logs whose stored image could not be decoded
+    pub skipped: u64,
+    pub original_correct: u64,
+    pub replayed_correct: u64,
+    pub original_accuracy: f64,
+    pub replayed_accuracy: f64,
+    pub accuracy_delta: f64,
+    /// Logs the replay fixed or broke
+    pub changed: Vec<ReplayChange>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReplayChange {
+    pub id: u64,
+    pub actual_text: String,
+    pub original_text: Option<String>,
+    pub replayed_text: Option<String>,
+    pub replayed_correct: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -156,6 +373,7 @@ pub struct LogResponse {
     pub user_id: Option<u64>,
     pub model_id: Option<u64>,
     pub image_hash: String,
+    pub image_hash_algorithm: String,
     pub predicted_text: Option<String>,
     pub actual_text: Option<String>,
     pub confidence: Option<f64>,
@@ -171,6 +389,7 @@ impl From<crate::models::CaptchaLog> for LogResponse {
             user_id: log.user_id,
             model_id: log.model_id,
             image_hash: log.image_hash,
+            image_hash_algorithm: log.image_hash_algorithm,
             predicted_text: log.predicted_text,
             actual_text: log.actual_text,
             confidence: log.confidence,
@@ -2,17 +2,73 @@
 //!
 //! This module contains all HTTP API handlers for the Captcha Service.
 
+pub mod api_keys;
+pub mod audit;
+pub mod cache;
 pub mod captcha;
+pub mod debug;
 pub mod health;
 pub mod logs;
 pub mod models;
+pub mod selftest;
 pub mod stats;
 pub mod training;
+pub mod version;
+
+use actix_web::HttpRequest;
+
+use crate::error::{CaptchaError, CaptchaResult};
 
 // Re-export handlers for convenience
-pub use captcha::{solve, solve_batch};
+pub use captcha::{solve, solve_batch, solve_archive};
 pub use health::health_check;
-pub use logs::{get_logs, get_log, update_log, export_logs};
-pub use models::{list_models, upload_model, get_model, update_model, delete_model, set_default_model};
+pub use logs::{get_logs, get_log, update_log, export_logs, recompute_correctness};
+pub use models::{list_models, upload_model, get_model, update_model, delete_model, set_default_model, list_solvers};
 pub use stats::{get_stats, get_model_stats, get_time_series_stats};
-pub use training::{start_training, get_training_status, list_training_jobs, cancel_training};
\ No newline at end of file
+pub use training::{start_training, validate_dataset, get_training_status, list_training_jobs, cancel_training};
+pub use version::version;
+
+/// Require the caller to be an admin (role forwarded by the gateway)
+pub fn require_admin(req: &HttpRequest) -> CaptchaResult<()> {
+    let role = req.headers()
+        .get("X-User-Role")
+        .and_then(|v| v.to_str().ok());
+
+    match role {
+        Some("admin") => Ok(()),
+        _ => Err(CaptchaError::Unauthorized),
+    }
+}
+
+/// Application state over the database in `CAPTCHA_TEST_DATABASE_URL`, for handler tests
+#[cfg(test)]
+pub async fn test_state() -> actix_web::web::Data<crate::AppState> {
+    use std::time::Duration;
+
+    let config = crate::config::Settings::new().unwrap();
+    let solver_manager = crate::solvers::SolverManager::new(&config.models, &config.processing)
+        .await
+        .unwrap();
+
+    actix_web::web::Data::new(crate::AppState {
+        db: crate::db::Database::connect_test().await,
+        solver_manager,
+        usage: crate::auth::UsageRecorder::default(),
+        idempotency: crate::idempotency::IdempotencyStore::new(
+            Duration::from_secs(config.processing.idempotency_ttl_seconds),
+            config.processing.idempotency_max_entries,
+        ),
+        solve_memo: crate::memo::SolveMemo::new(Duration::from_secs(config.processing.solve_memo_ttl_seconds)),
+        solve_log: crate::solve_log::SolveLogQueue::new(config.processing.solve_log_queue_capacity),
+        failures: None,
+        config,
+    })
+}
+
+/// Request from an admin, as forwarded by the gateway, for handler tests
+#[cfg(test)]
+pub fn admin_request() -> HttpRequest {
+    actix_web::test::TestRequest::default()
+        .insert_header(("X-User-Role", "admin"))
+        .to_http_request()
+}
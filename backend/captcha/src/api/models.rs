@@ -1,22 +1,41 @@
 //! Models API Handlers
 
+use std::collections::HashMap;
+use std::time::Duration;
+
 use actix_web::{web, HttpResponse, HttpRequest};
+use actix_web::http::header::ContentDisposition;
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
 use serde::{Deserialize, Serialize};
+use tokio_util::io::ReaderStream;
 
 use crate::AppState;
-use crate::error::CaptchaError;
-use crate::models::CaptchaModel;
+use crate::api::{audit, require_admin};
+use crate::error::{CaptchaError, CaptchaResult};
+use crate::models::{CaptchaModel, HashAlgorithm, ModelType, Page};
+use crate::validation::FieldError;
+
+/// Newest labeled logs per solver a calibration curve is fitted on
+const CALIBRATION_SAMPLE_LIMIT: u32 = 5000;
 
 /// List all available models
 pub async fn list_models(
     state: web::Data<AppState>,
     _req: HttpRequest,
+    query: web::Query<ListModelsQuery>,
 ) -> Result<HttpResponse, CaptchaError> {
-    let models = state.db.get_all_models().await?;
-    
-    let response: Vec<ModelResponse> = models.into_iter().map(|m| m.into()).collect();
-    
-    Ok(HttpResponse::Ok().json(response))
+    let limit = query.limit.unwrap_or(50);
+    let offset = query.offset.unwrap_or(0);
+
+    let models = state.db.get_all_models(limit, offset).await?;
+    let total = state.db.count_models().await?;
+
+    Ok(HttpResponse::Ok().json(Page::<ModelResponse> {
+        items: models.into_iter().map(|m| m.into()).collect(),
+        total,
+        limit,
+        offset,
+    }))
 }
 
 /// Upload a new model
@@ -25,6 +44,8 @@ pub async fn upload_model(
     req: HttpRequest,
     body: web::Json<UploadModelRequest>,
 ) -> Result<HttpResponse, CaptchaError> {
+    require_admin(&req)?;
+
     // Get user info from headers (forwarded by gateway)
     let user_id = req.headers()
         .get("X-User-ID")
@@ -36,24 +57,87 @@ pub async fn upload_model(
         return Err(CaptchaError::BadRequest("Model name is required".to_string()));
     }
 
-    if !["ocr", "cnn", "rnn", "transformer", "ensemble"].contains(&body.model_type.as_str()) {
-        return Err(CaptchaError::BadRequest("Invalid model type".to_string()));
+    let model_type: ModelType = body.model_type.parse().map_err(|reason| {
+        CaptchaError::Validation(vec![FieldError { field: "model_type".to_string(), error: reason }])
+    })?;
+
+    // The name becomes the file name
+    if !is_valid_model_name(&body.name) {
+        return Err(CaptchaError::BadRequest(
+            "Model name may only contain letters, digits, '-', '_' and '.'".to_string()
+        ));
     }
 
-    // TODO: Actually save the model file
-    // For now, just save metadata to database
-    
-    let model = state.db.create_model(
+    // Never replace a file a stored model (possibly the live one) points at
+    let file_path = format!("{}/{}.onnx", state.config.models.path, body.name);
+    if state.db.model_exists(&body.name, &file_path).await?
+        || tokio::fs::try_exists(&file_path).await.unwrap_or(true)
+    {
+        return Err(CaptchaError::Conflict(format!("Model {} already exists", body.name)));
+    }
+
+    // Stage the file with its checksum, which is verified whenever the file is loaded;
+    // it only takes the model's path once the row is inserted
+    let staged = match &body.model_data {
+        Some(data) => {
+            let bytes = BASE64.decode(data.trim())
+                .map_err(|e| CaptchaError::BadRequest(format!("Invalid model_data: {}", e)))?;
+            let temp_path = format!("{}.{}.tmp", file_path, uuid::Uuid::new_v4());
+            if let Err(e) = tokio::fs::write(&temp_path, &bytes).await {
+                let _ = tokio::fs::remove_file(&temp_path).await;
+                return Err(CaptchaError::ProcessingError(format!("Cannot save model file {}: {}", file_path, e)));
+            }
+            Some((temp_path, bytes.len() as u64, HashAlgorithm::Sha256.hash(&bytes)))
+        }
+        None => None,
+    };
+    let (file_size, file_sha256) = staged.as_ref()
+        .map_or((0, None), |(_, size, sha256)| (*size, Some(sha256.as_str())));
+
+    let created = state.db.create_model(
         &body.name,
-        &body.model_type,
+        &model_type,
         &body.version.clone().unwrap_or_else(|| "1.0.0".to_string()),
-        &format!("{}/{}.onnx", state.config.models.path, body.name),
-        0, // file size
+        &file_path,
+        file_size,
+        file_sha256,
+        body.description.as_deref(),
         user_id,
-        body.description.clone(),
-    ).await?;
+    ).await;
+
+    let model_id = match (created, staged) {
+        (Ok(model_id), Some((temp_path, _, _))) => {
+            // Rename is atomic, so a concurrent load never sees a half-written file
+            if let Err(e) = tokio::fs::rename(&temp_path, &file_path).await {
+                let _ = tokio::fs::remove_file(&temp_path).await;
+                return Err(CaptchaError::ProcessingError(format!("Cannot save model file {}: {}", file_path, e)));
+            }
+            model_id
+        }
+        (Ok(model_id), None) => model_id,
+        (Err(e), staged) => {
+            if let Some((temp_path, _, _)) = staged {
+                let _ = tokio::fs::remove_file(&temp_path).await;
+            }
+            return Err(e);
+        }
+    };
+
+    let model = state.db.get_model_by_id(model_id).await?
+        .ok_or(CaptchaError::ModelNotFound(format!("Model {} not found", model_id)))?;
+    let response = ModelResponse::from(model);
+    audit::record(
+        &state, &req, audit::MODEL_CREATE, audit::RESOURCE_MODEL,
+        Some(response.id), None, audit::snapshot(&response),
+    ).await;
+
+    Ok(HttpResponse::Created().json(response))
+}
 
-    Ok(HttpResponse::Created().json(ModelResponse::from(model)))
+/// Model names are used as file names: no path separators or leading dots
+fn is_valid_model_name(name: &str) -> bool {
+    !name.starts_with('.')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
 }
 
 /// Get model by ID
@@ -69,57 +153,141 @@ pub async fn get_model(
     Ok(HttpResponse::Ok().json(ModelResponse::from(model)))
 }
 
-/// Update model
+/// Download a model's stored file (admin)
+pub async fn download_model(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<u64>,
+) -> Result<HttpResponse, CaptchaError> {
+    require_admin(&req)?;
+
+    let model_id = path.into_inner();
+    let model = state.db.get_model_by_id(model_id).await?
+        .ok_or(CaptchaError::ModelNotFound(format!("Model {} not found", model_id)))?;
+
+    let (file, size) = open_model_file(&model).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/octet-stream")
+        .insert_header(ContentDisposition::attachment(download_filename(&model)))
+        .no_chunking(size)
+        .streaming(ReaderStream::new(file)))
+}
+
+/// Open a model's file and get its size. A missing file is `NotFound`, unlike a
+/// missing model row (`ModelNotFound`), so the two can be told apart.
+async fn open_model_file(model: &CaptchaModel) -> CaptchaResult<(tokio::fs::File, u64)> {
+    let file = tokio::fs::File::open(&model.file_path).await.map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => CaptchaError::NotFound(format!(
+            "File of model {} is missing on disk", model.id
+        )),
+        _ => CaptchaError::ProcessingError(format!("Cannot open file of model {}: {}", model.id, e)),
+    })?;
+    let size = file.metadata().await
+        .map_err(|e| CaptchaError::ProcessingError(format!("Cannot read file of model {}: {}", model.id, e)))?
+        .len();
+
+    Ok((file, size))
+}
+
+/// File name offered to the client: the stored file's name, else `<model name>.onnx`
+fn download_filename(model: &CaptchaModel) -> String {
+    std::path::Path::new(&model.file_path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("{}.onnx", model.name))
+}
+
+/// Update model (admin)
 pub async fn update_model(
     state: web::Data<AppState>,
+    req: HttpRequest,
     path: web::Path<u64>,
     body: web::Json<UpdateModelRequest>,
 ) -> Result<HttpResponse, CaptchaError> {
+    require_admin(&req)?;
+
     let model_id = path.into_inner();
     
     // Check if model exists
-    let _existing = state.db.get_model_by_id(model_id).await?
+    let existing = state.db.get_model_by_id(model_id).await?
         .ok_or(CaptchaError::ModelNotFound(format!("Model {} not found", model_id)))?;
 
-    // Update model
+    if body.is_default == Some(true) && body.is_active == Some(false) {
+        return Err(CaptchaError::BadRequest("Cannot make an inactive model the default".to_string()));
+    }
+
+    let removes_default = existing.is_default
+        && (body.is_active == Some(false) || body.is_default == Some(false));
+    let replacement = default_replacement(&state, &existing, removes_default, body.replacement_default_id).await?;
+
+    // Update model, promoting the replacement in the same transaction
     state.db.update_model(
         model_id,
         body.is_active,
         body.is_default,
-        body.description.clone(),
+        body.description.as_deref(),
+        replacement.as_ref().map(|r| r.id),
     ).await?;
+    if let Some(replacement) = &replacement {
+        record_promotion(&state, &req, &existing, replacement).await;
+    }
 
     // Fetch updated model
     let model = state.db.get_model_by_id(model_id).await?
         .ok_or(CaptchaError::ModelNotFound(format!("Model {} not found", model_id)))?;
 
-    Ok(HttpResponse::Ok().json(ModelResponse::from(model)))
+    let response = ModelResponse::from(model);
+    audit::record(
+        &state, &req, audit::MODEL_UPDATE, audit::RESOURCE_MODEL,
+        Some(model_id), audit::snapshot(&ModelResponse::from(existing)), audit::snapshot(&response),
+    ).await;
+
+    Ok(HttpResponse::Ok().json(response))
 }
 
-/// Delete model
+/// Delete model (admin)
 pub async fn delete_model(
     state: web::Data<AppState>,
+    req: HttpRequest,
     path: web::Path<u64>,
+    query: web::Query<DeleteModelQuery>,
 ) -> Result<HttpResponse, CaptchaError> {
+    require_admin(&req)?;
+
     let model_id = path.into_inner();
     
     // Check if model exists
-    let _existing = state.db.get_model_by_id(model_id).await?
+    let existing = state.db.get_model_by_id(model_id).await?
         .ok_or(CaptchaError::ModelNotFound(format!("Model {} not found", model_id)))?;
 
-    // Delete model
-    state.db.delete_model(model_id).await?;
+    let replacement = default_replacement(&state, &existing, existing.is_default, query.replacement_default_id).await?;
+
+    // Delete model, promoting the replacement in the same transaction
+    state.db.delete_model(model_id, replacement.as_ref().map(|r| r.id)).await?;
+    if let Some(replacement) = &replacement {
+        record_promotion(&state, &req, &existing, replacement).await;
+    }
+
+    audit::record(
+        &state, &req, audit::MODEL_DELETE, audit::RESOURCE_MODEL,
+        Some(model_id), audit::snapshot(&ModelResponse::from(existing)), None,
+    ).await;
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "message": "Model deleted successfully"
     })))
 }
 
-/// Set model as default
+/// Set model as default (admin)
 pub async fn set_default_model(
     state: web::Data<AppState>,
+    req: HttpRequest,
     path: web::Path<u64>,
 ) -> Result<HttpResponse, CaptchaError> {
+    require_admin(&req)?;
+
     let model_id = path.into_inner();
     
     // Check if model exists
@@ -137,11 +305,270 @@ pub async fn set_default_model(
     let model = state.db.get_model_by_id(model_id).await?
         .ok_or(CaptchaError::ModelNotFound(format!("Model {} not found", model_id)))?;
 
-    Ok(HttpResponse::Ok().json(ModelResponse::from(model)))
+    let response = ModelResponse::from(model);
+    audit::record(
+        &state, &req, audit::MODEL_SET_DEFAULT, audit::RESOURCE_MODEL,
+        Some(model_id), audit::snapshot(&ModelResponse::from(existing)), audit::snapshot(&response),
+    ).await;
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Model to make the default when a change removes the default model of a type, so
+/// solves asking for that type keep working. The change is rejected when it needs one
+/// and `replacement_id` doesn't name a valid one.
+async fn default_replacement(
+    state: &web::Data<AppState>,
+    model: &CaptchaModel,
+    removes_default: bool,
+    replacement_id: Option<u64>,
+) -> Result<Option<CaptchaModel>, CaptchaError> {
+    let replacement = match replacement_id.filter(|_| removes_default) {
+        Some(id) => Some(
+            state.db.get_model_by_id(id).await?
+                .ok_or(CaptchaError::ModelNotFound(format!("Model {} not found", id)))?,
+        ),
+        None => None,
+    };
+
+    Ok(check_default_replacement(model, removes_default, replacement.as_ref())?.cloned())
+}
+
+/// Audit `replacement` becoming the default in place of `model`
+async fn record_promotion(
+    state: &web::Data<AppState>,
+    req: &HttpRequest,
+    model: &CaptchaModel,
+    replacement: &CaptchaModel,
+) {
+    tracing::info!("Model {} replaces model {} as default", replacement.id, model.id);
+
+    let before = audit::snapshot(&ModelResponse::from(replacement.clone()));
+    let mut after = before.clone();
+    if let Some(after) = after.as_mut() {
+        after["is_default"] = serde_json::json!(true);
+    }
+    audit::record(
+        state, req, audit::MODEL_SET_DEFAULT, audit::RESOURCE_MODEL,
+        Some(replacement.id), before, after,
+    ).await;
+}
+
+/// Reject changes that would leave a model type without a default. Returns the model to promote, if any.
+fn check_default_replacement<'a>(
+    model: &CaptchaModel,
+    removes_default: bool,
+    replacement: Option<&'a CaptchaModel>,
+) -> Result<Option<&'a CaptchaModel>, CaptchaError> {
+    if !removes_default {
+        return Ok(None);
+    }
+
+    match replacement {
+        None => Err(CaptchaError::BadRequest(format!(
+            "Model {} is the default {} model; set replacement_default_id to another active {} model",
+            model.id, model.model_type, model.model_type
+        ))),
+        Some(r) if r.id == model.id => Err(CaptchaError::BadRequest(
+            "Replacement default must be a different model".to_string()
+        )),
+        Some(r) if r.model_type != model.model_type => Err(CaptchaError::BadRequest(format!(
+            "Replacement default model {} is a {} model, expected {}", r.id, r.model_type, model.model_type
+        ))),
+        Some(r) if !r.is_active => Err(CaptchaError::BadRequest(format!(
+            "Replacement default model {} is inactive", r.id
+        ))),
+        Some(r) => Ok(Some(r)),
+    }
+}
+
+/// List loaded solvers and whether each runs real inference or mock output
+pub async fn list_solvers(
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, CaptchaError> {
+    Ok(HttpResponse::Ok().json(state.solver_manager.solver_info()))
+}
+
+/// What a solver accepts: charset, input size, `top_k` and language support
+pub async fn get_solver_schema(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, CaptchaError> {
+    Ok(HttpResponse::Ok().json(state.solver_manager.solver_schema(&path.into_inner())?))
+}
+
+/// Current ensemble weights (historical accuracy per solver)
+pub async fn get_ensemble_weights(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+) -> Result<HttpResponse, CaptchaError> {
+    require_admin(&req)?;
+
+    Ok(HttpResponse::Ok().json(EnsembleWeightsResponse {
+        enabled: state.config.processing.ensemble_accuracy_weighting,
+        weights: state.solver_manager.ensemble_weights().await,
+        temperatures: state.solver_manager.temperatures().await,
+        calibration: state.solver_manager.calibration_samples().await,
+    }))
+}
+
+/// Reload ensemble weights and confidence temperatures from active models, and
+/// refit confidence calibration on labeled logs
+pub async fn refresh_ensemble_weights(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+) -> Result<HttpResponse, CaptchaError> {
+    require_admin(&req)?;
+
+    let models = state.db.get_active_models().await?;
+    let weights = state.solver_manager.refresh_ensemble_weights(&models).await;
+    let temperatures = state.solver_manager.refresh_temperatures(&models).await;
+    let calibration = refresh_calibration(&state).await?;
+
+    Ok(HttpResponse::Ok().json(EnsembleWeightsResponse {
+        enabled: state.config.processing.ensemble_accuracy_weighting,
+        weights,
+        temperatures,
+        calibration,
+    }))
+}
+
+/// Recompute each model's accuracy from labeled logs and store it (admin).
+/// Models with fewer than `accuracy_min_samples` labeled logs keep their accuracy.
+pub async fn recompute_accuracy(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+) -> Result<HttpResponse, CaptchaError> {
+    require_admin(&req)?;
+
+    let models = refresh_model_accuracy(&state).await?;
+    for change in models.iter().filter(|change| change.updated) {
+        audit::record(
+            &state, &req, audit::MODEL_RECOMPUTE_ACCURACY, audit::RESOURCE_MODEL, Some(change.id),
+            Some(serde_json::json!({ "accuracy": change.before })),
+            Some(serde_json::json!({ "accuracy": change.after })),
+        ).await;
+    }
+
+    Ok(HttpResponse::Ok().json(RecomputeAccuracyResponse {
+        min_samples: state.config.processing.accuracy_min_samples,
+        models,
+    }))
+}
+
+/// Write the accuracy measured on labeled logs back to the models, then reload
+/// the ensemble weights so they follow production performance
+pub async fn refresh_model_accuracy(state: &AppState) -> CaptchaResult<Vec<AccuracyChange>> {
+    let min_samples = state.config.processing.accuracy_min_samples;
+
+    let mut changes = Vec::new();
+    for (id, name, before, labeled, measured) in state.db.get_model_accuracy_stats().await? {
+        let change = accuracy_change(id, name, before, labeled.max(0) as u64, measured, min_samples);
+        if let (true, Some(accuracy)) = (change.updated, change.after) {
+            state.db.set_model_accuracy(id, accuracy).await?;
+        }
+        changes.push(change);
+    }
+
+    let active = state.db.get_active_models().await?;
+    state.solver_manager.refresh_ensemble_weights(&active).await;
+    refresh_calibration(state).await?;
+
+    Ok(changes)
+}
+
+/// Refit each solver's confidence calibration on its newest labeled logs.
+/// Returns the samples behind each fitted curve.
+pub async fn refresh_calibration(state: &AppState) -> CaptchaResult<HashMap<String, usize>> {
+    let mut samples = HashMap::new();
+    for name in state.solver_manager.available_solvers() {
+        let rows = state.db.get_calibration_samples(&name, CALIBRATION_SAMPLE_LIMIT).await?;
+        let rows = rows.into_iter().map(|(confidence, correct)| (confidence as f32, correct)).collect();
+        samples.insert(name, rows);
+    }
+
+    Ok(state.solver_manager.refresh_calibration(&samples).await)
+}
+
+/// Periodically recompute model accuracy in the background
+pub fn spawn_accuracy_refresher(state: web::Data<AppState>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match refresh_model_accuracy(&state).await {
+                Ok(changes) => {
+                    let updated = changes.iter().filter(|change| change.updated).count();
+                    tracing::info!("Recomputed model accuracy ({} of {} models updated)", updated, changes.len());
+                }
+                Err(e) => tracing::error!("Failed to recompute model accuracy: {}", e),
+            }
+        }
+    });
+}
+
+/// Periodically re-verify model file checksums, marking solvers whose file changed unavailable
+pub fn spawn_checksum_verifier(state: web::Data<AppState>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        // The first tick completes immediately; startup already verified the files
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            match state.db.get_active_models().await {
+                Ok(models) => {
+                    state.solver_manager.verify_checksums(&models).await;
+                }
+                Err(e) => tracing::error!("Failed to load models for checksum verification: {}", e),
+            }
+        }
+    });
+}
+
+/// Periodically retry loading models missing at startup, until every solver has its model
+pub fn spawn_model_loader(state: web::Data<AppState>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        // The first tick completes immediately; startup just tried to load them
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            if state.solver_manager.retry_model_loads().await.is_empty() {
+                tracing::info!("All solver models loaded, stopping retries");
+                break;
+            }
+        }
+    });
+}
+
+/// New accuracy for a model: the measured share of correct labeled logs, rounded
+/// to the 4 decimals the column stores, once there are at least `min_samples` of them
+fn accuracy_change(
+    id: u64,
+    name: String,
+    before: Option<f64>,
+    labeled_samples: u64,
+    measured: Option<f64>,
+    min_samples: u64,
+) -> AccuracyChange {
+    let after = match measured {
+        Some(accuracy) if labeled_samples > 0 && labeled_samples >= min_samples => {
+            Some((accuracy.clamp(0.0, 1.0) * 10_000.0).round() / 10_000.0)
+        }
+        _ => before,
+    };
+
+    AccuracyChange { id, name, labeled_samples, before, updated: after != before, after }
 }
 
 // Request/Response types
 
+#[derive(Debug, Deserialize)]
+pub struct ListModelsQuery {
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct UploadModelRequest {
     pub name: String,
@@ -157,6 +584,42 @@ pub struct UpdateModelRequest {
     pub is_active: Option<bool>,
     pub is_default: Option<bool>,
     pub description: Option<String>,
+    /// Active model to make default when this change demotes or deactivates the default
+    pub replacement_default_id: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteModelQuery {
+    /// Active model to make default when deleting the default
+    pub replacement_default_id: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EnsembleWeightsResponse {
+    pub enabled: bool,
+    pub weights: HashMap<String, f32>,
+    /// Confidence temperature per solver (solvers without one are uncalibrated)
+    pub temperatures: HashMap<String, f32>,
+    /// Labeled logs behind each solver's probability calibration (absent: uncalibrated)
+    pub calibration: HashMap<String, usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecomputeAccuracyResponse {
+    /// Labeled logs required before a model's accuracy is replaced
+    pub min_samples: u64,
+    pub models: Vec<AccuracyChange>,
+}
+
+/// A model's accuracy before and after recomputing it from labeled logs
+#[derive(Debug, Serialize, PartialEq)]
+pub struct AccuracyChange {
+    pub id: u64,
+    pub name: String,
+    pub labeled_samples: u64,
+    pub before: Option<f64>,
+    pub after: Option<f64>,
+    pub updated: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -166,6 +629,7 @@ pub struct ModelResponse {
     #[serde(rename = "type")]
     pub model_type: String,
     pub version: String,
+    pub file_sha256: Option<String>,
     pub accuracy: Option<f64>,
     pub is_active: bool,
     pub is_default: bool,
@@ -181,6 +645,7 @@ impl From<CaptchaModel> for ModelResponse {
             name: model.name,
             model_type: model.model_type,
             version: model.version,
+            file_sha256: model.file_sha256,
             accuracy: model.accuracy,
             is_active: model.is_active,
             is_default: model.is_default,
@@ -189,4 +654,276 @@ impl From<CaptchaModel> for ModelResponse {
             updated_at: model.updated_at.to_rfc3339(),
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AuditLogQuery;
+
+    fn model(id: u64, is_active: bool, is_default: bool) -> CaptchaModel {
+        CaptchaModel {
+            id,
+            name: format!("model-{}", id),
+            model_type: "cnn".to_string(),
+            version: "1.0.0".to_string(),
+            file_path: format!("/tmp/models/model-{}.onnx", id),
+            file_size_bytes: 0,
+            file_sha256: None,
+            accuracy: None,
+            is_active,
+            is_default,
+            metadata: None,
+            description: None,
+            created_by: None,
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_demote_default_without_replacement_rejected() {
+        let default = model(1, true, true);
+
+        let result = check_default_replacement(&default, true, None);
+        assert!(matches!(result, Err(CaptchaError::BadRequest(_))));
+    }
+
+    #[test]
+    fn test_demote_default_with_replacement() {
+        let default = model(1, true, true);
+        let other = model(2, true, false);
+
+        let promoted = check_default_replacement(&default, true, Some(&other)).unwrap();
+        assert_eq!(promoted.map(|m| m.id), Some(2));
+    }
+
+    #[test]
+    fn test_invalid_replacement_rejected() {
+        let default = model(1, true, true);
+        let inactive = model(2, false, false);
+
+        assert!(check_default_replacement(&default, true, Some(&inactive)).is_err());
+        assert!(check_default_replacement(&default, true, Some(&default)).is_err());
+
+        let mut other_type = model(3, true, false);
+        other_type.model_type = "ocr".to_string();
+        assert!(check_default_replacement(&default, true, Some(&other_type)).is_err());
+    }
+
+    #[test]
+    fn test_model_name_validation() {
+        assert!(is_valid_model_name("captcha_cnn-v2.1"));
+        assert!(!is_valid_model_name("../captcha_cnn"));
+        assert!(!is_valid_model_name("models/cnn"));
+        assert!(!is_valid_model_name(".hidden"));
+    }
+
+    #[test]
+    fn test_download_filename() {
+        assert_eq!(download_filename(&model(1, true, false)), "model-1.onnx");
+
+        let mut unnamed = model(2, true, false);
+        unnamed.file_path = "/".to_string();
+        assert_eq!(download_filename(&unnamed), "model-2.onnx");
+    }
+
+    #[tokio::test]
+    async fn test_open_model_file() {
+        let mut stored = model(1, true, false);
+        stored.file_path = std::env::temp_dir()
+            .join(format!("captcha-model-{}.onnx", uuid::Uuid::new_v4()))
+            .to_string_lossy()
+            .into_owned();
+
+        assert!(matches!(open_model_file(&stored).await, Err(CaptchaError::NotFound(_))));
+
+        std::fs::write(&stored.file_path, b"onnx").unwrap();
+        let (_, size) = open_model_file(&stored).await.unwrap();
+        std::fs::remove_file(&stored.file_path).unwrap();
+        assert_eq!(size, 4);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires CAPTCHA_TEST_DATABASE_URL"]
+    async fn test_download_unknown_model_not_found() {
+        let state = crate::api::test_state().await;
+
+        let result = download_model(state, crate::api::admin_request(), web::Path::from(u64::MAX)).await;
+        assert!(matches!(result, Err(CaptchaError::ModelNotFound(_))));
+    }
+
+    /// Insert an active, non-default model of `model_type` into the test database
+    async fn insert_model(state: &AppState, model_type: &str) -> u64 {
+        sqlx::query(
+            "INSERT INTO captcha_models (name, type, file_path, is_active, is_default) VALUES (?, ?, '/tmp/m.onnx', true, false)"
+        )
+        .bind(format!("handler-{}", uuid::Uuid::new_v4().simple()))
+        .bind(model_type)
+        .execute(state.db.pool())
+        .await
+        .unwrap()
+        .last_insert_id()
+    }
+
+    #[tokio::test]
+    #[ignore = "requires CAPTCHA_TEST_DATABASE_URL"]
+    async fn test_default_model_guard_through_handlers() {
+        let state = crate::api::test_state().await;
+        let default = insert_model(&state, "rnn").await;
+        let other = insert_model(&state, "rnn").await;
+        state.db.set_default_model(default).await.unwrap();
+
+        let no_replacement = || web::Query(DeleteModelQuery { replacement_default_id: None });
+        let anonymous = actix_web::test::TestRequest::default().to_http_request();
+        let result = delete_model(state.clone(), anonymous, web::Path::from(default), no_replacement()).await;
+        assert!(matches!(result, Err(CaptchaError::Unauthorized)));
+
+        // Deactivating or deleting the default without a replacement changes nothing
+        let deactivate = UpdateModelRequest {
+            is_active: Some(false),
+            is_default: None,
+            description: None,
+            replacement_default_id: None,
+        };
+        let result = update_model(state.clone(), crate::api::admin_request(), web::Path::from(default), web::Json(deactivate)).await;
+        assert!(matches!(result, Err(CaptchaError::BadRequest(_))));
+        let result = delete_model(state.clone(), crate::api::admin_request(), web::Path::from(default), no_replacement()).await;
+        assert!(matches!(result, Err(CaptchaError::BadRequest(_))));
+        let kept = state.db.get_model_by_id(default).await.unwrap().unwrap();
+        assert!(kept.is_active && kept.is_default);
+
+        // With a replacement, the default moves in the same operation
+        let replace = web::Query(DeleteModelQuery { replacement_default_id: Some(other) });
+        delete_model(state.clone(), crate::api::admin_request(), web::Path::from(default), replace).await.unwrap();
+        assert!(state.db.get_model_by_id(default).await.unwrap().is_none());
+        assert!(state.db.get_model_by_id(other).await.unwrap().unwrap().is_default);
+
+        sqlx::query("DELETE FROM captcha_models WHERE id = ?")
+            .bind(other)
+            .execute(state.db.pool())
+            .await
+            .unwrap();
+    }
+
+    /// Actions audited on a model, newest first
+    async fn audited_actions(state: &AppState, model_id: u64) -> Vec<String> {
+        let query = AuditLogQuery {
+            resource_type: Some(audit::RESOURCE_MODEL.to_string()),
+            resource_id: Some(model_id),
+            ..Default::default()
+        };
+        let (entries, _) = state.db.list_audit_logs(&query, 50, 0).await.unwrap();
+        entries.into_iter().map(|entry| entry.action).collect()
+    }
+
+    #[tokio::test]
+    #[ignore = "requires CAPTCHA_TEST_DATABASE_URL"]
+    async fn test_model_mutations_are_audited() {
+        let state = crate::api::test_state().await;
+        let changed = insert_model(&state, "transformer").await;
+        let promoted = insert_model(&state, "transformer").await;
+
+        let anonymous = actix_web::test::TestRequest::default().to_http_request();
+        let result = set_default_model(state.clone(), anonymous, web::Path::from(promoted)).await;
+        assert!(matches!(result, Err(CaptchaError::Unauthorized)));
+        assert!(audited_actions(&state, promoted).await.is_empty());
+
+        let update = UpdateModelRequest {
+            is_active: None,
+            is_default: None,
+            description: Some("audited".to_string()),
+            replacement_default_id: None,
+        };
+        update_model(state.clone(), crate::api::admin_request(), web::Path::from(changed), web::Json(update)).await.unwrap();
+        let query = web::Query(DeleteModelQuery { replacement_default_id: None });
+        delete_model(state.clone(), crate::api::admin_request(), web::Path::from(changed), query).await.unwrap();
+        set_default_model(state.clone(), crate::api::admin_request(), web::Path::from(promoted)).await.unwrap();
+
+        let mut actions = audited_actions(&state, changed).await;
+        actions.sort();
+        assert_eq!(actions, vec![audit::MODEL_DELETE, audit::MODEL_UPDATE]);
+        assert_eq!(audited_actions(&state, promoted).await, vec![audit::MODEL_SET_DEFAULT]);
+
+        sqlx::query("DELETE FROM captcha_models WHERE id = ?")
+            .bind(promoted)
+            .execute(state.db.pool())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "requires CAPTCHA_TEST_DATABASE_URL"]
+    async fn test_upload_model_stores_request_fields() {
+        let state = crate::api::test_state().await;
+        let user_id = sqlx::query("INSERT INTO users (email, password_hash) VALUES (?, 'x')")
+            .bind(format!("upload-{}@example.com", uuid::Uuid::new_v4().simple()))
+            .execute(state.db.pool())
+            .await
+            .unwrap()
+            .last_insert_id();
+        let upload = |model_type: &str| UploadModelRequest {
+            name: format!("upload-{}", uuid::Uuid::new_v4().simple()),
+            model_type: model_type.to_string(),
+            version: None,
+            description: Some("uploaded".to_string()),
+            model_data: None,
+        };
+        let request = || {
+            actix_web::test::TestRequest::default()
+                .insert_header(("X-User-Role", "admin"))
+                .insert_header(("X-User-ID", user_id.to_string()))
+                .to_http_request()
+        };
+
+        let Err(CaptchaError::Validation(errors)) = upload_model(state.clone(), request(), web::Json(upload("lstm"))).await else {
+            panic!("expected a validation error");
+        };
+        assert_eq!(errors[0].field, "model_type");
+
+        let body = upload("cnn");
+        let name = body.name.clone();
+        upload_model(state.clone(), request(), web::Json(body)).await.unwrap();
+        let model = state.db.get_model_by_name(&name).await.unwrap().unwrap();
+        assert_eq!(model.model_type, "cnn");
+        assert_eq!(model.description.as_deref(), Some("uploaded"));
+        assert_eq!(model.created_by, Some(user_id));
+
+        sqlx::query("DELETE FROM captcha_models WHERE id = ?")
+            .bind(model.id)
+            .execute(state.db.pool())
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM users WHERE id = ?")
+            .bind(user_id)
+            .execute(state.db.pool())
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn test_accuracy_change() {
+        let measured = accuracy_change(1, "cnn".to_string(), Some(0.95), 120, Some(0.871234), 50);
+        assert_eq!(measured.after, Some(0.8712));
+        assert!(measured.updated);
+
+        // Too few labeled logs: the stored accuracy is kept
+        let sparse = accuracy_change(2, "ocr".to_string(), Some(0.7), 10, Some(0.2), 50);
+        assert_eq!(sparse.after, Some(0.7));
+        assert!(!sparse.updated);
+
+        let unlabeled = accuracy_change(3, "rnn".to_string(), None, 0, None, 0);
+        assert_eq!(unlabeled.after, None);
+        assert!(!unlabeled.updated);
+
+        let unchanged = accuracy_change(4, "cnn-v2".to_string(), Some(0.9), 60, Some(0.9), 50);
+        assert!(!unchanged.updated);
+    }
+
+    #[test]
+    fn test_non_default_change_needs_no_replacement() {
+        let model = model(3, true, false);
+
+        assert!(check_default_replacement(&model, false, None).unwrap().is_none());
+    }
+}
@@ -0,0 +1,117 @@
+//! Self-Test Handler
+//!
+//! Runs every ready solver against a bundled captcha with known text, exercising
+//! decoding, preprocessing, inference and normalization without client input.
+
+use std::time::Instant;
+
+use actix_web::{web, HttpResponse};
+use serde::Serialize;
+
+use crate::AppState;
+use crate::api::captcha::load_image;
+use crate::error::CaptchaError;
+use crate::models::{CaseMode, NormalizeMode};
+use crate::solvers::SolveHints;
+
+/// Bundled captcha image
+const SELFTEST_IMAGE: &[u8] = include_bytes!("../../assets/selftest.png");
+
+/// Text rendered in [`SELFTEST_IMAGE`]
+const SELFTEST_TEXT: &str = "7K4P2";
+
+/// Solve the bundled captcha with each ready solver. Responds 503 unless every solver passes.
+pub async fn selftest(
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, CaptchaError> {
+    let image = load_image(SELFTEST_IMAGE, &state.config.processing.input_options())?;
+    let hints = SolveHints {
+        normalize: Some(NormalizeMode::UpperAlnum),
+        expected_length: Some(SELFTEST_TEXT.len()),
+        // Each solver must answer on its own
+        disable_fallback: true,
+        ..Default::default()
+    };
+
+    let mut solvers = Vec::new();
+    for info in state.solver_manager.solver_info().into_iter().filter(|info| info.ready) {
+        let start = Instant::now();
+        let outcome = state.solver_manager.solve(&image, Some(&info.name), None, &hints).await;
+        let time_ms = start.elapsed().as_millis() as u64;
+
+        solvers.push(match outcome {
+            Ok(result) => SelftestSolverResult {
+                passed: state.solver_manager.normalizer()
+                    .matches(&result.text, SELFTEST_TEXT, NormalizeMode::UpperAlnum, CaseMode::Upper),
+                name: info.name,
+                mock: info.mock,
+                text: Some(result.text),
+                confidence: Some(result.confidence),
+                time_ms,
+                error: None,
+            },
+            Err(e) => SelftestSolverResult {
+                name: info.name,
+                mock: info.mock,
+                passed: false,
+                text: None,
+                confidence: None,
+                time_ms,
+                error: Some(e.to_string()),
+            },
+        });
+    }
+
+    let passed = !solvers.is_empty() && solvers.iter().all(|s| s.passed);
+    for failed in solvers.iter().filter(|s| !s.passed) {
+        tracing::warn!(
+            "Self-test failed for solver {}: got {:?}, error {:?}",
+            failed.name, failed.text, failed.error
+        );
+    }
+
+    let response = SelftestResponse {
+        passed,
+        expected: SELFTEST_TEXT.to_string(),
+        solvers,
+    };
+
+    if passed {
+        Ok(HttpResponse::Ok().json(response))
+    } else {
+        Ok(HttpResponse::ServiceUnavailable().json(response))
+    }
+}
+
+// Response types
+
+#[derive(Debug, Serialize)]
+pub struct SelftestResponse {
+    /// Every ready solver recognized the bundled captcha
+    pub passed: bool,
+    pub expected: String,
+    pub solvers: Vec<SelftestSolverResult>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SelftestSolverResult {
+    pub name: String,
+    pub mock: bool,
+    pub passed: bool,
+    pub text: Option<String>,
+    pub confidence: Option<f32>,
+    pub time_ms: u64,
+    pub error: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solvers::preprocessor::InputOptions;
+
+    #[test]
+    fn test_bundled_image_decodes() {
+        let image = load_image(SELFTEST_IMAGE, &InputOptions::default()).unwrap();
+        assert_eq!((image.width(), image.height()), (160, 50));
+    }
+}
@@ -17,7 +17,7 @@ pub async fn get_stats(
         .and_then(|v| v.to_str().ok())
         .and_then(|s| s.parse::<u64>().ok());
 
-    let stats = state.db.get_stats(user_id).await?;
+    let stats = state.db.get_stats(user_id, state.config.processing.success_confidence_floor).await?;
 
     Ok(HttpResponse::Ok().json(stats))
 }
@@ -65,9 +65,11 @@ pub struct TimeSeriesQuery {
 pub struct StatsResponse {
     pub total_requests: u64,
     pub successful_requests: u64,
+    pub low_confidence_requests: u64,
     pub failed_requests: u64,
     pub average_processing_time_ms: f64,
     pub accuracy_rate: f64,
+    pub confidence_floor: f64,
     pub models_count: u32,
     pub active_models_count: u32,
 }
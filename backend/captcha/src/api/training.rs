@@ -5,7 +5,17 @@ use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
 
 use crate::AppState;
-use crate::error::CaptchaError;
+use crate::api::audit;
+use crate::dataset;
+use crate::error::{CaptchaError, CaptchaResult};
+use crate::models::{ModelType, Page, TrainingStatus, MODEL_TYPES};
+use crate::validation::Validator;
+
+/// Default number of individual problems returned by dataset validation
+const DEFAULT_MAX_PROBLEMS: usize = 50;
+
+/// Upper bound for `max_problems`
+const MAX_PROBLEMS_LIMIT: usize = 1000;
 
 /// Start a new training job
 pub async fn start_training(
@@ -19,20 +29,14 @@ pub async fn start_training(
         .and_then(|v| v.to_str().ok())
         .and_then(|s| s.parse::<u64>().ok());
 
-    // Validate request
-    if body.name.is_empty() {
-        return Err(CaptchaError::BadRequest("Job name is required".to_string()));
-    }
-
-    if !["ocr", "cnn", "rnn", "transformer", "ensemble"].contains(&body.model_type.as_str()) {
-        return Err(CaptchaError::BadRequest("Invalid model type".to_string()));
-    }
+    body.validate()?;
+    let model_type: ModelType = body.model_type.parse().map_err(CaptchaError::BadRequest)?;
 
     // Create training job in database
     let job = state.db.create_training_job(
         user_id,
         &body.name,
-        &body.model_type,
+        &model_type,
         &serde_json::to_value(&body.config).unwrap_or_default(),
         body.dataset_path.as_deref(),
     ).await?;
@@ -45,6 +49,32 @@ pub async fn start_training(
     Ok(HttpResponse::Created().json(TrainingJobResponse::from(job)))
 }
 
+/// Validate a dataset without starting training
+pub async fn validate_dataset(
+    state: web::Data<AppState>,
+    body: web::Json<ValidateDatasetRequest>,
+) -> Result<HttpResponse, CaptchaError> {
+    if body.dataset_path.trim().is_empty() {
+        return Err(CaptchaError::BadRequest("Dataset path is required".to_string()));
+    }
+
+    let root = std::path::PathBuf::from(body.dataset_path.trim());
+    let max_problems = body.max_problems.unwrap_or(DEFAULT_MAX_PROBLEMS).min(MAX_PROBLEMS_LIMIT);
+    let max_image_bytes = state.config.processing.max_image_size_mb as u64 * 1024 * 1024;
+
+    // Decoding every image is CPU and disk bound
+    let report = web::block(move || dataset::validate_dataset(&root, max_problems, max_image_bytes))
+        .await
+        .map_err(|e| CaptchaError::ProcessingError(e.to_string()))??;
+
+    tracing::info!(
+        "Validated dataset {}: {} images, {} problems",
+        body.dataset_path, report.images_total, report.problems_total
+    );
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
 /// Get training job status
 pub async fn get_training_status(
     state: web::Data<AppState>,
@@ -70,16 +100,23 @@ pub async fn list_training_jobs(
         .and_then(|v| v.to_str().ok())
         .and_then(|s| s.parse::<u64>().ok());
 
+    let limit = query.limit.unwrap_or(20);
+    let offset = query.offset.unwrap_or(0);
+
     let jobs = state.db.list_training_jobs(
         user_id,
-        query.status.as_deref(),
-        query.limit.unwrap_or(20),
-        query.offset.unwrap_or(0),
+        query.status,
+        limit,
+        offset,
     ).await?;
+    let total = state.db.count_training_jobs(user_id, query.status).await?;
 
-    let response: Vec<TrainingJobResponse> = jobs.into_iter().map(|j| j.into()).collect();
-
-    Ok(HttpResponse::Ok().json(response))
+    Ok(HttpResponse::Ok().json(Page::<TrainingJobResponse> {
+        items: jobs.into_iter().map(|j| j.into()).collect(),
+        total,
+        limit,
+        offset,
+    }))
 }
 
 /// Cancel a training job
@@ -106,15 +143,25 @@ pub async fn cancel_training(
         }
     }
 
-    // Check if job can be cancelled
-    if job.status != "pending" && job.status != "running" {
+    // Check if job can be cancelled; the update checks again under a row lock
+    if !job.status.can_transition_to(TrainingStatus::Cancelled) {
         return Err(CaptchaError::BadRequest(
             format!("Cannot cancel job with status: {}", job.status)
         ));
     }
 
     // Cancel the job
-    state.db.update_training_job_status(job_id, "cancelled", None).await?;
+    state.db.update_training_job_status(job_id, TrainingStatus::Cancelled, None).await?;
+
+    let before = TrainingJobResponse::from(job);
+    let mut after = audit::snapshot(&before);
+    if let Some(after) = after.as_mut() {
+        after["status"] = serde_json::json!(TrainingStatus::Cancelled);
+    }
+    audit::record(
+        &state, &req, audit::TRAINING_CANCEL, audit::RESOURCE_TRAINING_JOB,
+        Some(job_id), audit::snapshot(&before), after,
+    ).await;
 
     // TODO: Actually stop the training process if running
 
@@ -133,6 +180,28 @@ pub struct StartTrainingRequest {
     pub dataset_path: Option<String>,
 }
 
+impl StartTrainingRequest {
+    /// Reject the request with every invalid field, including those of `config`
+    fn validate(&self) -> CaptchaResult<()> {
+        let mut v = Validator::new();
+        v.check(!self.name.is_empty(), "name", "is required");
+        v.check(
+            MODEL_TYPES.contains(&self.model_type.as_str()),
+            "model_type",
+            format!("must be one of: {}", MODEL_TYPES.join(", ")),
+        );
+        v.nested("config", |v| self.config.check(v));
+        v.finish()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ValidateDatasetRequest {
+    pub dataset_path: String,
+    /// Maximum number of individual problems to return
+    pub max_problems: Option<usize>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TrainingConfig {
     #[serde(default = "default_epochs")]
@@ -150,6 +219,18 @@ pub struct TrainingConfig {
     pub patience: Option<u32>,
 }
 
+impl TrainingConfig {
+    fn check(&self, v: &mut Validator) {
+        v.check(self.epochs >= 1, "epochs", "must be >= 1");
+        v.check(self.batch_size >= 1, "batch_size", "must be >= 1");
+        v.check(self.learning_rate.is_finite() && self.learning_rate > 0.0, "learning_rate", "must be > 0");
+        v.check((0.0..1.0).contains(&self.validation_split), "validation_split", "must be >= 0 and < 1");
+        if let Some(patience) = self.patience {
+            v.check(patience >= 1, "patience", "must be >= 1");
+        }
+    }
+}
+
 fn default_epochs() -> u32 { 100 }
 fn default_batch_size() -> u32 { 32 }
 fn default_learning_rate() -> f64 { 0.001 }
@@ -157,7 +238,7 @@ fn default_validation_split() -> f64 { 0.2 }
 
 #[derive(Debug, Deserialize)]
 pub struct ListJobsQuery {
-    pub status: Option<String>,
+    pub status: Option<TrainingStatus>,
     pub limit: Option<u32>,
     pub offset: Option<u32>,
 }
@@ -167,7 +248,7 @@ pub struct TrainingJobResponse {
     pub id: u64,
     pub user_id: Option<u64>,
     pub name: String,
-    pub status: String,
+    pub status: TrainingStatus,
     pub model_type: String,
     pub config: serde_json::Value,
     pub progress: f64,
@@ -199,4 +280,82 @@ impl From<crate::models::TrainingJob> for TrainingJobResponse {
             created_at: job.created_at.to_rfc3339(),
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_start_training_validation() {
+        let request: StartTrainingRequest = serde_json::from_value(serde_json::json!({
+            "name": "digits",
+            "model_type": "cnn",
+            "config": {}
+        })).unwrap();
+        assert!(request.validate().is_ok());
+
+        let invalid: StartTrainingRequest = serde_json::from_value(serde_json::json!({
+            "name": "",
+            "model_type": "lstm",
+            "config": { "epochs": 0, "learning_rate": -0.1, "validation_split": 1.0, "patience": 5 }
+        })).unwrap();
+        let Err(CaptchaError::Validation(errors)) = invalid.validate() else {
+            panic!("expected a validation error");
+        };
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, vec!["name", "model_type", "config.epochs", "config.learning_rate", "config.validation_split"]);
+        assert_eq!(errors[2].error, "must be >= 1");
+    }
+
+    #[test]
+    fn test_http_solver_is_not_trainable() {
+        let request: StartTrainingRequest = serde_json::from_value(serde_json::json!({
+            "name": "remote",
+            "model_type": "http",
+            "config": {}
+        })).unwrap();
+        assert!(matches!(request.validate(), Err(CaptchaError::Validation(_))));
+    }
+
+    #[test]
+    fn test_training_status_parsing() {
+        for status in TrainingStatus::ALL {
+            assert_eq!(status.as_str().parse::<TrainingStatus>(), Ok(status));
+            assert_eq!(serde_json::to_value(status).unwrap(), serde_json::json!(status.to_string()));
+        }
+        assert_eq!(TrainingStatus::try_from("running"), Ok(TrainingStatus::Running));
+
+        // Near misses are unknown statuses, not silently empty filters
+        for invalid in ["cancelled ", "Cancelled", "done", ""] {
+            let err = invalid.parse::<TrainingStatus>().unwrap_err();
+            assert!(err.contains("expected one of: pending, running, completed, failed, cancelled"), "{}", err);
+        }
+
+        let query: ListJobsQuery = serde_json::from_value(serde_json::json!({ "status": "failed" })).unwrap();
+        assert_eq!(query.status, Some(TrainingStatus::Failed));
+        assert!(serde_json::from_value::<ListJobsQuery>(serde_json::json!({ "status": "cancelled " })).is_err());
+    }
+
+    #[test]
+    fn test_training_status_transitions() {
+        use TrainingStatus::*;
+
+        // (from, to) pairs the lifecycle allows; every other pair is rejected
+        let legal = [
+            (Pending, Running), (Pending, Failed), (Pending, Cancelled),
+            (Running, Running), (Running, Completed), (Running, Failed), (Running, Cancelled),
+        ];
+        for from in TrainingStatus::ALL {
+            for to in TrainingStatus::ALL {
+                assert_eq!(from.can_transition_to(to), legal.contains(&(from, to)), "{} -> {}", from, to);
+            }
+        }
+
+        for status in [Completed, Failed, Cancelled] {
+            assert!(status.is_terminal());
+            assert!(TrainingStatus::ALL.iter().all(|next| !status.can_transition_to(*next)));
+        }
+        assert!(!Pending.is_terminal() && !Running.is_terminal());
+    }
+}
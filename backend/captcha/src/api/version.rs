@@ -0,0 +1,47 @@
+//! Version and Build Info Handler
+
+use actix_web::HttpResponse;
+use chrono::DateTime;
+use serde::Serialize;
+
+/// Crate version, build provenance and compiled-in features
+pub async fn version() -> HttpResponse {
+    let build_timestamp = env!("BUILD_TIMESTAMP")
+        .parse::<i64>()
+        .ok()
+        .and_then(|secs| DateTime::from_timestamp(secs, 0))
+        .map(|t| t.to_rfc3339());
+
+    HttpResponse::Ok().json(VersionResponse {
+        service: "captcha".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        git_commit: env!("BUILD_GIT_COMMIT").to_string(),
+        build_timestamp,
+        features: BuildFeatures {
+            onnx: cfg!(feature = "onnx"),
+            tesseract: cfg!(feature = "tesseract"),
+            avif: cfg!(feature = "avif"),
+            grpc: cfg!(feature = "grpc"),
+        },
+    })
+}
+
+// Response types
+
+#[derive(Debug, Serialize)]
+pub struct VersionResponse {
+    pub service: String,
+    pub version: String,
+    pub git_commit: String,
+    pub build_timestamp: Option<String>,
+    pub features: BuildFeatures,
+}
+
+/// Optional cargo features; without `onnx`/`tesseract` the solvers serve mock output
+#[derive(Debug, Serialize)]
+pub struct BuildFeatures {
+    pub onnx: bool,
+    pub tesseract: bool,
+    pub avif: bool,
+    pub grpc: bool,
+}
@@ -0,0 +1,367 @@
+//! API Key Authentication
+//!
+//! Generation and verification of API keys. Only a salted hash and a short
+//! prefix of each key are stored; the plaintext is shown once at creation.
+//!
+//! Requests carrying an `X-API-Key` header are authenticated by
+//! [`api_key_auth`], which also enforces the key's scopes per route and
+//! records usage in a [`UsageRecorder`] that is flushed periodically.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{web, HttpMessage};
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+use crate::AppState;
+use crate::db::Database;
+use crate::error::CaptchaError;
+use crate::models::ApiKey;
+
+// Scopes an API key can be granted
+pub const SCOPE_SOLVE: &str = "solve";
+pub const SCOPE_MODELS: &str = "models";
+pub const SCOPE_TRAIN: &str = "train";
+pub const SCOPE_LOGS: &str = "logs";
+pub const SCOPE_STATS: &str = "stats";
+/// Expensive diagnostic endpoints; must be granted explicitly
+pub const SCOPE_DEBUG: &str = "debug";
+
+/// Every known scope
+pub const ALL_SCOPES: &[&str] = &[SCOPE_SOLVE, SCOPE_MODELS, SCOPE_TRAIN, SCOPE_LOGS, SCOPE_STATS, SCOPE_DEBUG];
+
+/// Scopes of keys created without an explicit scope list
+pub const DEFAULT_SCOPES: &[&str] = &[SCOPE_SOLVE, SCOPE_MODELS, SCOPE_TRAIN, SCOPE_LOGS, SCOPE_STATS];
+
+/// Prefix marking captcha-platform keys
+pub const KEY_PREFIX: &str = "cp_";
+
+/// Stored prefix length (matches `api_keys.key_prefix VARCHAR(8)`)
+pub const STORED_PREFIX_LEN: usize = 8;
+
+/// Random bytes in a generated key
+const KEY_BYTES: usize = 32;
+
+/// Random bytes in a per-key salt
+const SALT_BYTES: usize = 16;
+
+/// A freshly generated API key
+pub struct GeneratedKey {
+    /// Full plaintext key, returned to the caller once
+    pub plaintext: String,
+    /// Leading characters stored for identification and lookup
+    pub prefix: String,
+    /// Salted hash stored in `key_hash`
+    pub hash: String,
+}
+
+/// Generate a cryptographically random API key
+pub fn generate_key() -> GeneratedKey {
+    let mut bytes = [0u8; KEY_BYTES];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+
+    let plaintext = format!("{}{}", KEY_PREFIX, hex::encode(bytes));
+    let prefix = key_prefix(&plaintext);
+    let hash = hash_key(&plaintext);
+
+    GeneratedKey { plaintext, prefix, hash }
+}
+
+/// Get the stored prefix of a key
+pub fn key_prefix(key: &str) -> String {
+    key.chars().take(STORED_PREFIX_LEN).collect()
+}
+
+/// Hash a key with a fresh random salt, formatted as `sha256$<salt>$<digest>`
+pub fn hash_key(key: &str) -> String {
+    let mut salt = [0u8; SALT_BYTES];
+    rand::rngs::OsRng.fill_bytes(&mut salt);
+    let salt = hex::encode(salt);
+
+    format!("sha256${}${}", salt, salted_digest(&salt, key))
+}
+
+/// Check a plaintext key against a stored hash.
+/// Unsalted hex SHA256 hashes (created by the auth service) are also accepted.
+pub fn verify_key(key: &str, stored_hash: &str) -> bool {
+    let computed = match stored_hash.split('$').collect::<Vec<_>>().as_slice() {
+        ["sha256", salt, _] => format!("sha256${}${}", salt, salted_digest(salt, key)),
+        [_legacy] => hex::encode(Sha256::digest(key.as_bytes())),
+        _ => return false,
+    };
+
+    constant_time_eq(computed.as_bytes(), stored_hash.as_bytes())
+}
+
+/// Scope an API key needs to call a route, or `None` if the route is open to any key
+pub fn required_scope(path: &str) -> Option<&'static str> {
+    let route = path.strip_prefix("/captcha")?;
+    let section = route.trim_start_matches('/').split('/').next().unwrap_or("");
+
+    match section {
+        "solve" | "compare" | "selftest" => Some(SCOPE_SOLVE),
+        "models" | "solvers" => Some(SCOPE_MODELS),
+        "train" => Some(SCOPE_TRAIN),
+        "logs" => Some(SCOPE_LOGS),
+        "stats" => Some(SCOPE_STATS),
+        "debug" | "histogram" => Some(SCOPE_DEBUG),
+        // Admin routes also require the admin role, which keys never carry
+        "admin" => None,
+        _ => None,
+    }
+}
+
+/// Whether a key has been granted a scope.
+/// Keys without scopes (NULL or `[]`, e.g. issued before scoping) get [`DEFAULT_SCOPES`].
+pub fn has_scope(key: &ApiKey, scope: &str) -> bool {
+    let granted: Vec<&str> = key.scopes.as_ref()
+        .and_then(|s| s.as_array())
+        .map(|items| items.iter().filter_map(|v| v.as_str()).collect())
+        .unwrap_or_default();
+
+    if granted.is_empty() {
+        DEFAULT_SCOPES.contains(&scope)
+    } else {
+        granted.contains(&scope)
+    }
+}
+
+/// Authenticate `X-API-Key` requests and enforce scopes.
+///
+/// On success the key owner replaces any `X-User-ID` header, the role header is
+/// dropped, and the key is stored in the request extensions. Requests without
+/// an API key pass through unchanged (gateway JWT auth).
+pub async fn api_key_auth(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let presented = match req.headers().get("X-API-Key").and_then(|h| h.to_str().ok()) {
+        Some(key) => key.trim().to_string(),
+        None => return next.call(req).await,
+    };
+
+    let state = req.app_data::<web::Data<AppState>>()
+        .ok_or(CaptchaError::ProcessingError("Application state missing".to_string()))?
+        .clone();
+
+    let key = authorize_key(&state, &presented, req.path()).await?;
+
+    let headers = req.headers_mut();
+    headers.remove("X-User-Role");
+    headers.insert(
+        HeaderName::from_static("x-user-id"),
+        HeaderValue::from(key.user_id),
+    );
+    req.extensions_mut().insert(key);
+
+    next.call(req).await
+}
+
+/// Authenticate a presented key, check it may call `path` and record its use.
+/// Shared by [`api_key_auth`] and the gRPC server, which passes the REST path it mirrors.
+pub async fn authorize_key(state: &AppState, presented: &str, path: &str) -> Result<ApiKey, CaptchaError> {
+    let key = authenticate(state, presented).await?;
+
+    if let Some(scope) = required_scope(path) {
+        if !has_scope(&key, scope) {
+            return Err(CaptchaError::Forbidden(
+                format!("API key is missing required scope '{}'", scope)
+            ));
+        }
+    }
+
+    state.usage.record(key.id);
+    Ok(key)
+}
+
+/// Find the active, unexpired key matching a presented plaintext key
+async fn authenticate(state: &AppState, presented: &str) -> Result<ApiKey, CaptchaError> {
+    let legacy_hash = hex::encode(Sha256::digest(presented.as_bytes()));
+    let candidates = state.db.find_api_key_candidates(&key_prefix(presented), &legacy_hash).await?;
+
+    let key = candidates.into_iter()
+        .find(|k| verify_key(presented, &k.key_hash))
+        .ok_or(CaptchaError::Unauthorized)?;
+
+    if !key.is_active || key.expires_at.is_some_and(|t| t <= Utc::now()) {
+        return Err(CaptchaError::Unauthorized);
+    }
+
+    Ok(key)
+}
+
+/// Usage accumulated for one key since the last flush
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyUsage {
+    pub requests: u64,
+    pub last_used_at: DateTime<Utc>,
+}
+
+impl KeyUsage {
+    fn merge(&mut self, other: KeyUsage) {
+        self.requests += other.requests;
+        self.last_used_at = self.last_used_at.max(other.last_used_at);
+    }
+}
+
+/// Buffers API key usage in memory so authenticated requests don't wait on a
+/// database write; counters are written in batches by [`spawn_usage_flusher`]
+#[derive(Debug, Default)]
+pub struct UsageRecorder {
+    pending: Mutex<HashMap<u64, KeyUsage>>,
+}
+
+impl UsageRecorder {
+    /// Count one request for a key
+    pub fn record(&self, key_id: u64) {
+        self.add(key_id, KeyUsage { requests: 1, last_used_at: Utc::now() });
+    }
+
+    /// Usage recorded for a key that has not been flushed yet
+    pub fn pending(&self, key_id: u64) -> Option<KeyUsage> {
+        self.pending.lock().unwrap().get(&key_id).copied()
+    }
+
+    /// Write buffered usage to the database. Failed writes are kept for the next flush.
+    pub async fn flush(&self, db: &Database) {
+        let batch = std::mem::take(&mut *self.pending.lock().unwrap());
+
+        for (key_id, usage) in batch {
+            if let Err(e) = db.add_api_key_usage(key_id, usage.requests, usage.last_used_at).await {
+                tracing::warn!("Failed to record usage for API key {}: {}", key_id, e);
+                self.add(key_id, usage);
+            }
+        }
+    }
+
+    fn add(&self, key_id: u64, usage: KeyUsage) {
+        self.pending.lock().unwrap()
+            .entry(key_id)
+            .and_modify(|existing| existing.merge(usage))
+            .or_insert(usage);
+    }
+}
+
+/// Periodically flush API key usage counters in the background
+pub fn spawn_usage_flusher(state: web::Data<AppState>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            state.usage.flush(&state.db).await;
+        }
+    });
+}
+
+fn salted_digest(salt: &str, key: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(key.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_key_verifies() {
+        let key = generate_key();
+
+        assert!(key.plaintext.starts_with(KEY_PREFIX));
+        assert_eq!(key.prefix.len(), STORED_PREFIX_LEN);
+        assert!(key.plaintext.starts_with(&key.prefix));
+        assert!(verify_key(&key.plaintext, &key.hash));
+        assert!(!verify_key("cp_wrong", &key.hash));
+    }
+
+    #[test]
+    fn test_hash_is_salted() {
+        assert_ne!(hash_key("cp_same"), hash_key("cp_same"));
+    }
+
+    fn key_with_scopes(scopes: Option<serde_json::Value>) -> ApiKey {
+        ApiKey {
+            id: 1,
+            user_id: 7,
+            name: "test".to_string(),
+            key_prefix: "cp_00000".to_string(),
+            key_hash: String::new(),
+            scopes,
+            rate_limit: 100,
+            total_requests: 0,
+            last_used_at: None,
+            is_active: true,
+            expires_at: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_required_scope() {
+        assert_eq!(required_scope("/captcha/solve"), Some(SCOPE_SOLVE));
+        assert_eq!(required_scope("/captcha/solve/batch"), Some(SCOPE_SOLVE));
+        assert_eq!(required_scope("/captcha/selftest"), Some(SCOPE_SOLVE));
+        assert_eq!(required_scope("/captcha/compare"), Some(SCOPE_SOLVE));
+        assert_eq!(required_scope("/captcha/models/upload"), Some(SCOPE_MODELS));
+        assert_eq!(required_scope("/captcha/solvers"), Some(SCOPE_MODELS));
+        assert_eq!(required_scope("/captcha/train/3"), Some(SCOPE_TRAIN));
+        assert_eq!(required_scope("/captcha/logs/recompute"), Some(SCOPE_LOGS));
+        assert_eq!(required_scope("/captcha/stats"), Some(SCOPE_STATS));
+        assert_eq!(required_scope("/captcha/debug/components"), Some(SCOPE_DEBUG));
+        assert_eq!(required_scope("/captcha/histogram"), Some(SCOPE_DEBUG));
+        assert_eq!(required_scope("/health"), None);
+    }
+
+    #[test]
+    fn test_has_scope() {
+        let solve_only = key_with_scopes(Some(serde_json::json!(["solve"])));
+        assert!(has_scope(&solve_only, SCOPE_SOLVE));
+        assert!(!has_scope(&solve_only, SCOPE_TRAIN));
+        assert!(!has_scope(&solve_only, SCOPE_MODELS));
+
+        let unscoped = key_with_scopes(Some(serde_json::json!([])));
+        assert!(DEFAULT_SCOPES.iter().all(|s| has_scope(&unscoped, s)));
+        assert!(!has_scope(&unscoped, SCOPE_DEBUG));
+        assert!(has_scope(&key_with_scopes(None), SCOPE_TRAIN));
+
+        let debug = key_with_scopes(Some(serde_json::json!(["debug"])));
+        assert!(has_scope(&debug, SCOPE_DEBUG));
+    }
+
+    #[test]
+    fn test_usage_recorder_accumulates() {
+        let usage = UsageRecorder::default();
+        assert_eq!(usage.pending(1), None);
+
+        usage.record(1);
+        let first = usage.pending(1).unwrap();
+        usage.record(1);
+        usage.record(2);
+
+        let merged = usage.pending(1).unwrap();
+        assert_eq!(merged.requests, 2);
+        assert!(merged.last_used_at >= first.last_used_at);
+        assert_eq!(usage.pending(2).unwrap().requests, 1);
+    }
+
+    #[test]
+    fn test_legacy_unsalted_hash() {
+        let legacy = hex::encode(Sha256::digest(b"cp_legacy"));
+
+        assert!(verify_key("cp_legacy", &legacy));
+        assert!(!verify_key("cp_other", &legacy));
+    }
+}
@@ -0,0 +1,335 @@
+//! Benchmark CLI
+//!
+//! `captcha-service bench --model cnn --images ./dir` loads the solvers without
+//! starting the web server, solves every labeled image in a directory and prints
+//! latency percentiles, throughput and accuracy. Useful to evaluate a model
+//! before deploying it.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use image::DynamicImage;
+
+use crate::config::Settings;
+use crate::dataset;
+use crate::error::{CaptchaError, CaptchaResult};
+use crate::models::{CaseMode, NormalizeMode};
+use crate::solvers::{SolveHints, SolveResult, SolverManager};
+use crate::solvers::preprocessor::{ImagePreprocessor, InputOptions};
+
+pub const USAGE: &str = "\
+Usage: captcha-service bench --images <DIR> [--model <NAME>] [--warmup <N>] [--limit <N>] [--normalize <MODE>] [--case <CASE>]
+
+  --images <DIR>      Dataset directory (images/ + labels.csv), or a directory of
+                      images named after their text (e.g. 7K4P2.png)
+  --model <NAME>      Solver to benchmark, or `ensemble` (default: configured default solver)
+  --warmup <N>        Untimed solves before measuring (default: 3)
+  --limit <N>         Benchmark at most N images
+  --normalize <MODE>  alnum (default), upper_alnum, digits or none
+  --case <CASE>       upper, lower or preserve (default: the solver's configured case)";
+
+/// Parsed `bench` arguments
+#[derive(Debug, PartialEq)]
+pub struct BenchArgs {
+    pub images: PathBuf,
+    pub model: Option<String>,
+    pub warmup: usize,
+    pub limit: Option<usize>,
+    pub normalize: NormalizeMode,
+    pub case: Option<CaseMode>,
+}
+
+impl BenchArgs {
+    /// Parse the arguments following `bench`
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut images = None;
+        let mut model = None;
+        let mut warmup = 3;
+        let mut limit = None;
+        let mut normalize = NormalizeMode::default();
+        let mut case = None;
+
+        let mut iter = args.iter();
+        while let Some(flag) = iter.next() {
+            let mut value = || iter.next().ok_or_else(|| format!("Missing value for {}", flag));
+            match flag.as_str() {
+                "--images" => images = Some(PathBuf::from(value()?)),
+                "--model" => model = Some(value()?.clone()),
+                "--warmup" => warmup = parse_number(flag, value()?)?,
+                "--limit" => limit = Some(parse_number(flag, value()?)?),
+                "--normalize" => {
+                    normalize = match value()?.as_str() {
+                        "alnum" => NormalizeMode::Alnum,
+                        "upper_alnum" => NormalizeMode::UpperAlnum,
+                        "digits" => NormalizeMode::Digits,
+                        "none" => NormalizeMode::None,
+                        other => return Err(format!("Unknown normalize mode: {}", other)),
+                    }
+                }
+                "--case" => {
+                    case = Some(match value()?.as_str() {
+                        "upper" => CaseMode::Upper,
+                        "lower" => CaseMode::Lower,
+                        "preserve" => CaseMode::Preserve,
+                        other => return Err(format!("Unknown case: {}", other)),
+                    })
+                }
+                other => return Err(format!("Unknown argument: {}", other)),
+            }
+        }
+
+        let images = images.ok_or("--images is required")?;
+        Ok(Self { images, model, warmup, limit, normalize, case })
+    }
+}
+
+fn parse_number(flag: &str, value: &str) -> Result<usize, String> {
+    value.parse().map_err(|_| format!("Invalid number for {}: {}", flag, value))
+}
+
+/// Benchmark results
+#[derive(Debug)]
+pub struct BenchReport {
+    pub model: String,
+    pub images: usize,
+    pub correct: usize,
+    pub errors: usize,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub total: Duration,
+}
+
+impl BenchReport {
+    pub fn accuracy(&self) -> f64 {
+        if self.images == 0 { 0.0 } else { self.correct as f64 / self.images as f64 }
+    }
+
+    pub fn throughput(&self) -> f64 {
+        let secs = self.total.as_secs_f64();
+        if secs == 0.0 { 0.0 } else { self.images as f64 / secs }
+    }
+}
+
+impl std::fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "model:      {}", self.model)?;
+        writeln!(f, "images:     {} ({} errors)", self.images, self.errors)?;
+        writeln!(f, "accuracy:   {:.2}% ({}/{})", self.accuracy() * 100.0, self.correct, self.images)?;
+        writeln!(f, "latency:    p50 {:.1} ms, p95 {:.1} ms", ms(self.p50), ms(self.p95))?;
+        write!(f, "throughput: {:.1} images/s", self.throughput())
+    }
+}
+
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Entry point for `captcha-service bench <args>`: loads the solvers from the
+/// service configuration (no database, no web server) and prints the report
+pub async fn cli(args: &[String]) -> std::io::Result<()> {
+    let args = match BenchArgs::parse(args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let config = Settings::new().expect("Failed to load configuration");
+    let manager = SolverManager::new(&config.models, &config.processing)
+        .await
+        .expect("Failed to initialize solver manager");
+
+    for info in manager.solver_info() {
+        if info.mock {
+            eprintln!("warning: solver {} runs in mock mode", info.name);
+        }
+    }
+
+    match run(&manager, &args, &config.processing.input_options()).await {
+        Ok(report) => {
+            println!("{}", report);
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Labeled images to benchmark: the dataset layout when labels.csv exists,
+/// otherwise every image in the directory labeled by its file stem
+pub fn load_samples(dir: &Path) -> CaptchaResult<Vec<(PathBuf, String)>> {
+    if dir.join(dataset::LABELS_FILE).is_file() {
+        return dataset::labeled_images(dir);
+    }
+
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| CaptchaError::BadRequest(format!("Cannot read {}: {}", dir.display(), e)))?;
+
+    let mut samples: Vec<(PathBuf, String)> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter_map(|path| {
+            let label = path.file_stem()?.to_str()?.to_string();
+            Some((path, label))
+        })
+        .collect();
+    samples.sort();
+
+    Ok(samples)
+}
+
+/// Nearest-rank percentile of sorted latencies
+fn percentile(sorted: &[Duration], pct: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+async fn solve(
+    manager: &SolverManager,
+    model: Option<&str>,
+    image: &DynamicImage,
+    hints: &SolveHints,
+) -> CaptchaResult<SolveResult> {
+    match model {
+        Some("ensemble") => manager.solve_ensemble(image, None, hints).await,
+        _ => manager.solve(image, model, None, hints).await,
+    }
+}
+
+/// Solve every sample with the chosen solver and measure latency and accuracy.
+/// Images are decoded with the service's `input` adjustments (transparency, size cap).
+pub async fn run(manager: &SolverManager, args: &BenchArgs, input: &InputOptions) -> CaptchaResult<BenchReport> {
+    let mut samples = load_samples(&args.images)?;
+    if let Some(limit) = args.limit {
+        samples.truncate(limit);
+    }
+    if samples.is_empty() {
+        return Err(CaptchaError::BadRequest(format!(
+            "No labeled images found in {}", args.images.display()
+        )));
+    }
+
+    // Errors count against the benchmarked solver instead of falling back to another one
+    let hints = SolveHints {
+        normalize: Some(args.normalize),
+        case: args.case,
+        disable_fallback: true,
+        ..Default::default()
+    };
+    let model = args.model.as_deref();
+
+    let mut images = Vec::with_capacity(samples.len());
+    for (path, label) in samples {
+        match image::open(&path) {
+            Ok(image) => {
+                let image = ImagePreprocessor::to_eight_bit(image);
+                images.push((ImagePreprocessor::prepare_input(image, input), label));
+            }
+            Err(e) => tracing::warn!("Skipping {}: {}", path.display(), e),
+        }
+    }
+
+    if images.is_empty() {
+        return Err(CaptchaError::BadRequest("No decodable images to benchmark".to_string()));
+    }
+
+    // Warm up caches and lazy model loading outside the measurement
+    for (image, _) in images.iter().cycle().take(args.warmup) {
+        let _ = solve(manager, model, image, &hints).await;
+    }
+
+    let mut latencies = Vec::with_capacity(images.len());
+    let mut correct = 0;
+    let mut errors = 0;
+    let mut solver_name = None;
+    let start = Instant::now();
+
+    for (image, label) in &images {
+        let solve_start = Instant::now();
+        let result = solve(manager, model, image, &hints).await;
+        latencies.push(solve_start.elapsed());
+
+        match result {
+            Ok(result) => {
+                let case = manager.case_for(&result.solver_name, args.case);
+                if manager.normalizer().matches(&result.text, label, args.normalize, case) {
+                    correct += 1;
+                }
+                solver_name.get_or_insert(result.solver_name);
+            }
+            Err(e) => {
+                tracing::warn!("Solve failed: {}", e);
+                errors += 1;
+            }
+        }
+    }
+
+    let total = start.elapsed();
+    latencies.sort();
+
+    Ok(BenchReport {
+        model: args.model.clone().or(solver_name).unwrap_or_else(|| "default".to_string()),
+        images: images.len(),
+        correct,
+        errors,
+        p50: percentile(&latencies, 50.0),
+        p95: percentile(&latencies, 95.0),
+        total,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let parsed = BenchArgs::parse(&args(&["--model", "cnn", "--images", "./dir", "--limit", "10"])).unwrap();
+        assert_eq!(parsed, BenchArgs {
+            images: PathBuf::from("./dir"),
+            model: Some("cnn".to_string()),
+            warmup: 3,
+            limit: Some(10),
+            normalize: NormalizeMode::Alnum,
+            case: None,
+        });
+
+        assert!(BenchArgs::parse(&args(&["--model", "cnn"])).is_err());
+        assert!(BenchArgs::parse(&args(&["--images"])).is_err());
+        assert!(BenchArgs::parse(&args(&["--images", "d", "--limit", "x"])).is_err());
+        assert!(BenchArgs::parse(&args(&["--images", "d", "--verbose"])).is_err());
+    }
+
+    #[test]
+    fn test_percentile() {
+        let latencies: Vec<Duration> = (1..=20).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&latencies, 50.0), Duration::from_millis(10));
+        assert_eq!(percentile(&latencies, 95.0), Duration::from_millis(19));
+        assert_eq!(percentile(&[], 95.0), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_load_samples_from_file_names() {
+        let dir = std::env::temp_dir().join(format!("captcha-bench-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("7K4P2.png"), b"").unwrap();
+        std::fs::write(dir.join("AB12.jpg"), b"").unwrap();
+
+        let samples = load_samples(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let labels: Vec<&str> = samples.iter().map(|(_, label)| label.as_str()).collect();
+        assert_eq!(labels, vec!["7K4P2", "AB12"]);
+    }
+}
@@ -1,8 +1,15 @@
 //! Configuration module for Captcha Service
 
+use std::collections::HashMap;
+use std::time::Duration;
+
+use image::ImageFormat;
 use serde::Deserialize;
 use config::{Config, ConfigError, Environment, File};
 
+use crate::models::{CaseMode, DefaultStrategy, HashAlgorithm, NormalizeMode, PreprocessOptions, SolveRequest, SolveStrategy, WhitespaceMode};
+use crate::solvers::preprocessor::{parse_image_formats, DifficultyThresholds, InputOptions, Segmentation};
+
 /// Main settings structure
 #[derive(Debug, Clone, Deserialize)]
 pub struct Settings {
@@ -10,6 +17,114 @@ pub struct Settings {
     pub database: DatabaseSettings,
     pub models: ModelsSettings,
     pub processing: ProcessingSettings,
+    /// Named provider profiles selected with `"profile"` in solve requests
+    #[serde(default)]
+    pub profiles: HashMap<String, ProfileSettings>,
+    /// Log output format (`LOG_FORMAT`)
+    pub log_format: LogFormat,
+}
+
+/// Tracing output format
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// One JSON object per line, for log collectors
+    Json,
+    /// Multi-line, colored output for local development
+    Pretty,
+    /// Single-line human-readable output
+    Compact,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "json" => Ok(LogFormat::Json),
+            "pretty" => Ok(LogFormat::Pretty),
+            "compact" => Ok(LogFormat::Compact),
+            other => Err(format!("Unknown log format '{}'; expected json, pretty or compact", other)),
+        }
+    }
+}
+
+/// Opaque RGB color written as `#rrggbb` (the `#` is optional)
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub struct HexColor(pub [u8; 3]);
+
+impl std::str::FromStr for HexColor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.trim().trim_start_matches('#');
+        if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(format!("Invalid color '{}'; expected #rrggbb", s));
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+        Ok(HexColor([channel(0), channel(2), channel(4)]))
+    }
+}
+
+impl TryFrom<String> for HexColor {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Image formats uploads may be in, written as a comma-separated list of extensions
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub struct ImageFormats(pub Vec<ImageFormat>);
+
+impl TryFrom<String> for ImageFormats {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        parse_image_formats(&s).map(ImageFormats)
+    }
+}
+
+/// Solve parameters for one captcha provider. A request selecting the profile
+/// uses these for every field it leaves unset.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ProfileSettings {
+    pub model: Option<String>,
+    pub preprocess: Option<PreprocessOptions>,
+    pub charset: Option<String>,
+    pub expected_length: Option<usize>,
+    pub normalize: Option<NormalizeMode>,
+    pub case: Option<CaseMode>,
+    pub whitespace: Option<WhitespaceMode>,
+    pub language: Option<String>,
+    pub strategy: Option<SolveStrategy>,
+}
+
+impl ProfileSettings {
+    /// Fill the fields the request leaves unset
+    pub fn apply(&self, request: &mut SolveRequest) {
+        fn fill<T: Clone>(field: &mut Option<T>, default: &Option<T>) {
+            if field.is_none() {
+                field.clone_from(default);
+            }
+        }
+
+        // A requested model type takes the place of the profile's model
+        if request.model_type.is_none() {
+            fill(&mut request.model, &self.model);
+        }
+        fill(&mut request.preprocess, &self.preprocess);
+        fill(&mut request.charset, &self.charset);
+        fill(&mut request.expected_length, &self.expected_length);
+        fill(&mut request.normalize, &self.normalize);
+        fill(&mut request.case, &self.case);
+        fill(&mut request.whitespace, &self.whitespace);
+        fill(&mut request.language, &self.language);
+        fill(&mut request.strategy, &self.strategy);
+    }
 }
 
 /// Server configuration
@@ -17,6 +132,22 @@ pub struct Settings {
 pub struct ServerSettings {
     pub port: u16,
     pub host: String,
+    /// HTTP worker threads (0 = actix default, one per physical CPU)
+    pub workers: usize,
+    /// How long idle keep-alive connections stay open (0 disables keep-alive)
+    pub keep_alive_secs: u64,
+    /// Also accept HTTP/2 over cleartext (h2c with prior knowledge) on the same port
+    pub http2: bool,
+    /// Port of the gRPC API (0 disables it; needs the `grpc` feature)
+    pub grpc_port: u16,
+    /// Deadline for a whole request, including database and preprocessing (0 disables)
+    pub request_timeout_secs: u64,
+    /// Deadlines overriding `request_timeout_secs` for single solves, batches, exports and
+    /// log replays; solve and batch deadlines must exceed `processing.timeout_seconds`
+    pub solve_request_timeout_secs: u64,
+    pub batch_request_timeout_secs: u64,
+    pub export_request_timeout_secs: u64,
+    pub replay_request_timeout_secs: u64,
 }
 
 /// Database configuration
@@ -28,6 +159,11 @@ pub struct DatabaseSettings {
     pub user: String,
     pub password: String,
     pub max_connections: u32,
+    /// Apply pending schema migrations at startup
+    pub run_migrations: bool,
+    /// Connection URL of a read replica serving stats and listing queries; the primary otherwise
+    #[serde(default)]
+    pub replica_url: Option<String>,
 }
 
 /// Models configuration
@@ -37,6 +173,25 @@ pub struct ModelsSettings {
     pub default_model: String,
     pub ocr_enabled: bool,
     pub cnn_enabled: bool,
+    /// Per-solver timeouts in seconds (e.g. `cnn = 5`); `processing.timeout_seconds` otherwise
+    #[serde(default)]
+    pub timeouts: HashMap<String, u64>,
+    /// Per-solver letter case for `alnum` normalization (e.g. `cnn = "preserve"`); `processing.output_case` otherwise
+    #[serde(default)]
+    pub case: HashMap<String, CaseMode>,
+    /// Per-solver confidence temperatures (e.g. `cnn = 1.8`), used when the active model's
+    /// metadata has no `temperature`
+    #[serde(default)]
+    pub temperatures: HashMap<String, f32>,
+    /// Inference endpoint the `http` solver POSTs to; the solver is registered only when set
+    #[serde(default)]
+    pub http_url: Option<String>,
+    /// Solvers answering requests that name no model (`single` | `ensemble` | `fallback`)
+    pub default_strategy: DefaultStrategy,
+    /// Confidence below which the `fallback` strategy moves on to the next solver
+    pub fallback_min_confidence: f32,
+    /// Most characters a solver answer may have; longer decodings are truncated with a warning
+    pub max_output_length: usize,
 }
 
 /// Processing configuration
@@ -45,6 +200,97 @@ pub struct ProcessingSettings {
     pub max_image_size_mb: usize,
     pub timeout_seconds: u64,
     pub batch_size: usize,
+    /// Maximum total decoded pixels across all images of one batch
+    pub batch_max_total_pixels: u64,
+    /// Algorithm for image hashes in logs (`sha256` | `blake3`)
+    pub image_hash_algorithm: HashAlgorithm,
+    /// Letter → digit substitutions applied in `digits` normalization, e.g. "O0,I1,S5"
+    pub digit_substitutions: String,
+    /// Maximum upload size for archive solving
+    pub archive_max_size_mb: usize,
+    /// Maximum number of entries processed from one archive
+    pub archive_max_entries: usize,
+    /// Maximum uncompressed size of a single archive entry
+    pub archive_max_entry_size_mb: usize,
+    /// Retries for transient solver errors inside the ensemble
+    pub solver_retry_attempts: u32,
+    /// Base backoff before the first retry, doubled on each attempt
+    pub solver_retry_base_ms: u64,
+    /// Maximum solves running inference at once (0 = number of CPUs)
+    pub max_concurrent_solves: usize,
+    /// Threads of the dedicated inference pool (0 = number of CPUs)
+    pub inference_threads: usize,
+    /// Maximum requests waiting for a solve slot before new ones are rejected with 503
+    pub max_queue_depth: usize,
+    /// Retry-After value sent with 503 overload responses
+    pub overload_retry_after_seconds: u64,
+    /// How often buffered API key usage counters are written to the database
+    pub api_key_usage_flush_seconds: u64,
+    /// Comma-separated preprocessing variants tried by the `auto_preprocess` strategy
+    pub auto_preprocess_variants: String,
+    /// Confidence at which the `auto_preprocess` strategy stops trying variants
+    pub auto_preprocess_target: f32,
+    /// Comma-separated preprocessing variants the `by_difficulty` strategy uses for easy, medium and hard captchas
+    pub difficulty_presets: String,
+    /// Difficulty limits: easy needs both noise and components at or below the easy maximums,
+    /// hard needs either at or above the hard minimums
+    pub difficulty_easy_max_noise: f32,
+    pub difficulty_easy_max_components: usize,
+    pub difficulty_hard_min_noise: f32,
+    pub difficulty_hard_min_components: usize,
+    /// Scale each solver's confidence in the ensemble by its model's recorded accuracy
+    pub ensemble_accuracy_weighting: bool,
+    /// Letter case for `alnum` normalization when neither the request nor `models.case` sets one
+    pub output_case: CaseMode,
+    /// Whitespace handling in normalized output when the request sets none
+    pub whitespace: WhitespaceMode,
+    /// How long responses to requests with an `Idempotency-Key` are kept for replay
+    pub idempotency_ttl_seconds: u64,
+    /// Most `Idempotency-Key`s kept at once; the oldest are dropped first
+    pub idempotency_max_entries: usize,
+    /// How long solve results are reused for resubmits of the same image (0 disables)
+    pub solve_memo_ttl_seconds: u64,
+    /// Character segmentation defaults, overridable per request
+    pub segment_min_width: u32,
+    pub segment_min_height: u32,
+    pub segment_min_pixels: u32,
+    pub segment_split_width_ratio: f32,
+    /// Labeled logs a model needs before its accuracy is recomputed from them
+    pub accuracy_min_samples: u64,
+    /// Interval for recomputing model accuracy from labeled logs (0 disables)
+    pub accuracy_refresh_seconds: u64,
+    /// Labeled logs a solver needs before its confidences are calibrated to probabilities
+    pub calibration_min_samples: usize,
+    /// Solve logs kept in memory while waiting to be written to the database
+    pub solve_log_queue_capacity: usize,
+    /// Interval for writing queued solve logs
+    pub solve_log_flush_seconds: u64,
+    /// Minimum logged confidence for a prediction to count as successful in stats
+    pub success_confidence_floor: f64,
+    /// Interval for re-verifying model file checksums (0 verifies only at startup)
+    pub model_checksum_check_seconds: u64,
+    /// Interval for retrying models that failed to load at startup (0 disables)
+    pub model_load_retry_seconds: u64,
+    /// Average stroke width solver preprocessing normalizes toward when the request sets none (0 disables)
+    pub stroke_width_target: f32,
+    /// Color transparent pixels are composited over before grayscale conversion
+    pub alpha_background: HexColor,
+    /// Input images whose larger side exceeds this are downscaled before preprocessing (0 disables)
+    pub max_dimension: u32,
+    /// Formats an uploaded image may be in, checked against the format detected from its bytes
+    pub allowed_image_formats: ImageFormats,
+    /// Save images of empty or low-confidence solves to `failure_dir`
+    pub store_failures: bool,
+    /// Directory failed solve images and their predictions are written to
+    pub failure_dir: String,
+    /// Most failed solves kept in `failure_dir`; the oldest are deleted first (0 = unlimited)
+    pub failure_max_files: usize,
+    /// Most bytes of failed solves kept in `failure_dir`; the oldest are deleted first (0 = unlimited)
+    pub failure_max_bytes: u64,
+    /// Confidence below which a solve counts as failed for `store_failures`
+    pub failure_confidence_threshold: f32,
+    /// Keep each solved image in its `captcha_logs` row, for replaying labeled logs
+    pub store_log_images: bool,
 }
 
 impl Settings {
@@ -55,20 +301,82 @@ impl Settings {
         let s = Config::builder()
             // Start with default values
             .set_default("server.port", 8082)?
+            .set_default("log_format", if run_mode == "production" { "json" } else { "pretty" })?
             .set_default("server.host", "0.0.0.0")?
+            .set_default("server.workers", 0)?
+            .set_default("server.keep_alive_secs", 5)?
+            .set_default("server.http2", false)?
+            .set_default("server.grpc_port", 0)?
+            .set_default("server.request_timeout_secs", 60)?
+            .set_default("server.solve_request_timeout_secs", 45)?
+            .set_default("server.batch_request_timeout_secs", 120)?
+            .set_default("server.export_request_timeout_secs", 300)?
+            .set_default("server.replay_request_timeout_secs", 1800)?
             .set_default("database.host", "localhost")?
             .set_default("database.port", 3306)?
             .set_default("database.name", "captcha_platform")?
             .set_default("database.user", "captcha_user")?
             .set_default("database.password", "")?
             .set_default("database.max_connections", 10)?
+            .set_default("database.run_migrations", true)?
             .set_default("models.path", "/app/models")?
             .set_default("models.default_model", "tesseract-default")?
             .set_default("models.ocr_enabled", true)?
             .set_default("models.cnn_enabled", true)?
+            .set_default("models.default_strategy", "single")?
+            .set_default("models.fallback_min_confidence", 0.5)?
+            .set_default("models.max_output_length", 32)?
             .set_default("processing.max_image_size_mb", 10)?
             .set_default("processing.timeout_seconds", 30)?
             .set_default("processing.batch_size", 10)?
+            .set_default("processing.batch_max_total_pixels", 20_000_000)?
+            .set_default("processing.image_hash_algorithm", "blake3")?
+            .set_default("processing.digit_substitutions", crate::solvers::postprocess::DEFAULT_DIGIT_SUBSTITUTIONS)?
+            .set_default("processing.archive_max_size_mb", 100)?
+            .set_default("processing.archive_max_entries", 1000)?
+            .set_default("processing.archive_max_entry_size_mb", 2)?
+            .set_default("processing.solver_retry_attempts", 2)?
+            .set_default("processing.solver_retry_base_ms", 50)?
+            .set_default("processing.max_concurrent_solves", 0)?
+            .set_default("processing.inference_threads", 0)?
+            .set_default("processing.max_queue_depth", 64)?
+            .set_default("processing.overload_retry_after_seconds", 1)?
+            .set_default("processing.api_key_usage_flush_seconds", 10)?
+            .set_default("processing.auto_preprocess_variants", crate::solvers::preprocessor::DEFAULT_AUTO_PREPROCESS_VARIANTS)?
+            .set_default("processing.auto_preprocess_target", 0.8)?
+            .set_default("processing.difficulty_presets", crate::solvers::preprocessor::DEFAULT_DIFFICULTY_PRESETS)?
+            .set_default("processing.difficulty_easy_max_noise", 0.02)?
+            .set_default("processing.difficulty_easy_max_components", 8)?
+            .set_default("processing.difficulty_hard_min_noise", 0.1)?
+            .set_default("processing.difficulty_hard_min_components", 16)?
+            .set_default("processing.ensemble_accuracy_weighting", true)?
+            .set_default("processing.output_case", "upper")?
+            .set_default("processing.whitespace", "strip")?
+            .set_default("processing.idempotency_ttl_seconds", 3600)?
+            .set_default("processing.idempotency_max_entries", 10000)?
+            .set_default("processing.solve_memo_ttl_seconds", 5)?
+            .set_default("processing.segment_min_width", crate::solvers::preprocessor::ImagePreprocessor::DEFAULT_MIN_CHAR_WIDTH)?
+            .set_default("processing.segment_min_height", crate::solvers::preprocessor::ImagePreprocessor::DEFAULT_MIN_CHAR_HEIGHT)?
+            .set_default("processing.segment_min_pixels", crate::solvers::preprocessor::ImagePreprocessor::DEFAULT_MIN_CHAR_PIXELS)?
+            .set_default("processing.segment_split_width_ratio", f64::from(crate::solvers::preprocessor::ImagePreprocessor::DEFAULT_SPLIT_WIDTH_RATIO))?
+            .set_default("processing.accuracy_min_samples", 50)?
+            .set_default("processing.accuracy_refresh_seconds", 0)?
+            .set_default("processing.calibration_min_samples", 200)?
+            .set_default("processing.solve_log_queue_capacity", 10000)?
+            .set_default("processing.solve_log_flush_seconds", 1)?
+            .set_default("processing.success_confidence_floor", 0.0)?
+            .set_default("processing.model_checksum_check_seconds", 300)?
+            .set_default("processing.model_load_retry_seconds", 30)?
+            .set_default("processing.stroke_width_target", 0.0)?
+            .set_default("processing.alpha_background", "#ffffff")?
+            .set_default("processing.max_dimension", crate::solvers::preprocessor::ImagePreprocessor::DEFAULT_MAX_INPUT_DIMENSION)?
+            .set_default("processing.allowed_image_formats", crate::solvers::preprocessor::DEFAULT_ALLOWED_IMAGE_FORMATS)?
+            .set_default("processing.store_failures", false)?
+            .set_default("processing.store_log_images", false)?
+            .set_default("processing.failure_dir", "/app/failures")?
+            .set_default("processing.failure_max_files", 10000)?
+            .set_default("processing.failure_max_bytes", 1024 * 1024 * 1024)?
+            .set_default("processing.failure_confidence_threshold", 0.5)?
             // Load config file if exists
             .add_source(File::with_name("config/default").required(false))
             .add_source(File::with_name(&format!("config/{}", run_mode)).required(false))
@@ -120,14 +428,110 @@ impl Settings {
         if let Ok(password) = std::env::var("DB_PASSWORD") {
             settings.database.password = password;
         }
+        if let Ok(url) = std::env::var("DB_REPLICA_URL") {
+            settings.database.replica_url = Some(url).filter(|url| !url.is_empty());
+        }
         if let Ok(path) = std::env::var("MODELS_PATH") {
             settings.models.path = path;
         }
+        if let Ok(format) = std::env::var("LOG_FORMAT") {
+            settings.log_format = format.parse().map_err(ConfigError::Message)?;
+        }
+
+        settings.server.check_solve_deadlines(settings.processing.timeout_seconds)
+            .map_err(ConfigError::Message)?;
+        if settings.models.max_output_length == 0 {
+            return Err(ConfigError::Message("models.max_output_length must be at least 1".to_string()));
+        }
 
         Ok(settings)
     }
 }
 
+impl ProcessingSettings {
+    /// Resolve the solver concurrency limit, defaulting to the number of CPUs
+    pub fn solve_concurrency(&self) -> usize {
+        if self.max_concurrent_solves > 0 {
+            self.max_concurrent_solves
+        } else {
+            cpu_count()
+        }
+    }
+
+    /// Resolve the inference pool size, defaulting to the number of CPUs
+    pub fn inference_threads(&self) -> usize {
+        if self.inference_threads > 0 {
+            self.inference_threads
+        } else {
+            cpu_count()
+        }
+    }
+
+    /// Configured character segmentation parameters
+    pub fn segmentation(&self) -> Segmentation {
+        Segmentation {
+            min_width: self.segment_min_width,
+            min_height: self.segment_min_height,
+            min_pixels: self.segment_min_pixels,
+            split_width_ratio: self.segment_split_width_ratio,
+        }
+    }
+
+    /// Adjustments applied to every decoded input image
+    pub fn input_options(&self) -> InputOptions {
+        InputOptions {
+            alpha_background: self.alpha_background.0,
+            max_dimension: self.max_dimension,
+            allowed_formats: self.allowed_image_formats.0.clone(),
+        }
+    }
+
+    /// Thresholds for the `by_difficulty` strategy
+    pub fn difficulty_thresholds(&self) -> DifficultyThresholds {
+        DifficultyThresholds {
+            easy_max_noise: self.difficulty_easy_max_noise,
+            easy_max_components: self.difficulty_easy_max_components,
+            hard_min_noise: self.difficulty_hard_min_noise,
+            hard_min_components: self.difficulty_hard_min_components,
+        }
+    }
+}
+
+impl ServerSettings {
+    /// Deadline for a request to `path`, or `None` when it has none.
+    /// Batch and archive solves count as batches, CSV downloads (`.../export`) as exports.
+    pub fn request_timeout(&self, path: &str) -> Option<Duration> {
+        let path = path.trim_end_matches('/');
+        let secs = match path {
+            "/captcha/solve/batch" | "/captcha/solve/archive" => self.batch_request_timeout_secs,
+            "/captcha/solve" | "/captcha/compare" => self.solve_request_timeout_secs,
+            "/captcha/logs/replay" => self.replay_request_timeout_secs,
+            _ if path.ends_with("/export") => self.export_request_timeout_secs,
+            _ => self.request_timeout_secs,
+        };
+        (secs > 0).then(|| Duration::from_secs(secs))
+    }
+
+    /// Solve and batch deadlines must outlast the wait for a solve permit
+    /// (`permit_timeout_secs`), so overload is answered by the solver queue
+    /// rather than by the route deadline dropping a waiting handler
+    pub fn check_solve_deadlines(&self, permit_timeout_secs: u64) -> Result<(), String> {
+        let deadlines = [
+            ("solve_request_timeout_secs", self.solve_request_timeout_secs),
+            ("batch_request_timeout_secs", self.batch_request_timeout_secs),
+        ];
+        for (name, secs) in deadlines {
+            if secs > 0 && secs <= permit_timeout_secs {
+                return Err(format!(
+                    "server.{} ({}) must be 0 or longer than processing.timeout_seconds ({})",
+                    name, secs, permit_timeout_secs
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
 impl DatabaseSettings {
     /// Get database connection URL
     pub fn connection_url(&self) -> String {
@@ -136,4 +540,146 @@ impl DatabaseSettings {
             self.user, self.password, self.host, self.port, self.name
         )
     }
-}
\ No newline at end of file
+}
+
+/// Number of CPUs available to the process, or 4 when it can't be determined
+fn cpu_count() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::FileFormat;
+
+    #[test]
+    fn test_profile_fills_unset_fields() {
+        let config = Config::builder()
+            .add_source(File::from_str(r#"
+                [profiles.bank_x]
+                model = "cnn"
+                charset = "0123456789"
+                expected_length = 6
+                preprocess = { grayscale = true, threshold = 140 }
+            "#, FileFormat::Toml))
+            .build()
+            .unwrap();
+        let profiles: HashMap<String, ProfileSettings> = config.get("profiles").unwrap();
+
+        let mut request: SolveRequest = serde_json::from_value(serde_json::json!({
+            "image_base64": "", "profile": "bank_x", "expected_length": 5
+        })).unwrap();
+        profiles["bank_x"].apply(&mut request);
+
+        assert_eq!(request.model.as_deref(), Some("cnn"));
+        assert_eq!(request.charset.as_deref(), Some("0123456789"));
+        assert_eq!(request.preprocess.and_then(|p| p.threshold), Some(140));
+        // Request fields win over the profile
+        assert_eq!(request.expected_length, Some(5));
+
+        // A requested model type replaces the profile's model
+        let mut request: SolveRequest = serde_json::from_value(serde_json::json!({
+            "image_base64": "", "profile": "bank_x", "model_type": "ocr"
+        })).unwrap();
+        profiles["bank_x"].apply(&mut request);
+        assert_eq!(request.model, None);
+    }
+
+    #[test]
+    fn test_request_timeout_per_route() {
+        let server = ServerSettings {
+            port: 8082,
+            host: "0.0.0.0".to_string(),
+            workers: 0,
+            keep_alive_secs: 5,
+            http2: false,
+            grpc_port: 0,
+            request_timeout_secs: 60,
+            solve_request_timeout_secs: 45,
+            batch_request_timeout_secs: 0,
+            export_request_timeout_secs: 300,
+            replay_request_timeout_secs: 1800,
+        };
+
+        assert_eq!(server.request_timeout("/captcha/solve"), Some(Duration::from_secs(45)));
+        assert_eq!(server.request_timeout("/captcha/compare/"), Some(Duration::from_secs(45)));
+        assert_eq!(server.request_timeout("/captcha/logs/export"), Some(Duration::from_secs(300)));
+        assert_eq!(server.request_timeout("/captcha/logs/replay"), Some(Duration::from_secs(1800)));
+        assert_eq!(server.request_timeout("/captcha/models"), Some(Duration::from_secs(60)));
+        // Zero disables the deadline of its routes
+        assert_eq!(server.request_timeout("/captcha/solve/batch"), None);
+
+        assert!(server.check_solve_deadlines(30).is_ok());
+        assert!(server.check_solve_deadlines(45).is_err());
+
+        let tight_batch = ServerSettings { batch_request_timeout_secs: 20, ..server };
+        let error = tight_batch.check_solve_deadlines(30).unwrap_err();
+        assert!(error.contains("batch_request_timeout_secs"), "{}", error);
+    }
+
+    #[test]
+    fn test_log_format_parsing() {
+        assert_eq!("json".parse::<LogFormat>(), Ok(LogFormat::Json));
+        assert_eq!(" Pretty ".parse::<LogFormat>(), Ok(LogFormat::Pretty));
+        assert_eq!("compact".parse::<LogFormat>(), Ok(LogFormat::Compact));
+        assert!("text".parse::<LogFormat>().is_err());
+
+        let config = Config::builder()
+            .add_source(File::from_str(r#"log_format = "compact""#, FileFormat::Toml))
+            .build()
+            .unwrap();
+        assert_eq!(config.get::<LogFormat>("log_format").unwrap(), LogFormat::Compact);
+    }
+
+    #[test]
+    fn test_replica_url_is_optional() {
+        let primary_only = r#"
+            host = "db"
+            port = 3306
+            name = "captcha"
+            user = "captcha"
+            password = ""
+            max_connections = 10
+            run_migrations = true
+        "#;
+        let config = Config::builder().add_source(File::from_str(primary_only, FileFormat::Toml)).build().unwrap();
+        assert_eq!(config.try_deserialize::<DatabaseSettings>().unwrap().replica_url, None);
+
+        let with_replica = format!("{}\nreplica_url = \"mysql://reader@replica:3306/captcha\"", primary_only);
+        let config = Config::builder().add_source(File::from_str(&with_replica, FileFormat::Toml)).build().unwrap();
+        assert_eq!(
+            config.try_deserialize::<DatabaseSettings>().unwrap().replica_url.as_deref(),
+            Some("mysql://reader@replica:3306/captcha")
+        );
+    }
+
+    #[test]
+    fn test_hex_color_parsing() {
+        assert_eq!("#ffffff".parse::<HexColor>(), Ok(HexColor([255, 255, 255])));
+        assert_eq!(" 1A2b3C ".parse::<HexColor>(), Ok(HexColor([0x1a, 0x2b, 0x3c])));
+        assert!("#fff".parse::<HexColor>().is_err());
+        assert!("#gg0000".parse::<HexColor>().is_err());
+
+        let config = Config::builder()
+            .add_source(File::from_str(r##"alpha_background = "#000080""##, FileFormat::Toml))
+            .build()
+            .unwrap();
+        assert_eq!(config.get::<HexColor>("alpha_background").unwrap(), HexColor([0, 0, 0x80]));
+    }
+
+    #[test]
+    fn test_image_formats_parsing() {
+        let parse = |s: &str| ImageFormats::try_from(s.to_string());
+        assert_eq!(parse("png, JPG,jpeg"), Ok(ImageFormats(vec![ImageFormat::Png, ImageFormat::Jpeg])));
+        assert!(parse("png,psd").unwrap_err().contains("'psd'"));
+        assert!(parse(" , ").is_err());
+
+        let config = Config::builder()
+            .add_source(File::from_str(r#"allowed_image_formats = "webp""#, FileFormat::Toml))
+            .build()
+            .unwrap();
+        assert_eq!(config.get::<ImageFormats>("allowed_image_formats").unwrap(), ImageFormats(vec![ImageFormat::WebP]));
+    }
+}
@@ -0,0 +1,279 @@
+//! Training Dataset Validation
+//!
+//! Checks a dataset directory in the documented layout (`images/` plus
+//! `labels.csv` with `filename,label` rows) before training starts: every
+//! image must decode and have a label, and every label must point at an image.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{CaptchaError, CaptchaResult};
+use crate::models::{DatasetProblem, DatasetProblemKind, DatasetReport};
+
+/// Directory holding the dataset images
+pub const IMAGES_DIR: &str = "images";
+
+/// CSV file mapping image filenames to labels
+pub const LABELS_FILE: &str = "labels.csv";
+
+/// Validate the dataset at `root`, reporting at most `max_problems` individual problems.
+/// Images larger than `max_image_bytes` are reported instead of decoded.
+pub fn validate_dataset(root: &Path, max_problems: usize, max_image_bytes: u64) -> CaptchaResult<DatasetReport> {
+    if !root.is_dir() {
+        return Err(CaptchaError::BadRequest(format!(
+            "Dataset path is not a directory: {}", root.display()
+        )));
+    }
+
+    let mut report = DatasetReport::default();
+    let mut problems = Problems::new(max_problems);
+
+    let labels = read_labels(&root.join(LABELS_FILE), &mut report, &mut problems);
+
+    let images_dir = root.join(IMAGES_DIR);
+    let mut image_names = HashSet::new();
+    match fs::read_dir(&images_dir) {
+        Ok(entries) => {
+            let mut paths: Vec<_> = entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.is_file())
+                .collect();
+            paths.sort();
+
+            for path in paths {
+                let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                report.images_total += 1;
+
+                let size = fs::metadata(&path).map(|meta| meta.len()).unwrap_or(0);
+                if size > max_image_bytes {
+                    problems.push(DatasetProblemKind::ImageTooLarge, &name, format!("{} bytes", size));
+                } else if let Err(e) = decode(&path) {
+                    problems.push(DatasetProblemKind::CorruptImage, &name, e);
+                } else {
+                    report.images_valid += 1;
+                }
+
+                if !labels.contains_key(&name) {
+                    problems.push(DatasetProblemKind::MissingLabel, &name, "No row in labels.csv".to_string());
+                }
+                image_names.insert(name);
+            }
+        }
+        Err(e) => problems.push(
+            DatasetProblemKind::MissingImagesDir,
+            IMAGES_DIR,
+            e.to_string(),
+        ),
+    }
+
+    let mut unmatched: Vec<&String> = labels.keys().filter(|name| !image_names.contains(*name)).collect();
+    unmatched.sort();
+    for name in unmatched {
+        problems.push(DatasetProblemKind::MissingImage, name, "Listed in labels.csv but not in images/".to_string());
+    }
+
+    // Distribution over labels that belong to an existing image
+    for (name, label) in &labels {
+        if image_names.contains(name) {
+            report.labeled_images += 1;
+            *report.label_lengths.entry(label.chars().count()).or_default() += 1;
+            for c in label.chars() {
+                *report.class_distribution.entry(c).or_default() += 1;
+            }
+        }
+    }
+
+    report.problem_counts = problems.counts;
+    report.problems_total = report.problem_counts.values().sum();
+    report.problems = problems.sample;
+    report.valid = report.problems_total == 0 && report.labeled_images > 0;
+
+    Ok(report)
+}
+
+/// Images in `images/` that have a label in labels.csv, sorted by filename.
+/// Problems are skipped here; use [`validate_dataset`] to report them.
+pub fn labeled_images(root: &Path) -> CaptchaResult<Vec<(PathBuf, String)>> {
+    let labels_path = root.join(LABELS_FILE);
+    if !labels_path.is_file() {
+        return Err(CaptchaError::BadRequest(format!(
+            "Labels file not found: {}", labels_path.display()
+        )));
+    }
+
+    let labels = read_labels(&labels_path, &mut DatasetReport::default(), &mut Problems::new(0));
+    let images_dir = root.join(IMAGES_DIR);
+
+    let mut samples: Vec<(PathBuf, String)> = labels
+        .into_iter()
+        .map(|(name, label)| (images_dir.join(name), label))
+        .filter(|(path, _)| path.is_file())
+        .collect();
+    samples.sort();
+
+    Ok(samples)
+}
+
+/// Parse labels.csv into filename → label, recording malformed and duplicate rows
+fn read_labels(path: &Path, report: &mut DatasetReport, problems: &mut Problems) -> HashMap<String, String> {
+    let mut labels = HashMap::new();
+
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            problems.push(DatasetProblemKind::MissingLabelsFile, LABELS_FILE, e.to_string());
+            return labels;
+        }
+    };
+
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || (index == 0 && line.eq_ignore_ascii_case("filename,label")) {
+            continue;
+        }
+        report.labels_total += 1;
+
+        let location = format!("{}:{}", LABELS_FILE, index + 1);
+        let Some((filename, label)) = line.split_once(',') else {
+            problems.push(DatasetProblemKind::InvalidLabel, &location, "Expected `filename,label`".to_string());
+            continue;
+        };
+        let (filename, label) = (filename.trim(), label.trim());
+
+        if filename.is_empty() || label.is_empty() {
+            problems.push(DatasetProblemKind::InvalidLabel, &location, "Empty filename or label".to_string());
+        } else if labels.insert(filename.to_string(), label.to_string()).is_some() {
+            problems.push(DatasetProblemKind::DuplicateLabel, &location, format!("Duplicate entry for {}", filename));
+        }
+    }
+
+    labels
+}
+
+fn decode(path: &Path) -> Result<(), String> {
+    image::io::Reader::open(path)
+        .map_err(|e| e.to_string())?
+        .with_guessed_format()
+        .map_err(|e| e.to_string())?
+        .decode()
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Per-kind problem counts plus a capped sample of individual problems
+struct Problems {
+    counts: BTreeMap<DatasetProblemKind, usize>,
+    sample: Vec<DatasetProblem>,
+    max_sample: usize,
+}
+
+impl Problems {
+    fn new(max_sample: usize) -> Self {
+        Self { counts: BTreeMap::new(), sample: Vec::new(), max_sample }
+    }
+
+    fn push(&mut self, kind: DatasetProblemKind, file: &str, message: String) {
+        *self.counts.entry(kind).or_default() += 1;
+        if self.sample.len() < self.max_sample {
+            self.sample.push(DatasetProblem { kind, file: file.to_string(), message });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayImage, Luma};
+
+    fn dataset_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("captcha-dataset-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(dir.join(IMAGES_DIR)).unwrap();
+        dir
+    }
+
+    fn write_image(dir: &Path, name: &str) {
+        GrayImage::from_pixel(8, 4, Luma([255])).save(dir.join(IMAGES_DIR).join(name)).unwrap();
+    }
+
+    #[test]
+    fn test_valid_dataset() {
+        let dir = dataset_dir();
+        write_image(&dir, "a.png");
+        write_image(&dir, "b.png");
+        fs::write(dir.join(LABELS_FILE), "filename,label\na.png,AB12\nb.png,A9\n").unwrap();
+
+        let report = validate_dataset(&dir, 10, u64::MAX).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(report.valid);
+        assert_eq!(report.images_total, 2);
+        assert_eq!(report.images_valid, 2);
+        assert_eq!(report.labeled_images, 2);
+        assert_eq!(report.class_distribution[&'A'], 2);
+        assert_eq!(report.class_distribution[&'9'], 1);
+        assert_eq!(report.label_lengths[&4], 1);
+        assert_eq!(report.label_lengths[&2], 1);
+        assert_eq!(report.problems_total, 0);
+    }
+
+    #[test]
+    fn test_reports_problems() {
+        let dir = dataset_dir();
+        write_image(&dir, "a.png");
+        write_image(&dir, "unlabeled.png");
+        fs::write(dir.join(IMAGES_DIR).join("broken.png"), b"not an image").unwrap();
+        fs::write(
+            dir.join(LABELS_FILE),
+            "filename,label\na.png,AB\na.png,CD\nbroken.png,XY\nmissing.png,ZZ\nno-comma\n",
+        ).unwrap();
+
+        let report = validate_dataset(&dir, 2, u64::MAX).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!report.valid);
+        assert_eq!(report.images_total, 3);
+        assert_eq!(report.images_valid, 2);
+        assert_eq!(report.labels_total, 5);
+        assert_eq!(report.problem_counts[&DatasetProblemKind::CorruptImage], 1);
+        assert_eq!(report.problem_counts[&DatasetProblemKind::MissingLabel], 1);
+        assert_eq!(report.problem_counts[&DatasetProblemKind::MissingImage], 1);
+        assert_eq!(report.problem_counts[&DatasetProblemKind::DuplicateLabel], 1);
+        assert_eq!(report.problem_counts[&DatasetProblemKind::InvalidLabel], 1);
+        assert_eq!(report.problems_total, 5);
+        assert_eq!(report.problems.len(), 2);
+    }
+
+    #[test]
+    fn test_missing_layout() {
+        let dir = std::env::temp_dir().join(format!("captcha-dataset-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let report = validate_dataset(&dir, 10, u64::MAX).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!report.valid);
+        assert_eq!(report.problem_counts[&DatasetProblemKind::MissingLabelsFile], 1);
+        assert_eq!(report.problem_counts[&DatasetProblemKind::MissingImagesDir], 1);
+    }
+
+    #[test]
+    fn test_labeled_images() {
+        let dir = dataset_dir();
+        write_image(&dir, "a.png");
+        write_image(&dir, "unlabeled.png");
+        fs::write(dir.join(LABELS_FILE), "filename,label\nb.png,XY\na.png,AB12\nbad-row\n").unwrap();
+
+        let samples = labeled_images(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(samples, vec![(dir.join(IMAGES_DIR).join("a.png"), "AB12".to_string())]);
+    }
+
+    #[test]
+    fn test_rejects_non_directory() {
+        let result = validate_dataset(Path::new("/nonexistent/dataset"), 10, u64::MAX);
+        assert!(matches!(result, Err(CaptchaError::BadRequest(_))));
+    }
+}
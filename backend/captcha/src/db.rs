@@ -1,33 +1,68 @@
 //! Database module for Captcha Service
 
-use sqlx::{mysql::MySqlPoolOptions, MySql, Pool};
+use sqlx::{migrate::Migrator, mysql::MySqlPoolOptions, MySql, Pool, Transaction};
 use crate::config::DatabaseSettings;
 use crate::error::{CaptchaError, CaptchaResult};
-use crate::models::{CaptchaLog, CaptchaModel, TrainingJob, TrainingStatus, ModelType};
+use crate::models::{
+    ApiKey, AuditLogEntry, AuditLogQuery, CaptchaLog, CaptchaModel, Stats, TrainingJob, TrainingStatus, ModelType,
+};
 use chrono::{DateTime, Utc};
 
-/// Database wrapper
+/// Schema migrations shared with the MySQL container init scripts
+static MIGRATOR: Migrator = sqlx::migrate!("../../database/migrations");
+
+/// Database wrapper. Writes, and reads that must see them, go to the primary; the
+/// heavy read-only queries (stats, log and model listings) go to the read replica,
+/// which is the primary itself when none is configured.
 pub struct Database {
     pool: Pool<MySql>,
+    replica: Pool<MySql>,
 }
 
 impl Database {
-    /// Create a new database connection
+    /// Create a new database connection, and one to the read replica if configured
     pub async fn new(config: &DatabaseSettings) -> CaptchaResult<Self> {
-        let pool = MySqlPoolOptions::new()
-            .max_connections(config.max_connections)
-            .connect(&config.connection_url())
-            .await
-            .map_err(|e| CaptchaError::DatabaseError(e.to_string()))?;
+        let connect = |url: String| async move {
+            MySqlPoolOptions::new()
+                .max_connections(config.max_connections)
+                .connect(&url)
+                .await
+                .map_err(|e| CaptchaError::DatabaseError(e.to_string()))
+        };
+
+        let pool = connect(config.connection_url()).await?;
+        let replica = match &config.replica_url {
+            Some(url) => connect(url.clone()).await?,
+            None => pool.clone(),
+        };
 
-        Ok(Self { pool })
+        Ok(Self { pool, replica })
     }
 
-    /// Get the connection pool
+    /// Connect to the MySQL database in `CAPTCHA_TEST_DATABASE_URL` (schema applied)
+    #[cfg(test)]
+    pub async fn connect_test() -> Self {
+        let url = std::env::var("CAPTCHA_TEST_DATABASE_URL")
+            .expect("CAPTCHA_TEST_DATABASE_URL must point at a test database");
+        let pool = MySqlPoolOptions::new().max_connections(4).connect(&url).await.unwrap();
+        Self { replica: pool.clone(), pool }
+    }
+
+    /// Get the connection pool of the primary
     pub fn pool(&self) -> &Pool<MySql> {
         &self.pool
     }
 
+    /// Apply the schema migrations in `database/migrations` that have not run yet.
+    /// The migrations are embedded at build time and idempotent, so a database
+    /// created from the same files (e.g. by the MySQL init scripts) is adopted as is.
+    pub async fn run_migrations(&self) -> CaptchaResult<()> {
+        MIGRATOR
+            .run(&self.pool)
+            .await
+            .map_err(|e| CaptchaError::DatabaseError(format!("Migration failed: {}", e)))
+    }
+
     // ==================== Model Operations ====================
 
     /// Get all active models
@@ -37,8 +72,8 @@ impl Database {
             r#"
             SELECT 
                 id, name, 
-                type as "model_type: ModelType",
-                version, file_path, file_size_bytes,
+                type as model_type,
+                version, file_path, file_size_bytes, file_sha256,
                 accuracy, is_active, is_default,
                 metadata as "metadata: serde_json::Value",
                 description, created_by, created_at, updated_at
@@ -53,22 +88,23 @@ impl Database {
         Ok(models)
     }
 
-    /// Get default model
-    pub async fn get_default_model(&self) -> CaptchaResult<Option<CaptchaModel>> {
+    /// Get the default model of a model type
+    pub async fn get_default_model(&self, model_type: &str) -> CaptchaResult<Option<CaptchaModel>> {
         let model = sqlx::query_as!(
             CaptchaModel,
             r#"
             SELECT 
                 id, name, 
-                type as "model_type: ModelType",
-                version, file_path, file_size_bytes,
+                type as model_type,
+                version, file_path, file_size_bytes, file_sha256,
                 accuracy, is_active, is_default,
                 metadata as "metadata: serde_json::Value",
                 description, created_by, created_at, updated_at
             FROM captcha_models 
-            WHERE is_default = true AND is_active = true
+            WHERE type = ? AND is_default = true AND is_active = true
             LIMIT 1
-            "#
+            "#,
+            model_type
         )
         .fetch_optional(&self.pool)
         .await?;
@@ -83,8 +119,8 @@ impl Database {
             r#"
             SELECT 
                 id, name, 
-                type as "model_type: ModelType",
-                version, file_path, file_size_bytes,
+                type as model_type,
+                version, file_path, file_size_bytes, file_sha256,
                 accuracy, is_active, is_default,
                 metadata as "metadata: serde_json::Value",
                 description, created_by, created_at, updated_at
@@ -99,6 +135,201 @@ impl Database {
         Ok(model)
     }
 
+    /// Get model by ID, active or not
+    pub async fn get_model_by_id(&self, model_id: u64) -> CaptchaResult<Option<CaptchaModel>> {
+        let model = sqlx::query_as!(
+            CaptchaModel,
+            r#"
+            SELECT 
+                id, name, 
+                type as model_type,
+                version, file_path, file_size_bytes, file_sha256,
+                accuracy, is_active, is_default,
+                metadata as "metadata: serde_json::Value",
+                description, created_by, created_at, updated_at
+            FROM captcha_models 
+            WHERE id = ?
+            "#,
+            model_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(model)
+    }
+
+    /// List all models, newest first
+    pub async fn get_all_models(&self, limit: u32, offset: u32) -> CaptchaResult<Vec<CaptchaModel>> {
+        let models = sqlx::query_as!(
+            CaptchaModel,
+            r#"
+            SELECT 
+                id, name, 
+                type as model_type,
+                version, file_path, file_size_bytes, file_sha256,
+                accuracy, is_active, is_default,
+                metadata as "metadata: serde_json::Value",
+                description, created_by, created_at, updated_at
+            FROM captcha_models 
+            ORDER BY created_at DESC, id DESC
+            LIMIT ? OFFSET ?
+            "#,
+            limit,
+            offset
+        )
+        .fetch_all(&self.replica)
+        .await?;
+
+        Ok(models)
+    }
+
+    /// Count all models
+    pub async fn count_models(&self) -> CaptchaResult<u64> {
+        let row: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM captcha_models")
+            .fetch_one(&self.replica)
+            .await?;
+
+        Ok(row.0 as u64)
+    }
+
+    /// Whether any model, active or not, already uses `name` or `file_path`
+    pub async fn model_exists(&self, name: &str, file_path: &str) -> CaptchaResult<bool> {
+        let (count,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM captcha_models WHERE name = ? OR file_path = ?"
+        )
+            .bind(name)
+            .bind(file_path)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count > 0)
+    }
+
+    /// Make a model the only default of its model type, in one transaction.
+    /// The target must be active; afterwards its type must have exactly one default or nothing changes.
+    /// Defaults of other types are left as they are.
+    pub async fn set_default_model(&self, model_id: u64) -> CaptchaResult<()> {
+        let mut tx = self.pool.begin().await?;
+        Self::make_default(&mut tx, model_id).await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// [`Self::set_default_model`] inside the caller's transaction
+    async fn make_default(tx: &mut Transaction<'_, MySql>, model_id: u64) -> CaptchaResult<()> {
+        let target: Option<(bool, String)> = sqlx::query_as("SELECT is_active, type FROM captcha_models WHERE id = ?")
+            .bind(model_id)
+            .fetch_optional(&mut **tx)
+            .await?;
+
+        let model_type = match target {
+            None => return Err(CaptchaError::ModelNotFound(format!("Model {} not found", model_id))),
+            Some((false, _)) => {
+                return Err(CaptchaError::BadRequest("Cannot set inactive model as default".to_string()))
+            }
+            Some((true, model_type)) => model_type,
+        };
+
+        // One statement over every row of the type: locks rows in primary key order, so
+        // concurrent calls serialize instead of deadlocking
+        sqlx::query("UPDATE captcha_models SET is_default = (id = ? AND is_active) WHERE type = ?")
+            .bind(model_id)
+            .bind(&model_type)
+            .execute(&mut **tx)
+            .await?;
+
+        let (defaults,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM captcha_models WHERE type = ? AND is_default = true"
+        )
+            .bind(&model_type)
+            .fetch_one(&mut **tx)
+            .await?;
+        if defaults != 1 {
+            // Dropping the transaction rolls it back
+            return Err(CaptchaError::DatabaseError(format!(
+                "Expected exactly one default {} model after update, found {}", model_type, defaults
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Change a model's flags and description, leaving unset fields as they are, in one
+    /// transaction with promoting `replacement_default_id` (when the change removes the
+    /// default). Deactivating a model also clears its default flag; making it the default
+    /// demotes the other models of its type.
+    pub async fn update_model(
+        &self,
+        model_id: u64,
+        is_active: Option<bool>,
+        is_default: Option<bool>,
+        description: Option<&str>,
+        replacement_default_id: Option<u64>,
+    ) -> CaptchaResult<()> {
+        let mut tx = self.pool.begin().await?;
+        if let Some(replacement_id) = replacement_default_id {
+            Self::make_default(&mut tx, replacement_id).await?;
+        }
+
+        let result = sqlx::query(
+            r#"
+            UPDATE captcha_models
+            SET is_active = COALESCE(?, is_active),
+                is_default = is_default AND COALESCE(?, is_active) AND COALESCE(?, true),
+                description = COALESCE(?, description)
+            WHERE id = ?
+            "#
+        )
+            .bind(is_active)
+            .bind(is_active)
+            .bind(is_default)
+            .bind(description)
+            .bind(model_id)
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() == 0 && !Self::model_row_exists(&mut tx, model_id).await? {
+            return Err(CaptchaError::ModelNotFound(format!("Model {} not found", model_id)));
+        }
+
+        if is_default == Some(true) {
+            Self::make_default(&mut tx, model_id).await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Delete a model, in one transaction with promoting `replacement_default_id` first
+    /// (when the model is the default). Logs and training jobs keep their rows, unlinked.
+    pub async fn delete_model(&self, model_id: u64, replacement_default_id: Option<u64>) -> CaptchaResult<()> {
+        let mut tx = self.pool.begin().await?;
+        if let Some(replacement_id) = replacement_default_id {
+            Self::make_default(&mut tx, replacement_id).await?;
+        }
+
+        let result = sqlx::query("DELETE FROM captcha_models WHERE id = ?")
+            .bind(model_id)
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(CaptchaError::ModelNotFound(format!("Model {} not found", model_id)));
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Whether the model row exists, seen from inside the caller's transaction
+    async fn model_row_exists(tx: &mut Transaction<'_, MySql>, model_id: u64) -> CaptchaResult<bool> {
+        let row: Option<(u64,)> = sqlx::query_as("SELECT id FROM captcha_models WHERE id = ?")
+            .bind(model_id)
+            .fetch_optional(&mut **tx)
+            .await?;
+
+        Ok(row.is_some())
+    }
+
     /// Create a new model
     pub async fn create_model(
         &self,
@@ -107,20 +338,22 @@ impl Database {
         version: &str,
         file_path: &str,
         file_size: u64,
+        file_sha256: Option<&str>,
         description: Option<&str>,
         created_by: Option<u64>,
     ) -> CaptchaResult<u64> {
         let result = sqlx::query!(
             r#"
             INSERT INTO captcha_models 
-                (name, type, version, file_path, file_size_bytes, description, created_by, is_active, is_default)
-            VALUES (?, ?, ?, ?, ?, ?, ?, true, false)
+                (name, type, version, file_path, file_size_bytes, file_sha256, description, created_by, is_active, is_default)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, true, false)
             "#,
             name,
             model_type.to_string(),
             version,
             file_path,
             file_size,
+            file_sha256,
             description,
             created_by
         )
@@ -138,24 +371,28 @@ impl Database {
         user_id: Option<u64>,
         model_id: Option<u64>,
         image_hash: &str,
+        image_hash_algorithm: &str,
         predicted_text: Option<&str>,
         confidence: Option<f64>,
         processing_time_ms: u32,
         request_ip: Option<&str>,
+        image_base64: Option<&str>,
     ) -> CaptchaResult<u64> {
         let result = sqlx::query!(
             r#"
             INSERT INTO captcha_logs 
-                (user_id, model_id, image_hash, predicted_text, confidence, processing_time_ms, request_ip)
-            VALUES (?, ?, ?, ?, ?, ?, ?)
+                (user_id, model_id, image_hash, image_hash_algorithm, predicted_text, confidence, processing_time_ms, request_ip, image_base64)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
             user_id,
             model_id,
             image_hash,
+            image_hash_algorithm,
             predicted_text,
             confidence,
             processing_time_ms,
-            request_ip
+            request_ip,
+            image_base64
         )
         .execute(&self.pool)
         .await?;
@@ -192,14 +429,14 @@ impl Database {
             where_clause
         );
         let count_row: (i64,) = sqlx::query_as(&count_query)
-            .fetch_one(&self.pool)
+            .fetch_one(&self.replica)
             .await?;
         let total = count_row.0 as u64;
 
         // Get logs
         let logs_query = format!(
             r#"
-            SELECT id, user_id, model_id, image_hash, predicted_text, 
+            SELECT id, user_id, model_id, image_hash, image_hash_algorithm, predicted_text, 
                    actual_text, confidence, is_correct, processing_time_ms, 
                    request_ip, created_at
             FROM captcha_logs 
@@ -211,12 +448,133 @@ impl Database {
         );
 
         let logs = sqlx::query_as::<_, CaptchaLog>(&logs_query)
-            .fetch_all(&self.pool)
+            .fetch_all(&self.replica)
             .await?;
 
         Ok((logs, total))
     }
 
+    /// Get a batch of logs that have feedback, ordered by id and starting after `after_id`
+    pub async fn get_labeled_logs_batch(
+        &self,
+        after_id: u64,
+        limit: u32,
+    ) -> CaptchaResult<Vec<(u64, Option<String>, String, Option<bool>)>> {
+        let rows: Vec<(u64, Option<String>, String, Option<bool>)> = sqlx::query_as(
+            r#"
+            SELECT id, predicted_text, actual_text, is_correct
+            FROM captcha_logs
+            WHERE actual_text IS NOT NULL AND id > ?
+            ORDER BY id ASC
+            LIMIT ?
+            "#
+        )
+        .bind(after_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    /// The newest labeled logs below `before_id` that kept their image, optionally of
+    /// one model: `(id, predicted_text, actual_text, image_base64)`, newest first
+    pub async fn get_replay_logs(
+        &self,
+        model_id: Option<u64>,
+        before_id: u64,
+        limit: u32,
+    ) -> CaptchaResult<Vec<(u64, Option<String>, String, String)>> {
+        let rows = sqlx::query_as(
+            r#"
+            SELECT id, predicted_text, actual_text, image_base64
+            FROM captcha_logs
+            WHERE actual_text IS NOT NULL AND image_base64 IS NOT NULL
+              AND (? IS NULL OR model_id = ?)
+              AND id < ?
+            ORDER BY id DESC
+            LIMIT ?
+            "#
+        )
+        .bind(model_id)
+        .bind(model_id)
+        .bind(before_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    /// Returned confidence and correctness of the newest labeled logs answered by
+    /// models of `model_type`, the type a solver serves
+    pub async fn get_calibration_samples(
+        &self,
+        model_type: &str,
+        limit: u32,
+    ) -> CaptchaResult<Vec<(f64, bool)>> {
+        let rows = sqlx::query_as(
+            r#"
+            SELECT l.confidence, l.is_correct
+            FROM captcha_logs l
+            JOIN captcha_models m ON m.id = l.model_id
+            WHERE m.type = ? AND l.is_correct IS NOT NULL AND l.confidence IS NOT NULL
+            ORDER BY l.id DESC
+            LIMIT ?
+            "#
+        )
+        .bind(model_type)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    /// Every model's stored accuracy with the number of its labeled logs and the
+    /// share of them that were correct: `(id, name, accuracy, labeled, measured)`
+    pub async fn get_model_accuracy_stats(
+        &self,
+    ) -> CaptchaResult<Vec<(u64, String, Option<f64>, i64, Option<f64>)>> {
+        let rows = sqlx::query_as(
+            r#"
+            SELECT m.id, m.name, CAST(m.accuracy AS DOUBLE),
+                   COUNT(l.id),
+                   CAST(AVG(CASE WHEN l.is_correct = true THEN 1.0 ELSE 0.0 END) AS DOUBLE)
+            FROM captcha_models m
+            LEFT JOIN captcha_logs l ON l.model_id = m.id AND l.is_correct IS NOT NULL
+            GROUP BY m.id, m.name, m.accuracy
+            ORDER BY m.id
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    /// Store a model's accuracy
+    pub async fn set_model_accuracy(&self, model_id: u64, accuracy: f64) -> CaptchaResult<()> {
+        sqlx::query("UPDATE captcha_models SET accuracy = ? WHERE id = ?")
+            .bind(accuracy)
+            .bind(model_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Set the correctness flag of a log entry
+    pub async fn set_log_correctness(&self, log_id: u64, is_correct: bool) -> CaptchaResult<()> {
+        sqlx::query("UPDATE captcha_logs SET is_correct = ? WHERE id = ?")
+            .bind(is_correct)
+            .bind(log_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
     // ==================== Training Operations ====================
 
     /// Create a training job
@@ -254,7 +612,7 @@ impl Database {
             SELECT 
                 id, user_id, name,
                 status as "status: TrainingStatus",
-                model_type as "model_type: ModelType",
+                model_type,
                 config as "config: serde_json::Value",
                 dataset_path, dataset_size, progress,
                 current_epoch, total_epochs,
@@ -272,7 +630,64 @@ impl Database {
         Ok(job)
     }
 
-    /// Update training job status
+    /// List training jobs, newest first, optionally filtered by owner and status
+    pub async fn list_training_jobs(
+        &self,
+        user_id: Option<u64>,
+        status: Option<TrainingStatus>,
+        limit: u32,
+        offset: u32,
+    ) -> CaptchaResult<Vec<TrainingJob>> {
+        let status = status.map(|status| status.as_str());
+        let jobs = sqlx::query_as!(
+            TrainingJob,
+            r#"
+            SELECT 
+                id, user_id, name,
+                status as "status: TrainingStatus",
+                model_type,
+                config as "config: serde_json::Value",
+                dataset_path, dataset_size, progress,
+                current_epoch, total_epochs,
+                results as "results: serde_json::Value",
+                output_model_id, error_message,
+                started_at, completed_at, created_at, updated_at
+            FROM training_jobs 
+            WHERE (? IS NULL OR user_id = ?) AND (? IS NULL OR status = ?)
+            ORDER BY created_at DESC, id DESC
+            LIMIT ? OFFSET ?
+            "#,
+            user_id,
+            user_id,
+            status,
+            status,
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(jobs)
+    }
+
+    /// Count training jobs matching the same filters as `list_training_jobs`
+    pub async fn count_training_jobs(&self, user_id: Option<u64>, status: Option<TrainingStatus>) -> CaptchaResult<u64> {
+        let status = status.map(|status| status.as_str());
+        let row: (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM training_jobs WHERE (? IS NULL OR user_id = ?) AND (? IS NULL OR status = ?)"
+        )
+        .bind(user_id)
+        .bind(user_id)
+        .bind(status)
+        .bind(status)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.0 as u64)
+    }
+
+    /// Update training job status and progress. Like every status write, rejected with
+    /// `BadRequest` unless the job may move to `status` (see [`Self::lock_training_transition`]).
     pub async fn update_training_status(
         &self,
         job_id: u64,
@@ -281,6 +696,9 @@ impl Database {
         current_epoch: Option<u32>,
         error_message: Option<&str>,
     ) -> CaptchaResult<()> {
+        let mut tx = self.pool.begin().await?;
+        Self::lock_training_transition(&mut tx, job_id, *status).await?;
+
         sqlx::query!(
             r#"
             UPDATE training_jobs 
@@ -293,66 +711,338 @@ impl Database {
             error_message,
             job_id
         )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Move a training job to `status` without touching its progress, keeping its error
+    /// message unless a new one is given. Rejected like [`Self::update_training_status`].
+    pub async fn update_training_job_status(
+        &self,
+        job_id: u64,
+        status: TrainingStatus,
+        error_message: Option<&str>,
+    ) -> CaptchaResult<()> {
+        let mut tx = self.pool.begin().await?;
+        Self::lock_training_transition(&mut tx, job_id, status).await?;
+
+        sqlx::query(
+            "UPDATE training_jobs SET status = ?, error_message = COALESCE(?, error_message), updated_at = NOW() WHERE id = ?"
+        )
+            .bind(status.as_str())
+            .bind(error_message)
+            .bind(job_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Lock a training job's row for the rest of `tx` and check that its current status may
+    /// move to `next` ([`TrainingStatus::can_transition_to`]), so concurrent writers can't
+    /// both pass the check. Returns the current status.
+    async fn lock_training_transition(
+        tx: &mut sqlx::Transaction<'_, sqlx::MySql>,
+        job_id: u64,
+        next: TrainingStatus,
+    ) -> CaptchaResult<TrainingStatus> {
+        let row: Option<(String,)> = sqlx::query_as("SELECT status FROM training_jobs WHERE id = ? FOR UPDATE")
+            .bind(job_id)
+            .fetch_optional(&mut **tx)
+            .await?;
+
+        let Some((current,)) = row else {
+            return Err(CaptchaError::NotFound(format!("Training job {} not found", job_id)));
+        };
+        let current: TrainingStatus = current.parse().map_err(CaptchaError::DatabaseError)?;
+        if !current.can_transition_to(next) {
+            return Err(CaptchaError::BadRequest(format!(
+                "Cannot move training job {} from {} to {}", job_id, current, next
+            )));
+        }
+        Ok(current)
+    }
+
+    // ==================== Audit Operations ====================
+
+    /// Record an admin mutation
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_audit_log(
+        &self,
+        user_id: Option<u64>,
+        api_key_id: Option<u64>,
+        action: &str,
+        resource_type: &str,
+        resource_id: Option<u64>,
+        before_state: Option<&serde_json::Value>,
+        after_state: Option<&serde_json::Value>,
+        request_ip: Option<&str>,
+    ) -> CaptchaResult<u64> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO audit_log
+                (user_id, api_key_id, action, resource_type, resource_id, before_state, after_state, request_ip)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(user_id)
+        .bind(api_key_id)
+        .bind(action)
+        .bind(resource_type)
+        .bind(resource_id)
+        .bind(before_state)
+        .bind(after_state)
+        .bind(request_ip)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.last_insert_id())
+    }
+
+    /// Query the audit log, newest first. Returns the page and the total matching count.
+    pub async fn list_audit_logs(&self, query: &AuditLogQuery, limit: u32, offset: u32) -> CaptchaResult<(Vec<AuditLogEntry>, u64)> {
+        let where_clause = "(? IS NULL OR action = ?) AND (? IS NULL OR resource_type = ?) \
+            AND (? IS NULL OR resource_id = ?) AND (? IS NULL OR user_id = ?)";
+
+        let count_query = format!("SELECT COUNT(*) FROM audit_log WHERE {}", where_clause);
+        let (total,): (i64,) = sqlx::query_as(&count_query)
+            .bind(&query.action).bind(&query.action)
+            .bind(&query.resource_type).bind(&query.resource_type)
+            .bind(query.resource_id).bind(query.resource_id)
+            .bind(query.user_id).bind(query.user_id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        let entries_query = format!(
+            "SELECT * FROM audit_log WHERE {} ORDER BY created_at DESC, id DESC LIMIT ? OFFSET ?",
+            where_clause
+        );
+        let entries = sqlx::query_as::<_, AuditLogEntry>(&entries_query)
+            .bind(&query.action).bind(&query.action)
+            .bind(&query.resource_type).bind(&query.resource_type)
+            .bind(query.resource_id).bind(query.resource_id)
+            .bind(query.user_id).bind(query.user_id)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok((entries, total as u64))
+    }
+
+    // ==================== API Key Operations ====================
+
+    /// Create an API key (only the prefix and hash are stored)
+    pub async fn create_api_key(
+        &self,
+        user_id: u64,
+        name: &str,
+        key_prefix: &str,
+        key_hash: &str,
+        scopes: Option<&serde_json::Value>,
+        rate_limit: u32,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> CaptchaResult<u64> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO api_keys
+                (user_id, name, key_prefix, key_hash, scopes, rate_limit, expires_at, is_active)
+            VALUES (?, ?, ?, ?, ?, ?, ?, true)
+            "#
+        )
+        .bind(user_id)
+        .bind(name)
+        .bind(key_prefix)
+        .bind(key_hash)
+        .bind(scopes)
+        .bind(rate_limit)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.last_insert_id())
+    }
+
+    /// Get API key by ID
+    pub async fn get_api_key(&self, key_id: u64) -> CaptchaResult<Option<ApiKey>> {
+        let key = sqlx::query_as::<_, ApiKey>("SELECT * FROM api_keys WHERE id = ?")
+            .bind(key_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(key)
+    }
+
+    /// Find API keys that may match a presented key: by stored prefix, or by
+    /// unsalted hash for keys issued by the auth service
+    pub async fn find_api_key_candidates(&self, key_prefix: &str, legacy_hash: &str) -> CaptchaResult<Vec<ApiKey>> {
+        let keys = sqlx::query_as::<_, ApiKey>(
+            "SELECT * FROM api_keys WHERE key_prefix = ? OR key_hash = ?"
+        )
+        .bind(key_prefix)
+        .bind(legacy_hash)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(keys)
+    }
+
+    /// List API keys belonging to a user
+    pub async fn list_api_keys(&self, user_id: u64, limit: u32, offset: u32) -> CaptchaResult<Vec<ApiKey>> {
+        let keys = sqlx::query_as::<_, ApiKey>(
+            "SELECT * FROM api_keys WHERE user_id = ? ORDER BY created_at DESC, id DESC LIMIT ? OFFSET ?"
+        )
+        .bind(user_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(keys)
+    }
+
+    /// Count API keys belonging to a user
+    pub async fn count_api_keys(&self, user_id: u64) -> CaptchaResult<u64> {
+        let row: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM api_keys WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(row.0 as u64)
+    }
+
+    /// Replace the stored prefix and hash of an API key
+    pub async fn rotate_api_key(&self, key_id: u64, key_prefix: &str, key_hash: &str) -> CaptchaResult<()> {
+        sqlx::query("UPDATE api_keys SET key_prefix = ?, key_hash = ?, updated_at = NOW() WHERE id = ?")
+            .bind(key_prefix)
+            .bind(key_hash)
+            .bind(key_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Add buffered usage to an API key's counters
+    pub async fn add_api_key_usage(&self, key_id: u64, requests: u64, last_used_at: DateTime<Utc>) -> CaptchaResult<()> {
+        sqlx::query(
+            r#"
+            UPDATE api_keys
+            SET total_requests = total_requests + ?,
+                last_used_at = GREATEST(COALESCE(last_used_at, ?), ?),
+                updated_at = updated_at
+            WHERE id = ?
+            "#
+        )
+        .bind(requests)
+        .bind(last_used_at)
+        .bind(last_used_at)
+        .bind(key_id)
         .execute(&self.pool)
         .await?;
 
         Ok(())
     }
 
+    /// Deactivate an API key
+    pub async fn revoke_api_key(&self, key_id: u64) -> CaptchaResult<()> {
+        sqlx::query("UPDATE api_keys SET is_active = false, updated_at = NOW() WHERE id = ?")
+            .bind(key_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
     // ==================== Statistics ====================
 
-    /// Get statistics
-    pub async fn get_stats(&self) -> CaptchaResult<(u64, u64, u64, f64, f64, u64, u64)> {
+    /// Get statistics, for one user's logs when `user_id` is set. A log counts as
+    /// successful when it has a prediction with confidence at least `confidence_floor`;
+    /// predictions below the floor are counted as low confidence instead.
+    pub async fn get_stats(&self, user_id: Option<u64>, confidence_floor: f64) -> CaptchaResult<Stats> {
         // Total requests
-        let total: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM captcha_logs")
-            .fetch_one(&self.pool)
-            .await?;
+        let total: (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM captcha_logs WHERE (? IS NULL OR user_id = ?)"
+        )
+        .bind(user_id)
+        .bind(user_id)
+        .fetch_one(&self.replica)
+        .await?;
+
+        // Produced output (non-null predictions)
+        let predicted: (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM captcha_logs WHERE predicted_text IS NOT NULL AND (? IS NULL OR user_id = ?)"
+        )
+        .bind(user_id)
+        .bind(user_id)
+        .fetch_one(&self.replica)
+        .await?;
 
-        // Successful (non-null predictions)
+        // Successful (confident predictions)
         let successful: (i64,) = sqlx::query_as(
-            "SELECT COUNT(*) FROM captcha_logs WHERE predicted_text IS NOT NULL"
+            r#"
+            SELECT COUNT(*) FROM captcha_logs
+            WHERE predicted_text IS NOT NULL AND COALESCE(confidence, 0) >= ?
+              AND (? IS NULL OR user_id = ?)
+            "#
         )
-        .fetch_one(&self.pool)
+        .bind(confidence_floor)
+        .bind(user_id)
+        .bind(user_id)
+        .fetch_one(&self.replica)
         .await?;
 
-        // Failed
-        let failed = total.0 - successful.0;
+        // Failed (no prediction)
+        let failed = total.0 - predicted.0;
 
         // Average processing time
         let avg_time: (Option<f64>,) = sqlx::query_as(
-            "SELECT AVG(processing_time_ms) FROM captcha_logs"
+            "SELECT CAST(AVG(processing_time_ms) AS DOUBLE) FROM captcha_logs WHERE (? IS NULL OR user_id = ?)"
         )
-        .fetch_one(&self.pool)
+        .bind(user_id)
+        .bind(user_id)
+        .fetch_one(&self.replica)
         .await?;
 
         // Accuracy rate
         let accuracy: (Option<f64>,) = sqlx::query_as(
-            "SELECT AVG(CASE WHEN is_correct = true THEN 1.0 ELSE 0.0 END) FROM captcha_logs WHERE is_correct IS NOT NULL"
+            r#"
+            SELECT CAST(AVG(CASE WHEN is_correct = true THEN 1.0 ELSE 0.0 END) AS DOUBLE) FROM captcha_logs
+            WHERE is_correct IS NOT NULL AND (? IS NULL OR user_id = ?)
+            "#
         )
-        .fetch_one(&self.pool)
+        .bind(user_id)
+        .bind(user_id)
+        .fetch_one(&self.replica)
         .await?;
 
         // Models count
         let models: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM captcha_models")
-            .fetch_one(&self.pool)
+            .fetch_one(&self.replica)
             .await?;
 
         // Active models
         let active: (i64,) = sqlx::query_as(
             "SELECT COUNT(*) FROM captcha_models WHERE is_active = true"
         )
-        .fetch_one(&self.pool)
+        .fetch_one(&self.replica)
         .await?;
 
-        Ok((
-            total.0 as u64,
-            successful.0 as u64,
-            failed as u64,
-            avg_time.0.unwrap_or(0.0),
-            accuracy.0.unwrap_or(0.0),
-            models.0 as u64,
-            active.0 as u64,
-        ))
+        Ok(Stats {
+            total_requests: total.0 as u64,
+            successful_requests: successful.0 as u64,
+            low_confidence_requests: (predicted.0 - successful.0) as u64,
+            failed_requests: failed as u64,
+            average_processing_time_ms: avg_time.0.unwrap_or(0.0),
+            accuracy_rate: accuracy.0.unwrap_or(0.0),
+            confidence_floor,
+            models_count: models.0 as u32,
+            active_models_count: active.0 as u32,
+        })
     }
 }
 
@@ -366,6 +1056,7 @@ impl<'r> sqlx::FromRow<'r, sqlx::mysql::MySqlRow> for CaptchaLog {
             user_id: row.try_get("user_id")?,
             model_id: row.try_get("model_id")?,
             image_hash: row.try_get("image_hash")?,
+            image_hash_algorithm: row.try_get("image_hash_algorithm")?,
             predicted_text: row.try_get("predicted_text")?,
             actual_text: row.try_get("actual_text")?,
             confidence: row.try_get("confidence")?,
@@ -377,14 +1068,161 @@ impl<'r> sqlx::FromRow<'r, sqlx::mysql::MySqlRow> for CaptchaLog {
     }
 }
 
-impl std::fmt::Display for TrainingStatus {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            TrainingStatus::Pending => write!(f, "pending"),
-            TrainingStatus::Running => write!(f, "running"),
-            TrainingStatus::Completed => write!(f, "completed"),
-            TrainingStatus::Failed => write!(f, "failed"),
-            TrainingStatus::Cancelled => write!(f, "cancelled"),
+// Implement FromRow for AuditLogEntry
+impl<'r> sqlx::FromRow<'r, sqlx::mysql::MySqlRow> for AuditLogEntry {
+    fn from_row(row: &'r sqlx::mysql::MySqlRow) -> Result<Self, sqlx::Error> {
+        use sqlx::Row;
+
+        Ok(AuditLogEntry {
+            id: row.try_get("id")?,
+            user_id: row.try_get("user_id")?,
+            api_key_id: row.try_get("api_key_id")?,
+            action: row.try_get("action")?,
+            resource_type: row.try_get("resource_type")?,
+            resource_id: row.try_get("resource_id")?,
+            before_state: row.try_get("before_state")?,
+            after_state: row.try_get("after_state")?,
+            request_ip: row.try_get("request_ip")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+impl<'r> sqlx::FromRow<'r, sqlx::mysql::MySqlRow> for ApiKey {
+    fn from_row(row: &'r sqlx::mysql::MySqlRow) -> Result<Self, sqlx::Error> {
+        use sqlx::Row;
+
+        Ok(ApiKey {
+            id: row.try_get("id")?,
+            user_id: row.try_get("user_id")?,
+            name: row.try_get("name")?,
+            key_prefix: row.try_get("key_prefix")?,
+            key_hash: row.try_get("key_hash")?,
+            scopes: row.try_get("scopes")?,
+            rate_limit: row.try_get("rate_limit")?,
+            total_requests: row.try_get("total_requests")?,
+            last_used_at: row.try_get("last_used_at")?,
+            is_active: row.try_get("is_active")?,
+            expires_at: row.try_get("expires_at")?,
+            created_at: row.try_get("created_at")?,
+            updated_at: row.try_get("updated_at")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn insert_model(db: &Database, name: &str, model_type: &str) -> u64 {
+        sqlx::query(
+            "INSERT INTO captcha_models (name, type, file_path, is_active, is_default) VALUES (?, ?, '/tmp/m.onnx', true, false)"
+        )
+        .bind(name)
+        .bind(model_type)
+        .execute(&db.pool)
+        .await
+        .unwrap()
+        .last_insert_id()
+    }
+
+    async fn defaults(db: &Database, model_type: &str) -> Vec<u64> {
+        sqlx::query_as::<_, (u64,)>("SELECT id FROM captcha_models WHERE type = ? AND is_default = true")
+            .bind(model_type)
+            .fetch_all(&db.pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(id,)| id)
+            .collect()
+    }
+
+    #[tokio::test]
+    #[ignore = "requires CAPTCHA_TEST_DATABASE_URL"]
+    async fn test_set_default_model_leaves_exactly_one_default_per_type() {
+        let db = Database::connect_test().await;
+        let suffix = uuid::Uuid::new_v4().simple().to_string();
+        let a = insert_model(&db, &format!("default-a-{}", suffix), "cnn").await;
+        let b = insert_model(&db, &format!("default-b-{}", suffix), "cnn").await;
+        let ocr = insert_model(&db, &format!("default-ocr-{}", suffix), "ocr").await;
+
+        db.set_default_model(ocr).await.unwrap();
+        db.set_default_model(a).await.unwrap();
+        assert_eq!(defaults(&db, "cnn").await, vec![a]);
+
+        db.set_default_model(b).await.unwrap();
+        assert_eq!(defaults(&db, "cnn").await, vec![b]);
+        assert_eq!(db.get_default_model("cnn").await.unwrap().map(|m| m.id), Some(b));
+
+        // Concurrent switches still leave a single default
+        let (ra, rb) = tokio::join!(db.set_default_model(a), db.set_default_model(b));
+        ra.unwrap();
+        rb.unwrap();
+        let remaining = defaults(&db, "cnn").await;
+        assert_eq!(remaining.len(), 1);
+        assert!(remaining[0] == a || remaining[0] == b);
+
+        // Other types keep their own default
+        assert_eq!(defaults(&db, "ocr").await, vec![ocr]);
+
+        sqlx::query("DELETE FROM captcha_models WHERE id IN (?, ?, ?)")
+            .bind(a)
+            .bind(b)
+            .bind(ocr)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "requires CAPTCHA_TEST_DATABASE_URL"]
+    async fn test_get_model_by_id() {
+        let db = Database::connect_test().await;
+        let id = insert_model(&db, &format!("by-id-{}", uuid::Uuid::new_v4().simple()), "cnn").await;
+        assert_eq!(db.get_model_by_id(id).await.unwrap().map(|m| m.id), Some(id));
+
+        sqlx::query("DELETE FROM captcha_models WHERE id = ?")
+            .bind(id)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        assert!(db.get_model_by_id(id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires CAPTCHA_TEST_DATABASE_URL"]
+    async fn test_stats_confidence_floor() {
+        let db = Database::connect_test().await;
+        let user_id = sqlx::query("INSERT INTO users (email, password_hash) VALUES (?, 'x')")
+            .bind(format!("stats-{}@example.com", uuid::Uuid::new_v4().simple()))
+            .execute(&db.pool)
+            .await
+            .unwrap()
+            .last_insert_id();
+
+        for (text, confidence) in [(Some("AB12"), Some(0.95)), (Some("CD34"), Some(0.3)), (None, None)] {
+            db.create_log(Some(user_id), None, "hash", "sha256", text, confidence, 10, None, None).await.unwrap();
         }
+
+        let stats = db.get_stats(Some(user_id), 0.5).await.unwrap();
+        assert_eq!(stats.total_requests, 3);
+        assert_eq!(stats.successful_requests, 1);
+        assert_eq!(stats.low_confidence_requests, 1);
+        assert_eq!(stats.failed_requests, 1);
+
+        // Without a floor every prediction counts as successful
+        let stats = db.get_stats(Some(user_id), 0.0).await.unwrap();
+        assert_eq!((stats.successful_requests, stats.low_confidence_requests), (2, 0));
+
+        sqlx::query("DELETE FROM captcha_logs WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM users WHERE id = ?")
+            .bind(user_id)
+            .execute(&db.pool)
+            .await
+            .unwrap();
     }
-}
\ No newline at end of file
+}
@@ -1,46 +1,86 @@
 //! Error types for Captcha Service
 
+use actix_web::http::StatusCode;
 use actix_web::{HttpResponse, ResponseError};
 use std::fmt;
 
+use crate::validation::FieldError;
+
 /// Custom error types for the captcha service
 #[derive(Debug)]
 pub enum CaptchaError {
     /// Invalid image data
     InvalidImage(String),
+    /// Image in a recognized format whose data is truncated or corrupt
+    CorruptImage(String),
+    /// Image in a recognized format that `processing.allowed_image_formats` excludes
+    FormatNotAllowed(String),
     /// Image too large
     ImageTooLarge,
     /// Model not found
     ModelNotFound(String),
+    /// Known model type without a solver in this build; the message lists the loaded solvers
+    UnsupportedModelType(String),
     /// Model loading failed
     ModelLoadError(String),
-    /// Processing timeout
-    Timeout,
+    /// No solver is ready to run (all failed to initialize or are disabled); the message says why
+    NoReadySolvers(String),
+    /// Processing timeout in the named solver
+    Timeout(String),
+    /// The whole request exceeded its route's deadline, in seconds
+    RequestTimeout(u64),
+    /// Too many requests queued; retry after the given number of seconds
+    Overloaded(u64),
     /// Database error
     DatabaseError(String),
     /// Internal processing error
     ProcessingError(String),
     /// Invalid request
     BadRequest(String),
+    /// Prediction too uncertain to send as a plain-text answer; holds its confidence
+    LowConfidence(f32),
+    /// Request fields failing validation, each with its path and reason
+    Validation(Vec<FieldError>),
     /// Unauthorized
     Unauthorized,
+    /// Authenticated but not permitted
+    Forbidden(String),
     /// Not found
     NotFound(String),
+    /// Conflicts with an earlier request (e.g. a reused idempotency key)
+    Conflict(String),
 }
 
 impl fmt::Display for CaptchaError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             CaptchaError::InvalidImage(msg) => write!(f, "Invalid image: {}", msg),
+            CaptchaError::CorruptImage(msg) => write!(f, "Corrupt image: {}", msg),
+            CaptchaError::FormatNotAllowed(msg) => write!(f, "Image format not allowed: {}", msg),
             CaptchaError::ImageTooLarge => write!(f, "Image exceeds maximum allowed size"),
             CaptchaError::ModelNotFound(name) => write!(f, "Model not found: {}", name),
+            CaptchaError::UnsupportedModelType(msg) => write!(f, "Unsupported model type: {}", msg),
             CaptchaError::ModelLoadError(msg) => write!(f, "Failed to load model: {}", msg),
-            CaptchaError::Timeout => write!(f, "Processing timeout"),
+            CaptchaError::NoReadySolvers(msg) => write!(f, "No ready solvers: {}", msg),
+            CaptchaError::Timeout(solver) => write!(f, "Processing timeout in solver {}", solver),
+            CaptchaError::RequestTimeout(secs) => write!(f, "Request exceeded its {}s deadline", secs),
+            CaptchaError::Overloaded(_) => write!(f, "Service overloaded"),
             CaptchaError::DatabaseError(msg) => write!(f, "Database error: {}", msg),
             CaptchaError::ProcessingError(msg) => write!(f, "Processing error: {}", msg),
             CaptchaError::BadRequest(msg) => write!(f, "Bad request: {}", msg),
+            CaptchaError::LowConfidence(confidence) => write!(f, "Low confidence prediction ({:.2})", confidence),
+            CaptchaError::Validation(errors) => {
+                write!(f, "Validation failed: ")?;
+                for (i, e) in errors.iter().enumerate() {
+                    let separator = if i == 0 { "" } else { "; " };
+                    write!(f, "{}{}: {}", separator, e.field, e.error)?;
+                }
+                Ok(())
+            }
             CaptchaError::Unauthorized => write!(f, "Unauthorized"),
+            CaptchaError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
             CaptchaError::NotFound(msg) => write!(f, "Not found: {}", msg),
+            CaptchaError::Conflict(msg) => write!(f, "Conflict: {}", msg),
         }
     }
 }
@@ -48,44 +88,105 @@ impl fmt::Display for CaptchaError {
 impl std::error::Error for CaptchaError {}
 
 impl ResponseError for CaptchaError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            CaptchaError::InvalidImage(_) => StatusCode::BAD_REQUEST,
+            CaptchaError::CorruptImage(_) => StatusCode::BAD_REQUEST,
+            CaptchaError::FormatNotAllowed(_) => StatusCode::BAD_REQUEST,
+            CaptchaError::ImageTooLarge => StatusCode::BAD_REQUEST,
+            CaptchaError::ModelNotFound(_) => StatusCode::NOT_FOUND,
+            CaptchaError::UnsupportedModelType(_) => StatusCode::BAD_REQUEST,
+            CaptchaError::ModelLoadError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            CaptchaError::NoReadySolvers(_) => StatusCode::SERVICE_UNAVAILABLE,
+            CaptchaError::Timeout(_) => StatusCode::REQUEST_TIMEOUT,
+            CaptchaError::RequestTimeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            CaptchaError::Overloaded(_) => StatusCode::SERVICE_UNAVAILABLE,
+            CaptchaError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            CaptchaError::ProcessingError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            CaptchaError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            CaptchaError::LowConfidence(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            CaptchaError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            CaptchaError::Unauthorized => StatusCode::UNAUTHORIZED,
+            CaptchaError::Forbidden(_) => StatusCode::FORBIDDEN,
+            CaptchaError::NotFound(_) => StatusCode::NOT_FOUND,
+            CaptchaError::Conflict(_) => StatusCode::CONFLICT,
+        }
+    }
+
     fn error_response(&self) -> HttpResponse {
-        let (status, error_code, message) = match self {
-            CaptchaError::InvalidImage(msg) => {
-                (actix_web::http::StatusCode::BAD_REQUEST, "invalid_image", msg.clone())
-            }
-            CaptchaError::ImageTooLarge => {
-                (actix_web::http::StatusCode::BAD_REQUEST, "image_too_large", "Image exceeds maximum allowed size".to_string())
-            }
-            CaptchaError::ModelNotFound(name) => {
-                (actix_web::http::StatusCode::NOT_FOUND, "model_not_found", format!("Model '{}' not found", name))
-            }
-            CaptchaError::ModelLoadError(msg) => {
-                (actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, "model_load_error", msg.clone())
-            }
-            CaptchaError::Timeout => {
-                (actix_web::http::StatusCode::REQUEST_TIMEOUT, "timeout", "Processing timeout".to_string())
-            }
-            CaptchaError::DatabaseError(msg) => {
-                (actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, "database_error", msg.clone())
-            }
-            CaptchaError::ProcessingError(msg) => {
-                (actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, "processing_error", msg.clone())
-            }
-            CaptchaError::BadRequest(msg) => {
-                (actix_web::http::StatusCode::BAD_REQUEST, "bad_request", msg.clone())
-            }
-            CaptchaError::Unauthorized => {
-                (actix_web::http::StatusCode::UNAUTHORIZED, "unauthorized", "Unauthorized".to_string())
-            }
-            CaptchaError::NotFound(msg) => {
-                (actix_web::http::StatusCode::NOT_FOUND, "not_found", msg.clone())
-            }
-        };
+        let mut builder = HttpResponse::build(self.status_code());
+        if let CaptchaError::Overloaded(retry_after) = self {
+            builder.insert_header((actix_web::http::header::RETRY_AFTER, retry_after.to_string()));
+        }
+
+        let mut body = serde_json::json!({
+            "error": self.error_code(),
+            "message": self.message()
+        });
+        if let CaptchaError::Validation(errors) = self {
+            body["fields"] = serde_json::json!(errors);
+        }
+
+        builder.json(body)
+    }
+}
+
+impl CaptchaError {
+    /// Machine-readable error category, as sent in the `error` field of error bodies
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            CaptchaError::InvalidImage(_) => "invalid_image",
+            CaptchaError::CorruptImage(_) => "corrupt_image",
+            CaptchaError::FormatNotAllowed(_) => "format_not_allowed",
+            CaptchaError::ImageTooLarge => "image_too_large",
+            CaptchaError::ModelNotFound(_) => "model_not_found",
+            CaptchaError::UnsupportedModelType(_) => "unsupported_model_type",
+            CaptchaError::ModelLoadError(_) => "model_load_error",
+            CaptchaError::NoReadySolvers(_) => "no_ready_solvers",
+            CaptchaError::Timeout(_) => "timeout",
+            CaptchaError::RequestTimeout(_) => "request_timeout",
+            CaptchaError::Overloaded(_) => "overloaded",
+            CaptchaError::DatabaseError(_) => "database_error",
+            CaptchaError::ProcessingError(_) => "processing_error",
+            CaptchaError::BadRequest(_) => "bad_request",
+            CaptchaError::LowConfidence(_) => "low_confidence",
+            CaptchaError::Validation(_) => "validation_error",
+            CaptchaError::Unauthorized => "unauthorized",
+            CaptchaError::Forbidden(_) => "forbidden",
+            CaptchaError::NotFound(_) => "not_found",
+            CaptchaError::Conflict(_) => "conflict",
+        }
+    }
+
+    /// Human-readable description, as sent in the `message` field of error bodies
+    pub fn message(&self) -> String {
+        match self {
+            CaptchaError::InvalidImage(msg) => msg.clone(),
+            CaptchaError::CorruptImage(msg) => msg.clone(),
+            CaptchaError::FormatNotAllowed(msg) => msg.clone(),
+            CaptchaError::ImageTooLarge => "Image exceeds maximum allowed size".to_string(),
+            CaptchaError::ModelNotFound(name) => format!("Model '{}' not found", name),
+            CaptchaError::UnsupportedModelType(msg) => msg.clone(),
+            CaptchaError::ModelLoadError(msg) => msg.clone(),
+            CaptchaError::NoReadySolvers(msg) => msg.clone(),
+            CaptchaError::Timeout(solver) => format!("Solver '{}' timed out", solver),
+            CaptchaError::RequestTimeout(secs) => format!("Request did not complete within {} seconds", secs),
+            CaptchaError::Overloaded(_) => "Service is overloaded, try again later".to_string(),
+            CaptchaError::DatabaseError(msg) => msg.clone(),
+            CaptchaError::ProcessingError(msg) => msg.clone(),
+            CaptchaError::BadRequest(msg) => msg.clone(),
+            CaptchaError::LowConfidence(confidence) => format!("No confident answer (confidence {:.2})", confidence),
+            CaptchaError::Validation(errors) => format!("{} invalid field(s)", errors.len()),
+            CaptchaError::Unauthorized => "Unauthorized".to_string(),
+            CaptchaError::Forbidden(msg) => msg.clone(),
+            CaptchaError::NotFound(msg) => msg.clone(),
+            CaptchaError::Conflict(msg) => msg.clone(),
+        }
+    }
 
-        HttpResponse::build(status).json(serde_json::json!({
-            "error": error_code,
-            "message": message
-        }))
+    /// Whether retrying the same operation may succeed (e.g. an inference runtime hiccup)
+    pub fn is_transient(&self) -> bool {
+        matches!(self, CaptchaError::ProcessingError(_) | CaptchaError::Timeout(_))
     }
 }
 
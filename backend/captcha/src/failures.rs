@@ -0,0 +1,274 @@
+//! Failed Solve Corpus
+//!
+//! With `processing.store_failures` set, images whose solve came back empty or
+//! below `processing.failure_confidence_threshold` are saved to
+//! `processing.failure_dir`, building a corpus of hard cases to label and train on
+//! without keeping every image. Each failure is written as `<hash>.png` (the decoded
+//! image the solver was given, after any crop) next to `<hash>.json` holding the
+//! prediction. The hash is of the pixels, so resubmits of an image overwrite one entry.
+//!
+//! Failures are handed to a single writer thread through a bounded queue and
+//! dropped when it is full, so a flood of unsolvable images can't tie up the
+//! blocking pool. The writer keeps the corpus within `processing.failure_max_files`
+//! entries and `processing.failure_max_bytes`, deleting the oldest entries first.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+
+use chrono::{DateTime, Utc};
+use image::DynamicImage;
+use serde::Serialize;
+
+use crate::config::ProcessingSettings;
+use crate::error::{CaptchaError, CaptchaResult};
+use crate::models::HashAlgorithm;
+
+/// Prediction saved next to a failed image
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct FailureRecord {
+    pub image_hash: String,
+    pub predicted_text: String,
+    pub confidence: f32,
+    pub solver: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Failures waiting for the writer beyond this are dropped
+const WRITE_QUEUE_CAPACITY: usize = 64;
+
+/// Saves images of failed solves
+pub struct FailureStore {
+    threshold: f32,
+    hash_algorithm: HashAlgorithm,
+    queue: SyncSender<(DynamicImage, FailureRecord)>,
+}
+
+impl FailureStore {
+    /// Store configured by `processing`, or `None` unless `store_failures` is set
+    /// (or its writer thread can't be started)
+    pub fn from_settings(processing: &ProcessingSettings) -> Option<Self> {
+        if !processing.store_failures {
+            return None;
+        }
+
+        let mut corpus = Corpus::open(
+            PathBuf::from(&processing.failure_dir),
+            processing.failure_max_files,
+            processing.failure_max_bytes,
+        );
+        let (queue, pending) = mpsc::sync_channel::<(DynamicImage, FailureRecord)>(WRITE_QUEUE_CAPACITY);
+        let writer = std::thread::Builder::new()
+            .name("failure-writer".to_string())
+            .spawn(move || {
+                for (image, record) in pending {
+                    if let Err(e) = corpus.save(&image, &record) {
+                        tracing::warn!("Failed to store failed solve {}: {}", record.image_hash, e);
+                    }
+                }
+            });
+        if let Err(e) = writer {
+            tracing::warn!("Cannot start failure writer, not storing failed solves: {}", e);
+            return None;
+        }
+
+        Some(Self {
+            threshold: processing.failure_confidence_threshold,
+            hash_algorithm: processing.image_hash_algorithm,
+            queue,
+        })
+    }
+
+    /// Whether a solve answering `text` with `confidence` counts as failed
+    pub fn is_failure(&self, text: &str, confidence: f32) -> bool {
+        text.is_empty() || confidence < self.threshold
+    }
+
+    /// Save `image` in the background if its solve failed. Write errors and failures
+    /// dropped from a full queue are logged, never returned: the corpus must not fail
+    /// the solve it records.
+    pub fn record(&self, image: &DynamicImage, text: &str, confidence: f32, solver: &str) {
+        if !self.is_failure(text, confidence) {
+            return;
+        }
+
+        let record = FailureRecord {
+            image_hash: self.hash_algorithm.hash(image.as_bytes()),
+            predicted_text: text.to_string(),
+            confidence,
+            solver: solver.to_string(),
+            created_at: Utc::now(),
+        };
+        match self.queue.try_send((image.clone(), record)) {
+            Ok(()) => {}
+            Err(TrySendError::Full((_, record))) => {
+                tracing::debug!("Failure writer busy, not storing failed solve {}", record.image_hash);
+            }
+            Err(TrySendError::Disconnected((_, record))) => {
+                tracing::warn!("Failure writer stopped, not storing failed solve {}", record.image_hash);
+            }
+        }
+    }
+}
+
+/// The saved failures on disk, oldest first, kept within a file count and byte budget
+struct Corpus {
+    dir: PathBuf,
+    /// Most entries kept (0 = unlimited)
+    max_files: usize,
+    /// Most bytes kept across images and predictions (0 = unlimited)
+    max_bytes: u64,
+    /// Hash and size in bytes of each saved entry
+    entries: VecDeque<(String, u64)>,
+    bytes: u64,
+}
+
+impl Corpus {
+    /// Index the entries already in `dir` by modification time, pruning it to the limits
+    fn open(dir: PathBuf, max_files: usize, max_bytes: u64) -> Self {
+        let mut found: Vec<_> = fs::read_dir(&dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|file| {
+                let path = file.path();
+                if path.extension()? != "png" {
+                    return None;
+                }
+                let hash = path.file_stem()?.to_str()?.to_string();
+                let metadata = file.metadata().ok()?;
+                let size = metadata.len() + fs::metadata(path.with_extension("json")).map_or(0, |m| m.len());
+                Some((metadata.modified().ok(), hash, size))
+            })
+            .collect();
+        found.sort();
+
+        let mut corpus = Self { dir, max_files, max_bytes, entries: VecDeque::new(), bytes: 0 };
+        for (_, hash, size) in found {
+            corpus.bytes += size;
+            corpus.entries.push_back((hash, size));
+        }
+        corpus.prune();
+        corpus
+    }
+
+    /// Write a failure, replacing an earlier entry of the same image, then prune
+    fn save(&mut self, image: &DynamicImage, record: &FailureRecord) -> CaptchaResult<()> {
+        if let Some(index) = self.entries.iter().position(|(hash, _)| *hash == record.image_hash) {
+            if let Some((_, size)) = self.entries.remove(index) {
+                self.bytes -= size;
+            }
+        }
+
+        let size = write(&self.dir, image, record)?;
+        self.bytes += size;
+        self.entries.push_back((record.image_hash.clone(), size));
+        self.prune();
+        Ok(())
+    }
+
+    /// Delete the oldest entries until the corpus is within its limits
+    fn prune(&mut self) {
+        while (self.max_files > 0 && self.entries.len() > self.max_files)
+            || (self.max_bytes > 0 && self.bytes > self.max_bytes)
+        {
+            let Some((hash, size)) = self.entries.pop_front() else {
+                break;
+            };
+            self.bytes -= size;
+            for extension in ["png", "json"] {
+                let path = self.dir.join(format!("{}.{}", hash, extension));
+                if let Err(e) = fs::remove_file(&path) {
+                    tracing::warn!("Failed to prune failed solve {}: {}", path.display(), e);
+                }
+            }
+        }
+    }
+}
+
+/// Write the image and its prediction to `dir`, creating it if needed.
+/// Returns the bytes written.
+fn write(dir: &Path, image: &DynamicImage, record: &FailureRecord) -> CaptchaResult<u64> {
+    let io_error = |e: std::io::Error| CaptchaError::ProcessingError(format!("{}: {}", dir.display(), e));
+
+    fs::create_dir_all(dir).map_err(io_error)?;
+    let image_path = dir.join(format!("{}.png", record.image_hash));
+    image
+        .save_with_format(&image_path, image::ImageFormat::Png)
+        .map_err(|e| CaptchaError::ProcessingError(format!("Failed to encode image: {}", e)))?;
+
+    let json = serde_json::to_vec_pretty(record).unwrap_or_default();
+    fs::write(dir.join(format!("{}.json", record.image_hash)), &json).map_err(io_error)?;
+
+    let image_size = fs::metadata(&image_path).map_err(io_error)?.len();
+    Ok(image_size + json.len() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(image_hash: &str) -> FailureRecord {
+        FailureRecord {
+            image_hash: image_hash.to_string(),
+            predicted_text: "A?".to_string(),
+            confidence: 0.2,
+            solver: "ocr".to_string(),
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_is_failure() {
+        let (queue, _pending) = mpsc::sync_channel(1);
+        let store = FailureStore { threshold: 0.5, hash_algorithm: HashAlgorithm::Blake3, queue };
+
+        assert!(store.is_failure("", 0.9));
+        assert!(store.is_failure("AB12", 0.3));
+        assert!(!store.is_failure("AB12", 0.5));
+    }
+
+    #[test]
+    fn test_write_saves_image_and_prediction() {
+        let dir = std::env::temp_dir().join(format!("captcha-failures-{}", uuid::Uuid::new_v4()));
+        let image = DynamicImage::ImageLuma8(image::GrayImage::from_pixel(4, 2, image::Luma([90])));
+        let written = write(&dir, &image, &record("abc")).unwrap();
+        assert_eq!(
+            written,
+            fs::metadata(dir.join("abc.png")).unwrap().len() + fs::metadata(dir.join("abc.json")).unwrap().len()
+        );
+
+        let saved = image::open(dir.join("abc.png")).unwrap();
+        assert_eq!(saved.to_luma8(), image.to_luma8());
+        let json: serde_json::Value = serde_json::from_slice(&fs::read(dir.join("abc.json")).unwrap()).unwrap();
+        assert_eq!(json["predicted_text"], "A?");
+        assert_eq!(json["solver"], "ocr");
+        assert!((json["confidence"].as_f64().unwrap() - 0.2).abs() < 1e-6);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_corpus_prunes_oldest_entries() {
+        let dir = std::env::temp_dir().join(format!("captcha-failures-{}", uuid::Uuid::new_v4()));
+        let image = DynamicImage::ImageLuma8(image::GrayImage::from_pixel(4, 2, image::Luma([90])));
+        let exists = |hash: &str| dir.join(format!("{}.png", hash)).exists();
+
+        let mut corpus = Corpus::open(dir.clone(), 2, 0);
+        for hash in ["a", "b", "a", "c"] {
+            corpus.save(&image, &record(hash)).unwrap();
+        }
+        // Resaving "a" made "b" the oldest entry
+        assert!(!exists("b") && exists("a") && exists("c"));
+        assert!(!dir.join("b.json").exists());
+
+        // Reopening indexes what is on disk and applies a tighter byte budget
+        let reopened = Corpus::open(dir.clone(), 0, corpus.bytes - 1);
+        assert_eq!(reopened.entries.len(), 1);
+        assert!(reopened.bytes < corpus.bytes);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
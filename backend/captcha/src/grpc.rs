@@ -0,0 +1,285 @@
+//! gRPC Server
+//!
+//! Serves `proto/captcha.proto` on `server.grpc_port` next to the REST API, for
+//! internal callers that want to skip JSON and base64 on the solve path. RPCs share
+//! the application state with REST and go through the same solve path: profiles,
+//! validation, memo, solve log and failure corpus behave exactly as for
+//! `POST /captcha/solve`. Metadata plays the role of REST headers: `x-api-key`
+//! (checked for the `solve` scope), else the gateway-forwarded `x-user-id` and
+//! `x-forwarded-for`.
+
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use actix_web::http::StatusCode;
+use actix_web::{web, ResponseError};
+use tokio::sync::{mpsc, Semaphore};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::{Stream, StreamExt};
+use tonic::metadata::MetadataMap;
+use tonic::{Code, Request, Response, Status, Streaming};
+
+use crate::AppState;
+use crate::api::captcha::{self as rest, Caller};
+use crate::auth;
+use crate::error::{CaptchaError, CaptchaResult};
+use crate::models::{self, SolveResponse};
+
+/// Code generated from `proto/captcha.proto`
+pub mod pb {
+    tonic::include_proto!("captcha.v1");
+}
+
+use pb::captcha_server::{Captcha, CaptchaServer};
+use pb::solve_stream_response::Result as StreamResult;
+
+/// REST route the RPCs mirror, whose scope API keys need
+const SOLVE_PATH: &str = "/captcha/solve";
+
+/// Room for the request fields next to an image of the maximum size
+const MESSAGE_OVERHEAD_BYTES: usize = 64 * 1024;
+
+/// Serve the gRPC API on `port` until the process exits
+pub async fn serve(state: web::Data<AppState>, port: u16) -> Result<(), tonic::transport::Error> {
+    let max_message_size = state.config.processing.max_image_size_mb * 1024 * 1024 + MESSAGE_OVERHEAD_BYTES;
+    let service = CaptchaServer::new(CaptchaService { state })
+        .max_decoding_message_size(max_message_size);
+
+    tonic::transport::Server::builder()
+        .add_service(service)
+        .serve(SocketAddr::from(([0, 0, 0, 0], port)))
+        .await
+}
+
+pub struct CaptchaService {
+    state: web::Data<AppState>,
+}
+
+impl CaptchaService {
+    /// Caller of an RPC, authenticating its API key if it presents one
+    async fn caller(&self, metadata: &MetadataMap, remote_addr: Option<SocketAddr>) -> CaptchaResult<Caller> {
+        let value = |name: &str| metadata.get(name).and_then(|v| v.to_str().ok());
+
+        let request_ip = value("x-forwarded-for")
+            .map(|s| s.to_string())
+            .or_else(|| remote_addr.map(|a| a.ip().to_string()));
+
+        if let Some(presented) = value("x-api-key") {
+            let key = auth::authorize_key(&self.state, presented.trim(), SOLVE_PATH).await?;
+            return Ok(Caller { scope: format!("key:{}", key.id), user_id: Some(key.user_id), request_ip });
+        }
+
+        let user_id = value("x-user-id").and_then(|s| s.parse::<u64>().ok());
+        let scope = match user_id {
+            Some(user_id) => format!("user:{}", user_id),
+            None => format!("ip:{}", request_ip.clone().unwrap_or_default()),
+        };
+        Ok(Caller { scope, user_id, request_ip })
+    }
+}
+
+#[tonic::async_trait]
+impl Captcha for CaptchaService {
+    async fn solve(&self, request: Request<pb::SolveRequest>) -> Result<Response<pb::SolveResponse>, Status> {
+        let caller = self.caller(request.metadata(), request.remote_addr()).await.map_err(status)?;
+
+        solve_one(&self.state, &caller, request.into_inner())
+            .await
+            .map(Response::new)
+            .map_err(status)
+    }
+
+    type SolveStreamStream = Pin<Box<dyn Stream<Item = Result<pb::SolveStreamResponse, Status>> + Send>>;
+
+    /// Solve each request as it arrives, at most `processing.batch_size` at a time
+    async fn solve_stream(
+        &self,
+        request: Request<Streaming<pb::SolveRequest>>,
+    ) -> Result<Response<Self::SolveStreamStream>, Status> {
+        let caller = Arc::new(self.caller(request.metadata(), request.remote_addr()).await.map_err(status)?);
+        let mut incoming = request.into_inner();
+
+        let concurrency = self.state.config.processing.batch_size.max(1);
+        let permits = Arc::new(Semaphore::new(concurrency));
+        let (tx, rx) = mpsc::channel(concurrency);
+        let state = self.state.clone();
+
+        tokio::spawn(async move {
+            while let Some(item) = incoming.next().await {
+                let request = match item {
+                    Ok(request) => request,
+                    Err(e) => {
+                        let _ = tx.send(Err(e)).await;
+                        break;
+                    }
+                };
+                let Ok(permit) = Arc::clone(&permits).acquire_owned().await else {
+                    break;
+                };
+
+                let (state, caller, tx) = (state.clone(), Arc::clone(&caller), tx.clone());
+                tokio::spawn(async move {
+                    let id = request.id.clone();
+                    let result = match solve_one(&state, &caller, request).await {
+                        Ok(response) => StreamResult::Response(response),
+                        Err(e) => StreamResult::Error(pb::SolveError {
+                            code: e.error_code().to_string(),
+                            message: e.message(),
+                        }),
+                    };
+                    drop(permit);
+                    let _ = tx.send(Ok(pb::SolveStreamResponse { id, result: Some(result) })).await;
+                });
+            }
+        });
+
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
+}
+
+/// Solve one RPC request the way `POST /captcha/solve` solves its body
+async fn solve_one(state: &AppState, caller: &Caller, request: pb::SolveRequest) -> CaptchaResult<pb::SolveResponse> {
+    let mut body = solve_request(&request)?;
+    rest::apply_profile(state, &mut body)?;
+
+    let response = rest::solve_request_bytes(state, caller, &body, &request.image).await?;
+    Ok(response.into())
+}
+
+/// REST request equivalent to an RPC request, its image left out. Going through the
+/// REST deserializer keeps enumeration values and their errors identical.
+fn solve_request(request: &pb::SolveRequest) -> CaptchaResult<models::SolveRequest> {
+    let fields = serde_json::json!({
+        "image_base64": "",
+        "model": request.model,
+        "model_type": request.model_type,
+        "profile": request.profile,
+        "expected_length": request.expected_length,
+        "top_k": request.top_k,
+        "strategy": request.strategy,
+        "normalize": request.normalize,
+        "case": request.case,
+        "whitespace": request.whitespace,
+        "charset": request.charset,
+        "language": request.language,
+        "fallback": request.fallback,
+        "raw_output": request.raw_output,
+        "crop": request.crop.as_ref().map(|c| serde_json::json!({ "x": c.x, "y": c.y, "w": c.w, "h": c.h })),
+    });
+
+    serde_json::from_value(fields)
+        .map_err(|e| CaptchaError::BadRequest(format!("Invalid solve request: {}", e)))
+}
+
+impl From<SolveResponse> for pb::SolveResponse {
+    fn from(response: SolveResponse) -> Self {
+        Self {
+            text: response.text,
+            confidence: response.confidence,
+            model: response.model,
+            processing_time_ms: response.processing_time_ms,
+            preprocess_ms: response.preprocess_ms,
+            inference_ms: response.inference_ms,
+            length_mismatch: response.length_mismatch,
+            candidates: response.candidates
+                .into_iter()
+                .map(|c| pb::Candidate { text: c.text, confidence: c.confidence })
+                .collect(),
+        }
+    }
+}
+
+/// gRPC status for an error, with the REST error code in `x-error-code` metadata
+fn status(err: CaptchaError) -> Status {
+    let code = match err.status_code() {
+        StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY => Code::InvalidArgument,
+        StatusCode::UNAUTHORIZED => Code::Unauthenticated,
+        StatusCode::FORBIDDEN => Code::PermissionDenied,
+        StatusCode::NOT_FOUND => Code::NotFound,
+        StatusCode::CONFLICT => Code::AlreadyExists,
+        StatusCode::REQUEST_TIMEOUT => Code::DeadlineExceeded,
+        StatusCode::SERVICE_UNAVAILABLE => Code::Unavailable,
+        _ => Code::Internal,
+    };
+
+    let message = match &err {
+        CaptchaError::Validation(errors) => errors
+            .iter()
+            .map(|e| format!("{}: {}", e.field, e.error))
+            .collect::<Vec<_>>()
+            .join("; "),
+        _ => err.message(),
+    };
+
+    let mut status = Status::new(code, message);
+    if let Ok(value) = err.error_code().parse() {
+        status.metadata_mut().insert("x-error-code", value);
+    }
+    if let CaptchaError::Overloaded(retry_after) = err {
+        if let Ok(value) = retry_after.to_string().parse() {
+            status.metadata_mut().insert("retry-after", value);
+        }
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CaseMode, SolveStrategy};
+    use crate::validation::FieldError;
+
+    #[test]
+    fn test_solve_request_mirrors_rest_fields() {
+        let request = pb::SolveRequest {
+            image: vec![1, 2, 3],
+            model_type: Some("cnn".to_string()),
+            expected_length: Some(5),
+            strategy: Some("digits".to_string()),
+            case: Some("preserve".to_string()),
+            crop: Some(pb::CropRegion { x: 1, y: 2, w: 30, h: 10 }),
+            ..Default::default()
+        };
+
+        let body = solve_request(&request).unwrap();
+
+        assert_eq!(body.model_type.as_deref(), Some("cnn"));
+        assert_eq!(body.expected_length, Some(5));
+        assert_eq!(body.strategy, Some(SolveStrategy::Digits));
+        assert_eq!(body.case, Some(CaseMode::Preserve));
+        assert_eq!(body.crop.as_ref().map(|c| (c.x, c.y, c.w, c.h)), Some((1, 2, 30, 10)));
+        assert!(body.model.is_none());
+        assert!(!body.raw_output);
+    }
+
+    #[test]
+    fn test_solve_request_rejects_unknown_enum_value() {
+        let request = pb::SolveRequest { strategy: Some("fastest".to_string()), ..Default::default() };
+
+        let err = solve_request(&request).unwrap_err();
+
+        assert!(matches!(err, CaptchaError::BadRequest(msg) if msg.contains("fastest")));
+    }
+
+    #[test]
+    fn test_status_mapping() {
+        let not_found = status(CaptchaError::ModelNotFound("lstm".to_string()));
+        assert_eq!(not_found.code(), Code::NotFound);
+        assert_eq!(not_found.metadata().get("x-error-code").unwrap(), "model_not_found");
+
+        let overloaded = status(CaptchaError::Overloaded(3));
+        assert_eq!(overloaded.code(), Code::Unavailable);
+        assert_eq!(overloaded.metadata().get("retry-after").unwrap(), "3");
+
+        let invalid = status(CaptchaError::Validation(vec![FieldError {
+            field: "top_k".to_string(),
+            error: "must be >= 1".to_string(),
+        }]));
+        assert_eq!(invalid.code(), Code::InvalidArgument);
+        assert_eq!(invalid.message(), "top_k: must be >= 1");
+
+        assert_eq!(status(CaptchaError::Timeout("cnn".to_string())).code(), Code::DeadlineExceeded);
+        assert_eq!(status(CaptchaError::ProcessingError("boom".to_string())).code(), Code::Internal);
+    }
+}
@@ -0,0 +1,325 @@
+//! Idempotency Keys
+//!
+//! Clients that retry on network errors send the same `Idempotency-Key` header
+//! with each attempt. The first request with a key is processed normally and
+//! its response is kept for a TTL; later requests with the same key and the
+//! same body get the stored response instead of being solved (and logged) again.
+//! Keys are scoped per caller, so two callers can't see each other's results.
+//! The store holds at most a configured number of keys, evicting the oldest
+//! first, and drops expired keys in expiry order rather than scanning them all.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::{HttpMessage, HttpRequest};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::error::{CaptchaError, CaptchaResult};
+use crate::models::ApiKey;
+
+/// Request header carrying the client-chosen key
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// Response header set when the response is a stored replay
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "Idempotent-Replayed";
+
+/// Longest accepted idempotency key
+pub const MAX_KEY_LENGTH: usize = 255;
+
+/// What to do with a request carrying an idempotency key
+pub enum Begin<'a> {
+    /// First use of the key: process the request, then `complete` the claim.
+    /// Dropping the claim (failed or cancelled request) releases the key for a retry.
+    New(Claim<'a>),
+    /// The key already completed with this body: return the stored response
+    Replay(serde_json::Value),
+}
+
+/// An in-flight key, released on drop unless completed
+pub struct Claim<'a> {
+    store: &'a IdempotencyStore,
+    id: (String, String),
+    completed: bool,
+}
+
+impl Claim<'_> {
+    /// Store the successful response for later requests with the same key
+    pub fn complete(mut self, response: serde_json::Value) {
+        self.completed = true;
+        let mut keys = self.store.keys.lock().unwrap();
+        if let Some(Entry::InFlight { fingerprint, .. }) = keys.entries.remove(&self.id) {
+            let expires_at = Instant::now() + self.store.ttl;
+            keys.entries.insert(self.id.clone(), Entry::Done { fingerprint, response, expires_at });
+            keys.expiry.push_back((expires_at, self.id.clone()));
+        }
+    }
+}
+
+impl Drop for Claim<'_> {
+    fn drop(&mut self) {
+        if self.completed {
+            return;
+        }
+        let mut keys = self.store.keys.lock().unwrap();
+        if matches!(keys.entries.get(&self.id), Some(Entry::InFlight { .. })) {
+            keys.entries.remove(&self.id);
+        }
+    }
+}
+
+enum Entry {
+    InFlight { fingerprint: String, expires_at: Instant },
+    Done { fingerprint: String, response: serde_json::Value, expires_at: Instant },
+}
+
+impl Entry {
+    fn expires_at(&self) -> Instant {
+        match self {
+            Entry::InFlight { expires_at, .. } | Entry::Done { expires_at, .. } => *expires_at,
+        }
+    }
+}
+
+/// Stored keys, with their expiry times in the order they were set.
+/// The TTL is fixed, so that order is also expiry order. A key's expiry is
+/// queued again when its request completes; queue items whose time no longer
+/// matches their key's entry are stale and skipped.
+#[derive(Default)]
+struct Keys {
+    entries: HashMap<(String, String), Entry>,
+    expiry: VecDeque<(Instant, (String, String))>,
+}
+
+impl Keys {
+    /// Drop keys expired at `now`
+    fn expire(&mut self, now: Instant) {
+        while let Some((expires_at, _)) = self.expiry.front() {
+            if *expires_at > now {
+                break;
+            }
+            self.pop_oldest();
+        }
+    }
+
+    /// Drop keys, oldest first, until there is room for one more under `max_entries`
+    fn evict(&mut self, max_entries: usize) {
+        while self.entries.len() >= max_entries && !self.expiry.is_empty() {
+            self.pop_oldest();
+        }
+    }
+
+    /// Remove the front of the expiry queue and its key, unless the item is stale
+    fn pop_oldest(&mut self) {
+        let Some((expires_at, id)) = self.expiry.pop_front() else {
+            return;
+        };
+        if self.entries.get(&id).is_some_and(|entry| entry.expires_at() == expires_at) {
+            self.entries.remove(&id);
+        }
+    }
+
+    /// Drop stale queue items once they outnumber the keys, bounding the queue
+    /// when claims are released (failed requests) faster than they expire
+    fn compact(&mut self) {
+        if self.expiry.len() <= 2 * self.entries.len().max(1) {
+            return;
+        }
+        let entries = &self.entries;
+        self.expiry.retain(|(expires_at, id)| {
+            entries.get(id).is_some_and(|entry| entry.expires_at() == *expires_at)
+        });
+    }
+}
+
+/// In-memory store of responses by (caller scope, idempotency key)
+pub struct IdempotencyStore {
+    keys: Mutex<Keys>,
+    ttl: Duration,
+    max_entries: usize,
+}
+
+impl IdempotencyStore {
+    /// Keep responses for `ttl`, holding at most `max_entries` keys (at least one)
+    pub fn new(ttl: Duration, max_entries: usize) -> Self {
+        Self { keys: Mutex::new(Keys::default()), ttl, max_entries: max_entries.max(1) }
+    }
+
+    /// Claim `key` for a request whose body hashes to `fingerprint`.
+    /// Fails with `Conflict` when the key was used with a different body
+    /// or an earlier request with the key is still being processed.
+    pub fn begin(&self, scope: &str, key: &str, fingerprint: &str) -> CaptchaResult<Begin<'_>> {
+        let now = Instant::now();
+        let mut keys = self.keys.lock().unwrap();
+        keys.expire(now);
+
+        let id = (scope.to_string(), key.to_string());
+        match keys.entries.get(&id) {
+            None => {
+                keys.compact();
+                keys.evict(self.max_entries);
+                let expires_at = now + self.ttl;
+                keys.entries.insert(id.clone(), Entry::InFlight {
+                    fingerprint: fingerprint.to_string(),
+                    expires_at,
+                });
+                keys.expiry.push_back((expires_at, id.clone()));
+                Ok(Begin::New(Claim { store: self, id, completed: false }))
+            }
+            Some(Entry::InFlight { fingerprint: stored, .. } | Entry::Done { fingerprint: stored, .. })
+                if stored != fingerprint =>
+            {
+                Err(CaptchaError::Conflict(
+                    "Idempotency-Key was already used with a different request body".to_string(),
+                ))
+            }
+            Some(Entry::InFlight { .. }) => Err(CaptchaError::Conflict(
+                "A request with this Idempotency-Key is still being processed".to_string(),
+            )),
+            Some(Entry::Done { response, .. }) => Ok(Begin::Replay(response.clone())),
+        }
+    }
+}
+
+/// The validated `Idempotency-Key` header, if present
+pub fn idempotency_key(req: &HttpRequest) -> CaptchaResult<Option<String>> {
+    let Some(value) = req.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(None);
+    };
+
+    let key = value.to_str().map(str::trim).unwrap_or_default();
+    if key.is_empty() || key.len() > MAX_KEY_LENGTH || !key.chars().all(|c| c.is_ascii_graphic()) {
+        return Err(CaptchaError::BadRequest(format!(
+            "{} must be 1-{} visible ASCII characters", IDEMPOTENCY_KEY_HEADER, MAX_KEY_LENGTH
+        )));
+    }
+
+    Ok(Some(key.to_string()))
+}
+
+/// Whose keys a request uses: the API key, else the gateway-forwarded user, else the client IP
+pub fn caller_scope(req: &HttpRequest) -> String {
+    if let Some(key) = req.extensions().get::<ApiKey>() {
+        return format!("key:{}", key.id);
+    }
+
+    let user_id = req.headers()
+        .get("X-User-ID")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| s.parse::<u64>().ok());
+    if let Some(user_id) = user_id {
+        return format!("user:{}", user_id);
+    }
+
+    let ip = req.headers()
+        .get("X-Forwarded-For")
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string())
+        .or_else(|| req.peer_addr().map(|a| a.ip().to_string()))
+        .unwrap_or_default();
+    format!("ip:{}", ip)
+}
+
+/// Fingerprint of a parsed request body, compared across requests sharing a key.
+/// Hashing the parsed form ignores whitespace and key order differences.
+pub fn fingerprint<T: Serialize>(body: &T) -> String {
+    hex::encode(Sha256::digest(serde_json::to_vec(body).unwrap_or_default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use serde_json::json;
+
+    fn claim<'a>(store: &'a IdempotencyStore, scope: &str, fingerprint: &str) -> Claim<'a> {
+        match store.begin(scope, "abc", fingerprint).unwrap() {
+            Begin::New(claim) => claim,
+            Begin::Replay(_) => panic!("expected a new claim"),
+        }
+    }
+
+    #[test]
+    fn test_replays_completed_request() {
+        let store = IdempotencyStore::new(Duration::from_secs(60), 100);
+
+        let first = claim(&store, "key:1", "f1");
+        assert!(matches!(store.begin("key:1", "abc", "f1"), Err(CaptchaError::Conflict(_))));
+
+        first.complete(json!({ "text": "AB12" }));
+        assert!(matches!(
+            store.begin("key:1", "abc", "f1").unwrap(),
+            Begin::Replay(response) if response == json!({ "text": "AB12" })
+        ));
+
+        // Same key, different body
+        assert!(matches!(store.begin("key:1", "abc", "f2"), Err(CaptchaError::Conflict(_))));
+        // Same key, different caller
+        claim(&store, "key:2", "f2");
+    }
+
+    #[test]
+    fn test_dropped_claim_and_expiry_release_key() {
+        let store = IdempotencyStore::new(Duration::from_secs(60), 100);
+        drop(claim(&store, "key:1", "f1"));
+        claim(&store, "key:1", "f2");
+
+        let expiring = IdempotencyStore::new(Duration::ZERO, 100);
+        claim(&expiring, "key:1", "f1").complete(json!({}));
+        claim(&expiring, "key:1", "f2");
+    }
+
+    #[test]
+    fn test_oldest_keys_evicted_at_capacity() {
+        let store = IdempotencyStore::new(Duration::from_secs(60), 2);
+        let begin = |key: &str| match store.begin("key:1", key, "f1").unwrap() {
+            Begin::New(claim) => claim.complete(json!({ "key": key })),
+            Begin::Replay(_) => panic!("expected a new claim"),
+        };
+
+        begin("a");
+        begin("b");
+        begin("c");
+
+        let keys = store.keys.lock().unwrap();
+        assert_eq!(keys.entries.len(), 2);
+        assert!(!keys.entries.contains_key(&("key:1".to_string(), "a".to_string())));
+        assert!(keys.entries.contains_key(&("key:1".to_string(), "c".to_string())));
+    }
+
+    #[test]
+    fn test_released_claims_dont_grow_expiry_queue() {
+        let store = IdempotencyStore::new(Duration::from_secs(60), 100);
+        for i in 0..50 {
+            drop(claim(&store, &format!("key:{}", i), "f1"));
+        }
+
+        let keys = store.keys.lock().unwrap();
+        assert!(keys.entries.is_empty());
+        assert!(keys.expiry.len() <= 2, "{} queued", keys.expiry.len());
+    }
+
+    #[test]
+    fn test_idempotency_key_header() {
+        let missing = TestRequest::default().to_http_request();
+        assert_eq!(idempotency_key(&missing).unwrap(), None);
+
+        let valid = TestRequest::default().insert_header((IDEMPOTENCY_KEY_HEADER, " order-42 ")).to_http_request();
+        assert_eq!(idempotency_key(&valid).unwrap(), Some("order-42".to_string()));
+
+        let too_long = TestRequest::default()
+            .insert_header((IDEMPOTENCY_KEY_HEADER, "x".repeat(MAX_KEY_LENGTH + 1)))
+            .to_http_request();
+        assert!(idempotency_key(&too_long).is_err());
+    }
+
+    #[test]
+    fn test_caller_scope() {
+        let user = TestRequest::default().insert_header(("X-User-ID", "7")).to_http_request();
+        assert_eq!(caller_scope(&user), "user:7");
+
+        let anonymous = TestRequest::default().peer_addr("10.0.0.1:1234".parse().unwrap()).to_http_request();
+        assert_eq!(caller_scope(&anonymous), "ip:10.0.0.1");
+    }
+}
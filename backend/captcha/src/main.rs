@@ -6,25 +6,48 @@
 //! - Ensemble (combining multiple models)
 
 mod api;
+mod auth;
+mod bench;
 mod config;
+mod dataset;
+mod failures;
+#[cfg(feature = "grpc")]
+mod grpc;
 mod models;
 mod solvers;
 mod error;
 mod db;
+mod idempotency;
+mod memo;
+mod solve_log;
+mod timeout;
+mod validation;
 
 use actix_cors::Cors;
 use actix_web::{web, App, HttpServer, middleware};
+use actix_web::http::KeepAlive;
 use tracing::{info, Level};
-use tracing_subscriber::{EnvFilter, fmt, prelude::*};
+use tracing_subscriber::{EnvFilter, Layer, fmt, prelude::*, registry::LookupSpan};
 
-use crate::config::Settings;
+use crate::auth::UsageRecorder;
+use crate::config::{LogFormat, Settings};
 use crate::db::Database;
+use crate::idempotency::IdempotencyStore;
+use crate::memo::SolveMemo;
+use crate::failures::FailureStore;
+use crate::solve_log::SolveLogQueue;
 use crate::solvers::SolverManager;
 
 /// Application state shared across handlers
 pub struct AppState {
     pub db: Database,
     pub solver_manager: SolverManager,
+    pub usage: UsageRecorder,
+    pub idempotency: IdempotencyStore,
+    pub solve_memo: SolveMemo,
+    pub solve_log: SolveLogQueue,
+    /// Saves images of failed solves when `processing.store_failures` is set
+    pub failures: Option<FailureStore>,
     pub config: Settings,
 }
 
@@ -33,20 +56,32 @@ async fn main() -> std::io::Result<()> {
     // Load environment variables
     dotenv::dotenv().ok();
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("bench") {
+        // Human-readable warnings on stderr, the report on stdout
+        tracing_subscriber::registry()
+            .with(fmt::layer().with_writer(std::io::stderr))
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn")))
+            .init();
+        return bench::cli(&args[1..]).await;
+    }
+
+    // Load configuration
+    let config = Settings::new().expect("Failed to load configuration");
+
     // Initialize tracing
     let filter = EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| EnvFilter::new("info"));
     
     tracing_subscriber::registry()
-        .with(fmt::layer().json())
+        .with(log_layer(config.log_format))
         .with(filter)
         .init();
 
     info!("Starting Captcha Service...");
 
-    // Load configuration
-    let config = Settings::new().expect("Failed to load configuration");
     let port = config.server.port;
+    let archive_limit = config.processing.archive_max_size_mb * 1024 * 1024;
 
     // Initialize database connection
     let db = Database::new(&config.database)
@@ -54,25 +89,94 @@ async fn main() -> std::io::Result<()> {
         .expect("Failed to connect to database");
 
     info!("Connected to database");
+    if config.database.replica_url.is_some() {
+        info!("Stats and listing queries use the read replica");
+    }
+
+    if config.database.run_migrations {
+        db.run_migrations().await.expect("Failed to run database migrations");
+        info!("Database schema is up to date");
+    }
 
     // Initialize solver manager
-    let solver_manager = SolverManager::new(&config.models)
+    let solver_manager = SolverManager::new(&config.models, &config.processing)
         .await
         .expect("Failed to initialize solver manager");
 
     info!("Solver manager initialized with {} models", solver_manager.model_count());
 
+    match db.get_active_models().await {
+        Ok(models) => {
+            solver_manager.refresh_ensemble_weights(&models).await;
+            solver_manager.refresh_temperatures(&models).await;
+            solver_manager.verify_checksums(&models).await;
+        }
+        Err(e) => tracing::warn!("Failed to load ensemble weights and model checksums: {}", e),
+    }
+
     // Create shared application state
     let app_state = web::Data::new(AppState {
         db,
         solver_manager,
+        usage: UsageRecorder::default(),
+        idempotency: IdempotencyStore::new(
+            std::time::Duration::from_secs(config.processing.idempotency_ttl_seconds),
+            config.processing.idempotency_max_entries,
+        ),
+        solve_memo: SolveMemo::new(std::time::Duration::from_secs(config.processing.solve_memo_ttl_seconds)),
+        solve_log: SolveLogQueue::new(config.processing.solve_log_queue_capacity),
+        failures: FailureStore::from_settings(&config.processing),
         config: config.clone(),
     });
 
+    if let Err(e) = api::models::refresh_calibration(&app_state).await {
+        tracing::warn!("Failed to load confidence calibration: {}", e);
+    }
+
+    auth::spawn_usage_flusher(
+        app_state.clone(),
+        std::time::Duration::from_secs(config.processing.api_key_usage_flush_seconds.max(1)),
+    );
+
+    solve_log::spawn_flusher(
+        app_state.clone(),
+        std::time::Duration::from_secs(config.processing.solve_log_flush_seconds.max(1)),
+    );
+
+    if config.processing.model_checksum_check_seconds > 0 {
+        api::models::spawn_checksum_verifier(
+            app_state.clone(),
+            std::time::Duration::from_secs(config.processing.model_checksum_check_seconds),
+        );
+    }
+
+    let pending_models = app_state.solver_manager.pending_models();
+    if config.processing.model_load_retry_seconds > 0 && !pending_models.is_empty() {
+        info!(
+            "Retrying model load for {} every {}s",
+            pending_models.join(", "),
+            config.processing.model_load_retry_seconds
+        );
+        api::models::spawn_model_loader(
+            app_state.clone(),
+            std::time::Duration::from_secs(config.processing.model_load_retry_seconds),
+        );
+    }
+
+    if config.processing.accuracy_refresh_seconds > 0 {
+        api::models::spawn_accuracy_refresher(
+            app_state.clone(),
+            std::time::Duration::from_secs(config.processing.accuracy_refresh_seconds),
+        );
+    }
+
+    start_grpc(&app_state, config.server.grpc_port);
+
     info!("Starting HTTP server on port {}", port);
 
     // Start HTTP server
-    HttpServer::new(move || {
+    let server_state = app_state.clone();
+    let server = HttpServer::new(move || {
         // Configure CORS
         let cors = Cors::default()
             .allow_any_origin()
@@ -81,26 +185,132 @@ async fn main() -> std::io::Result<()> {
             .max_age(3600);
 
         App::new()
-            .app_data(app_state.clone())
+            .app_data(server_state.clone())
+            .wrap(middleware::from_fn(auth::api_key_auth))
+            // Outside auth, so its database lookups count toward the deadline
+            .wrap(middleware::from_fn(timeout::request_timeout))
             .wrap(cors)
             .wrap(middleware::Logger::default())
             .wrap(middleware::Compress::default())
             // Health check
             .route("/health", web::get().to(api::health::health_check))
+            .route("/version", web::get().to(api::version::version))
             // API routes
             .service(
                 web::scope("/captcha")
-                    .route("/solve", web::post().to(api::captcha::solve))
-                    .route("/solve/batch", web::post().to(api::captcha::solve_batch))
+                    .service(
+                        web::resource("/solve")
+                            .app_data(api::captcha::solve_json_config())
+                            .route(web::post().to(api::captcha::solve))
+                    )
+                    .service(
+                        web::resource("/solve/batch")
+                            .app_data(api::captcha::solve_json_config())
+                            .route(web::post().to(api::captcha::solve_batch))
+                    )
+                    .route("/compare", web::post().to(api::captcha::compare))
+                    .route("/histogram", web::post().to(api::debug::histogram))
+                    .service(
+                        web::resource("/solve/archive")
+                            .app_data(web::PayloadConfig::new(archive_limit))
+                            .route(web::post().to(api::captcha::solve_archive))
+                    )
                     .route("/models", web::get().to(api::models::list_models))
                     .route("/models/upload", web::post().to(api::models::upload_model))
+                    .route("/models/{model_id}", web::patch().to(api::models::update_model))
+                    .route("/models/{model_id}", web::delete().to(api::models::delete_model))
+                    .route("/models/{model_id}/default", web::post().to(api::models::set_default_model))
+                    .route("/models/{model_id}/download", web::get().to(api::models::download_model))
+                    .route("/solvers", web::get().to(api::models::list_solvers))
+                    .route("/solvers/{name}/schema", web::get().to(api::models::get_solver_schema))
+                    .route("/selftest", web::get().to(api::selftest::selftest))
+                    .route("/train", web::get().to(api::training::list_training_jobs))
                     .route("/train", web::post().to(api::training::start_training))
+                    .route("/train/validate", web::post().to(api::training::validate_dataset))
                     .route("/train/{job_id}", web::get().to(api::training::get_training_status))
                     .route("/logs", web::get().to(api::logs::get_logs))
+                    .route("/logs/recompute", web::post().to(api::logs::recompute_correctness))
+                    .route("/logs/replay", web::post().to(api::logs::replay_logs))
                     .route("/stats", web::get().to(api::stats::get_stats))
+                    .route("/cache", web::delete().to(api::cache::clear_cache))
+                    .route("/cache/stats", web::get().to(api::cache::cache_stats))
+                    .route("/debug/components", web::post().to(api::debug::components))
+                    .route("/debug/segments", web::post().to(api::debug::segments))
+                    .route("/admin/ensemble/weights", web::get().to(api::models::get_ensemble_weights))
+                    .route("/admin/ensemble/weights/refresh", web::post().to(api::models::refresh_ensemble_weights))
+                    .route("/admin/models/accuracy/recompute", web::post().to(api::models::recompute_accuracy))
+                    .route("/admin/audit-log", web::get().to(api::audit::list_audit_logs))
+                    .route("/admin/api-keys", web::get().to(api::api_keys::list_api_keys))
+                    .route("/admin/api-keys", web::post().to(api::api_keys::create_api_key))
+                    .route("/admin/api-keys/{key_id}/rotate", web::post().to(api::api_keys::rotate_api_key))
+                    .route("/admin/api-keys/{key_id}/revoke", web::post().to(api::api_keys::revoke_api_key))
             )
     })
-    .bind(format!("0.0.0.0:{}", port))?
-    .run()
-    .await
+    .keep_alive(keep_alive(config.server.keep_alive_secs));
+
+    let server = if config.server.workers > 0 {
+        server.workers(config.server.workers)
+    } else {
+        server
+    };
+
+    let address = format!("0.0.0.0:{}", port);
+    let server = if config.server.http2 {
+        server.bind_auto_h2c(address)?
+    } else {
+        server.bind(address)?
+    };
+
+    let result = server.run().await;
+
+    // Don't lose usage and solve logs recorded since the last periodic flush
+    app_state.usage.flush(&app_state.db).await;
+    app_state.solve_log.flush(&app_state.db).await;
+
+    result
+}
+
+/// Serve the gRPC API on `port` in the background; 0 leaves it off
+#[cfg(feature = "grpc")]
+fn start_grpc(state: &web::Data<AppState>, port: u16) {
+    if port == 0 {
+        return;
+    }
+
+    info!("Starting gRPC server on port {}", port);
+    let state = state.clone();
+    tokio::spawn(async move {
+        if let Err(e) = grpc::serve(state, port).await {
+            tracing::error!("gRPC server stopped: {}", e);
+        }
+    });
+}
+
+#[cfg(not(feature = "grpc"))]
+fn start_grpc(_state: &web::Data<AppState>, port: u16) {
+    if port != 0 {
+        tracing::warn!("server.grpc_port is set but this build has no gRPC support (enable the `grpc` feature)");
+    }
+}
+
+/// Keep-alive for the configured idle timeout; 0 disables keep-alive
+fn keep_alive(secs: u64) -> KeepAlive {
+    if secs == 0 {
+        KeepAlive::Disabled
+    } else {
+        KeepAlive::Timeout(std::time::Duration::from_secs(secs))
+    }
+}
+
+/// Log output layer for the configured format. Each `fmt` format is a different
+/// layer type, so the layer is boxed to pick one at runtime.
+fn log_layer<S>(format: LogFormat) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    match format {
+        LogFormat::Json => fmt::layer().json().boxed(),
+        LogFormat::Pretty => fmt::layer().pretty().boxed(),
+        LogFormat::Compact => fmt::layer().compact().boxed(),
+    }
 }
\ No newline at end of file
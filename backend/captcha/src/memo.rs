@@ -0,0 +1,364 @@
+//! Solve Memoization
+//!
+//! A live-typing UI resubmits the same captcha every time the user edits their
+//! answer. The memo keeps each solve result for a few seconds, keyed by the
+//! caller, the perceptual hash of the image and the other request parameters,
+//! so resubmitting the same captcha, even re-encoded, skips the solvers.
+//!
+//! The caller is part of the hashed key rather than stored beside it: identical
+//! captchas solved by two tenants hash to unrelated keys, so one tenant's solve
+//! can't be served to, or timed by, another. Keys are salted per process, and
+//! the memo holds no image hash that could be matched against the solve log.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use image::DynamicImage;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+use crate::error::CaptchaResult;
+use crate::idempotency;
+use crate::models::SolveRequest;
+use crate::solvers::SolveResult;
+
+/// Side of the grayscale thumbnail the DCT runs on
+const HASH_INPUT_SIZE: usize = 32;
+
+/// Side of the low-frequency DCT block kept in the hash (64 bits)
+const HASH_BLOCK_SIZE: usize = 8;
+
+/// 64-bit DCT perceptual hash: bit `i` is set when the `i`-th low-frequency
+/// coefficient is above their median. Robust to re-encoding and small resizes.
+pub fn perceptual_hash(image: &DynamicImage) -> u64 {
+    let size = HASH_INPUT_SIZE as u32;
+    let thumbnail = image
+        .resize_exact(size, size, image::imageops::FilterType::Triangle)
+        .to_luma8();
+
+    let cos: Vec<Vec<f64>> = (0..HASH_BLOCK_SIZE)
+        .map(|u| {
+            (0..HASH_INPUT_SIZE)
+                .map(|x| ((2 * x + 1) as f64 * u as f64 * std::f64::consts::PI / (2 * HASH_INPUT_SIZE) as f64).cos())
+                .collect()
+        })
+        .collect();
+
+    // Separable 2D DCT, computing only the low-frequency block
+    let mut rows = vec![[0.0f64; HASH_BLOCK_SIZE]; HASH_INPUT_SIZE];
+    for (y, row) in rows.iter_mut().enumerate() {
+        for (u, coefficient) in row.iter_mut().enumerate() {
+            *coefficient = (0..HASH_INPUT_SIZE)
+                .map(|x| thumbnail.get_pixel(x as u32, y as u32).0[0] as f64 * cos[u][x])
+                .sum();
+        }
+    }
+
+    let mut coefficients = Vec::with_capacity(HASH_BLOCK_SIZE * HASH_BLOCK_SIZE);
+    for cos_v in &cos {
+        for u in 0..HASH_BLOCK_SIZE {
+            coefficients.push(rows.iter().zip(cos_v).map(|(row, c)| row[u] * c).sum::<f64>());
+        }
+    }
+
+    // The DC term only reflects overall brightness and is left out of the median
+    let mut sorted = coefficients[1..].to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let median = sorted[sorted.len() / 2];
+
+    coefficients
+        .iter()
+        .enumerate()
+        .filter(|(_, &c)| c > median)
+        .fold(0u64, |hash, (i, _)| hash | (1 << i))
+}
+
+/// Salted hash of the caller scope, image perceptual hash and the other request fields
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MemoKey([u8; 32]);
+
+impl MemoKey {
+    pub fn new(salt: &[u8], scope: &str, image: &DynamicImage, request: &SolveRequest) -> Self {
+        let mut params = serde_json::to_value(request).unwrap_or_default();
+        if let Some(fields) = params.as_object_mut() {
+            fields.remove("image_base64");
+        }
+
+        let mut hasher = Sha256::new();
+        hasher.update(salt);
+        // Length-prefixed so no scope can run into the fields after it
+        hasher.update((scope.len() as u64).to_le_bytes());
+        hasher.update(scope.as_bytes());
+        hasher.update(perceptual_hash(image).to_le_bytes());
+        hasher.update(idempotency::fingerprint(&params).as_bytes());
+        Self(hasher.finalize().into())
+    }
+}
+
+/// Lookups and contents of a [`SolveMemo`] since the process started
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Results currently memoized and not expired
+    pub size: usize,
+    /// Expired results removed to make room for new ones
+    pub evictions: u64,
+}
+
+/// Short-lived in-memory store of solve results
+pub struct SolveMemo {
+    entries: Mutex<HashMap<MemoKey, (SolveResult, Instant)>>,
+    ttl: Duration,
+    /// Random per process, so keys can't be precomputed from an image and a caller
+    salt: [u8; 16],
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl SolveMemo {
+    /// A zero TTL disables memoization
+    pub fn new(ttl: Duration) -> Self {
+        let mut salt = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut salt);
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            ttl,
+            salt,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.ttl.is_zero()
+    }
+
+    /// Key for a solve request, or `None` when memoization is disabled
+    pub fn key(&self, scope: &str, image: &DynamicImage, request: &SolveRequest) -> Option<MemoKey> {
+        self.enabled().then(|| MemoKey::new(&self.salt, scope, image, request))
+    }
+
+    /// The memoized result for `key`, if it has not expired
+    pub fn get(&self, key: &MemoKey) -> Option<SolveResult> {
+        let entries = self.entries.lock().unwrap();
+        let result = entries
+            .get(key)
+            .filter(|(_, expires_at)| *expires_at > Instant::now())
+            .map(|(result, _)| result.clone());
+
+        let counter = if result.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        result
+    }
+
+    /// The memoized result of `request` on `image`, else the result of `solve`, memoized
+    /// when it succeeds. Requests with `no_cache` neither read nor fill the memo.
+    pub async fn get_or_solve(
+        &self,
+        scope: &str,
+        image: &DynamicImage,
+        request: &SolveRequest,
+        solve: impl Future<Output = CaptchaResult<SolveResult>>,
+    ) -> CaptchaResult<SolveResult> {
+        let key = if request.no_cache { None } else { self.key(scope, image, request) };
+        if let Some(result) = key.as_ref().and_then(|key| self.get(key)) {
+            return Ok(result);
+        }
+
+        let result = solve.await?;
+        if let Some(key) = key {
+            self.insert(key, result.clone());
+        }
+        Ok(result)
+    }
+
+    pub fn insert(&self, key: MemoKey, result: SolveResult) {
+        if !self.enabled() {
+            return;
+        }
+
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.len();
+        entries.retain(|_, (_, expires_at)| *expires_at > now);
+        self.evictions.fetch_add((before - entries.len()) as u64, Ordering::Relaxed);
+        entries.insert(key, (result, now + self.ttl));
+    }
+
+    pub fn stats(&self) -> MemoStats {
+        let now = Instant::now();
+        let size = self.entries.lock().unwrap()
+            .values()
+            .filter(|(_, expires_at)| *expires_at > now)
+            .count();
+
+        MemoStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            size,
+            evictions: self.evictions.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Drop every memoized result, returning how many were not expired yet.
+    /// The counters keep running.
+    pub fn clear(&self) -> usize {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        let live = entries.values().filter(|(_, expires_at)| *expires_at > now).count();
+        entries.clear();
+        live
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn captcha(text_offset: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(120, 40, |x, y| {
+            let stroke = (x + text_offset) % 21 < 7 && (8..32).contains(&y);
+            if stroke || (x + 2 * y) % 29 < 1 { Rgb([20, 20, 20]) } else { Rgb([235, 235, 235]) }
+        }))
+    }
+
+    fn request(model: &str) -> SolveRequest {
+        serde_json::from_value(serde_json::json!({ "image_base64": "ignored", "model": model })).unwrap()
+    }
+
+    fn result(text: &str) -> SolveResult {
+        SolveResult {
+            text: text.to_string(),
+            confidence: 0.9,
+            solver_name: "ocr".to_string(),
+            length_mismatch: None,
+            preprocess_ms: 1,
+            inference_ms: 2,
+            candidates: Vec::new(),
+            preprocess_variant: None,
+            processed_image: None,
+            char_positions: None,
+        }
+    }
+
+    #[test]
+    fn test_perceptual_hash_ignores_resizing() {
+        let image = captcha(0);
+        let resized = image.resize_exact(180, 60, image::imageops::FilterType::Lanczos3);
+
+        let distance = (perceptual_hash(&image) ^ perceptual_hash(&resized)).count_ones();
+        assert!(distance <= 2, "distance {}", distance);
+        assert_ne!(perceptual_hash(&image), perceptual_hash(&captcha(3)));
+    }
+
+    #[test]
+    fn test_memo_key_covers_parameters() {
+        let image = captcha(0);
+        let key = MemoKey::new(b"salt", "key:1", &image, &request("ocr"));
+
+        // The base64 payload is not part of the key, the decoded image is
+        let mut same = request("ocr");
+        same.image_base64 = "different encoding".to_string();
+        assert_eq!(key, MemoKey::new(b"salt", "key:1", &image, &same));
+
+        assert_ne!(key, MemoKey::new(b"salt", "key:1", &image, &request("cnn")));
+        assert_ne!(key, MemoKey::new(b"salt", "key:2", &image, &request("ocr")));
+        assert_ne!(key, MemoKey::new(b"pepper", "key:1", &image, &request("ocr")));
+    }
+
+    #[test]
+    fn test_memo_is_per_caller() {
+        let image = captcha(0);
+        let memo = SolveMemo::new(Duration::from_secs(5));
+
+        memo.insert(memo.key("key:1", &image, &request("ocr")).unwrap(), result("AB12"));
+
+        assert!(memo.get(&memo.key("key:1", &image, &request("ocr")).unwrap()).is_some());
+        assert!(memo.get(&memo.key("key:2", &image, &request("ocr")).unwrap()).is_none());
+        assert!(memo.get(&memo.key("user:1", &image, &request("ocr")).unwrap()).is_none());
+
+        // Another process (or restart) derives different keys for the same request
+        let other = SolveMemo::new(Duration::from_secs(5));
+        assert_ne!(memo.key("key:1", &image, &request("ocr")), other.key("key:1", &image, &request("ocr")));
+    }
+
+    #[test]
+    fn test_memo_expiry_and_disabled() {
+        let image = captcha(0);
+
+        let memo = SolveMemo::new(Duration::from_secs(5));
+        let key = memo.key("key:1", &image, &request("ocr")).unwrap();
+        assert!(memo.get(&key).is_none());
+        memo.insert(key.clone(), result("AB12"));
+        assert_eq!(memo.get(&key).unwrap().text, "AB12");
+
+        let disabled = SolveMemo::new(Duration::ZERO);
+        assert!(disabled.key("key:1", &image, &request("ocr")).is_none());
+        disabled.insert(key.clone(), result("AB12"));
+        assert!(disabled.get(&key).is_none());
+    }
+
+    #[test]
+    fn test_memo_stats_and_clear() {
+        let image = captcha(0);
+        let memo = SolveMemo::new(Duration::from_millis(50));
+        let key = memo.key("key:1", &image, &request("ocr")).unwrap();
+
+        assert!(memo.get(&key).is_none());
+        memo.insert(key.clone(), result("AB12"));
+        assert!(memo.get(&key).is_some());
+        assert_eq!(memo.stats(), MemoStats { hits: 1, misses: 1, size: 1, evictions: 0 });
+
+        // Expired results stop counting and are evicted by the next insert
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(memo.stats().size, 0);
+        memo.insert(memo.key("key:2", &image, &request("ocr")).unwrap(), result("CD34"));
+        assert_eq!(memo.stats().evictions, 1);
+
+        assert_eq!(memo.clear(), 1);
+        assert!(memo.get(&key).is_none());
+        assert_eq!(memo.stats(), MemoStats { hits: 1, misses: 2, size: 0, evictions: 1 });
+    }
+
+    #[tokio::test]
+    async fn test_no_cache_skips_memo() {
+        let image = captcha(0);
+        let memo = SolveMemo::new(Duration::from_secs(5));
+        let solves = AtomicU64::new(0);
+        let solve = || async {
+            solves.fetch_add(1, Ordering::Relaxed);
+            Ok(result("AB12"))
+        };
+
+        let mut uncached = request("ocr");
+        uncached.no_cache = true;
+        for _ in 0..2 {
+            memo.get_or_solve("key:1", &image, &uncached, solve()).await.unwrap();
+        }
+        assert_eq!(solves.load(Ordering::Relaxed), 2);
+        assert_eq!(memo.stats(), MemoStats { hits: 0, misses: 0, size: 0, evictions: 0 });
+
+        // Without it the second request is served from the memo
+        for _ in 0..2 {
+            memo.get_or_solve("key:1", &image, &request("ocr"), solve()).await.unwrap();
+        }
+        assert_eq!(solves.load(Ordering::Relaxed), 3);
+        assert_eq!(memo.stats(), MemoStats { hits: 1, misses: 1, size: 1, evictions: 0 });
+
+        // A memoized result is not returned to a no_cache request either
+        memo.get_or_solve("key:1", &image, &uncached, solve()).await.unwrap();
+        assert_eq!(solves.load(Ordering::Relaxed), 4);
+        assert_eq!(memo.stats().hits, 1);
+    }
+}
@@ -2,6 +2,8 @@
 //!
 //! This module contains all data structures used throughout the application.
 
+use std::collections::BTreeMap;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
@@ -10,11 +12,199 @@ use serde::{Deserialize, Serialize};
 // =============================================================================
 
 /// Request for solving a single captcha
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct SolveRequest {
     pub image_base64: String,
     pub model: Option<String>,
+    /// Solve with the default model of this type (e.g. `cnn`) instead of naming a `model`
+    pub model_type: Option<String>,
     pub preprocess: Option<PreprocessOptions>,
+    pub normalize: Option<NormalizeMode>,
+    pub expected_length: Option<usize>,
+    /// Return up to this many candidate solutions
+    pub top_k: Option<usize>,
+    pub strategy: Option<SolveStrategy>,
+    /// Overrides for the `auto_preprocess` strategy
+    pub auto_preprocess: Option<AutoPreprocessOptions>,
+    /// Tesseract language for the OCR solver, e.g. `chi_sim` or `eng+rus` (default `eng`)
+    pub language: Option<String>,
+    /// Letter case for `alnum` normalization (default: the solver's configured case)
+    pub case: Option<CaseMode>,
+    /// Spaces in normalized output (default: `processing.whitespace`)
+    pub whitespace: Option<WhitespaceMode>,
+    /// Characters the answer may contain; others are removed after normalization
+    pub charset: Option<String>,
+    /// Named provider profile supplying defaults for the fields above
+    pub profile: Option<String>,
+    /// Attach the final preprocessed image (base64 PNG) to the response
+    #[serde(default)]
+    pub include_processed_image: bool,
+    /// Attach the approximate horizontal position of each character (CNN only)
+    #[serde(default)]
+    pub include_char_positions: bool,
+    /// Attach the confidence as a calibrated probability of being correct, in percent
+    #[serde(default)]
+    pub include_probability: bool,
+    /// Retry with another solver when the chosen one fails mid-solve (default: true)
+    pub fallback: Option<bool>,
+    /// Solve only this region of the decoded image
+    pub crop: Option<CropRegion>,
+    /// Return the solver's text as is: no normalization, case, charset or length truncation
+    #[serde(default)]
+    pub raw_output: bool,
+    /// Solve afresh: neither reuse a memoized result nor memoize this one
+    #[serde(default)]
+    pub no_cache: bool,
+}
+
+/// Pixel rectangle of an image, from its top-left corner
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub struct CropRegion {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+/// How the solver manager runs a solve
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SolveStrategy {
+    /// Solve once with the given preprocessing
+    #[default]
+    Single,
+    /// Try preprocessing variants until one reaches the confidence target
+    AutoPreprocess,
+    /// Solve once with the recognizers restricted to the digits 0-9
+    Digits,
+    /// Estimate the captcha's difficulty and solve once with the preprocessing preset for it
+    ByDifficulty,
+}
+
+/// Which solvers answer a request that names no model (`models.default_strategy`)
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DefaultStrategy {
+    /// The default solver, falling back to another one only when it errors
+    #[default]
+    Single,
+    /// Every available solver; the most confident answer wins
+    Ensemble,
+    /// Solvers one after another until an answer is confident enough
+    Fallback,
+}
+
+/// Options for the `auto_preprocess` strategy (defaults come from configuration)
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct AutoPreprocessOptions {
+    /// Variant names to try, in order
+    pub variants: Option<Vec<String>>,
+    pub confidence_target: Option<f32>,
+}
+
+/// A named preprocessing configuration chosen by the `auto_preprocess` or `by_difficulty` strategy
+#[derive(Debug, Clone, Serialize)]
+pub struct PreprocessVariant {
+    pub name: String,
+    /// Options to pass as `preprocess` to reproduce this result
+    pub options: PreprocessOptions,
+    /// Difficulty estimate the preset was selected by (`by_difficulty` only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub difficulty: Option<DifficultyEstimate>,
+}
+
+/// Difficulty level of a captcha, each with its own preprocessing preset
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+/// Cheap difficulty estimate from the connected components of the binarized image
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct DifficultyEstimate {
+    pub level: Difficulty,
+    /// Share of dark pixels in speck-sized components
+    pub noise: f32,
+    /// Dark components larger than a speck
+    pub components: usize,
+}
+
+/// Envelope for paginated list responses
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Total number of items matching the query, across all pages
+    pub total: u64,
+    pub limit: u32,
+    pub offset: u32,
+}
+
+/// A candidate solution with its confidence
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Candidate {
+    pub text: String,
+    pub confidence: f32,
+}
+
+/// Horizontal extent of one recognized character, in pixels of the solved image
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct CharPosition {
+    /// The character as the model read it, before normalization
+    pub text: String,
+    pub x_start: u32,
+    /// Exclusive
+    pub x_end: u32,
+}
+
+/// Estimated chance that an answer is correct (`include_probability` only)
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct Probability {
+    /// ~P(correct) × 100, from the answering solver's labeled logs when `calibrated`,
+    /// otherwise the raw confidence × 100
+    pub percent: f32,
+    pub calibrated: bool,
+}
+
+/// How solver output text is normalized before being returned
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NormalizeMode {
+    /// Map confusable letters to digits (O→0, I→1, S→5, ...) and keep digits only
+    Digits,
+    /// Keep alphanumeric characters, with letter case set by [`CaseMode`]
+    #[default]
+    Alnum,
+    /// Keep alphanumeric characters and uppercase them, whatever the case mode
+    UpperAlnum,
+    /// Return solver output unchanged
+    None,
+}
+
+/// Letter case applied by `alnum` normalization
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CaseMode {
+    #[default]
+    Upper,
+    Lower,
+    /// Keep the case the solver produced
+    Preserve,
+}
+
+/// Whitespace in normalized output, for captchas whose answer contains spaces
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WhitespaceMode {
+    /// Remove all whitespace, like other non-alphanumeric characters
+    #[default]
+    Strip,
+    /// Turn each run of whitespace into one space and trim both ends
+    Collapse,
+    /// Keep whitespace as the solver produced it
+    Preserve,
 }
 
 /// Response from solving a captcha
@@ -24,6 +214,25 @@ pub struct SolveResponse {
     pub confidence: f32,
     pub model: String,
     pub processing_time_ms: u64,
+    pub preprocess_ms: u64,
+    pub inference_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub length_mismatch: Option<bool>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub candidates: Vec<Candidate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preprocess_variant: Option<PreprocessVariant>,
+    /// Base64 PNG of the image the solver read (`include_processed_image` only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub processed_image_base64: Option<String>,
+    /// Approximate position of each recognized character (`include_char_positions` only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub char_positions: Option<Vec<CharPosition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub probability: Option<Probability>,
+    /// 0-1 solvability of the submitted image from its contrast, noise and component count
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_quality: Option<f32>,
 }
 
 /// Request for batch solving
@@ -46,6 +255,92 @@ pub struct BatchResult {
     pub success: bool,
     pub result: Option<SolveResponse>,
     pub error: Option<String>,
+    /// Error category (e.g. `invalid_image`, `timeout`), as in single-solve error bodies
+    pub error_code: Option<String>,
+    /// Time spent decoding (and cropping) this image
+    pub decode_ms: u64,
+    /// Time spent on this image from decoding to its result, whether it succeeded or not
+    pub time_ms: u64,
+}
+
+/// Request for solving one image with several solvers side by side
+#[derive(Debug, Deserialize)]
+pub struct CompareRequest {
+    pub image_base64: String,
+    /// Solvers to run, in response order
+    pub models: Vec<String>,
+    pub preprocess: Option<PreprocessOptions>,
+    pub normalize: Option<NormalizeMode>,
+    pub expected_length: Option<usize>,
+    pub top_k: Option<usize>,
+    pub strategy: Option<SolveStrategy>,
+    pub auto_preprocess: Option<AutoPreprocessOptions>,
+    pub language: Option<String>,
+    pub case: Option<CaseMode>,
+    pub whitespace: Option<WhitespaceMode>,
+    pub charset: Option<String>,
+    #[serde(default)]
+    pub include_processed_image: bool,
+    #[serde(default)]
+    pub include_char_positions: bool,
+    #[serde(default)]
+    pub raw_output: bool,
+}
+
+/// Each solver's answer for the same image
+#[derive(Debug, Serialize)]
+pub struct CompareResponse {
+    pub results: Vec<CompareResult>,
+    /// Whether at least two solvers answered and all answers are the same text
+    pub agree: bool,
+    pub total_time_ms: u64,
+}
+
+/// Result of one solver in a comparison
+#[derive(Debug, Serialize)]
+pub struct CompareResult {
+    pub model: String,
+    pub success: bool,
+    pub result: Option<SolveResponse>,
+    pub error: Option<String>,
+    pub error_code: Option<String>,
+}
+
+/// Query options for the processed image of solve responses
+#[derive(Debug, Default, Deserialize)]
+pub struct ProcessedImageQuery {
+    /// Bits per pixel of `processed_image_base64`: 8 (default), or 1 when the image is binary
+    pub bitdepth: Option<u8>,
+}
+
+/// Query options for archive solving
+#[derive(Debug, Deserialize)]
+pub struct ArchiveSolveQuery {
+    pub model: Option<String>,
+    pub normalize: Option<NormalizeMode>,
+    pub expected_length: Option<usize>,
+    pub language: Option<String>,
+    pub case: Option<CaseMode>,
+    pub whitespace: Option<WhitespaceMode>,
+}
+
+/// Response from archive solving
+#[derive(Debug, Serialize)]
+pub struct ArchiveSolveResponse {
+    pub results: Vec<ArchiveEntryResult>,
+    /// Entries that were not images (directories, text files, ...)
+    pub skipped: Vec<String>,
+    pub total_time_ms: u64,
+}
+
+/// Result for a single archive entry
+#[derive(Debug, Serialize)]
+pub struct ArchiveEntryResult {
+    pub filename: String,
+    pub success: bool,
+    pub result: Option<SolveResponse>,
+    pub error: Option<String>,
+    pub error_code: Option<String>,
 }
 
 /// Image preprocessing options
@@ -54,14 +349,180 @@ pub struct PreprocessOptions {
     pub grayscale: Option<bool>,
     pub threshold: Option<u8>,
     pub denoise: Option<bool>,
+    /// Filter the `denoise` step applies (default Gaussian blur)
+    pub denoise_method: Option<DenoiseMethod>,
+    /// Gaussian sigma, median radius or bilateral spatial sigma, depending on the method
+    pub denoise_strength: Option<f32>,
     pub resize_width: Option<u32>,
     pub resize_height: Option<u32>,
+    /// Block radius for adaptive thresholding
+    pub adaptive_threshold: Option<u32>,
+    /// Dilate or erode strokes toward this average width in pixels (0 disables)
+    pub stroke_width: Option<f32>,
+    /// Thin strokes to 1px skeletons after binarization
+    pub thin: Option<bool>,
+    pub color_filter: Option<ColorFilterOptions>,
+    /// Named steps to run in this order instead of the default pipeline (see `PREPROCESS_STEPS`)
+    pub steps: Option<Vec<String>>,
+}
+
+/// Filter used by the `denoise` preprocessing step
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DenoiseMethod {
+    /// Blurs noise and strokes alike
+    #[default]
+    Gaussian,
+    /// Removes isolated specks (salt-and-pepper noise)
+    Median,
+    /// Smooths within regions of similar intensity, keeping stroke edges sharp
+    Bilateral,
+}
+
+/// Color space used when matching pixels against a target color
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorSpace {
+    /// Euclidean distance between RGB triples (tolerance 0-441)
+    #[default]
+    Rgb,
+    /// Circular distance between hues (tolerance in degrees 0-180)
+    Hsv,
+}
+
+/// Color-based foreground extraction options
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ColorFilterOptions {
+    /// Target foreground color as [r, g, b]
+    pub target: [u8; 3],
+    pub tolerance: Option<f32>,
+    pub space: Option<ColorSpace>,
+}
+
+/// Bounding box and size of one connected component
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ComponentBox {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Number of foreground pixels in the component
+    pub area: u32,
+}
+
+/// Runtime state of a loaded solver
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct SolverInfo {
+    pub name: String,
+    pub ready: bool,
+    /// Whether the solver serves mock output instead of real Tesseract/ONNX inference
+    pub mock: bool,
+}
+
+/// What a solver accepts and returns, so clients can build requests without per-solver knowledge
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct SolverCapabilities {
+    /// Characters the solver can output, or `None` when its output is not restricted
+    pub charset: Option<String>,
+    /// Size images are resized to before inference, for models with a fixed input
+    pub input_size: Option<InputSize>,
+    /// Whether `top_k` can return more than one distinct candidate
+    pub top_k: bool,
+    /// Whether the decoder uses `expected_length`; other solvers only check their answer against it
+    pub expected_length_hint: bool,
+    /// Whether results carry a confidence per character
+    pub per_char_confidence: bool,
+    /// Whether `include_char_positions` returns character positions
+    pub char_positions: bool,
+    /// Values accepted in `language`; empty when the solver ignores it
+    pub languages: Vec<String>,
+}
+
+/// Width and height of a model input, in pixels
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub struct InputSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Capability descriptor of a loaded solver
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct SolverSchema {
+    #[serde(flatten)]
+    pub info: SolverInfo,
+    #[serde(flatten)]
+    pub capabilities: SolverCapabilities,
+    /// Values accepted in `strategy`
+    pub strategies: Vec<SolveStrategy>,
 }
 
 // =============================================================================
 // Database Models
 // =============================================================================
 
+/// Model types accepted for uploaded models and training jobs
+pub const MODEL_TYPES: &[&str] = &["ocr", "cnn", "rnn", "transformer", "ensemble"];
+
+/// Kind of model, stored by name in `captcha_models.type` and `training_jobs.model_type`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, sqlx::Type)]
+#[serde(try_from = "String", rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum ModelType {
+    Ocr,
+    Cnn,
+    Rnn,
+    Transformer,
+    Ensemble,
+}
+
+impl ModelType {
+    pub const ALL: [ModelType; 5] = [
+        ModelType::Ocr,
+        ModelType::Cnn,
+        ModelType::Rnn,
+        ModelType::Transformer,
+        ModelType::Ensemble,
+    ];
+
+    /// Name stored in the database and used by the API
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModelType::Ocr => "ocr",
+            ModelType::Cnn => "cnn",
+            ModelType::Rnn => "rnn",
+            ModelType::Transformer => "transformer",
+            ModelType::Ensemble => "ensemble",
+        }
+    }
+}
+
+impl std::fmt::Display for ModelType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for ModelType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL.into_iter().find(|model_type| model_type.as_str() == s).ok_or_else(|| {
+            format!("Unknown model type '{}'; expected one of: {}", s, MODEL_TYPES.join(", "))
+        })
+    }
+}
+
+impl TryFrom<String> for ModelType {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Solvers a request may name: every model type, plus solvers without model files
+pub const SOLVER_NAMES: &[&str] = &["ocr", "cnn", "rnn", "transformer", "ensemble", "http"];
+
 /// Captcha model stored in database
 #[derive(Debug, Clone, Serialize)]
 pub struct CaptchaModel {
@@ -71,6 +532,8 @@ pub struct CaptchaModel {
     pub version: String,
     pub file_path: String,
     pub file_size_bytes: u64,
+    /// Hex SHA256 of the file recorded at upload, checked when the file is loaded
+    pub file_sha256: Option<String>,
     pub accuracy: Option<f64>,
     pub is_active: bool,
     pub is_default: bool,
@@ -81,6 +544,37 @@ pub struct CaptchaModel {
     pub updated_at: DateTime<Utc>,
 }
 
+/// Algorithm used to compute `CaptchaLog.image_hash`
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    /// Used by logs written before the algorithm was configurable
+    Sha256,
+    #[default]
+    Blake3,
+}
+
+impl HashAlgorithm {
+    /// Hash data to a lowercase hex digest
+    pub fn hash(&self, data: &[u8]) -> String {
+        match self {
+            HashAlgorithm::Sha256 => {
+                use sha2::{Digest, Sha256};
+                hex::encode(Sha256::digest(data))
+            }
+            HashAlgorithm::Blake3 => blake3::hash(data).to_hex().to_string(),
+        }
+    }
+
+    /// Name stored alongside each hash
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Blake3 => "blake3",
+        }
+    }
+}
+
 /// Captcha processing log entry
 #[derive(Debug, Clone, Serialize)]
 pub struct CaptchaLog {
@@ -88,6 +582,7 @@ pub struct CaptchaLog {
     pub user_id: Option<u64>,
     pub model_id: Option<u64>,
     pub image_hash: String,
+    pub image_hash_algorithm: String,
     pub image_base64: Option<String>,
     pub predicted_text: Option<String>,
     pub actual_text: Option<String>,
@@ -100,13 +595,98 @@ pub struct CaptchaLog {
     pub created_at: DateTime<Utc>,
 }
 
+/// Lifecycle state of a training job, stored by name in `training_jobs.status`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, sqlx::Type)]
+#[serde(try_from = "String", rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum TrainingStatus {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl TrainingStatus {
+    pub const ALL: [TrainingStatus; 5] = [
+        TrainingStatus::Pending,
+        TrainingStatus::Running,
+        TrainingStatus::Completed,
+        TrainingStatus::Failed,
+        TrainingStatus::Cancelled,
+    ];
+
+    /// Name stored in the database and used by the API
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrainingStatus::Pending => "pending",
+            TrainingStatus::Running => "running",
+            TrainingStatus::Completed => "completed",
+            TrainingStatus::Failed => "failed",
+            TrainingStatus::Cancelled => "cancelled",
+        }
+    }
+
+    /// Whether a job in this state is finished for good
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, TrainingStatus::Completed | TrainingStatus::Failed | TrainingStatus::Cancelled)
+    }
+
+    /// Whether a job may move from this state to `next`: a pending job starts, fails or is
+    /// cancelled; a running job reports progress (staying running) until it completes, fails
+    /// or is cancelled; finished jobs never change.
+    pub fn can_transition_to(&self, next: TrainingStatus) -> bool {
+        use TrainingStatus::*;
+
+        matches!(
+            (self, next),
+            (Pending, Running | Failed | Cancelled)
+                | (Running, Running | Completed | Failed | Cancelled)
+        )
+    }
+}
+
+impl std::fmt::Display for TrainingStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for TrainingStatus {
+    type Err = String;
+
+    /// Exact names only: a stray space or capital is an unknown status, not a near match
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL.into_iter().find(|status| status.as_str() == s).ok_or_else(|| {
+            let names: Vec<&str> = Self::ALL.iter().map(|status| status.as_str()).collect();
+            format!("Unknown training status '{}'; expected one of: {}", s, names.join(", "))
+        })
+    }
+}
+
+impl TryFrom<&str> for TrainingStatus {
+    type Error = String;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl TryFrom<String> for TrainingStatus {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
 /// Training job
 #[derive(Debug, Clone, Serialize)]
 pub struct TrainingJob {
     pub id: u64,
     pub user_id: Option<u64>,
     pub name: String,
-    pub status: String,
+    pub status: TrainingStatus,
     pub model_type: String,
     pub config: serde_json::Value,
     pub dataset_path: Option<String>,
@@ -141,6 +721,33 @@ pub struct ApiKey {
     pub updated_at: DateTime<Utc>,
 }
 
+/// Audit trail entry for an admin mutation
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditLogEntry {
+    pub id: u64,
+    pub user_id: Option<u64>,
+    pub api_key_id: Option<u64>,
+    /// What was done, e.g. `model.update`
+    pub action: String,
+    pub resource_type: String,
+    pub resource_id: Option<u64>,
+    pub before_state: Option<serde_json::Value>,
+    pub after_state: Option<serde_json::Value>,
+    pub request_ip: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Filters for querying the audit log
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditLogQuery {
+    pub action: Option<String>,
+    pub resource_type: Option<String>,
+    pub resource_id: Option<u64>,
+    pub user_id: Option<u64>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
 /// User
 #[derive(Debug, Clone, Serialize)]
 pub struct User {
@@ -159,10 +766,15 @@ pub struct User {
 #[derive(Debug, Serialize)]
 pub struct Stats {
     pub total_requests: u64,
+    /// Predictions with confidence at least `confidence_floor`
     pub successful_requests: u64,
+    /// Predictions below `confidence_floor`
+    pub low_confidence_requests: u64,
+    /// Requests without a prediction
     pub failed_requests: u64,
     pub average_processing_time_ms: f64,
     pub accuracy_rate: f64,
+    pub confidence_floor: f64,
     pub models_count: u32,
     pub active_models_count: u32,
 }
@@ -218,6 +830,53 @@ impl Default for TrainingConfig {
     }
 }
 
+/// Kind of problem found while validating a training dataset
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum DatasetProblemKind {
+    MissingImagesDir,
+    MissingLabelsFile,
+    CorruptImage,
+    ImageTooLarge,
+    /// Image without a row in labels.csv
+    MissingLabel,
+    /// Row in labels.csv without an image
+    MissingImage,
+    InvalidLabel,
+    DuplicateLabel,
+}
+
+/// A single dataset problem
+#[derive(Debug, Clone, Serialize)]
+pub struct DatasetProblem {
+    pub kind: DatasetProblemKind,
+    /// Image filename, or `labels.csv:<line>` for label rows
+    pub file: String,
+    pub message: String,
+}
+
+/// Result of validating a training dataset
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DatasetReport {
+    /// No problems were found and at least one image is labeled
+    pub valid: bool,
+    pub images_total: usize,
+    /// Images that decoded successfully
+    pub images_valid: usize,
+    /// Data rows in labels.csv
+    pub labels_total: usize,
+    /// Images that have a label
+    pub labeled_images: usize,
+    /// Occurrences of each character across labels
+    pub class_distribution: BTreeMap<char, usize>,
+    /// Number of labels of each length
+    pub label_lengths: BTreeMap<usize, usize>,
+    pub problems_total: usize,
+    pub problem_counts: BTreeMap<DatasetProblemKind, usize>,
+    /// The first problems found, up to the requested limit
+    pub problems: Vec<DatasetProblem>,
+}
+
 /// Training results
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TrainingResults {
@@ -0,0 +1,178 @@
+//! Solve Log Queue
+//!
+//! Solve requests are recorded in `captcha_logs` in the background, so a slow or
+//! unavailable database never delays or fails a solve whose inference worked.
+//! Entries wait in a bounded in-memory queue written by [`spawn_flusher`]; entries
+//! that fail to write stay queued, and entries arriving while the queue is full
+//! (e.g. during a long database outage) are dropped with a warning.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use actix_web::web;
+
+use crate::AppState;
+use crate::db::Database;
+use crate::error::CaptchaResult;
+
+/// One solve to record in `captcha_logs`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SolveLogEntry {
+    pub user_id: Option<u64>,
+    /// Solver that answered; resolved to a model id (by name, else its type's default) when written
+    pub solver_name: String,
+    pub image_hash: String,
+    pub image_hash_algorithm: &'static str,
+    pub predicted_text: String,
+    /// Confidence returned to the caller, after calibration; compared with the stats confidence floor
+    pub confidence: f64,
+    pub processing_time_ms: u32,
+    pub request_ip: Option<String>,
+    /// Base64 of the solved image, kept when `processing.store_log_images` is set
+    pub image_base64: Option<String>,
+}
+
+/// Bounded queue of solve log entries waiting to be written
+pub struct SolveLogQueue {
+    pending: Mutex<VecDeque<SolveLogEntry>>,
+    capacity: usize,
+    dropped: AtomicU64,
+}
+
+impl SolveLogQueue {
+    pub fn new(capacity: usize) -> Self {
+        Self { pending: Mutex::new(VecDeque::new()), capacity, dropped: AtomicU64::new(0) }
+    }
+
+    /// Queue an entry; when the queue is full the entry is dropped and counted
+    pub fn push(&self, entry: SolveLogEntry) {
+        let mut pending = self.pending.lock().unwrap();
+        if pending.len() >= self.capacity {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        pending.push_back(entry);
+    }
+
+    /// Number of entries waiting to be written
+    pub fn queued(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+
+    fn pop(&self) -> Option<SolveLogEntry> {
+        self.pending.lock().unwrap().pop_front()
+    }
+
+    /// Put back an entry that failed to write, ahead of newer ones.
+    /// If the queue filled up meanwhile, the newest entry is dropped instead.
+    fn requeue(&self, entry: SolveLogEntry) {
+        let mut pending = self.pending.lock().unwrap();
+        pending.push_front(entry);
+        if pending.len() > self.capacity {
+            pending.pop_back();
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Write queued entries in order. On the first failure the entry goes back to
+    /// the front of the queue and the rest wait for the next flush.
+    pub async fn flush(&self, db: &Database) {
+        let dropped = self.dropped.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            tracing::warn!("Solve log queue full ({} entries), dropped {} entries", self.capacity, dropped);
+        }
+
+        while let Some(entry) = self.pop() {
+            if let Err(e) = write(db, &entry).await {
+                self.requeue(entry);
+                tracing::warn!("Failed to write solve log ({} queued): {}", self.queued(), e);
+                break;
+            }
+        }
+    }
+}
+
+async fn write(db: &Database, entry: &SolveLogEntry) -> CaptchaResult<()> {
+    // Solvers are named after the model type they serve, so a solver without a model
+    // of its own name is attributed to the default model of its type
+    let model = match db.get_model_by_name(&entry.solver_name).await? {
+        Some(model) => Some(model),
+        None => db.get_default_model(&entry.solver_name).await?,
+    };
+    let model_id = model.map(|m| m.id);
+
+    db.create_log(
+        entry.user_id,
+        model_id,
+        &entry.image_hash,
+        entry.image_hash_algorithm,
+        Some(&entry.predicted_text),
+        Some(entry.confidence),
+        entry.processing_time_ms,
+        entry.request_ip.as_deref(),
+        entry.image_base64.as_deref(),
+    ).await?;
+
+    Ok(())
+}
+
+/// Periodically write queued solve logs in the background
+pub fn spawn_flusher(state: web::Data<AppState>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            state.solve_log.flush(&state.db).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(text: &str) -> SolveLogEntry {
+        SolveLogEntry {
+            user_id: Some(7),
+            solver_name: "ocr".to_string(),
+            image_hash: "abc".to_string(),
+            image_hash_algorithm: "blake3",
+            predicted_text: text.to_string(),
+            confidence: 0.9,
+            processing_time_ms: 12,
+            request_ip: None,
+            image_base64: None,
+        }
+    }
+
+    #[test]
+    fn test_queue_drops_entries_beyond_capacity() {
+        let queue = SolveLogQueue::new(2);
+        queue.push(entry("A"));
+        queue.push(entry("B"));
+        queue.push(entry("C"));
+
+        assert_eq!(queue.queued(), 2);
+        assert_eq!(queue.dropped.load(Ordering::Relaxed), 1);
+        assert_eq!(queue.pop().unwrap().predicted_text, "A");
+    }
+
+    #[test]
+    fn test_requeued_entry_is_written_first() {
+        let queue = SolveLogQueue::new(2);
+        queue.push(entry("A"));
+        queue.push(entry("B"));
+
+        let failed = queue.pop().unwrap();
+        queue.push(entry("C"));
+        queue.requeue(failed);
+
+        // The oldest entry stays first; the queue never grows past its capacity
+        assert_eq!(queue.queued(), 2);
+        assert_eq!(queue.dropped.load(Ordering::Relaxed), 1);
+        assert_eq!(queue.pop().unwrap().predicted_text, "A");
+        assert_eq!(queue.pop().unwrap().predicted_text, "B");
+    }
+}
@@ -0,0 +1,119 @@
+//! Confidence Calibration
+//!
+//! Solver confidences are scores, not probabilities: the mock CNN never leaves
+//! 0.5–0.98 and Tesseract's scale differs from the CNN's. A [`CalibrationCurve`]
+//! maps a returned confidence to the share of answers with that confidence that
+//! were correct, fitted on labeled logs of the solver by isotonic regression, so
+//! the mapping never decreases as confidence rises.
+
+/// Monotone map from a solver's returned confidence to its estimated P(correct)
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalibrationCurve {
+    /// `(mean confidence, accuracy)` of each pooled block, by increasing confidence
+    points: Vec<(f32, f32)>,
+    samples: usize,
+}
+
+impl CalibrationCurve {
+    /// Fit on `(confidence, correct)` samples, or `None` with fewer than `min_samples`.
+    /// Pool-adjacent-violators: samples grouped and sorted by confidence are merged
+    /// into blocks until the blocks' accuracy is non-decreasing.
+    pub fn fit(samples: &[(f32, bool)], min_samples: usize) -> Option<Self> {
+        let mut sorted: Vec<(f32, bool)> = samples.iter().copied().filter(|(c, _)| c.is_finite()).collect();
+        if sorted.is_empty() || sorted.len() < min_samples {
+            return None;
+        }
+        sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        // Answers with the same confidence start out as one group
+        let mut groups: Vec<(f32, f64, f64)> = Vec::new();
+        for &(confidence, correct) in &sorted {
+            let correct = if correct { 1.0 } else { 0.0 };
+            match groups.last_mut() {
+                Some((c, k, n)) if *c == confidence => {
+                    *k += correct;
+                    *n += 1.0;
+                }
+                _ => groups.push((confidence, correct, 1.0)),
+            }
+        }
+
+        // (confidence sum, correct count, sample count) per block
+        let mut blocks: Vec<(f64, f64, f64)> = Vec::new();
+        for (confidence, correct, count) in groups {
+            blocks.push((confidence as f64 * count, correct, count));
+            while blocks.len() > 1 {
+                let (c2, k2, n2) = blocks[blocks.len() - 1];
+                let (c1, k1, n1) = blocks[blocks.len() - 2];
+                if k1 / n1 < k2 / n2 {
+                    break;
+                }
+                blocks.pop();
+                *blocks.last_mut().unwrap() = (c1 + c2, k1 + k2, n1 + n2);
+            }
+        }
+
+        let points = blocks
+            .iter()
+            .map(|(c, k, n)| ((c / n) as f32, (k / n) as f32))
+            .collect();
+        Some(Self { points, samples: sorted.len() })
+    }
+
+    /// Estimated probability that an answer with `confidence` is correct, interpolated
+    /// linearly between blocks and flat beyond the first and last
+    pub fn probability(&self, confidence: f32) -> f32 {
+        let upper = self.points.partition_point(|(c, _)| *c < confidence);
+        match (upper.checked_sub(1).map(|i| self.points[i]), self.points.get(upper)) {
+            (Some((c0, p0)), Some(&(c1, p1))) if c1 > c0 => p0 + (p1 - p0) * (confidence - c0) / (c1 - c0),
+            (_, Some(&(_, p))) | (Some((_, p)), None) => p,
+            (None, None) => confidence.clamp(0.0, 1.0),
+        }
+    }
+
+    /// Labeled answers the curve was fitted on
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit_needs_min_samples() {
+        assert!(CalibrationCurve::fit(&[(0.9, true), (0.6, false)], 3).is_none());
+        assert!(CalibrationCurve::fit(&[], 0).is_none());
+        assert_eq!(CalibrationCurve::fit(&[(0.9, true), (0.6, false)], 2).unwrap().samples(), 2);
+    }
+
+    #[test]
+    fn test_overconfident_solver() {
+        // Answers around 0.9 are right half the time, around 0.6 a quarter of the time
+        let mut samples = Vec::new();
+        for i in 0..100 {
+            samples.push((0.9, i % 2 == 0));
+            samples.push((0.6, i % 4 == 0));
+        }
+        let curve = CalibrationCurve::fit(&samples, 50).unwrap();
+
+        assert!((curve.probability(0.9) - 0.5).abs() < 1e-6);
+        assert!((curve.probability(0.6) - 0.25).abs() < 1e-6);
+        assert!((curve.probability(0.75) - 0.375).abs() < 1e-6);
+        // Flat beyond the fitted range
+        assert!((curve.probability(0.99) - 0.5).abs() < 1e-6);
+        assert!((curve.probability(0.1) - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_curve_is_monotone() {
+        // Noisy labels where a higher confidence bucket happens to do worse
+        let samples = [(0.5, false), (0.6, true), (0.7, false), (0.8, true), (0.9, true), (0.95, false)];
+        let curve = CalibrationCurve::fit(&samples, 1).unwrap();
+
+        let probabilities: Vec<f32> = (0..=20).map(|i| curve.probability(i as f32 / 20.0)).collect();
+        assert!(probabilities.windows(2).all(|w| w[0] <= w[1] + 1e-6), "{:?}", probabilities);
+        assert!(probabilities.iter().all(|p| (0.0..=1.0).contains(p)));
+    }
+}
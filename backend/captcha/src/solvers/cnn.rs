@@ -4,23 +4,51 @@
 
 use image::DynamicImage;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
 use std::sync::Arc;
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 use crate::error::{CaptchaError, CaptchaResult};
-use crate::models::PreprocessOptions;
-use super::{CaptchaSolver, SolveResult};
+use crate::models::{Candidate, CharPosition, InputSize, PreprocessOptions, SolverCapabilities};
+use super::{CaptchaSolver, SolveHints, SolveResult, DIGIT_CHARSET};
 use super::preprocessor::ImagePreprocessor;
 
+/// One character of a CTC decoding and the frames its class won
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Emission {
+    class: usize,
+    first_frame: usize,
+    last_frame: usize,
+    probability: f32,
+}
+
+/// Decoded text with the output it came from
+struct Inference {
+    text: String,
+    confidence: f32,
+    candidates: Vec<Candidate>,
+    /// CTC alignment of `text` and the number of frames it spans
+    alignment: Vec<Emission>,
+    seq_len: usize,
+}
+
 /// CNN-based captcha solver using ONNX models
 pub struct CnnSolver {
     ready: AtomicBool,
+    mock: AtomicBool,
+    /// Whether the default model loaded; until it does the solver serves mock output
+    loaded: AtomicBool,
     models_path: String,
     // In production, this would hold the ONNX session
     // model: Option<tract_onnx::prelude::SimplePlan<...>>,
     charset: Vec<char>,
     input_width: u32,
     input_height: u32,
+    /// Stroke width preprocessing aims for when the request sets none (0 disables)
+    stroke_width_target: f32,
+    /// Most characters a decoding may emit (`models.max_output_length`)
+    max_output_length: usize,
 }
 
 impl CnnSolver {
@@ -28,23 +56,27 @@ impl CnnSolver {
     const DEFAULT_CHARSET: &'static str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
     
     /// Create a new CNN solver
-    pub async fn new(models_path: &str) -> CaptchaResult<Self> {
+    pub async fn new(models_path: &str, stroke_width_target: f32, max_output_length: usize) -> CaptchaResult<Self> {
         let solver = Self {
             ready: AtomicBool::new(false),
+            mock: AtomicBool::new(true),
+            loaded: AtomicBool::new(false),
             models_path: models_path.to_string(),
             charset: Self::DEFAULT_CHARSET.chars().collect(),
             input_width: 200,
             input_height: 50,
+            stroke_width_target,
+            max_output_length,
         };
 
         // Try to load the default model
         match solver.load_default_model() {
             Ok(_) => {
-                solver.ready.store(true, Ordering::SeqCst);
+                solver.mark_loaded();
                 Ok(solver)
             }
             Err(e) => {
-                tracing::warn!("CNN model not loaded, using mock: {}", e);
+                tracing::warn!("CNN model not loaded, using mock until it loads: {}", e);
                 // Still return solver but in mock mode
                 solver.ready.store(true, Ordering::SeqCst);
                 Ok(solver)
@@ -52,8 +84,21 @@ impl CnnSolver {
         }
     }
 
+    /// Switch from mock output to the loaded model
+    fn mark_loaded(&self) {
+        self.loaded.store(true, Ordering::SeqCst);
+        // Real inference additionally requires the onnx feature
+        self.mock.store(!cfg!(feature = "onnx"), Ordering::SeqCst);
+        self.ready.store(true, Ordering::SeqCst);
+    }
+
+    /// File the default model is loaded from
+    fn model_path(&self) -> PathBuf {
+        Path::new(&self.models_path).join("captcha_cnn.onnx")
+    }
+
     fn load_default_model(&self) -> CaptchaResult<()> {
-        let model_path = Path::new(&self.models_path).join("captcha_cnn.onnx");
+        let model_path = self.model_path();
         
         if !model_path.exists() {
             return Err(CaptchaError::ModelNotFound(
@@ -82,8 +127,48 @@ impl CnnSolver {
         Ok(())
     }
 
+    /// Default captcha length assumed by the mock model
+    const DEFAULT_CAPTCHA_LENGTH: usize = 6;
+
+    /// Lower bound on beams kept during CTC beam search
+    const MIN_BEAM_WIDTH: usize = 8;
+
+    /// Upper bound on beams kept during CTC beam search, whatever `top_k` asks for
+    const MAX_BEAM_WIDTH: usize = 64;
+
+    /// Horizontal downsampling of the model: one CTC frame per this many input columns
+    const FRAME_STRIDE: u32 = 4;
+
+    /// Classes the decoder may emit for this request, or `None` for the full charset
+    fn allowed_classes(&self, hints: &SolveHints) -> Option<Vec<bool>> {
+        hints
+            .digits_only()
+            .then(|| self.charset.iter().map(|c| DIGIT_CHARSET.contains(*c)).collect())
+    }
+
+    /// Zero the probabilities of disallowed classes and re-normalize each frame over
+    /// the allowed classes and blank, so decoding only sees the restricted charset
+    fn restrict_classes(&self, output: &[f32], allowed: &[bool]) -> Vec<f32> {
+        let num_classes = self.charset.len() + 1;
+        let mut restricted = output.to_vec();
+
+        for frame in restricted.chunks_mut(num_classes) {
+            for (class, p) in frame.iter_mut().enumerate() {
+                if class < allowed.len() && !allowed[class] {
+                    *p = 0.0;
+                }
+            }
+            let total: f32 = frame.iter().sum();
+            if total > 0.0 {
+                frame.iter_mut().for_each(|p| *p /= total);
+            }
+        }
+
+        restricted
+    }
+
     /// Run inference on preprocessed image
-    fn run_inference(&self, image: &DynamicImage) -> CaptchaResult<(String, f32)> {
+    fn run_inference(&self, image: &DynamicImage, hints: &SolveHints) -> CaptchaResult<Inference> {
         // Resize image to model input size
         let resized = image.resize_exact(
             self.input_width,
@@ -105,17 +190,96 @@ impl CnnSolver {
         {
             // Run model inference
             // let output = self.model.run(tvec!(input_tensor))?;
-            // Parse output to get text and confidence
+            // Parse output with self.decode(&output, seq_len, hints)
         }
 
         // Mock inference for development
-        let (text, confidence) = self.mock_inference(&input, width, height);
+        let charset: Vec<char> = match self.allowed_classes(hints) {
+            Some(allowed) => self.charset.iter().zip(allowed).filter(|(_, ok)| *ok).map(|(c, _)| *c).collect(),
+            None => self.charset.clone(),
+        };
+        let (text, confidence) = self.mock_inference(&charset, &input, width, height, hints.expected_length);
+
+        // Lay the mock text out as CTC output, so positions and candidates come from the real decoder
+        let classes: Vec<usize> = text
+            .chars()
+            .filter_map(|c| self.charset.iter().position(|&k| k == c))
+            .collect();
+        let seq_len = ((width / Self::FRAME_STRIDE) as usize).max(4 * classes.len());
+        let (alignment, mut candidates) = self.decode(&self.mock_ctc_output(&classes, seq_len), seq_len, hints);
+        if candidates.is_empty() {
+            candidates.push(Candidate { text: text.clone(), confidence });
+        }
+
+        Ok(Inference { text, confidence, candidates, alignment, seq_len })
+    }
+
+    /// Decode per-frame class probabilities: the greedy alignment of the answer and,
+    /// when the request sets `top_k`, the most probable labelings from beam search.
+    /// A restricted strategy narrows the output to its charset first
+    fn decode(&self, output: &[f32], seq_len: usize, hints: &SolveHints) -> (Vec<Emission>, Vec<Candidate>) {
+        let restricted;
+        let output = match self.allowed_classes(hints) {
+            Some(allowed) => {
+                restricted = self.restrict_classes(output, &allowed);
+                &restricted[..]
+            }
+            None => output,
+        };
+
+        let alignment = self.align_ctc_output(output, seq_len, hints.expected_length);
+        let candidates = match hints.top_k {
+            Some(top_k) => self.beam_search_ctc(output, seq_len, Some(top_k)),
+            None => Vec::new(),
+        };
+        (alignment, candidates)
+    }
+
+    /// Per-frame probabilities spelling out `classes`: each character wins the middle
+    /// half of an equal share of the frames, blank wins the rest
+    fn mock_ctc_output(&self, classes: &[usize], seq_len: usize) -> Vec<f32> {
+        let num_classes = self.charset.len() + 1;
+        let blank = num_classes - 1;
+        let mut output = vec![0.0; seq_len * num_classes];
+
+        for t in 0..seq_len {
+            output[t * num_classes + blank] = 1.0;
+        }
+        if classes.is_empty() {
+            return output;
+        }
 
-        Ok((text, confidence))
+        // Each character holds the middle of its share; only the edge frames of the span
+        // are uncertain, so a character can't split into a repeat of itself
+        let share = seq_len / classes.len();
+        for (i, &class) in classes.iter().enumerate() {
+            let span = (i * share + share / 4)..(i * share + (3 * share).div_ceil(4));
+            for t in span.clone() {
+                let edge = t == span.start || t + 1 == span.end;
+                output[t * num_classes + blank] = if edge { 0.1 } else { 0.0 };
+                output[t * num_classes + class] = if edge { 0.9 } else { 1.0 };
+            }
+        }
+        output
+    }
+
+    /// Horizontal extent of each aligned character in an image `width` pixels wide,
+    /// taking the frames a character won as an equal slice of the width each
+    fn char_positions(&self, alignment: &[Emission], seq_len: usize, width: u32) -> Vec<CharPosition> {
+        let x = |frame: usize| (frame as u64 * width as u64 / seq_len.max(1) as u64) as u32;
+
+        alignment
+            .iter()
+            .map(|e| CharPosition {
+                text: self.charset[e.class].to_string(),
+                x_start: x(e.first_frame),
+                x_end: x(e.last_frame + 1),
+            })
+            .collect()
     }
 
     /// Mock inference for development/testing
-    fn mock_inference(&self, input: &[f32], width: u32, height: u32) -> (String, f32) {
+    fn mock_inference(&self, charset: &[char], input: &[f32], width: u32, height: u32, expected_length: Option<usize>) -> (String, f32) {
         // Simulate CNN output by analyzing input patterns
         let avg = input.iter().sum::<f32>() / input.len() as f32;
         let variance: f32 = input.iter()
@@ -124,13 +288,13 @@ impl CnnSolver {
 
         // Generate pseudo-random but deterministic output based on input statistics
         let mut result = String::new();
-        let captcha_length = 6;
+        let captcha_length = expected_length.unwrap_or(Self::DEFAULT_CAPTCHA_LENGTH).min(self.max_output_length);
 
         for i in 0..captcha_length {
             // Use input statistics to select character
-            let idx_float = ((avg * (i as f32 + 1.0) + variance * 100.0) * 1000.0) % self.charset.len() as f32;
-            let idx = idx_float.abs() as usize % self.charset.len();
-            result.push(self.charset[idx]);
+            let idx_float = ((avg * (i as f32 + 1.0) + variance * 100.0) * 1000.0) % charset.len() as f32;
+            let idx = idx_float.abs() as usize % charset.len();
+            result.push(charset[idx]);
         }
 
         // Confidence based on variance (more distinct patterns = higher confidence)
@@ -139,11 +303,30 @@ impl CnnSolver {
         (result, confidence)
     }
 
-    /// Decode CTC output to text
-    fn decode_ctc_output(&self, output: &[f32], seq_len: usize) -> String {
+    /// Decode CTC output to text, see [`Self::align_ctc_output`]
+    #[cfg(test)]
+    fn decode_ctc_output(&self, output: &[f32], seq_len: usize, expected_length: Option<usize>) -> String {
+        self.align_ctc_output(output, seq_len, expected_length)
+            .iter()
+            .map(|e| self.charset[e.class])
+            .collect()
+    }
+
+    /// Greedy CTC decoding, keeping the frames each emitted character spans.
+    ///
+    /// When `expected_length` is given, the greedy path is adjusted to it after decoding:
+    /// surplus characters with the lowest probability are dropped, and missing characters
+    /// are recovered from blank frames whose best non-blank class scored highest. Beam
+    /// search candidates are not length-adjusted.
+    /// Decoding stops once `max_output_length` characters were emitted.
+    fn align_ctc_output(&self, output: &[f32], seq_len: usize, expected_length: Option<usize>) -> Vec<Emission> {
         let num_classes = self.charset.len() + 1; // +1 for blank token
-        let mut result = String::new();
-        let mut prev_class = num_classes; // blank
+        let blank = num_classes - 1;
+
+        let mut emitted: Vec<Emission> = Vec::new();
+        // Best non-blank alternative for frames where blank won
+        let mut alternatives: Vec<Emission> = Vec::new();
+        let mut prev_class = blank;
 
         for t in 0..seq_len {
             let start = t * num_classes;
@@ -153,51 +336,191 @@ impl CnnSolver {
                 break;
             }
 
+            let frame = &output[start..end];
+
             // Find argmax
-            let (max_idx, _) = output[start..end]
+            let (max_idx, &max_prob) = frame
                 .iter()
                 .enumerate()
-                .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+                .max_by(|a, b| a.1.total_cmp(b.1))
                 .unwrap();
 
             // CTC decoding: skip blanks and repeated characters
-            if max_idx != num_classes - 1 && max_idx != prev_class {
+            if max_idx != blank && max_idx != prev_class {
+                if emitted.len() >= self.max_output_length {
+                    tracing::warn!(
+                        "CNN decoding exceeds {} characters, ignoring frames from {} of {}",
+                        self.max_output_length, t, seq_len
+                    );
+                    break;
+                }
                 if max_idx < self.charset.len() {
-                    result.push(self.charset[max_idx]);
+                    emitted.push(Emission { class: max_idx, first_frame: t, last_frame: t, probability: max_prob });
                 }
+            } else if max_idx != blank {
+                // A repeat extends the character it continues
+                if let Some(last) = emitted.last_mut().filter(|e| e.class == max_idx && e.last_frame + 1 == t) {
+                    last.last_frame = t;
+                }
+            } else if let Some((alt_idx, &alt_prob)) = frame[..blank]
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.total_cmp(b.1))
+            {
+                alternatives.push(Emission { class: alt_idx, first_frame: t, last_frame: t, probability: alt_prob });
             }
             prev_class = max_idx;
         }
 
-        result
+        if let Some(expected) = expected_length {
+            if emitted.len() > expected {
+                // Drop the least confident characters, keeping order
+                let mut by_prob: Vec<usize> = (0..emitted.len()).collect();
+                by_prob.sort_by(|&a, &b| emitted[a].probability.total_cmp(&emitted[b].probability));
+                let mut drop: Vec<usize> = by_prob[..emitted.len() - expected].to_vec();
+                drop.sort_unstable_by(|a, b| b.cmp(a));
+                for idx in drop {
+                    emitted.remove(idx);
+                }
+            } else if emitted.len() < expected {
+                alternatives.sort_by(|a, b| b.probability.total_cmp(&a.probability));
+                for alt in alternatives {
+                    if emitted.len() >= expected {
+                        break;
+                    }
+                    // An insertion equal to an adjacent character would collapse under CTC
+                    let pos = emitted.partition_point(|e| e.first_frame < alt.first_frame);
+                    let collides = (pos > 0 && emitted[pos - 1].class == alt.class && emitted[pos - 1].last_frame + 1 == alt.first_frame)
+                        || (pos < emitted.len() && emitted[pos].class == alt.class && alt.first_frame + 1 == emitted[pos].first_frame);
+                    if !collides {
+                        emitted.insert(pos, alt);
+                    }
+                }
+            }
+        }
+
+        emitted
+    }
+
+    /// CTC prefix beam search returning the `top_k` most probable labelings.
+    ///
+    /// `output` holds per-frame class probabilities (blank is the last class).
+    /// Each candidate's confidence is the total probability of its labeling.
+    fn beam_search_ctc(&self, output: &[f32], seq_len: usize, top_k: Option<usize>) -> Vec<Candidate> {
+        let num_classes = self.charset.len() + 1;
+        let blank = num_classes - 1;
+        let top_k = top_k.unwrap_or(1).max(1);
+        let beam_width = top_k.saturating_mul(2).clamp(Self::MIN_BEAM_WIDTH, Self::MAX_BEAM_WIDTH);
+
+        // prefix -> (probability ending in blank, probability ending in non-blank)
+        let mut beams: Vec<(Vec<usize>, (f32, f32))> = vec![(Vec::new(), (1.0, 0.0))];
+
+        for t in 0..seq_len {
+            let start = t * num_classes;
+            let end = start + num_classes;
+            if end > output.len() {
+                break;
+            }
+            let frame = &output[start..end];
+
+            let mut next: HashMap<Vec<usize>, (f32, f32)> = HashMap::new();
+            for (prefix, (p_blank, p_char)) in &beams {
+                for (class, &p) in frame.iter().enumerate() {
+                    if p <= 0.0 {
+                        continue;
+                    }
+                    if class == blank {
+                        next.entry(prefix.clone()).or_default().0 += (p_blank + p_char) * p;
+                        continue;
+                    }
+
+                    if prefix.len() >= self.max_output_length {
+                        // Labelings at the length bound absorb further characters unchanged
+                        next.entry(prefix.clone()).or_default().1 += (p_blank + p_char) * p;
+                        continue;
+                    }
+
+                    let mut extended = prefix.clone();
+                    extended.push(class);
+                    if prefix.last() == Some(&class) {
+                        // A repeat only extends the prefix when separated by a blank
+                        next.entry(extended).or_default().1 += p_blank * p;
+                        next.entry(prefix.clone()).or_default().1 += p_char * p;
+                    } else {
+                        next.entry(extended).or_default().1 += (p_blank + p_char) * p;
+                    }
+                }
+            }
+
+            beams = next.into_iter().collect();
+            beams.sort_by(|a, b| (b.1.0 + b.1.1).total_cmp(&(a.1.0 + a.1.1)));
+            beams.truncate(beam_width);
+        }
+
+        beams
+            .into_iter()
+            .filter(|(prefix, _)| !prefix.is_empty())
+            .take(top_k)
+            .map(|(prefix, (p_blank, p_char))| Candidate {
+                text: prefix.iter().map(|&c| self.charset[c]).collect(),
+                confidence: p_blank + p_char,
+            })
+            .collect()
     }
 }
 
 #[async_trait::async_trait]
 impl CaptchaSolver for CnnSolver {
-    async fn solve(&self, image: &DynamicImage, options: Option<&PreprocessOptions>) -> CaptchaResult<SolveResult> {
+    async fn solve(
+        &self,
+        image: &DynamicImage,
+        options: Option<&PreprocessOptions>,
+        hints: &SolveHints,
+    ) -> CaptchaResult<SolveResult> {
         if !self.is_ready() {
             return Err(CaptchaError::ModelLoadError("CNN solver not ready".to_string()));
         }
 
         // Preprocess image
-        let preprocess_opts = options.cloned().unwrap_or_else(|| PreprocessOptions {
-            grayscale: Some(true),
-            threshold: None, // CNN works better without hard threshold
-            denoise: Some(true),
-            resize_width: Some(self.input_width),
-            resize_height: Some(self.input_height),
-        });
+        let preprocess_start = Instant::now();
+        let mut preprocess_opts = ImagePreprocessor::with_stroke_width(
+            options.cloned().unwrap_or_else(|| PreprocessOptions {
+                grayscale: Some(true),
+                threshold: None, // CNN works better without hard threshold
+                denoise: Some(true),
+                resize_width: Some(self.input_width),
+                resize_height: Some(self.input_height),
+                ..Default::default()
+            }),
+            self.stroke_width_target,
+        );
+        // The model needs its fixed input size even when the caller's options omit it
+        preprocess_opts.resize_width.get_or_insert(self.input_width);
+        preprocess_opts.resize_height.get_or_insert(self.input_height);
 
         let processed = ImagePreprocessor::preprocess(image, &preprocess_opts)?;
+        let preprocess_ms = preprocess_start.elapsed().as_millis() as u64;
 
         // Run inference
-        let (text, confidence) = self.run_inference(&processed)?;
+        let inference_start = Instant::now();
+        let inference = self.run_inference(&processed, hints)?;
+        let inference_ms = inference_start.elapsed().as_millis() as u64;
+
+        // Frames span the resized input, which spans the whole image given to the solver
+        let char_positions = hints.include_char_positions
+            .then(|| self.char_positions(&inference.alignment, inference.seq_len, image.width()));
 
         Ok(SolveResult {
-            text,
-            confidence,
+            text: inference.text,
+            confidence: inference.confidence,
             solver_name: self.name().to_string(),
+            length_mismatch: None,
+            preprocess_ms,
+            inference_ms,
+            candidates: inference.candidates,
+            preprocess_variant: None,
+            processed_image: hints.include_processed_image.then_some(processed),
+            char_positions,
         })
     }
 
@@ -208,26 +531,78 @@ impl CaptchaSolver for CnnSolver {
     fn is_ready(&self) -> bool {
         self.ready.load(Ordering::SeqCst)
     }
+
+    fn is_mock(&self) -> bool {
+        self.mock.load(Ordering::SeqCst)
+    }
+
+    fn model_file(&self) -> Option<PathBuf> {
+        Some(self.model_path())
+    }
+
+    fn needs_model(&self) -> bool {
+        !self.loaded.load(Ordering::SeqCst)
+    }
+
+    fn reload_model(&self) -> CaptchaResult<()> {
+        self.load_default_model()?;
+        self.mark_loaded();
+        Ok(())
+    }
+
+    fn capabilities(&self) -> SolverCapabilities {
+        SolverCapabilities {
+            charset: Some(self.charset.iter().collect()),
+            input_size: Some(InputSize { width: self.input_width, height: self.input_height }),
+            // Beam search yields ranked candidates, and the decoder favors the expected length
+            top_k: true,
+            expected_length_hint: true,
+            per_char_confidence: false,
+            // From the frames of the CTC alignment
+            char_positions: true,
+            languages: Vec::new(),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Ready mock solver over `charset`, at the default input size and output limit
+    fn test_solver(charset: &str) -> CnnSolver {
+        CnnSolver {
+            ready: AtomicBool::new(true),
+            mock: AtomicBool::new(true),
+            loaded: AtomicBool::new(false),
+            models_path: "/tmp".to_string(),
+            charset: charset.chars().collect(),
+            input_width: 200,
+            input_height: 50,
+            stroke_width_target: 0.0,
+            max_output_length: 32,
+        }
+    }
+
     #[test]
     fn test_charset() {
         assert_eq!(CnnSolver::DEFAULT_CHARSET.len(), 36);
     }
 
+    #[test]
+    fn test_capabilities() {
+        let solver = test_solver("ABC");
+
+        let capabilities = solver.capabilities();
+        assert_eq!(capabilities.charset.as_deref(), Some("ABC"));
+        assert_eq!(capabilities.input_size, Some(InputSize { width: 200, height: 50 }));
+        assert!(capabilities.top_k);
+        assert!(capabilities.char_positions);
+    }
+
     #[test]
     fn test_ctc_decode() {
-        let solver = CnnSolver {
-            ready: AtomicBool::new(true),
-            models_path: "/tmp".to_string(),
-            charset: "ABC".chars().collect(),
-            input_width: 200,
-            input_height: 50,
-        };
+        let solver = test_solver("ABC");
 
         // Test CTC decoding logic
         // A=0, B=1, C=2, blank=3
@@ -239,7 +614,175 @@ mod tests {
             0.0, 0.0, 1.0, 0.0,  // C
         ];
 
-        let result = solver.decode_ctc_output(&output, 5);
+        let result = solver.decode_ctc_output(&output, 5, None);
         assert_eq!(result, "ABC");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_char_positions_from_alignment() {
+        let solver = test_solver("ABC");
+
+        // A=0, B=1, C=2, blank=3
+        let output = vec![
+            0.0, 0.0, 0.0, 1.0,  // blank
+            1.0, 0.0, 0.0, 0.0,  // A
+            1.0, 0.0, 0.0, 0.0,  // A (same character)
+            0.0, 0.0, 0.0, 1.0,  // blank
+            0.0, 0.0, 0.0, 1.0,  // blank
+            0.0, 1.0, 0.0, 0.0,  // B
+            0.0, 0.0, 0.0, 1.0,  // blank
+            0.0, 1.0, 0.0, 0.0,  // B (new character after the blank)
+        ];
+
+        let alignment = solver.align_ctc_output(&output, 8, None);
+        let spans: Vec<(usize, usize, usize)> = alignment.iter().map(|e| (e.class, e.first_frame, e.last_frame)).collect();
+        assert_eq!(spans, [(0, 1, 2), (1, 5, 5), (1, 7, 7)]);
+
+        // 8 frames over 160 px: 20 px per frame
+        let positions = solver.char_positions(&alignment, 8, 160);
+        assert_eq!(positions, [
+            CharPosition { text: "A".to_string(), x_start: 20, x_end: 60 },
+            CharPosition { text: "B".to_string(), x_start: 100, x_end: 120 },
+            CharPosition { text: "B".to_string(), x_start: 140, x_end: 160 },
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_char_positions_on_request() {
+        let solver = test_solver(CnnSolver::DEFAULT_CHARSET);
+        let image = DynamicImage::new_rgb8(120, 40);
+
+        assert!(solver.solve(&image, None, &SolveHints::default()).await.unwrap().char_positions.is_none());
+
+        let hints = SolveHints { include_char_positions: true, expected_length: Some(5), ..Default::default() };
+        let result = solver.solve(&image, None, &hints).await.unwrap();
+        let positions = result.char_positions.unwrap();
+
+        // One ordered, non-overlapping range per character, within the input image
+        assert_eq!(positions.iter().map(|p| p.text.as_str()).collect::<String>(), result.text);
+        assert!(positions.windows(2).all(|w| w[0].x_end <= w[1].x_start));
+        assert!(positions.iter().all(|p| p.x_start < p.x_end && p.x_end <= 120));
+    }
+
+    #[test]
+    fn test_ctc_decode_expected_length() {
+        let solver = test_solver("ABC");
+
+        // A=0, B=1, C=2, blank=3
+        let output = vec![
+            0.9, 0.0, 0.0, 0.1,  // A
+            0.0, 0.0, 0.4, 0.6,  // blank, C is runner-up
+            0.0, 0.3, 0.0, 0.7,  // blank, B is runner-up
+            0.0, 0.0, 0.8, 0.2,  // C
+            0.0, 0.55, 0.0, 0.45, // B (weak)
+        ];
+
+        assert_eq!(solver.decode_ctc_output(&output, 5, None), "ACB");
+        assert_eq!(solver.decode_ctc_output(&output, 5, Some(2)), "AC");
+        assert_eq!(solver.decode_ctc_output(&output, 5, Some(4)), "ACCB");
+    }
+
+    #[test]
+    fn test_digits_restriction() {
+        let solver = test_solver("O0");
+        let hints = SolveHints { strategy: crate::models::SolveStrategy::Digits, ..Default::default() };
+        let allowed = solver.allowed_classes(&hints).unwrap();
+        assert_eq!(allowed, vec![false, true]);
+        assert!(solver.allowed_classes(&SolveHints::default()).is_none());
+
+        // O=0, 0=1, blank=2: the letter wins unrestricted, the digit once restricted
+        let output = vec![
+            0.6, 0.3, 0.1,
+            0.0, 0.0, 1.0,
+        ];
+        assert_eq!(solver.decode_ctc_output(&output, 2, None), "O");
+
+        let restricted = solver.restrict_classes(&output, &allowed);
+        assert_eq!(solver.decode_ctc_output(&restricted, 2, None), "0");
+        // Probability mass is re-normalized over the digit and blank
+        assert!((restricted[1] - 0.75).abs() < 1e-6);
+        assert!((restricted[..3].iter().sum::<f32>() - 1.0).abs() < 1e-6);
+
+        // The request path applies the restriction before decoding
+        let (alignment, _) = solver.decode(&output, 2, &hints);
+        assert_eq!(alignment.iter().map(|e| e.class).collect::<Vec<_>>(), vec![1]);
+        let (alignment, _) = solver.decode(&output, 2, &SolveHints::default());
+        assert_eq!(alignment.iter().map(|e| e.class).collect::<Vec<_>>(), vec![0]);
+    }
+
+    #[tokio::test]
+    async fn test_processed_image_on_request() {
+        let solver = test_solver(CnnSolver::DEFAULT_CHARSET);
+        let image = DynamicImage::new_rgb8(120, 40);
+
+        let result = solver.solve(&image, None, &SolveHints::default()).await.unwrap();
+        assert!(result.processed_image.is_none());
+
+        let hints = SolveHints { include_processed_image: true, ..Default::default() };
+        let processed = solver.solve(&image, None, &hints).await.unwrap().processed_image.unwrap();
+        // The image the model saw: resized to its input and grayscale
+        assert_eq!((processed.width(), processed.height()), (200, 50));
+        assert!(processed.as_luma8().is_some());
+    }
+
+    #[test]
+    fn test_beam_search_top_k() {
+        let solver = test_solver("AB");
+
+        // A=0, B=1, blank=2
+        let output = vec![
+            0.6, 0.4, 0.0,
+            0.0, 0.0, 1.0,
+        ];
+
+        let candidates = solver.beam_search_ctc(&output, 2, Some(2));
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(candidates[0].text, "A");
+        assert!((candidates[0].confidence - 0.6).abs() < 1e-6);
+        assert_eq!(candidates[1].text, "B");
+        assert!((candidates[1].confidence - 0.4).abs() < 1e-6);
+
+        // The beam width is capped however many candidates are asked for
+        assert_eq!(solver.beam_search_ctc(&output, 2, Some(usize::MAX)).len(), 2);
+
+        // A NaN from a broken model is ordered, not a panic
+        let broken = vec![
+            f32::NAN, 0.4, 0.0,
+            0.0, 0.0, 1.0,
+        ];
+        assert_eq!(solver.beam_search_ctc(&broken, 2, Some(2)).len(), 2);
+        solver.align_ctc_output(&broken, 2, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_top_k_candidates_from_beam_search() {
+        let solver = test_solver(CnnSolver::DEFAULT_CHARSET);
+        let image = DynamicImage::new_rgb8(120, 40);
+
+        let single = solver.solve(&image, None, &SolveHints::default()).await.unwrap();
+        assert_eq!(single.candidates, vec![Candidate { text: single.text.clone(), confidence: single.confidence }]);
+
+        let hints = SolveHints { top_k: Some(3), ..Default::default() };
+        let result = solver.solve(&image, None, &hints).await.unwrap();
+        assert_eq!(result.candidates.len(), 3);
+        assert_eq!(result.candidates[0].text, result.text);
+        assert!(result.candidates.windows(2).all(|w| w[0].confidence >= w[1].confidence));
+    }
+
+    #[test]
+    fn test_decoding_bounded_by_max_output_length() {
+        let solver = CnnSolver { max_output_length: 3, ..test_solver("AB") };
+
+        // A=0, B=1, blank=2: alternating A and B for 6 frames
+        let output: Vec<f32> = (0..6)
+            .flat_map(|t| if t % 2 == 0 { [1.0, 0.0, 0.0] } else { [0.0, 1.0, 0.0] })
+            .collect();
+
+        assert_eq!(solver.decode_ctc_output(&output, 6, None), "ABA");
+        let candidates = solver.beam_search_ctc(&output, 6, Some(1));
+        assert_eq!(candidates[0].text, "ABA");
+
+        let (text, _) = solver.mock_inference(&solver.charset, &[0.5; 4], 2, 2, Some(10));
+        assert_eq!(text.len(), 3);
+    }
+}
@@ -0,0 +1,274 @@
+//! HTTP Solver
+//!
+//! Delegates recognition to an external model-serving endpoint (e.g. a GPU box
+//! running the model outside this service). The image is preprocessed here like
+//! for the CNN solver, then POSTed as a normalized grayscale tensor to
+//! `models.http_url`; the endpoint answers with the recognized text.
+
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use crate::error::{CaptchaError, CaptchaResult};
+use crate::models::{Candidate, InputSize, PreprocessOptions, SolverCapabilities};
+use super::{CaptchaSolver, SolveHints, SolveResult};
+use super::preprocessor::ImagePreprocessor;
+
+/// Body POSTed to the inference endpoint
+#[derive(Debug, Serialize)]
+struct InferenceRequest {
+    /// Tensor shape, `[1, 1, height, width]`
+    shape: [u32; 4],
+    /// Row-major pixel intensities in `0.0..=1.0`
+    data: Vec<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expected_length: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_k: Option<usize>,
+}
+
+/// Body the inference endpoint answers with
+#[derive(Debug, Deserialize)]
+struct InferenceResponse {
+    text: String,
+    confidence: f32,
+    #[serde(default)]
+    candidates: Vec<Candidate>,
+}
+
+/// Captcha solver backed by a remote inference endpoint
+pub struct HttpSolver {
+    client: reqwest::Client,
+    url: String,
+    input_width: u32,
+    input_height: u32,
+    /// Stroke width preprocessing aims for when the request sets none (0 disables)
+    stroke_width_target: f32,
+}
+
+impl HttpSolver {
+    /// Create a solver POSTing to `url`, giving up on a request after `timeout`
+    pub fn new(url: &str, timeout: Duration, stroke_width_target: f32) -> CaptchaResult<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(|e| CaptchaError::ModelLoadError(format!("Failed to create HTTP client: {}", e)))?;
+
+        Ok(Self {
+            client,
+            url: url.to_string(),
+            input_width: 200,
+            input_height: 50,
+            stroke_width_target,
+        })
+    }
+
+    /// Request body for a preprocessed image
+    fn inference_request(&self, processed: &DynamicImage, hints: &SolveHints) -> InferenceRequest {
+        let gray = processed.to_luma8();
+        let (width, height) = gray.dimensions();
+
+        InferenceRequest {
+            shape: [1, 1, height, width],
+            data: gray.pixels().map(|p| (p.0[0] as f32) / 255.0).collect(),
+            expected_length: hints.expected_length,
+            top_k: hints.top_k,
+        }
+    }
+
+    /// POST the request and parse the answer
+    async fn infer(&self, request: &InferenceRequest) -> CaptchaResult<InferenceResponse> {
+        let response = self.client
+            .post(&self.url)
+            .json(request)
+            .send()
+            .await
+            .map_err(|e| self.upstream_error("Inference endpoint request failed", e))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(CaptchaError::ProcessingError(format!(
+                "Inference endpoint returned {}: {}",
+                status,
+                body.chars().take(200).collect::<String>()
+            )));
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| self.upstream_error("Invalid inference endpoint response", e))
+    }
+
+    /// A timed-out request is reported as a solver timeout, any other failure as a processing error
+    fn upstream_error(&self, context: &str, e: reqwest::Error) -> CaptchaError {
+        if e.is_timeout() {
+            CaptchaError::Timeout(self.name().to_string())
+        } else {
+            CaptchaError::ProcessingError(format!("{}: {}", context, e))
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl CaptchaSolver for HttpSolver {
+    async fn solve(
+        &self,
+        image: &DynamicImage,
+        options: Option<&PreprocessOptions>,
+        hints: &SolveHints,
+    ) -> CaptchaResult<SolveResult> {
+        let preprocess_start = Instant::now();
+        let mut preprocess_opts = ImagePreprocessor::with_stroke_width(
+            options.cloned().unwrap_or_else(|| PreprocessOptions {
+                grayscale: Some(true),
+                denoise: Some(true),
+                resize_width: Some(self.input_width),
+                resize_height: Some(self.input_height),
+                ..Default::default()
+            }),
+            self.stroke_width_target,
+        );
+        // The remote model expects a fixed input size even when the caller's options omit it
+        preprocess_opts.resize_width.get_or_insert(self.input_width);
+        preprocess_opts.resize_height.get_or_insert(self.input_height);
+
+        let processed = ImagePreprocessor::preprocess(image, &preprocess_opts)?;
+        let preprocess_ms = preprocess_start.elapsed().as_millis() as u64;
+
+        let inference_start = Instant::now();
+        let response = self.infer(&self.inference_request(&processed, hints)).await?;
+        let inference_ms = inference_start.elapsed().as_millis() as u64;
+
+        let candidates = if response.candidates.is_empty() {
+            vec![Candidate { text: response.text.clone(), confidence: response.confidence }]
+        } else {
+            response.candidates
+        };
+
+        Ok(SolveResult {
+            text: response.text,
+            confidence: response.confidence.clamp(0.0, 1.0),
+            solver_name: self.name().to_string(),
+            length_mismatch: None,
+            preprocess_ms,
+            inference_ms,
+            candidates,
+            preprocess_variant: None,
+            processed_image: hints.include_processed_image.then_some(processed),
+            char_positions: None,
+        })
+    }
+
+    fn name(&self) -> &str {
+        "http"
+    }
+
+    fn is_ready(&self) -> bool {
+        true
+    }
+
+    fn capabilities(&self) -> SolverCapabilities {
+        SolverCapabilities {
+            input_size: Some(InputSize { width: self.input_width, height: self.input_height }),
+            // Both hints are forwarded to the endpoint
+            top_k: true,
+            expected_length_hint: true,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serve one connection answering with `status` and `body` after `delay`, returning the URL
+    async fn serve_once(status: &'static str, body: &'static str, delay: Duration) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/infer", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 1 << 20];
+            let _ = socket.read(&mut buf).await;
+            tokio::time::sleep(delay).await;
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status, body.len(), body
+            );
+            let _ = socket.write_all(response.as_bytes()).await;
+        });
+
+        url
+    }
+
+    fn image() -> DynamicImage {
+        DynamicImage::ImageLuma8(image::GrayImage::from_pixel(40, 20, image::Luma([200])))
+    }
+
+    #[test]
+    fn test_inference_request_tensor() {
+        let solver = HttpSolver::new("http://unused", Duration::from_secs(1), 0.0).unwrap();
+        let processed = DynamicImage::ImageLuma8(image::GrayImage::from_pixel(3, 2, image::Luma([255])));
+        let hints = SolveHints { expected_length: Some(5), ..Default::default() };
+
+        let request = solver.inference_request(&processed, &hints);
+
+        assert_eq!(request.shape, [1, 1, 2, 3]);
+        assert_eq!(request.data, vec![1.0; 6]);
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["expected_length"], 5);
+        assert!(json.get("top_k").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_solve_parses_response() {
+        let url = serve_once(
+            "200 OK",
+            r#"{"text":"AB12","confidence":0.9,"candidates":[{"text":"AB12","confidence":0.9},{"text":"A812","confidence":0.4}]}"#,
+            Duration::ZERO,
+        ).await;
+        let solver = HttpSolver::new(&url, Duration::from_secs(5), 0.0).unwrap();
+
+        let result = solver.solve(&image(), None, &SolveHints::default()).await.unwrap();
+
+        assert_eq!(result.text, "AB12");
+        assert!((result.confidence - 0.9).abs() < 1e-6);
+        assert_eq!(result.solver_name, "http");
+        assert_eq!(result.candidates.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_upstream_error_is_processing_error() {
+        let url = serve_once("503 Service Unavailable", r#"{"error":"model loading"}"#, Duration::ZERO).await;
+        let solver = HttpSolver::new(&url, Duration::from_secs(5), 0.0).unwrap();
+
+        let err = solver.solve(&image(), None, &SolveHints::default()).await.unwrap_err();
+
+        assert!(matches!(&err, CaptchaError::ProcessingError(msg) if msg.contains("503") && msg.contains("model loading")));
+    }
+
+    #[tokio::test]
+    async fn test_malformed_response_is_processing_error() {
+        let url = serve_once("200 OK", r#"{"label":"AB12"}"#, Duration::ZERO).await;
+        let solver = HttpSolver::new(&url, Duration::from_secs(5), 0.0).unwrap();
+
+        let err = solver.solve(&image(), None, &SolveHints::default()).await.unwrap_err();
+
+        assert!(matches!(err, CaptchaError::ProcessingError(_)));
+    }
+
+    #[tokio::test]
+    async fn test_slow_endpoint_times_out() {
+        let url = serve_once("200 OK", r#"{"text":"AB12","confidence":0.9}"#, Duration::from_secs(2)).await;
+        let solver = HttpSolver::new(&url, Duration::from_millis(100), 0.0).unwrap();
+
+        let err = solver.solve(&image(), None, &SolveHints::default()).await.unwrap_err();
+
+        assert!(matches!(err, CaptchaError::Timeout(ref solver) if solver == "http"));
+    }
+}
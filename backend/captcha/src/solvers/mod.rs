@@ -5,30 +5,83 @@
 //! - CNN: Deep learning based recognition
 //! - Ensemble: Combines multiple models for better accuracy
 
+pub mod calibration;
 pub mod ocr;
 pub mod cnn;
+pub mod http;
 pub mod preprocessor;
+pub mod postprocess;
+pub mod pool;
+#[cfg(test)]
+mod regression;
+#[cfg(test)]
+mod preprocessor_scalar;
 
+use std::future::Future;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::{RwLock, Semaphore};
 use std::collections::HashMap;
 use image::DynamicImage;
+use rand::Rng;
+use tracing::Instrument;
 
-use crate::config::ModelsSettings;
+use crate::config::{ModelsSettings, ProcessingSettings};
 use crate::error::{CaptchaError, CaptchaResult};
-use crate::models::{SolveResponse, PreprocessOptions, CaptchaModel};
+use crate::validation::Validator;
+use crate::models::{
+    SolveResponse, SolveRequest, PreprocessOptions, CaptchaModel, NormalizeMode, Candidate, CharPosition,
+    SolveStrategy, AutoPreprocessOptions, PreprocessVariant, SolverInfo, CaseMode, SOLVER_NAMES,
+    SolverCapabilities, SolverSchema, Difficulty, WhitespaceMode, DefaultStrategy, Probability,
+};
+use calibration::CalibrationCurve;
+use pool::{InferencePool, SolvePermit};
+use postprocess::TextNormalizer;
+use preprocessor::{DifficultyThresholds, ImagePreprocessor};
 
 /// Trait for captcha solvers
 #[async_trait::async_trait]
 pub trait CaptchaSolver: Send + Sync {
     /// Solve a captcha image
-    async fn solve(&self, image: &DynamicImage, options: Option<&PreprocessOptions>) -> CaptchaResult<SolveResult>;
+    async fn solve(
+        &self,
+        image: &DynamicImage,
+        options: Option<&PreprocessOptions>,
+        hints: &SolveHints,
+    ) -> CaptchaResult<SolveResult>;
     
     /// Get solver name
     fn name(&self) -> &str;
     
     /// Check if solver is ready
     fn is_ready(&self) -> bool;
+
+    /// Check if solver is serving mock output instead of real inference
+    fn is_mock(&self) -> bool {
+        false
+    }
+
+    /// Model file the solver loads, checked against the checksum recorded at upload
+    fn model_file(&self) -> Option<PathBuf> {
+        None
+    }
+
+    /// Whether the solver's model failed to load at startup and has not loaded since
+    fn needs_model(&self) -> bool {
+        false
+    }
+
+    /// Try again to load a model that failed to load at startup. Blocking.
+    fn reload_model(&self) -> CaptchaResult<()> {
+        Ok(())
+    }
+
+    /// What the solver accepts and returns, reported by the solver schema endpoint
+    fn capabilities(&self) -> SolverCapabilities {
+        SolverCapabilities::default()
+    }
 }
 
 /// Result from a solver
@@ -37,23 +90,191 @@ pub struct SolveResult {
     pub text: String,
     pub confidence: f32,
     pub solver_name: String,
+    /// Set when an expected length was given: whether the raw output length differed
+    pub length_mismatch: Option<bool>,
+    /// Time spent preprocessing the image
+    pub preprocess_ms: u64,
+    /// Time spent in the recognition model
+    pub inference_ms: u64,
+    /// Alternative solutions ordered by confidence (populated when `top_k` is requested)
+    pub candidates: Vec<Candidate>,
+    /// Preprocessing variant that produced this result (`auto_preprocess` strategy only)
+    pub preprocess_variant: Option<PreprocessVariant>,
+    /// Final preprocessed image fed to the recognizer (`include_processed_image` only)
+    pub processed_image: Option<DynamicImage>,
+    /// Approximate character positions in the input image (`include_char_positions` only)
+    pub char_positions: Option<Vec<CharPosition>>,
+}
+
+/// Characters recognized under the `digits` strategy
+pub const DIGIT_CHARSET: &str = "0123456789";
+
+/// Most candidate solutions a request may ask for with `top_k`
+pub const MAX_TOP_K: usize = 10;
+
+/// Per-request hints about the captcha being solved
+#[derive(Debug, Clone, Default)]
+pub struct SolveHints {
+    /// Known number of characters in the captcha
+    pub expected_length: Option<usize>,
+    pub normalize: Option<NormalizeMode>,
+    /// Number of candidate solutions to return
+    pub top_k: Option<usize>,
+    pub strategy: SolveStrategy,
+    pub auto_preprocess: Option<AutoPreprocessOptions>,
+    /// Tesseract language spec for the OCR solver
+    pub language: Option<String>,
+    /// Letter case for `alnum` normalization, overriding the solver default
+    pub case: Option<CaseMode>,
+    /// Whitespace handling, overriding the service default
+    pub whitespace: Option<WhitespaceMode>,
+    /// Characters allowed in the answer, checked after normalization
+    pub charset: Option<String>,
+    /// Keep the preprocessed image in the result
+    pub include_processed_image: bool,
+    /// Locate each character in the result, for solvers that can
+    pub include_char_positions: bool,
+    /// Fail instead of retrying with another solver when the chosen one errors
+    pub disable_fallback: bool,
+    /// Skip post-processing and keep the solver's text unchanged
+    pub raw_output: bool,
+}
+
+impl SolveHints {
+    /// Collect hints from a solve request
+    pub fn from_request(request: &SolveRequest) -> Self {
+        Self {
+            expected_length: request.expected_length,
+            normalize: request.normalize,
+            top_k: request.top_k,
+            strategy: request.strategy.unwrap_or_default(),
+            auto_preprocess: request.auto_preprocess.clone(),
+            language: request.language.clone(),
+            case: request.case,
+            whitespace: request.whitespace,
+            charset: request.charset.clone(),
+            include_processed_image: request.include_processed_image,
+            include_char_positions: request.include_char_positions,
+            disable_fallback: request.fallback == Some(false),
+            raw_output: request.raw_output,
+        }
+    }
+
+    /// Whether recognizers should only consider [`DIGIT_CHARSET`]
+    pub fn digits_only(&self) -> bool {
+        self.strategy == SolveStrategy::Digits
+    }
+
+    /// Reject hints that no solver can honor, given the longest answer solvers may give
+    pub fn validate(&self, max_output_length: usize) -> CaptchaResult<()> {
+        let mut v = Validator::new();
+        self.check(&mut v, max_output_length);
+        v.finish()
+    }
+
+    /// Record every invalid hint in `v`, under the name of its solve request field
+    pub fn check(&self, v: &mut Validator, max_output_length: usize) {
+        if let Some(expected) = self.expected_length {
+            v.check(
+                expected <= max_output_length,
+                "expected_length",
+                format!("must be at most the maximum output length {}", max_output_length),
+            );
+        }
+        if let Some(top_k) = self.top_k {
+            v.check(
+                (1..=MAX_TOP_K).contains(&top_k),
+                "top_k",
+                format!("must be between 1 and {}", MAX_TOP_K),
+            );
+        }
+        if let Some(language) = &self.language {
+            match ocr::validate_language(language, &ocr::tessdata_path()) {
+                Ok(()) => {}
+                Err(CaptchaError::BadRequest(reason)) => v.add("language", reason),
+                Err(e) => v.add("language", e.to_string()),
+            }
+        }
+        v.check(self.charset.as_deref() != Some(""), "charset", "must not be empty");
+
+        if let Some(auto) = &self.auto_preprocess {
+            v.nested("auto_preprocess", |v| {
+                for (i, name) in auto.variants.iter().flatten().enumerate() {
+                    v.check(
+                        ImagePreprocessor::variant(name).is_some(),
+                        &format!("variants[{}]", i),
+                        format!("unknown preprocessing variant '{}'", name),
+                    );
+                }
+                if let Some(target) = auto.confidence_target {
+                    v.check((0.0..=1.0).contains(&target), "confidence_target", "must be between 0 and 1");
+                }
+            });
+        }
+    }
 }
 
 /// Manages multiple captcha solvers
 pub struct SolverManager {
     solvers: HashMap<String, Arc<dyn CaptchaSolver>>,
     default_solver: String,
+    /// How requests that name no solver are solved
+    default_strategy: DefaultStrategy,
+    /// Confidence the `fallback` strategy accepts without trying the next solver
+    fallback_min_confidence: f32,
+    /// Most characters an answer may have, bounding pathological model output
+    max_output_length: usize,
     models_path: String,
+    normalizer: TextNormalizer,
+    /// Letter case for `alnum` normalization when the request doesn't set one
+    output_case: CaseMode,
+    /// Per-solver overrides of `output_case`
+    solver_case: HashMap<String, CaseMode>,
+    /// Whitespace handling when the request sets none
+    whitespace: WhitespaceMode,
+    retry_attempts: u32,
+    retry_base_ms: u64,
+    /// Bounds concurrent inference to avoid CPU thrash under burst load
+    solve_permits: Arc<Semaphore>,
+    /// Threads the solvers run on, apart from the async runtime and its blocking pool
+    inference: InferencePool,
+    /// How long a request may wait for a solve permit, and the default solve timeout
+    permit_timeout: Duration,
+    /// Solve timeouts overriding `permit_timeout` for specific solvers
+    solver_timeouts: HashMap<String, Duration>,
+    /// Requests currently waiting for a permit
+    queued: AtomicUsize,
+    max_queue_depth: usize,
+    overload_retry_after: u64,
+    /// Default variants and confidence target for the `auto_preprocess` strategy
+    auto_variants: Vec<String>,
+    auto_target: f32,
+    /// Difficulty limits and the easy, medium and hard presets of the `by_difficulty` strategy
+    difficulty_thresholds: DifficultyThresholds,
+    difficulty_presets: [String; 3],
+    /// Whether the ensemble ranks results by accuracy-weighted confidence
+    accuracy_weighting: bool,
+    /// Historical accuracy per solver, refreshed from `captcha_models`
+    ensemble_weights: RwLock<HashMap<String, f32>>,
+    /// Temperatures from `models.temperatures`, the fallback for models without calibration metadata
+    configured_temperatures: HashMap<String, f32>,
+    /// Confidence temperature per solver, refreshed from `captcha_models` metadata
+    temperatures: RwLock<HashMap<String, f32>>,
+    /// Solvers whose model file failed checksum verification, with the reason
+    checksum_failures: std::sync::Mutex<HashMap<String, String>>,
+    /// Confidence → P(correct) per solver, refreshed from labeled logs
+    calibration: RwLock<HashMap<String, CalibrationCurve>>,
+    calibration_min_samples: usize,
 }
 
 impl SolverManager {
     /// Create a new solver manager
-    pub async fn new(config: &ModelsSettings) -> CaptchaResult<Self> {
+    pub async fn new(config: &ModelsSettings, processing: &ProcessingSettings) -> CaptchaResult<Self> {
         let mut solvers: HashMap<String, Arc<dyn CaptchaSolver>> = HashMap::new();
 
         // Initialize OCR solver if enabled
         if config.ocr_enabled {
-            match ocr::OcrSolver::new(&config.path).await {
+            match ocr::OcrSolver::new(&config.path, processing.stroke_width_target).await {
                 Ok(solver) => {
                     solvers.insert("ocr".to_string(), Arc::new(solver));
                     tracing::info!("OCR solver initialized");
@@ -66,7 +287,7 @@ impl SolverManager {
 
         // Initialize CNN solver if enabled
         if config.cnn_enabled {
-            match cnn::CnnSolver::new(&config.path).await {
+            match cnn::CnnSolver::new(&config.path, processing.stroke_width_target, config.max_output_length).await {
                 Ok(solver) => {
                     solvers.insert("cnn".to_string(), Arc::new(solver));
                     tracing::info!("CNN solver initialized");
@@ -77,11 +298,27 @@ impl SolverManager {
             }
         }
 
+        // Initialize HTTP solver if an inference endpoint is configured
+        if let Some(url) = config.http_url.as_deref().filter(|url| !url.is_empty()) {
+            let timeout = config.timeouts.get("http").copied().unwrap_or(processing.timeout_seconds);
+            match http::HttpSolver::new(url, Duration::from_secs(timeout), processing.stroke_width_target) {
+                Ok(solver) => {
+                    solvers.insert("http".to_string(), Arc::new(solver));
+                    tracing::info!("HTTP solver initialized for {}", url);
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to initialize HTTP solver: {}", e);
+                }
+            }
+        }
+
         // Determine default solver
         let default_solver = if solvers.contains_key("cnn") {
             "cnn".to_string()
         } else if solvers.contains_key("ocr") {
             "ocr".to_string()
+        } else if solvers.contains_key("http") {
+            "http".to_string()
         } else {
             return Err(CaptchaError::ModelLoadError(
                 "No solvers available".to_string()
@@ -91,7 +328,37 @@ impl SolverManager {
         Ok(Self {
             solvers,
             default_solver,
+            default_strategy: config.default_strategy,
+            fallback_min_confidence: config.fallback_min_confidence,
+            max_output_length: config.max_output_length,
             models_path: config.path.clone(),
+            normalizer: TextNormalizer::from_spec(&processing.digit_substitutions),
+            output_case: processing.output_case,
+            solver_case: config.case.clone(),
+            whitespace: processing.whitespace,
+            retry_attempts: processing.solver_retry_attempts,
+            retry_base_ms: processing.solver_retry_base_ms,
+            solve_permits: Arc::new(Semaphore::new(processing.solve_concurrency())),
+            inference: InferencePool::new(processing.inference_threads())?,
+            permit_timeout: Duration::from_secs(processing.timeout_seconds),
+            solver_timeouts: config.timeouts
+                .iter()
+                .map(|(name, secs)| (name.clone(), Duration::from_secs(*secs)))
+                .collect(),
+            queued: AtomicUsize::new(0),
+            max_queue_depth: processing.max_queue_depth,
+            overload_retry_after: processing.overload_retry_after_seconds,
+            auto_variants: parse_variants(&processing.auto_preprocess_variants),
+            auto_target: processing.auto_preprocess_target,
+            difficulty_thresholds: processing.difficulty_thresholds(),
+            difficulty_presets: parse_difficulty_presets(&processing.difficulty_presets),
+            accuracy_weighting: processing.ensemble_accuracy_weighting,
+            ensemble_weights: RwLock::new(HashMap::new()),
+            configured_temperatures: valid_temperatures(&config.temperatures),
+            temperatures: RwLock::new(valid_temperatures(&config.temperatures)),
+            checksum_failures: std::sync::Mutex::new(HashMap::new()),
+            calibration: RwLock::new(HashMap::new()),
+            calibration_min_samples: processing.calibration_min_samples,
         })
     }
 
@@ -100,25 +367,290 @@ impl SolverManager {
         self.solvers.len()
     }
 
-    /// Solve a captcha using the specified or default solver
+    /// Solve a captcha using the specified solver, or the configured default strategy
     pub async fn solve(
         &self,
         image: &DynamicImage,
         model_name: Option<&str>,
         options: Option<&PreprocessOptions>,
+        hints: &SolveHints,
     ) -> CaptchaResult<SolveResult> {
-        let solver_name = model_name.unwrap_or(&self.default_solver);
+        hints.validate(self.max_output_length)?;
+        if let Some(options) = options {
+            ImagePreprocessor::validate(options)?;
+        }
+
+        match (model_name, self.default_strategy) {
+            (Some(solver_name), _) => self.solve_single(solver_name, image, options, hints).await,
+            (None, DefaultStrategy::Single) => {
+                self.solve_single(&self.default_solver, image, options, hints).await
+            }
+            (None, DefaultStrategy::Ensemble) => self.solve_ensemble(image, options, hints).await,
+            (None, DefaultStrategy::Fallback) => self.solve_fallback_chain(image, options, hints).await,
+        }
+    }
 
+    /// Solve with one solver, retrying with another one when it errors mid-solve
+    async fn solve_single(
+        &self,
+        solver_name: &str,
+        image: &DynamicImage,
+        options: Option<&PreprocessOptions>,
+        hints: &SolveHints,
+    ) -> CaptchaResult<SolveResult> {
         let solver = self.solvers.get(solver_name)
-            .ok_or_else(|| CaptchaError::ModelNotFound(solver_name.to_string()))?;
+            .ok_or_else(|| self.missing_solver_error(solver_name))?;
 
+        if let Some(reason) = self.checksum_failures.lock().unwrap().get(solver_name) {
+            return Err(CaptchaError::ModelLoadError(
+                format!("Solver {} is unavailable: {}", solver_name, reason)
+            ));
+        }
         if !solver.is_ready() {
             return Err(CaptchaError::ModelLoadError(
                 format!("Solver {} is not ready", solver_name)
             ));
         }
 
-        solver.solve(image, options).await
+        let permit = self.acquire_permit(solver_name).await?;
+
+        match self.solve_with(&permit, solver_name, solver, image, options, hints).await {
+            Err(CaptchaError::ProcessingError(e)) if !hints.disable_fallback => {
+                let Some((fallback_name, fallback)) = self.fallback_for(solver_name) else {
+                    return Err(CaptchaError::ProcessingError(e));
+                };
+                tracing::warn!("Solver {} failed ({}), falling back to {}", solver_name, e, fallback_name);
+                self.solve_with(&permit, fallback_name, fallback, image, options, hints).await
+            }
+            result => result,
+        }
+    }
+
+    /// Run one solver with the requested strategy under its timeout
+    async fn solve_with(
+        &self,
+        permit: &SolvePermit,
+        solver_name: &str,
+        solver: &Arc<dyn CaptchaSolver>,
+        image: &DynamicImage,
+        options: Option<&PreprocessOptions>,
+        hints: &SolveHints,
+    ) -> CaptchaResult<SolveResult> {
+        self.with_timeout(solver_name, async {
+            match hints.strategy {
+                SolveStrategy::Single | SolveStrategy::Digits => {
+                    let result = self.run_solver(permit, solver, image, options, hints).await?;
+                    Ok(self.finalize(result, hints))
+                }
+                SolveStrategy::AutoPreprocess => {
+                    self.solve_auto_preprocess(permit, solver, image, options, hints).await
+                }
+                SolveStrategy::ByDifficulty => {
+                    self.solve_by_difficulty(permit, solver, image, options, hints).await
+                }
+            }
+        }).await
+    }
+
+    /// Try the available solvers one after another, default solver first, returning the
+    /// first readable answer of at least `fallback_min_confidence`, else the most confident one
+    async fn solve_fallback_chain(
+        &self,
+        image: &DynamicImage,
+        options: Option<&PreprocessOptions>,
+        hints: &SolveHints,
+    ) -> CaptchaResult<SolveResult> {
+        let mut chain: Vec<&str> = self.solvers
+            .iter()
+            .filter(|(name, solver)| self.is_available(name, solver.as_ref()))
+            .map(|(name, _)| name.as_str())
+            .collect();
+        chain.sort_by_key(|name| (*name != self.default_solver, *name));
+        if chain.is_empty() {
+            return Err(self.no_ready_solvers_error());
+        }
+
+        // The chain is the fallback: each solver answers on its own
+        let hints = SolveHints { disable_fallback: true, ..hints.clone() };
+        let mut best: Option<SolveResult> = None;
+        let mut first_error = None;
+
+        for name in chain {
+            match self.solve_single(name, image, options, &hints).await {
+                Ok(result) if !result.text.is_empty() && result.confidence >= self.fallback_min_confidence => {
+                    return Ok(result);
+                }
+                Ok(result) => {
+                    tracing::debug!("Solver {} answered with confidence {}, trying the next one", name, result.confidence);
+                    if best.as_ref().is_none_or(|b| result.confidence > b.confidence) {
+                        best = Some(result);
+                    }
+                }
+                Err(e @ CaptchaError::Overloaded(_)) => return Err(e),
+                Err(e) => {
+                    tracing::warn!("Solver {} failed: {}", name, e);
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        match (best, first_error) {
+            (Some(result), _) => Ok(result),
+            (None, Some(e)) => Err(e),
+            (None, None) => Err(CaptchaError::ProcessingError("All solvers failed".to_string())),
+        }
+    }
+
+    /// Ready solver to retry with when `failed` errors mid-solve:
+    /// the default solver, else the first other ready solver by name
+    fn fallback_for(&self, failed: &str) -> Option<(&str, &Arc<dyn CaptchaSolver>)> {
+        let mut candidates: Vec<(&str, &Arc<dyn CaptchaSolver>)> = self.solvers
+            .iter()
+            .filter(|(name, solver)| name.as_str() != failed && self.is_available(name, solver.as_ref()))
+            .map(|(name, solver)| (name.as_str(), solver))
+            .collect();
+        candidates.sort_by_key(|(name, _)| (*name != self.default_solver, *name));
+        candidates.into_iter().next()
+    }
+
+    /// Error for a solver name that isn't loaded: a known solver without an
+    /// instance in this build is reported with the loaded solvers, anything else is not found
+    fn missing_solver_error(&self, solver_name: &str) -> CaptchaError {
+        if !SOLVER_NAMES.contains(&solver_name) {
+            return CaptchaError::ModelNotFound(solver_name.to_string());
+        }
+
+        let mut available: Vec<&str> = self.solvers.keys().map(String::as_str).collect();
+        available.sort_unstable();
+        CaptchaError::UnsupportedModelType(format!(
+            "Model type '{}' is not supported by this build; available: {}",
+            solver_name, available.join(", ")
+        ))
+    }
+
+    /// Error for a solve needing at least one ready solver when none is,
+    /// with the reason each loaded solver is unavailable
+    fn no_ready_solvers_error(&self) -> CaptchaError {
+        let failures = self.checksum_failures.lock().unwrap();
+        let mut reasons: Vec<String> = self.solvers
+            .keys()
+            .map(|name| match failures.get(name) {
+                Some(reason) => format!("{} ({})", name, reason),
+                None => format!("{} (not ready)", name),
+            })
+            .collect();
+        reasons.sort_unstable();
+
+        CaptchaError::NoReadySolvers(if reasons.is_empty() {
+            "no solvers are loaded".to_string()
+        } else {
+            format!("no loaded solver is ready: {}", reasons.join(", "))
+        })
+    }
+
+    /// Solve timeout for a solver: its configured override, else the global timeout
+    fn timeout_for(&self, solver_name: &str) -> Duration {
+        self.solver_timeouts.get(solver_name).copied().unwrap_or(self.permit_timeout)
+    }
+
+    /// Run a solve, failing with `Timeout` tagged with the solver once its timeout elapses
+    async fn with_timeout<T>(
+        &self,
+        solver_name: &str,
+        solve: impl Future<Output = CaptchaResult<T>>,
+    ) -> CaptchaResult<T> {
+        let limit = self.timeout_for(solver_name);
+        match tokio::time::timeout(limit, solve).await {
+            Ok(result) => result,
+            Err(_) => {
+                tracing::warn!("Solver {} timed out after {:?}", solver_name, limit);
+                Err(CaptchaError::Timeout(solver_name.to_string()))
+            }
+        }
+    }
+
+    /// Try preprocessing variants in order, returning the first result that reaches
+    /// the confidence target, or the most confident one if none does.
+    /// The caller's resize and color filter options apply to every variant.
+    async fn solve_auto_preprocess(
+        &self,
+        permit: &SolvePermit,
+        solver: &Arc<dyn CaptchaSolver>,
+        image: &DynamicImage,
+        options: Option<&PreprocessOptions>,
+        hints: &SolveHints,
+    ) -> CaptchaResult<SolveResult> {
+        let overrides = hints.auto_preprocess.clone().unwrap_or_default();
+        let names = overrides.variants.unwrap_or_else(|| self.auto_variants.clone());
+        let target = overrides.confidence_target.unwrap_or(self.auto_target);
+
+        if names.is_empty() {
+            return Err(CaptchaError::BadRequest("No preprocessing variants to try".to_string()));
+        }
+
+        let variants = names
+            .into_iter()
+            .map(|name| match ImagePreprocessor::variant(&name) {
+                Some(variant) => Ok((name, variant)),
+                None => Err(CaptchaError::BadRequest(format!("Unknown preprocessing variant '{}'", name))),
+            })
+            .collect::<CaptchaResult<Vec<_>>>()?;
+
+        let mut best: Option<SolveResult> = None;
+        for (name, variant) in variants {
+            let variant_options = ImagePreprocessor::with_variant(options, &variant);
+
+            let mut result = match self.run_solver(permit, solver, image, Some(&variant_options), hints).await {
+                Ok(result) => self.finalize(result, hints),
+                Err(e) if e.is_transient() => {
+                    tracing::warn!("Preprocessing variant {} failed: {}", name, e);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            result.preprocess_variant = Some(PreprocessVariant { name, options: variant_options, difficulty: None });
+
+            let reached_target = result.confidence >= target;
+            if best.as_ref().map_or(true, |b| result.confidence > b.confidence) {
+                best = Some(result);
+            }
+            if reached_target {
+                break;
+            }
+        }
+
+        best.ok_or_else(|| CaptchaError::ProcessingError("All preprocessing variants failed".to_string()))
+    }
+
+    /// Solve once with the preprocessing preset for the image's estimated difficulty,
+    /// applied on top of the caller's options like an `auto_preprocess` variant
+    async fn solve_by_difficulty(
+        &self,
+        permit: &SolvePermit,
+        solver: &Arc<dyn CaptchaSolver>,
+        image: &DynamicImage,
+        options: Option<&PreprocessOptions>,
+        hints: &SolveHints,
+    ) -> CaptchaResult<SolveResult> {
+        let estimate = ImagePreprocessor::estimate_difficulty(image, &self.difficulty_thresholds)?;
+        let name = match estimate.level {
+            Difficulty::Easy => &self.difficulty_presets[0],
+            Difficulty::Medium => &self.difficulty_presets[1],
+            Difficulty::Hard => &self.difficulty_presets[2],
+        };
+        let variant = ImagePreprocessor::variant(name)
+            .ok_or_else(|| CaptchaError::BadRequest(format!("Unknown preprocessing variant '{}'", name)))?;
+        let variant_options = ImagePreprocessor::with_variant(options, &variant);
+        tracing::debug!(?estimate, preset = %name, "Selected preprocessing preset by difficulty");
+
+        let result = self.run_solver(permit, solver, image, Some(&variant_options), hints).await?;
+        let mut result = self.finalize(result, hints);
+        result.preprocess_variant = Some(PreprocessVariant {
+            name: name.clone(),
+            options: variant_options,
+            difficulty: Some(estimate),
+        });
+        Ok(result)
     }
 
     /// Solve using all available solvers and return the best result
@@ -126,16 +658,29 @@ impl SolverManager {
         &self,
         image: &DynamicImage,
         options: Option<&PreprocessOptions>,
+        hints: &SolveHints,
     ) -> CaptchaResult<SolveResult> {
+        hints.validate(self.max_output_length)?;
+        if let Some(options) = options {
+            ImagePreprocessor::validate(options)?;
+        }
+        let available: Vec<(&String, &Arc<dyn CaptchaSolver>)> = self.solvers
+            .iter()
+            .filter(|(name, solver)| self.is_available(name, solver.as_ref()))
+            .collect();
+        // Nothing to run is a deployment problem, not a failed solve
+        if available.is_empty() {
+            return Err(self.no_ready_solvers_error());
+        }
+
         let mut results: Vec<SolveResult> = Vec::new();
+        let permit = self.acquire_permit("ensemble").await?;
 
-        for (name, solver) in &self.solvers {
-            if solver.is_ready() {
-                match solver.solve(image, options).await {
-                    Ok(result) => results.push(result),
-                    Err(e) => {
-                        tracing::warn!("Solver {} failed: {}", name, e);
-                    }
+        for (name, solver) in available {
+            match self.solve_with_retry(&permit, solver, image, options, hints).await {
+                Ok(result) => results.push(result),
+                Err(e) => {
+                    tracing::warn!("Solver {} failed: {}", name, e);
                 }
             }
         }
@@ -146,10 +691,197 @@ impl SolverManager {
             ));
         }
 
-        // Return result with highest confidence
-        results.sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap());
-        
-        Ok(results.remove(0))
+        // An answer that is blank after post-processing is a failure, however confident
+        results.retain(|r| {
+            let readable = !self.normalize_text(&r.text, &r.solver_name, hints).is_empty();
+            if !readable {
+                tracing::warn!("Solver {} returned no readable text", r.solver_name);
+            }
+            readable
+        });
+        if results.is_empty() {
+            return Err(CaptchaError::ProcessingError("no readable text".to_string()));
+        }
+
+        // Return result with highest (accuracy-weighted) confidence
+        let weights = self.ensemble_weights.read().await;
+        let score = |r: &SolveResult| r.confidence * ensemble_weight(&weights, &r.solver_name);
+        results.sort_by(|a, b| score(b).total_cmp(&score(a)));
+        drop(weights);
+
+        // Each solver's output is one candidate
+        let candidates: Vec<Candidate> = results
+            .iter()
+            .map(|r| Candidate { text: r.text.clone(), confidence: r.confidence })
+            .collect();
+
+        let mut best = results.remove(0);
+        best.candidates = candidates;
+
+        Ok(self.finalize(best, hints))
+    }
+
+    /// Wait for a free solve slot for `solver_name`, giving up after the configured timeout.
+    /// Sheds load with `Overloaded` when the wait queue is already full.
+    async fn acquire_permit(&self, solver_name: &str) -> CaptchaResult<SolvePermit> {
+        if let Ok(permit) = Arc::clone(&self.solve_permits).try_acquire_owned() {
+            return Ok(Arc::new(permit));
+        }
+
+        // Held across the wait so the slot is released even if this future is dropped
+        let slot = QueueSlot::take(&self.queued);
+        if slot.waiting > self.max_queue_depth {
+            tracing::warn!("Solve queue full ({} waiting), shedding request", self.max_queue_depth);
+            return Err(CaptchaError::Overloaded(self.overload_retry_after));
+        }
+
+        let acquired = tokio::time::timeout(self.permit_timeout, Arc::clone(&self.solve_permits).acquire_owned()).await;
+        drop(slot);
+
+        match acquired {
+            Ok(Ok(permit)) => Ok(Arc::new(permit)),
+            Ok(Err(_)) => Err(CaptchaError::ProcessingError("Solver pool closed".to_string())),
+            Err(_) => {
+                tracing::warn!("Timed out waiting for a solve permit for {}", solver_name);
+                Err(CaptchaError::Timeout(solver_name.to_string()))
+            }
+        }
+    }
+
+    /// Run a solver under its timeout, retrying transient errors (including timeouts)
+    /// with exponential backoff and full jitter.
+    /// Permanent errors (e.g. `ModelNotFound`) are returned immediately.
+    async fn solve_with_retry(
+        &self,
+        permit: &SolvePermit,
+        solver: &Arc<dyn CaptchaSolver>,
+        image: &DynamicImage,
+        options: Option<&PreprocessOptions>,
+        hints: &SolveHints,
+    ) -> CaptchaResult<SolveResult> {
+        let mut attempt = 0;
+        loop {
+            match self.with_timeout(solver.name(), self.run_solver(permit, solver, image, options, hints)).await {
+                Err(e) if e.is_transient() && attempt < self.retry_attempts => {
+                    let delay = retry_delay_ms(self.retry_base_ms, attempt);
+                    attempt += 1;
+                    tracing::debug!(
+                        "Solver {} transient failure (attempt {}), retrying in {}ms: {}",
+                        solver.name(), attempt, delay, e
+                    );
+                    tokio::time::sleep(Duration::from_millis(delay)).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Run one solver and calibrate its confidences so they are comparable across solvers.
+    /// The inference job keeps a share of `permit` until it finishes, even past a timeout.
+    async fn run_solver(
+        &self,
+        permit: &SolvePermit,
+        solver: &Arc<dyn CaptchaSolver>,
+        image: &DynamicImage,
+        options: Option<&PreprocessOptions>,
+        hints: &SolveHints,
+    ) -> CaptchaResult<SolveResult> {
+        let mut result = self.inference
+            .solve(Arc::clone(solver), image.clone(), options.cloned(), hints.clone(), Arc::clone(permit))
+            .instrument(tracing::debug_span!("solver", name = solver.name()))
+            .await?;
+
+        if let Some(&temperature) = self.temperatures.read().await.get(&result.solver_name) {
+            result.confidence = temperature_scale(result.confidence, temperature);
+            for candidate in &mut result.candidates {
+                candidate.confidence = temperature_scale(candidate.confidence, temperature);
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Normalize solver output, drop characters outside the charset and reconcile it with
+    /// the expected length, if any.
+    /// Overlong results are truncated; any mismatch scales confidence by the length ratio.
+    /// Candidates are normalized, deduplicated and cut to `top_k`, or dropped when not requested.
+    /// Texts beyond `max_output_length` are cut to it, even for raw output.
+    fn finalize(&self, mut result: SolveResult, hints: &SolveHints) -> SolveResult {
+        let solver_name = result.solver_name.clone();
+        let normalize = |text: &str| self.bound_length(self.normalize_text(text, &solver_name, hints), &solver_name);
+        result.text = normalize(&result.text);
+        if let Some(positions) = result.char_positions.as_mut() {
+            positions.truncate(self.max_output_length);
+        }
+
+        match hints.top_k.filter(|&k| k > 0) {
+            Some(k) => {
+                let mut candidates: Vec<Candidate> = Vec::with_capacity(k.min(result.candidates.len()));
+                for candidate in std::mem::take(&mut result.candidates) {
+                    let text = normalize(&candidate.text);
+                    if candidates.len() < k && !candidates.iter().any(|c| c.text == text) {
+                        candidates.push(Candidate { text, confidence: candidate.confidence });
+                    }
+                }
+                if candidates.is_empty() {
+                    candidates.push(Candidate { text: result.text.clone(), confidence: result.confidence });
+                }
+                result.candidates = candidates;
+            }
+            None => result.candidates.clear(),
+        }
+
+        if let Some(expected) = hints.expected_length.filter(|&n| n > 0) {
+            let actual = result.text.chars().count();
+            let mismatch = actual != expected;
+
+            if mismatch {
+                let ratio = actual.min(expected) as f32 / actual.max(expected) as f32;
+                result.confidence *= ratio;
+            }
+            if actual > expected && !hints.raw_output {
+                result.text = result.text.chars().take(expected).collect();
+            }
+
+            result.length_mismatch = Some(mismatch);
+        }
+
+        result
+    }
+
+    /// `text` cut to `max_output_length` characters, warning when it was longer
+    fn bound_length(&self, text: String, solver_name: &str) -> String {
+        let length = text.chars().count();
+        if length <= self.max_output_length {
+            return text;
+        }
+
+        tracing::warn!(
+            "Solver {} returned {} characters, truncating to the maximum output length {}",
+            solver_name, length, self.max_output_length
+        );
+        text.chars().take(self.max_output_length).collect()
+    }
+
+    /// Post-process one solver output: normalization, letter case and charset
+    fn normalize_text(&self, text: &str, solver_name: &str, hints: &SolveHints) -> String {
+        if hints.raw_output {
+            return text.to_string();
+        }
+
+        let mode = match hints.normalize {
+            Some(mode) => mode,
+            None if hints.digits_only() => NormalizeMode::Digits,
+            None => NormalizeMode::default(),
+        };
+        let case = self.case_for(solver_name, hints.case);
+        let whitespace = hints.whitespace.unwrap_or(self.whitespace);
+
+        let mut text = self.normalizer.normalize(text, mode, case, whitespace);
+        if let Some(charset) = &hints.charset {
+            text.retain(|c| charset.contains(c));
+        }
+        text
     }
 
     /// Get list of available solvers
@@ -157,31 +889,1437 @@ impl SolverManager {
         self.solvers.keys().cloned().collect()
     }
 
-    /// Load a custom model
-    pub async fn load_model(&mut self, model: &CaptchaModel) -> CaptchaResult<()> {
-        // Implementation depends on model type
-        tracing::info!("Loading model: {} ({})", model.name, model.model_type);
-        
-        // TODO: Implement custom model loading
-        Ok(())
+    /// Get the text normalizer applied to solver output
+    pub fn normalizer(&self) -> &TextNormalizer {
+        &self.normalizer
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Letter case for a solver's output: the requested case, else the solver's configured case,
+    /// else the service default
+    pub fn case_for(&self, solver_name: &str, requested: Option<CaseMode>) -> CaseMode {
+        requested
+            .or_else(|| self.solver_case.get(solver_name).copied())
+            .unwrap_or(self.output_case)
+    }
 
-    #[tokio::test]
-    async fn test_solver_manager_creation() {
-        let config = ModelsSettings {
-            path: "/tmp/models".to_string(),
-            default_model: "ocr".to_string(),
-            ocr_enabled: true,
-            cnn_enabled: false,
+    /// Letter case used when no request or solver override applies
+    pub fn output_case(&self) -> CaseMode {
+        self.output_case
+    }
+
+    /// Most characters an answer may have (`models.max_output_length`)
+    pub fn max_output_length(&self) -> usize {
+        self.max_output_length
+    }
+
+    /// Get readiness and mock state of each solver, sorted by name
+    pub fn solver_info(&self) -> Vec<SolverInfo> {
+        let mut info: Vec<SolverInfo> = self.solvers
+            .iter()
+            .map(|(name, solver)| self.info(name, solver.as_ref()))
+            .collect();
+        info.sort_by(|a, b| a.name.cmp(&b.name));
+        info
+    }
+
+    /// Capability descriptor of a loaded solver
+    pub fn solver_schema(&self, name: &str) -> CaptchaResult<SolverSchema> {
+        let solver = self.solvers.get(name)
+            .ok_or_else(|| self.missing_solver_error(name))?;
+
+        Ok(SolverSchema {
+            info: self.info(name, solver.as_ref()),
+            capabilities: solver.capabilities(),
+            strategies: vec![
+                SolveStrategy::Single,
+                SolveStrategy::AutoPreprocess,
+                SolveStrategy::Digits,
+                SolveStrategy::ByDifficulty,
+            ],
+        })
+    }
+
+    fn info(&self, name: &str, solver: &dyn CaptchaSolver) -> SolverInfo {
+        SolverInfo {
+            name: name.to_string(),
+            ready: self.is_available(name, solver),
+            mock: solver.is_mock(),
+        }
+    }
+
+    /// Get names of solvers running in mock mode
+    pub fn mock_solvers(&self) -> Vec<String> {
+        self.solver_info()
+            .into_iter()
+            .filter(|info| info.mock)
+            .map(|info| info.name)
+            .collect()
+    }
+
+    /// Reload ensemble weights from the recorded accuracy of active models.
+    /// Each solver takes the accuracy of the first active model of its type, so the
+    /// default model wins when `models` is ordered default-first. Returns the new weights.
+    pub async fn refresh_ensemble_weights(&self, models: &[CaptchaModel]) -> HashMap<String, f32> {
+        let weights = if self.accuracy_weighting {
+            self.solvers
+                .keys()
+                .filter_map(|name| {
+                    models.iter()
+                        .filter(|m| m.is_active && &m.model_type == name)
+                        .find_map(|m| m.accuracy)
+                        .map(|accuracy| (name.clone(), (accuracy as f32).clamp(0.0, 1.0)))
+                })
+                .collect()
+        } else {
+            HashMap::new()
         };
 
-        // This will likely fail without actual tesseract installed
-        // Just testing the structure
-        let _ = SolverManager::new(&config).await;
+        tracing::info!("Ensemble weights: {:?}", weights);
+        *self.ensemble_weights.write().await = weights.clone();
+        weights
+    }
+
+    /// Get the current ensemble weights
+    pub async fn ensemble_weights(&self) -> HashMap<String, f32> {
+        self.ensemble_weights.read().await.clone()
+    }
+
+    /// Reload confidence temperatures from the `temperature` metadata of active models.
+    /// Solvers without one keep their configured temperature, if any.
+    pub async fn refresh_temperatures(&self, models: &[CaptchaModel]) -> HashMap<String, f32> {
+        let mut temperatures = self.configured_temperatures.clone();
+        for name in self.solvers.keys() {
+            let from_metadata = models.iter()
+                .filter(|m| m.is_active && &m.model_type == name)
+                .find_map(|m| m.metadata.as_ref()?.get("temperature")?.as_f64())
+                .map(|t| t as f32);
+
+            match from_metadata {
+                Some(t) if t.is_finite() && t > 0.0 => {
+                    temperatures.insert(name.clone(), t);
+                }
+                Some(t) => tracing::warn!("Ignoring invalid temperature {} for solver {}", t, name),
+                None => {}
+            }
+        }
+
+        tracing::info!("Confidence temperatures: {:?}", temperatures);
+        *self.temperatures.write().await = temperatures.clone();
+        temperatures
+    }
+
+    /// Get the current confidence temperatures
+    pub async fn temperatures(&self) -> HashMap<String, f32> {
+        self.temperatures.read().await.clone()
     }
-}
\ No newline at end of file
+
+    /// Refit each solver's calibration curve on `(returned confidence, correct)` samples
+    /// of its labeled logs. Solvers with fewer than `calibration_min_samples` stay
+    /// uncalibrated. Returns the samples behind each fitted curve.
+    pub async fn refresh_calibration(&self, samples: &HashMap<String, Vec<(f32, bool)>>) -> HashMap<String, usize> {
+        let curves: HashMap<String, CalibrationCurve> = samples
+            .iter()
+            .filter_map(|(name, samples)| {
+                CalibrationCurve::fit(samples, self.calibration_min_samples).map(|curve| (name.clone(), curve))
+            })
+            .collect();
+        let fitted: HashMap<String, usize> = curves.iter().map(|(name, curve)| (name.clone(), curve.samples())).collect();
+
+        tracing::info!("Confidence calibration samples: {:?}", fitted);
+        *self.calibration.write().await = curves;
+        fitted
+    }
+
+    /// Labeled samples behind each solver's calibration curve
+    pub async fn calibration_samples(&self) -> HashMap<String, usize> {
+        self.calibration.read().await.iter().map(|(name, curve)| (name.clone(), curve.samples())).collect()
+    }
+
+    /// `confidence` of an answer by `solver_name` as an estimated probability of being
+    /// correct, or the confidence itself when the solver has no calibration curve
+    pub async fn probability(&self, solver_name: &str, confidence: f32) -> Probability {
+        let curve = self.calibration.read().await;
+        let (p, calibrated) = match curve.get(solver_name) {
+            Some(curve) => (curve.probability(confidence), true),
+            None => (confidence, false),
+        };
+
+        Probability { percent: (p.clamp(0.0, 1.0) * 1000.0).round() / 10.0, calibrated }
+    }
+
+    /// Whether a solver can take requests: ready, and its model file passed checksum verification
+    fn is_available(&self, name: &str, solver: &dyn CaptchaSolver) -> bool {
+        solver.is_ready() && !self.checksum_failures.lock().unwrap().contains_key(name)
+    }
+
+    /// Check each solver's model file against the SHA256 recorded for the active model
+    /// stored at that path. A solver whose file doesn't match (or can't be read) is
+    /// unavailable until a later verification passes; files without a recorded checksum
+    /// are not checked. Returns the failures by solver.
+    pub async fn verify_checksums(&self, models: &[CaptchaModel]) -> HashMap<String, String> {
+        let mut failures = HashMap::new();
+
+        for (name, solver) in &self.solvers {
+            let Some(path) = solver.model_file() else {
+                continue;
+            };
+            let Some(expected) = models.iter()
+                .filter(|m| m.is_active && &m.model_type == name && Path::new(&m.file_path) == path)
+                .find_map(|m| m.file_sha256.clone())
+            else {
+                continue;
+            };
+
+            let file = path.clone();
+            let actual = tokio::task::spawn_blocking(move || sha256_file(&file))
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|hashed| hashed.map_err(|e| e.to_string()));
+
+            match actual {
+                Ok(actual) if actual.eq_ignore_ascii_case(&expected) => {}
+                Ok(actual) => {
+                    failures.insert(name.clone(), format!(
+                        "checksum mismatch for {}: expected {}, found {}", path.display(), expected, actual
+                    ));
+                }
+                Err(e) => {
+                    failures.insert(name.clone(), format!("cannot verify {}: {}", path.display(), e));
+                }
+            }
+        }
+
+        let mut current = self.checksum_failures.lock().unwrap();
+        for (name, reason) in &failures {
+            tracing::error!("Solver {} marked unavailable: {}", name, reason);
+        }
+        for name in current.keys().filter(|name| !failures.contains_key(*name)) {
+            tracing::info!("Solver {} model file verified, available again", name);
+        }
+        *current = failures.clone();
+        failures
+    }
+
+    /// Solvers currently unavailable because their model file failed checksum verification
+    pub fn checksum_failures(&self) -> HashMap<String, String> {
+        self.checksum_failures.lock().unwrap().clone()
+    }
+
+    /// Model file each registered solver loads, sorted by solver. Solvers without a
+    /// model file (tesseract) are not listed.
+    pub fn model_files(&self) -> Vec<(String, PathBuf)> {
+        let mut files: Vec<(String, PathBuf)> = self.solvers
+            .iter()
+            .filter_map(|(name, solver)| solver.model_file().map(|path| (name.clone(), path)))
+            .collect();
+        files.sort();
+        files
+    }
+
+    /// Solvers whose model failed to load at startup and has not loaded since, sorted
+    pub fn pending_models(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.solvers
+            .iter()
+            .filter(|(_, solver)| solver.needs_model())
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Try again to load each model missing at startup (e.g. still being downloaded by a
+    /// sidecar), logging every attempt. Returns the solvers still waiting for their model.
+    pub async fn retry_model_loads(&self) -> Vec<String> {
+        for name in self.pending_models() {
+            let solver = self.solvers[&name].clone();
+            let loaded = tokio::task::spawn_blocking(move || solver.reload_model())
+                .await
+                .map_err(|e| CaptchaError::ModelLoadError(e.to_string()))
+                .and_then(|result| result);
+
+            match loaded {
+                Ok(()) => tracing::info!("Solver {} model loaded on retry", name),
+                Err(e) => tracing::warn!("Solver {} model still not loaded: {}", name, e),
+            }
+        }
+        self.pending_models()
+    }
+
+    /// Load a custom model
+    pub async fn load_model(&mut self, model: &CaptchaModel) -> CaptchaResult<()> {
+        // Implementation depends on model type
+        tracing::info!("Loading model: {} ({})", model.name, model.model_type);
+        
+        // TODO: Implement custom model loading
+        Ok(())
+    }
+}
+
+/// Hex SHA256 of a file, read in chunks
+pub fn sha256_file(path: &Path) -> std::io::Result<String> {
+    use sha2::{Digest, Sha256};
+
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// A place in the solve wait queue, given back when dropped
+struct QueueSlot<'a> {
+    queued: &'a AtomicUsize,
+    /// Callers waiting, this one included, when the slot was taken
+    waiting: usize,
+}
+
+impl<'a> QueueSlot<'a> {
+    fn take(queued: &'a AtomicUsize) -> Self {
+        let waiting = queued.fetch_add(1, Ordering::SeqCst) + 1;
+        Self { queued, waiting }
+    }
+}
+
+impl Drop for QueueSlot<'_> {
+    fn drop(&mut self) {
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Parse a comma-separated list of preprocessing variant names, dropping unknown ones
+fn parse_variants(spec: &str) -> Vec<String> {
+    spec.split(',')
+        .map(|name| name.trim())
+        .filter(|name| !name.is_empty())
+        .filter(|name| {
+            let known = ImagePreprocessor::variant(name).is_some();
+            if !known {
+                tracing::warn!("Ignoring unknown preprocessing variant: {:?}", name);
+            }
+            known
+        })
+        .map(|name| name.to_string())
+        .collect()
+}
+
+/// Parse the easy, medium and hard presets of the `by_difficulty` strategy,
+/// falling back to the defaults unless exactly three known variants are given
+fn parse_difficulty_presets(spec: &str) -> [String; 3] {
+    parse_variants(spec).try_into().unwrap_or_else(|_| {
+        tracing::warn!("Expected three difficulty presets (easy, medium, hard), got {:?}; using defaults", spec);
+        parse_variants(preprocessor::DEFAULT_DIFFICULTY_PRESETS)
+            .try_into()
+            .expect("default difficulty presets are valid")
+    })
+}
+
+/// Weight for a solver's confidence in the ensemble. Solvers without a recorded
+/// accuracy get the mean of the known weights, or 1.0 when none are known.
+fn ensemble_weight(weights: &HashMap<String, f32>, solver_name: &str) -> f32 {
+    if let Some(weight) = weights.get(solver_name) {
+        return *weight;
+    }
+    if weights.is_empty() {
+        1.0
+    } else {
+        weights.values().sum::<f32>() / weights.len() as f32
+    }
+}
+
+/// Temperature scaling of a confidence: `sigmoid(logit(p) / T)`.
+/// `T > 1` softens overconfident outputs, `T < 1` sharpens them, `T = 1` is the identity.
+fn temperature_scale(confidence: f32, temperature: f32) -> f32 {
+    let p = confidence.clamp(1e-6, 1.0 - 1e-6);
+    let logit = (p / (1.0 - p)).ln();
+    1.0 / (1.0 + (-logit / temperature).exp())
+}
+
+/// Keep only usable (positive, finite) temperatures
+fn valid_temperatures(temperatures: &HashMap<String, f32>) -> HashMap<String, f32> {
+    temperatures
+        .iter()
+        .filter(|(name, &t)| {
+            let valid = t.is_finite() && t > 0.0;
+            if !valid {
+                tracing::warn!("Ignoring invalid temperature {} for solver {}", t, name);
+            }
+            valid
+        })
+        .map(|(name, &t)| (name.clone(), t))
+        .collect()
+}
+
+/// Backoff for the given retry attempt: a random delay in `[0, base * 2^attempt]`
+fn retry_delay_ms(base_ms: u64, attempt: u32) -> u64 {
+    let cap = base_ms.saturating_mul(1u64 << attempt.min(16));
+    rand::thread_rng().gen_range(0..=cap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_solver_manager_creation() {
+        let config = ModelsSettings {
+            path: "/tmp/models".to_string(),
+            default_model: "ocr".to_string(),
+            ocr_enabled: true,
+            cnn_enabled: false,
+            timeouts: HashMap::from([("cnn".to_string(), 5)]),
+            case: HashMap::from([("cnn".to_string(), CaseMode::Preserve)]),
+            temperatures: HashMap::from([("cnn".to_string(), 1.5)]),
+            http_url: None,
+            default_strategy: DefaultStrategy::Single,
+            fallback_min_confidence: 0.5,
+            max_output_length: 32,
+        };
+        let processing = ProcessingSettings {
+            max_image_size_mb: 10,
+            timeout_seconds: 30,
+            batch_size: 10,
+            batch_max_total_pixels: 20_000_000,
+            image_hash_algorithm: Default::default(),
+            digit_substitutions: postprocess::DEFAULT_DIGIT_SUBSTITUTIONS.to_string(),
+            archive_max_size_mb: 100,
+            archive_max_entries: 1000,
+            archive_max_entry_size_mb: 2,
+            solver_retry_attempts: 2,
+            solver_retry_base_ms: 50,
+            max_concurrent_solves: 2,
+            max_queue_depth: 64,
+            overload_retry_after_seconds: 1,
+            api_key_usage_flush_seconds: 10,
+            auto_preprocess_variants: preprocessor::DEFAULT_AUTO_PREPROCESS_VARIANTS.to_string(),
+            auto_preprocess_target: 0.8,
+            difficulty_presets: preprocessor::DEFAULT_DIFFICULTY_PRESETS.to_string(),
+            difficulty_easy_max_noise: 0.02,
+            difficulty_easy_max_components: 8,
+            difficulty_hard_min_noise: 0.1,
+            difficulty_hard_min_components: 16,
+            ensemble_accuracy_weighting: true,
+            output_case: CaseMode::Upper,
+            whitespace: WhitespaceMode::Strip,
+            idempotency_ttl_seconds: 3600,
+            idempotency_max_entries: 10000,
+            solve_memo_ttl_seconds: 5,
+            segment_min_width: 1,
+            segment_min_height: 4,
+            segment_min_pixels: 6,
+            segment_split_width_ratio: 1.8,
+            accuracy_min_samples: 50,
+            accuracy_refresh_seconds: 0,
+            calibration_min_samples: 200,
+            solve_log_queue_capacity: 10000,
+            solve_log_flush_seconds: 1,
+            success_confidence_floor: 0.0,
+            model_checksum_check_seconds: 300,
+            model_load_retry_seconds: 30,
+            stroke_width_target: 0.0,
+            inference_threads: 0,
+            alpha_background: crate::config::HexColor([255, 255, 255]),
+            max_dimension: 2048,
+            allowed_image_formats: crate::config::ImageFormats(vec![image::ImageFormat::Png]),
+            store_failures: false,
+            failure_dir: "/tmp/failures".to_string(),
+            failure_max_files: 10000,
+            failure_max_bytes: 1024 * 1024 * 1024,
+            failure_confidence_threshold: 0.5,
+            store_log_images: false,
+        };
+
+        // This will likely fail without actual tesseract installed
+        // Just testing the structure
+        let _ = SolverManager::new(&config, &processing).await;
+    }
+
+    fn empty_manager() -> SolverManager {
+        SolverManager {
+            solvers: HashMap::new(),
+            default_solver: "ocr".to_string(),
+            default_strategy: DefaultStrategy::Single,
+            fallback_min_confidence: 0.5,
+            max_output_length: 32,
+            models_path: "/tmp/models".to_string(),
+            normalizer: TextNormalizer::default(),
+            output_case: CaseMode::Upper,
+            solver_case: HashMap::new(),
+            whitespace: WhitespaceMode::Strip,
+            retry_attempts: 2,
+            retry_base_ms: 0,
+            solve_permits: Arc::new(Semaphore::new(1)),
+            inference: InferencePool::new(2).unwrap(),
+            permit_timeout: Duration::from_millis(50),
+            solver_timeouts: HashMap::new(),
+            queued: AtomicUsize::new(0),
+            max_queue_depth: 1,
+            overload_retry_after: 1,
+            auto_variants: parse_variants(preprocessor::DEFAULT_AUTO_PREPROCESS_VARIANTS),
+            auto_target: 0.8,
+            difficulty_thresholds: DifficultyThresholds::default(),
+            difficulty_presets: parse_difficulty_presets(preprocessor::DEFAULT_DIFFICULTY_PRESETS),
+            accuracy_weighting: true,
+            ensemble_weights: RwLock::new(HashMap::new()),
+            configured_temperatures: HashMap::new(),
+            temperatures: RwLock::new(HashMap::new()),
+            checksum_failures: std::sync::Mutex::new(HashMap::new()),
+            calibration: RwLock::new(HashMap::new()),
+            calibration_min_samples: 200,
+        }
+    }
+
+    fn result(text: &str, confidence: f32) -> SolveResult {
+        SolveResult {
+            text: text.to_string(),
+            confidence,
+            solver_name: "ocr".to_string(),
+            length_mismatch: None,
+            preprocess_ms: 0,
+            inference_ms: 0,
+            candidates: Vec::new(),
+            preprocess_variant: None,
+            processed_image: None,
+            char_positions: None,
+        }
+    }
+
+    #[test]
+    fn test_finalize_case() {
+        let mut manager = empty_manager();
+        manager.solver_case.insert("ocr".to_string(), CaseMode::Preserve);
+
+        let by_solver = manager.finalize(result("aB-12", 0.8), &SolveHints::default());
+        assert_eq!(by_solver.text, "aB12");
+
+        let hints = SolveHints { case: Some(CaseMode::Lower), ..Default::default() };
+        assert_eq!(manager.finalize(result("aB-12", 0.8), &hints).text, "ab12");
+
+        let hints = SolveHints { normalize: Some(NormalizeMode::UpperAlnum), ..Default::default() };
+        assert_eq!(manager.finalize(result("aB-12", 0.8), &hints).text, "AB12");
+
+        manager.solver_case.clear();
+        assert_eq!(manager.finalize(result("aB-12", 0.8), &SolveHints::default()).text, "AB12");
+    }
+
+    #[test]
+    fn test_finalize_whitespace() {
+        let mut manager = empty_manager();
+        assert_eq!(manager.finalize(result("ab  cd", 0.8), &SolveHints::default()).text, "ABCD");
+
+        let hints = SolveHints { whitespace: Some(WhitespaceMode::Collapse), ..Default::default() };
+        assert_eq!(manager.finalize(result(" ab  cd ", 0.8), &hints).text, "AB CD");
+
+        manager.whitespace = WhitespaceMode::Preserve;
+        assert_eq!(manager.finalize(result("ab  cd", 0.8), &SolveHints::default()).text, "AB  CD");
+        // A charset without a space still removes it
+        let hints = SolveHints { charset: Some("ABCD".to_string()), ..Default::default() };
+        assert_eq!(manager.finalize(result("ab  cd", 0.8), &hints).text, "ABCD");
+    }
+
+    #[test]
+    fn test_finalize_charset() {
+        let manager = empty_manager();
+        let hints = SolveHints { charset: Some("0123456789".to_string()), top_k: Some(2), ..Default::default() };
+        let mut raw = result("1O2-3", 0.9);
+        raw.candidates = vec![
+            Candidate { text: "1O2-3".to_string(), confidence: 0.9 },
+            Candidate { text: "1A23".to_string(), confidence: 0.5 },
+        ];
+
+        let finalized = manager.finalize(raw, &hints);
+        assert_eq!(finalized.text, "123");
+        // Both candidates collapse to the same answer
+        assert_eq!(finalized.candidates.len(), 1);
+
+        let empty = SolveHints { charset: Some(String::new()), ..Default::default() };
+        assert!(empty.validate(32).is_err());
+    }
+
+    #[test]
+    fn test_hints_validation_reports_every_field() {
+        let hints = SolveHints {
+            top_k: Some(usize::MAX),
+            charset: Some(String::new()),
+            auto_preprocess: Some(AutoPreprocessOptions {
+                variants: Some(vec!["raw".to_string(), "sharpen".to_string()]),
+                confidence_target: Some(1.5),
+            }),
+            ..Default::default()
+        };
+
+        let Err(CaptchaError::Validation(errors)) = hints.validate(32) else {
+            panic!("expected a validation error");
+        };
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            vec!["top_k", "charset", "auto_preprocess.variants[1]", "auto_preprocess.confidence_target"]
+        );
+
+        assert!(SolveHints { top_k: Some(0), ..Default::default() }.validate(32).is_err());
+        assert!(SolveHints { top_k: Some(MAX_TOP_K), ..Default::default() }.validate(32).is_ok());
+    }
+
+    #[test]
+    fn test_finalize_digits_strategy() {
+        let manager = empty_manager();
+        let hints = SolveHints { strategy: SolveStrategy::Digits, ..Default::default() };
+        // Lookalike letters map to digits unless another normalization is requested
+        assert_eq!(manager.finalize(result("1O2S", 0.9), &hints).text, "1025");
+
+        let explicit = SolveHints { normalize: Some(NormalizeMode::None), ..hints };
+        assert_eq!(manager.finalize(result("1O2S", 0.9), &explicit).text, "1O2S");
+    }
+
+    #[test]
+    fn test_finalize_raw_output() {
+        let manager = empty_manager();
+        let hints = SolveHints {
+            raw_output: true,
+            normalize: Some(NormalizeMode::Alnum),
+            charset: Some("0123456789".to_string()),
+            expected_length: Some(4),
+            ..Default::default()
+        };
+
+        let raw = manager.finalize(result("ab 1-2.", 0.8), &hints);
+        assert_eq!(raw.text, "ab 1-2.");
+        // The length is still checked, without truncating the text
+        assert_eq!(raw.length_mismatch, Some(true));
+    }
+
+    #[test]
+    fn test_finalize_expected_length() {
+        let manager = empty_manager();
+        let hints = SolveHints { expected_length: Some(4), ..Default::default() };
+
+        let exact = manager.finalize(result("ab12", 0.8), &hints);
+        assert_eq!(exact.text, "AB12");
+        assert_eq!(exact.length_mismatch, Some(false));
+        assert_eq!(exact.confidence, 0.8);
+
+        let long = manager.finalize(result("AB12CD5", 0.8), &hints);
+        assert_eq!(long.text, "AB12");
+        assert_eq!(long.length_mismatch, Some(true));
+        assert!(long.confidence < 0.8);
+
+        let short = manager.finalize(result("AB", 0.8), &hints);
+        assert_eq!(short.text, "AB");
+        assert_eq!(short.length_mismatch, Some(true));
+        assert!((short.confidence - 0.4).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn test_probability_from_calibration() {
+        let mut manager = empty_manager();
+        manager.calibration_min_samples = 4;
+
+        // Uncalibrated solvers report their confidence as is
+        let raw = manager.probability("ocr", 0.876).await;
+        assert_eq!(raw, Probability { percent: 87.6, calibrated: false });
+
+        let samples = HashMap::from([
+            ("ocr".to_string(), vec![(0.9, true), (0.9, false), (0.9, true), (0.9, false)]),
+            ("cnn".to_string(), vec![(0.9, true)]),
+        ]);
+        let fitted = manager.refresh_calibration(&samples).await;
+        assert_eq!(fitted, HashMap::from([("ocr".to_string(), 4)]));
+
+        assert_eq!(manager.probability("ocr", 0.9).await, Probability { percent: 50.0, calibrated: true });
+        assert!(!manager.probability("cnn", 0.9).await.calibrated);
+    }
+
+    #[test]
+    fn test_finalize_max_output_length() {
+        let mut manager = empty_manager();
+        manager.max_output_length = 4;
+
+        let long = manager.finalize(result("AB12CD5", 0.8), &SolveHints::default());
+        assert_eq!(long.text, "AB12");
+        assert_eq!(long.confidence, 0.8);
+
+        // The bound holds even for output returned as is
+        let hints = SolveHints { raw_output: true, ..Default::default() };
+        assert_eq!(manager.finalize(result("ab-12cd", 0.8), &hints).text, "ab-1");
+    }
+
+    #[tokio::test]
+    async fn test_expected_length_beyond_max_output_length() {
+        let mut manager = empty_manager();
+        manager.max_output_length = 4;
+
+        let image = DynamicImage::new_luma8(8, 8);
+        let hints = SolveHints { expected_length: Some(5), ..Default::default() };
+        for err in [
+            manager.solve(&image, None, None, &hints).await.unwrap_err(),
+            manager.solve_ensemble(&image, None, &hints).await.unwrap_err(),
+        ] {
+            let CaptchaError::Validation(errors) = err else {
+                panic!("expected a validation error, got {:?}", err);
+            };
+            assert_eq!(errors[0].field, "expected_length");
+            assert_eq!(errors[0].error, "must be at most the maximum output length 4");
+        }
+
+        let fitting = SolveHints { expected_length: Some(4), ..Default::default() };
+        assert!(fitting.validate(4).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_ocr_reads_one_character_per_segment_with_expected_length() {
+        let mut manager = empty_manager();
+        let ocr = ocr::OcrSolver::new("/tmp/models", 0.0).await.unwrap();
+        manager.solvers.insert("ocr".to_string(), Arc::new(ocr));
+
+        // Three separate glyphs
+        let image = DynamicImage::ImageLuma8(image::GrayImage::from_fn(70, 20, |x, _| {
+            let ink = (5..15).contains(&x) || (25..35).contains(&x) || (50..60).contains(&x);
+            image::Luma([if ink { 0 } else { 255 }])
+        }));
+
+        for expected in [3, 5] {
+            let hints = SolveHints { expected_length: Some(expected), ..Default::default() };
+            let solved = manager.solve(&image, Some("ocr"), None, &hints).await.unwrap();
+            assert_eq!(solved.text.chars().count(), expected, "{}", solved.text);
+            assert_eq!(solved.length_mismatch, Some(false));
+        }
+    }
+
+    #[test]
+    fn test_finalize_without_hint() {
+        let manager = empty_manager();
+
+        let plain = manager.finalize(result("AB12CD", 0.8), &SolveHints::default());
+        assert_eq!(plain.length_mismatch, None);
+        assert_eq!(plain.confidence, 0.8);
+    }
+
+    #[test]
+    fn test_retry_delay_bounds() {
+        for attempt in 0..4 {
+            assert!(retry_delay_ms(50, attempt) <= 50 << attempt);
+        }
+        assert_eq!(retry_delay_ms(0, 3), 0);
+    }
+
+    #[test]
+    fn test_transient_classification() {
+        assert!(CaptchaError::ProcessingError("runtime hiccup".to_string()).is_transient());
+        assert!(CaptchaError::Timeout("cnn".to_string()).is_transient());
+        assert!(!CaptchaError::ModelNotFound("cnn".to_string()).is_transient());
+        assert!(!CaptchaError::InvalidImage("bad".to_string()).is_transient());
+    }
+
+    /// Solver that fails with the given error a fixed number of times before succeeding
+    struct FlakySolver {
+        failures: std::sync::atomic::AtomicU32,
+        error: fn() -> CaptchaError,
+    }
+
+    #[async_trait::async_trait]
+    impl CaptchaSolver for FlakySolver {
+        async fn solve(
+            &self,
+            _image: &DynamicImage,
+            _options: Option<&PreprocessOptions>,
+            _hints: &SolveHints,
+        ) -> CaptchaResult<SolveResult> {
+            if self.failures.load(Ordering::SeqCst) > 0 {
+                self.failures.fetch_sub(1, Ordering::SeqCst);
+                return Err((self.error)());
+            }
+            Ok(result("AB12", 0.9))
+        }
+
+        fn name(&self) -> &str {
+            "flaky"
+        }
+
+        fn is_ready(&self) -> bool {
+            true
+        }
+    }
+
+    fn flaky_manager(failures: u32, error: fn() -> CaptchaError) -> SolverManager {
+        let mut manager = empty_manager();
+        manager.solvers.insert("flaky".to_string(), Arc::new(FlakySolver {
+            failures: std::sync::atomic::AtomicU32::new(failures),
+            error,
+        }));
+        manager
+    }
+
+    /// Solver reporting fixed readiness and mock state
+    struct StubSolver {
+        mock: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl CaptchaSolver for StubSolver {
+        async fn solve(
+            &self,
+            _image: &DynamicImage,
+            _options: Option<&PreprocessOptions>,
+            _hints: &SolveHints,
+        ) -> CaptchaResult<SolveResult> {
+            Ok(result("AB12", 0.9))
+        }
+
+        fn name(&self) -> &str {
+            "stub"
+        }
+
+        fn is_ready(&self) -> bool {
+            true
+        }
+
+        fn is_mock(&self) -> bool {
+            self.mock
+        }
+    }
+
+    #[test]
+    fn test_solver_info_reports_mock_mode() {
+        let mut manager = empty_manager();
+        manager.solvers.insert("ocr".to_string(), Arc::new(StubSolver { mock: true }));
+        manager.solvers.insert("cnn".to_string(), Arc::new(StubSolver { mock: false }));
+
+        let info = manager.solver_info();
+        assert_eq!(info.len(), 2);
+        assert_eq!(info[0], SolverInfo { name: "cnn".to_string(), ready: true, mock: false });
+        assert_eq!(info[1], SolverInfo { name: "ocr".to_string(), ready: true, mock: true });
+        assert_eq!(manager.mock_solvers(), vec!["ocr".to_string()]);
+    }
+
+    /// Solver loading its model from a file
+    struct FileSolver {
+        path: PathBuf,
+    }
+
+    #[async_trait::async_trait]
+    impl CaptchaSolver for FileSolver {
+        async fn solve(
+            &self,
+            _image: &DynamicImage,
+            _options: Option<&PreprocessOptions>,
+            _hints: &SolveHints,
+        ) -> CaptchaResult<SolveResult> {
+            Ok(result("AB12", 0.9))
+        }
+
+        fn name(&self) -> &str {
+            "cnn"
+        }
+
+        fn is_ready(&self) -> bool {
+            true
+        }
+
+        fn model_file(&self) -> Option<PathBuf> {
+            Some(self.path.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_checksum_mismatch_marks_solver_unavailable() {
+        let path = std::env::temp_dir().join(format!("captcha-cnn-{}.onnx", uuid::Uuid::new_v4()));
+        std::fs::write(&path, b"onnx model").unwrap();
+
+        let mut manager = empty_manager();
+        manager.solvers.insert("cnn".to_string(), Arc::new(FileSolver { path: path.clone() }));
+        manager.solvers.insert("ocr".to_string(), Arc::new(StubSolver { mock: false }));
+
+        let mut stored = model("captcha_cnn", "cnn", None, true);
+        stored.file_path = path.to_string_lossy().into_owned();
+        stored.file_sha256 = Some(sha256_file(&path).unwrap().to_uppercase());
+        assert!(manager.verify_checksums(std::slice::from_ref(&stored)).await.is_empty());
+
+        // A partial write leaves a different file behind
+        std::fs::write(&path, b"onnx").unwrap();
+        let failures = manager.verify_checksums(std::slice::from_ref(&stored)).await;
+        assert!(failures["cnn"].contains("checksum mismatch"));
+        assert!(!manager.solver_info()[0].ready);
+
+        let hints = SolveHints { disable_fallback: true, ..Default::default() };
+        let solved = manager.solve(&DynamicImage::new_luma8(4, 4), Some("cnn"), None, &hints).await;
+        assert!(matches!(solved, Err(CaptchaError::ModelLoadError(_))));
+        assert!(manager.fallback_for("ocr").is_none());
+
+        // Restoring the file makes the solver available again
+        std::fs::write(&path, b"onnx model").unwrap();
+        assert!(manager.verify_checksums(std::slice::from_ref(&stored)).await.is_empty());
+        assert!(manager.solver_info()[0].ready);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_model_files_lists_only_solvers_with_a_model() {
+        let path = PathBuf::from("/models/captcha_cnn.onnx");
+        let mut manager = empty_manager();
+        manager.solvers.insert("cnn".to_string(), Arc::new(FileSolver { path: path.clone() }));
+        manager.solvers.insert("tesseract".to_string(), Arc::new(StubSolver { mock: false }));
+
+        assert_eq!(manager.model_files(), vec![("cnn".to_string(), path)]);
+    }
+
+    #[tokio::test]
+    async fn test_model_missing_at_startup_loads_on_retry() {
+        let dir = std::env::temp_dir().join(format!("captcha-models-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let solver = cnn::CnnSolver::new(dir.to_str().unwrap(), 0.0, 32).await.unwrap();
+
+        let mut manager = empty_manager();
+        manager.solvers.insert("cnn".to_string(), Arc::new(solver));
+        manager.solvers.insert("ocr".to_string(), Arc::new(StubSolver { mock: false }));
+        assert_eq!(manager.pending_models(), vec!["cnn".to_string()]);
+        assert_eq!(manager.retry_model_loads().await, vec!["cnn".to_string()]);
+
+        // The sidecar finishes downloading the model
+        std::fs::write(dir.join("captcha_cnn.onnx"), b"onnx model").unwrap();
+        assert!(manager.retry_model_loads().await.is_empty());
+        assert!(manager.pending_models().is_empty());
+        assert!(manager.solver_info()[0].ready);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Solver returning a fixed answer under its own name
+    struct FixedSolver {
+        name: &'static str,
+        text: &'static str,
+        confidence: f32,
+    }
+
+    #[async_trait::async_trait]
+    impl CaptchaSolver for FixedSolver {
+        async fn solve(
+            &self,
+            _image: &DynamicImage,
+            _options: Option<&PreprocessOptions>,
+            _hints: &SolveHints,
+        ) -> CaptchaResult<SolveResult> {
+            let mut result = result(self.text, self.confidence);
+            result.solver_name = self.name.to_string();
+            Ok(result)
+        }
+
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn is_ready(&self) -> bool {
+            true
+        }
+    }
+
+    fn model(name: &str, model_type: &str, accuracy: Option<f64>, is_active: bool) -> CaptchaModel {
+        CaptchaModel {
+            id: 1,
+            name: name.to_string(),
+            model_type: model_type.to_string(),
+            version: "1.0.0".to_string(),
+            file_path: format!("/tmp/models/{}.onnx", name),
+            file_size_bytes: 0,
+            file_sha256: None,
+            accuracy,
+            is_active,
+            is_default: false,
+            metadata: None,
+            description: None,
+            created_by: None,
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ensemble_skips_blank_answers() {
+        let mut manager = empty_manager();
+        manager.solvers.insert("ocr".to_string(), Arc::new(FixedSolver { name: "ocr", text: "AB12", confidence: 0.4 }));
+        // Punctuation only: blank after normalization despite the high confidence
+        manager.solvers.insert("cnn".to_string(), Arc::new(FixedSolver { name: "cnn", text: " -.", confidence: 0.99 }));
+
+        let image = DynamicImage::new_luma8(10, 10);
+        let result = manager.solve_ensemble(&image, None, &SolveHints::default()).await.unwrap();
+        assert_eq!(result.text, "AB12");
+        assert_eq!(result.solver_name, "ocr");
+    }
+
+    #[test]
+    fn test_solver_schema() {
+        let mut manager = empty_manager();
+        manager.solvers.insert("ocr".to_string(), Arc::new(FixedSolver { name: "ocr", text: "AB12", confidence: 0.9 }));
+
+        let schema = manager.solver_schema("ocr").unwrap();
+        assert_eq!(schema.info, SolverInfo { name: "ocr".to_string(), ready: true, mock: false });
+        assert_eq!(schema.capabilities, SolverCapabilities::default());
+        assert!(schema.strategies.contains(&SolveStrategy::Digits));
+
+        let json = serde_json::to_value(&schema).unwrap();
+        assert_eq!(json["name"], "ocr");
+        assert_eq!(json["top_k"], false);
+
+        assert!(matches!(manager.solver_schema("my-model"), Err(CaptchaError::ModelNotFound(_))));
+        assert!(matches!(manager.solver_schema("cnn"), Err(CaptchaError::UnsupportedModelType(_))));
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_model_type_lists_available_solvers() {
+        let mut manager = empty_manager();
+        manager.solvers.insert("ocr".to_string(), Arc::new(FixedSolver { name: "ocr", text: "AB12", confidence: 0.9 }));
+        manager.solvers.insert("cnn".to_string(), Arc::new(FixedSolver { name: "cnn", text: "AB12", confidence: 0.9 }));
+        let image = DynamicImage::new_luma8(10, 10);
+
+        let err = manager.solve(&image, Some("transformer"), None, &SolveHints::default()).await.unwrap_err();
+        assert_eq!(err.error_code(), "unsupported_model_type");
+        assert!(matches!(
+            err,
+            CaptchaError::UnsupportedModelType(ref msg)
+                if msg == "Model type 'transformer' is not supported by this build; available: cnn, ocr"
+        ), "{:?}", err);
+
+        let err = manager.solve(&image, Some("my-model"), None, &SolveHints::default()).await.unwrap_err();
+        assert!(matches!(err, CaptchaError::ModelNotFound(ref name) if name == "my-model"), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn test_ensemble_all_blank_is_an_error() {
+        let mut manager = empty_manager();
+        manager.solvers.insert("ocr".to_string(), Arc::new(FixedSolver { name: "ocr", text: "", confidence: 0.9 }));
+        manager.solvers.insert("cnn".to_string(), Arc::new(FixedSolver { name: "cnn", text: "??", confidence: 0.95 }));
+
+        let image = DynamicImage::new_luma8(10, 10);
+        let err = manager.solve_ensemble(&image, None, &SolveHints::default()).await.unwrap_err();
+        assert!(matches!(err, CaptchaError::ProcessingError(ref msg) if msg == "no readable text"), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn test_ensemble_weighted_by_accuracy() {
+        let mut manager = empty_manager();
+        manager.solvers.insert("ocr".to_string(), Arc::new(FixedSolver { name: "ocr", text: "OCR1", confidence: 0.9 }));
+        manager.solvers.insert("cnn".to_string(), Arc::new(FixedSolver { name: "cnn", text: "CNN1", confidence: 0.8 }));
+        let image = DynamicImage::new_luma8(10, 10);
+
+        // Unweighted: highest raw confidence wins
+        let result = manager.solve_ensemble(&image, None, &SolveHints::default()).await.unwrap();
+        assert_eq!(result.text, "OCR1");
+
+        let weights = manager.refresh_ensemble_weights(&[
+            model("cnn-v2", "cnn", Some(0.95), true),
+            model("cnn-v1", "cnn", Some(0.5), true),
+            model("tess", "ocr", Some(0.6), true),
+            model("old-ocr", "ocr", Some(0.99), false),
+        ]).await;
+        assert_eq!(weights.get("cnn"), Some(&0.95));
+        assert_eq!(weights.get("ocr"), Some(&0.6));
+
+        // 0.8 * 0.95 beats 0.9 * 0.6; reported confidence stays unweighted
+        let result = manager.solve_ensemble(&image, None, &SolveHints::default()).await.unwrap();
+        assert_eq!(result.text, "CNN1");
+        assert_eq!(result.confidence, 0.8);
+    }
+
+    #[tokio::test]
+    async fn test_ensemble_weights_disabled() {
+        let mut manager = empty_manager();
+        manager.accuracy_weighting = false;
+        manager.solvers.insert("cnn".to_string(), Arc::new(FixedSolver { name: "cnn", text: "CNN1", confidence: 0.8 }));
+
+        let weights = manager.refresh_ensemble_weights(&[model("cnn-v1", "cnn", Some(0.9), true)]).await;
+        assert!(weights.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_temperature_calibration_in_ensemble() {
+        let mut manager = empty_manager();
+        manager.configured_temperatures.insert("ocr".to_string(), 0.5);
+        manager.solvers.insert("ocr".to_string(), Arc::new(FixedSolver { name: "ocr", text: "OCR1", confidence: 0.8 }));
+        manager.solvers.insert("cnn".to_string(), Arc::new(FixedSolver { name: "cnn", text: "CNN1", confidence: 0.95 }));
+        let image = DynamicImage::new_luma8(10, 10);
+
+        // Uncalibrated: the overconfident CNN wins
+        let result = manager.solve_ensemble(&image, None, &SolveHints::default()).await.unwrap();
+        assert_eq!(result.text, "CNN1");
+
+        let mut cnn = model("cnn-v2", "cnn", None, true);
+        cnn.metadata = Some(serde_json::json!({ "temperature": 3.0 }));
+        let temperatures = manager.refresh_temperatures(&[cnn]).await;
+        assert_eq!(temperatures.get("cnn"), Some(&3.0));
+        assert_eq!(temperatures.get("ocr"), Some(&0.5));
+
+        // cnn 0.95 -> ~0.73, ocr 0.8 -> ~0.94; the returned confidence is calibrated
+        let result = manager.solve_ensemble(&image, None, &SolveHints::default()).await.unwrap();
+        assert_eq!(result.text, "OCR1");
+        assert!((result.confidence - 0.9412).abs() < 1e-3, "{}", result.confidence);
+    }
+
+    #[test]
+    fn test_temperature_scale() {
+        assert!((temperature_scale(0.8, 1.0) - 0.8).abs() < 1e-6);
+        assert!(temperature_scale(0.99, 2.0) < 0.99);
+        assert!(temperature_scale(0.7, 0.5) > 0.7);
+        assert!((temperature_scale(0.5, 4.0) - 0.5).abs() < 1e-6);
+        assert!(temperature_scale(1.0, 2.0) < 1.0);
+
+        let configured = HashMap::from([("cnn".to_string(), 2.0), ("ocr".to_string(), 0.0), ("rnn".to_string(), f32::NAN)]);
+        assert_eq!(valid_temperatures(&configured), HashMap::from([("cnn".to_string(), 2.0)]));
+    }
+
+    #[test]
+    fn test_ensemble_weight_fallback() {
+        let mut weights = HashMap::new();
+        assert_eq!(ensemble_weight(&weights, "ocr"), 1.0);
+
+        weights.insert("cnn".to_string(), 0.9);
+        weights.insert("rnn".to_string(), 0.5);
+        assert_eq!(ensemble_weight(&weights, "cnn"), 0.9);
+        assert!((ensemble_weight(&weights, "ocr") - 0.7).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn test_ensemble_retries_transient_errors() {
+        let manager = flaky_manager(2, || CaptchaError::ProcessingError("hiccup".to_string()));
+        let image = DynamicImage::new_luma8(10, 10);
+
+        let result = manager.solve_ensemble(&image, None, &SolveHints::default()).await.unwrap();
+        assert_eq!(result.text, "AB12");
+    }
+
+    #[tokio::test]
+    async fn test_ensemble_does_not_retry_permanent_errors() {
+        let manager = flaky_manager(1, || CaptchaError::ModelNotFound("flaky".to_string()));
+        let image = DynamicImage::new_luma8(10, 10);
+
+        let err = manager.solve_ensemble(&image, None, &SolveHints::default()).await.unwrap_err();
+        assert!(matches!(err, CaptchaError::ProcessingError(ref msg) if msg == "All solvers failed"), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn test_ensemble_without_ready_solvers() {
+        let image = DynamicImage::new_luma8(10, 10);
+
+        let err = empty_manager().solve_ensemble(&image, None, &SolveHints::default()).await.unwrap_err();
+        assert!(matches!(err, CaptchaError::NoReadySolvers(ref msg) if msg == "no solvers are loaded"), "{:?}", err);
+
+        let mut manager = empty_manager();
+        manager.solvers.insert("ocr".to_string(), Arc::new(FixedSolver { name: "ocr", text: "AB12", confidence: 0.9 }));
+        manager.checksum_failures.lock().unwrap().insert("ocr".to_string(), "checksum mismatch".to_string());
+
+        let err = manager.solve_ensemble(&image, None, &SolveHints::default()).await.unwrap_err();
+        assert!(matches!(err, CaptchaError::NoReadySolvers(ref msg) if msg.contains("ocr (checksum mismatch)")), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn test_solve_falls_back_on_processing_error() {
+        let mut manager = flaky_manager(1, || CaptchaError::ProcessingError("onnx".to_string()));
+        manager.solvers.insert("ocr".to_string(), Arc::new(FixedSolver { name: "ocr", text: "XY34", confidence: 0.7 }));
+        let image = DynamicImage::new_luma8(10, 10);
+
+        let result = manager.solve(&image, Some("flaky"), None, &SolveHints::default()).await.unwrap();
+        assert_eq!(result.text, "XY34");
+        assert_eq!(result.solver_name, "ocr");
+    }
+
+    #[tokio::test]
+    async fn test_solve_fallback_can_be_disabled() {
+        let mut manager = flaky_manager(1, || CaptchaError::ProcessingError("onnx".to_string()));
+        manager.solvers.insert("ocr".to_string(), Arc::new(FixedSolver { name: "ocr", text: "XY34", confidence: 0.7 }));
+        let image = DynamicImage::new_luma8(10, 10);
+
+        let hints = SolveHints { disable_fallback: true, ..Default::default() };
+        let result = manager.solve(&image, Some("flaky"), None, &hints).await;
+        assert!(matches!(result, Err(CaptchaError::ProcessingError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_solve_does_not_fall_back_on_bad_input() {
+        let mut manager = flaky_manager(1, || CaptchaError::InvalidImage("empty".to_string()));
+        manager.solvers.insert("ocr".to_string(), Arc::new(FixedSolver { name: "ocr", text: "XY34", confidence: 0.7 }));
+        let image = DynamicImage::new_luma8(10, 10);
+
+        let result = manager.solve(&image, Some("flaky"), None, &SolveHints::default()).await;
+        assert!(matches!(result, Err(CaptchaError::InvalidImage(_))));
+    }
+
+    #[test]
+    fn test_fallback_prefers_default_solver() {
+        let mut manager = flaky_manager(0, || CaptchaError::ProcessingError("onnx".to_string()));
+        assert!(manager.fallback_for("flaky").is_none());
+
+        manager.solvers.insert("cnn".to_string(), Arc::new(FixedSolver { name: "cnn", text: "AB12", confidence: 0.9 }));
+        manager.solvers.insert("ocr".to_string(), Arc::new(FixedSolver { name: "ocr", text: "AB12", confidence: 0.9 }));
+        assert_eq!(manager.fallback_for("flaky").map(|(name, _)| name), Some("ocr"));
+        assert_eq!(manager.fallback_for("ocr").map(|(name, _)| name), Some("cnn"));
+    }
+
+    #[tokio::test]
+    async fn test_default_strategy_routes_unnamed_requests() {
+        let mut manager = empty_manager();
+        manager.solvers.insert("ocr".to_string(), Arc::new(FixedSolver { name: "ocr", text: "AB12", confidence: 0.4 }));
+        manager.solvers.insert("cnn".to_string(), Arc::new(FixedSolver { name: "cnn", text: "XY34", confidence: 0.9 }));
+        let image = DynamicImage::new_luma8(10, 10);
+
+        let result = manager.solve(&image, None, None, &SolveHints::default()).await.unwrap();
+        assert_eq!(result.solver_name, "ocr");
+
+        manager.default_strategy = DefaultStrategy::Ensemble;
+        let result = manager.solve(&image, None, None, &SolveHints::default()).await.unwrap();
+        assert_eq!(result.solver_name, "cnn");
+
+        // A named solver is used as is whatever the strategy
+        let result = manager.solve(&image, Some("ocr"), None, &SolveHints::default()).await.unwrap();
+        assert_eq!(result.solver_name, "ocr");
+    }
+
+    #[tokio::test]
+    async fn test_fallback_strategy_stops_at_confident_answer() {
+        let mut manager = empty_manager();
+        manager.default_strategy = DefaultStrategy::Fallback;
+        manager.solvers.insert("ocr".to_string(), Arc::new(FixedSolver { name: "ocr", text: "AB12", confidence: 0.3 }));
+        manager.solvers.insert("cnn".to_string(), Arc::new(FixedSolver { name: "cnn", text: "XY34", confidence: 0.6 }));
+        manager.solvers.insert("http".to_string(), Arc::new(FixedSolver { name: "http", text: "QQ99", confidence: 0.95 }));
+        let image = DynamicImage::new_luma8(10, 10);
+
+        // ocr (default) is too unsure, cnn is next by name and good enough
+        let result = manager.solve(&image, None, None, &SolveHints::default()).await.unwrap();
+        assert_eq!(result.solver_name, "cnn");
+
+        // Nobody reaches the bar: the most confident answer wins
+        manager.fallback_min_confidence = 0.99;
+        let result = manager.solve(&image, None, None, &SolveHints::default()).await.unwrap();
+        assert_eq!(result.solver_name, "http");
+    }
+
+    #[tokio::test]
+    async fn test_fallback_strategy_skips_failing_solver() {
+        let mut manager = flaky_manager(1, || CaptchaError::ProcessingError("onnx".to_string()));
+        manager.default_strategy = DefaultStrategy::Fallback;
+        manager.default_solver = "flaky".to_string();
+        manager.solvers.insert("ocr".to_string(), Arc::new(FixedSolver { name: "ocr", text: "XY34", confidence: 0.7 }));
+        let image = DynamicImage::new_luma8(10, 10);
+
+        let result = manager.solve(&image, None, None, &SolveHints::default()).await.unwrap();
+        assert_eq!(result.text, "XY34");
+        assert_eq!(result.solver_name, "ocr");
+    }
+
+    #[tokio::test]
+    async fn test_solve_times_out_when_saturated() {
+        let manager = flaky_manager(0, || CaptchaError::Timeout("flaky".to_string()));
+        let image = DynamicImage::new_luma8(10, 10);
+
+        let _held = manager.solve_permits.acquire().await.unwrap();
+        let result = manager.solve(&image, Some("flaky"), None, &SolveHints::default()).await;
+        assert!(matches!(result, Err(CaptchaError::Timeout(name)) if name == "flaky"));
+    }
+
+    /// Solver that takes a fixed time to answer
+    struct SlowSolver {
+        delay: Duration,
+    }
+
+    #[async_trait::async_trait]
+    impl CaptchaSolver for SlowSolver {
+        async fn solve(
+            &self,
+            _image: &DynamicImage,
+            _options: Option<&PreprocessOptions>,
+            _hints: &SolveHints,
+        ) -> CaptchaResult<SolveResult> {
+            tokio::time::sleep(self.delay).await;
+            Ok(result("AB12", 0.9))
+        }
+
+        fn name(&self) -> &str {
+            "slow"
+        }
+
+        fn is_ready(&self) -> bool {
+            true
+        }
+    }
+
+    #[tokio::test]
+    async fn test_per_solver_timeout() {
+        let mut manager = empty_manager();
+        manager.permit_timeout = Duration::from_secs(5);
+        manager.solvers.insert("slow".to_string(), Arc::new(SlowSolver { delay: Duration::from_millis(200) }));
+        let image = DynamicImage::new_luma8(10, 10);
+
+        // Global timeout is long enough
+        assert!(manager.solve(&image, Some("slow"), None, &SolveHints::default()).await.is_ok());
+
+        manager.solver_timeouts.insert("slow".to_string(), Duration::from_millis(20));
+        let result = manager.solve(&image, Some("slow"), None, &SolveHints::default()).await;
+        assert!(matches!(result, Err(CaptchaError::Timeout(name)) if name == "slow"));
+    }
+
+    #[tokio::test]
+    async fn test_solve_sheds_load_when_queue_full() {
+        let manager = flaky_manager(0, || CaptchaError::Timeout("flaky".to_string()));
+        let image = DynamicImage::new_luma8(10, 10);
+
+        let _held = manager.solve_permits.acquire().await.unwrap();
+        manager.queued.store(1, Ordering::SeqCst);
+
+        let result = manager.solve(&image, Some("flaky"), None, &SolveHints::default()).await;
+        assert!(matches!(result, Err(CaptchaError::Overloaded(1))));
+        assert_eq!(manager.queued.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_permit_wait_leaves_queue() {
+        let mut manager = empty_manager();
+        manager.permit_timeout = Duration::from_secs(5);
+
+        let held = manager.solve_permits.acquire().await.unwrap();
+
+        // The caller goes away (client disconnect, route deadline) while still waiting
+        let waiting = tokio::time::timeout(Duration::from_millis(20), manager.acquire_permit("cnn")).await;
+        assert!(waiting.is_err());
+        assert_eq!(manager.queued.load(Ordering::SeqCst), 0);
+
+        // The queue still admits callers once the permit frees up
+        drop(held);
+        assert!(manager.acquire_permit("cnn").await.is_ok());
+        assert_eq!(manager.queued.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_finalize_top_k_candidates() {
+        let manager = empty_manager();
+        let mut solved = result("ab12", 0.9);
+        solved.candidates = vec![
+            Candidate { text: "ab12".to_string(), confidence: 0.9 },
+            Candidate { text: "AB-12".to_string(), confidence: 0.7 },
+            Candidate { text: "AB13".to_string(), confidence: 0.5 },
+            Candidate { text: "A813".to_string(), confidence: 0.3 },
+        ];
+
+        let hints = SolveHints { top_k: Some(2), ..Default::default() };
+        let texts: Vec<String> = manager.finalize(solved.clone(), &hints)
+            .candidates
+            .into_iter()
+            .map(|c| c.text)
+            .collect();
+        assert_eq!(texts, vec!["AB12", "AB13"]);
+
+        assert!(manager.finalize(solved, &SolveHints::default()).candidates.is_empty());
+    }
+
+    #[test]
+    fn test_finalize_top_k_single_result() {
+        let manager = empty_manager();
+        let hints = SolveHints { top_k: Some(3), ..Default::default() };
+
+        let candidates = manager.finalize(result("xy9", 0.6), &hints).candidates;
+        assert_eq!(candidates, vec![Candidate { text: "XY9".to_string(), confidence: 0.6 }]);
+
+        // Nothing is allocated up front for the requested count
+        let huge = SolveHints { top_k: Some(usize::MAX), ..Default::default() };
+        assert_eq!(manager.finalize(result("xy9", 0.6), &huge).candidates.len(), 1);
+    }
+
+    /// Solver that is only confident on adaptively thresholded input, counting its calls
+    struct VariantSensitiveSolver {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl CaptchaSolver for VariantSensitiveSolver {
+        async fn solve(
+            &self,
+            _image: &DynamicImage,
+            options: Option<&PreprocessOptions>,
+            _hints: &SolveHints,
+        ) -> CaptchaResult<SolveResult> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let adaptive = options.is_some_and(|o| o.adaptive_threshold.is_some());
+            Ok(result("AB12", if adaptive { 0.85 } else { 0.5 }))
+        }
+
+        fn name(&self) -> &str {
+            "variant"
+        }
+
+        fn is_ready(&self) -> bool {
+            true
+        }
+    }
+
+    fn auto_hints(target: f32, variants: Option<&[&str]>) -> SolveHints {
+        SolveHints {
+            strategy: SolveStrategy::AutoPreprocess,
+            auto_preprocess: Some(AutoPreprocessOptions {
+                variants: variants.map(|v| v.iter().map(|s| s.to_string()).collect()),
+                confidence_target: Some(target),
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_auto_preprocess() {
+        let image = DynamicImage::new_luma8(10, 10);
+        let solver = Arc::new(VariantSensitiveSolver { calls: AtomicUsize::new(0) });
+        let manager = empty_manager();
+        let permit = manager.acquire_permit("ocr").await.unwrap();
+
+        // Stops at the first variant reaching the target
+        let dyn_solver: Arc<dyn CaptchaSolver> = solver.clone();
+        let stopped = manager.solve_auto_preprocess(&permit, &dyn_solver, &image, None, &auto_hints(0.8, None)).await.unwrap();
+        assert_eq!(stopped.preprocess_variant.unwrap().name, "adaptive");
+        assert_eq!(solver.calls.swap(0, Ordering::SeqCst), 3);
+
+        // Falls back to the most confident variant
+        let best = manager.solve_auto_preprocess(&permit, &dyn_solver, &image, None, &auto_hints(0.95, None)).await.unwrap();
+        assert_eq!(best.preprocess_variant.unwrap().name, "adaptive");
+        assert_eq!(best.confidence, 0.85);
+        assert_eq!(solver.calls.swap(0, Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_solve_by_difficulty_reports_preset() {
+        // A uniform image is one component without noise: easy
+        let image = DynamicImage::new_luma8(10, 10);
+        let solver: Arc<dyn CaptchaSolver> = Arc::new(VariantSensitiveSolver { calls: AtomicUsize::new(0) });
+        let mut manager = empty_manager();
+        let hints = SolveHints { strategy: SolveStrategy::ByDifficulty, ..Default::default() };
+        let permit = manager.acquire_permit("ocr").await.unwrap();
+
+        let result = manager.solve_by_difficulty(&permit, &solver, &image, None, &hints).await.unwrap();
+        let variant = result.preprocess_variant.unwrap();
+        assert_eq!(variant.name, "raw");
+        assert_eq!(variant.difficulty.unwrap().level, Difficulty::Easy);
+        assert_eq!(result.confidence, 0.5);
+
+        manager.difficulty_presets = parse_difficulty_presets("adaptive,threshold,denoise_threshold");
+        let result = manager.solve_by_difficulty(&permit, &solver, &image, None, &hints).await.unwrap();
+        assert_eq!(result.preprocess_variant.unwrap().name, "adaptive");
+        assert_eq!(result.confidence, 0.85);
+    }
+
+    #[test]
+    fn test_parse_difficulty_presets() {
+        assert_eq!(parse_difficulty_presets("adaptive, raw ,threshold"), ["adaptive", "raw", "threshold"]);
+        // Anything but three known variants falls back to the defaults
+        let defaults = parse_difficulty_presets(preprocessor::DEFAULT_DIFFICULTY_PRESETS);
+        assert_eq!(defaults, ["raw", "threshold", "denoise_threshold"]);
+        assert_eq!(parse_difficulty_presets("raw,threshold"), defaults);
+        assert_eq!(parse_difficulty_presets("raw,sharpen,threshold"), defaults);
+    }
+
+    #[tokio::test]
+    async fn test_auto_preprocess_rejects_unknown_variant() {
+        let image = DynamicImage::new_luma8(10, 10);
+        let solver = Arc::new(VariantSensitiveSolver { calls: AtomicUsize::new(0) });
+        let hints = auto_hints(0.8, Some(&["raw", "sharpen"]));
+
+        let dyn_solver: Arc<dyn CaptchaSolver> = solver.clone();
+        let manager = empty_manager();
+        let permit = manager.acquire_permit("ocr").await.unwrap();
+        let result = manager.solve_auto_preprocess(&permit, &dyn_solver, &image, None, &hints).await;
+        assert!(matches!(result, Err(CaptchaError::BadRequest(_))));
+        assert_eq!(solver.calls.load(Ordering::SeqCst), 0);
+    }
+}
@@ -3,26 +3,83 @@
 //! This solver uses Tesseract OCR for text recognition in captcha images.
 
 use image::DynamicImage;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
 
 use crate::error::{CaptchaError, CaptchaResult};
-use crate::models::PreprocessOptions;
-use super::{CaptchaSolver, SolveResult};
+use crate::models::{Candidate, PreprocessOptions, SolverCapabilities};
+use super::{CaptchaSolver, SolveHints, SolveResult, DIGIT_CHARSET};
 use super::preprocessor::ImagePreprocessor;
 
+/// Tesseract language used when the request doesn't specify one
+pub const DEFAULT_LANGUAGE: &str = "eng";
+
+/// Directory holding the `<lang>.traineddata` files
+pub fn tessdata_path() -> PathBuf {
+    std::env::var("TESSDATA_PREFIX")
+        .unwrap_or_else(|_| "/usr/share/tesseract-ocr/4.00/tessdata".to_string())
+        .into()
+}
+
+/// Language codes with a traineddata file in `tessdata`, sorted
+pub fn installed_languages(tessdata: &Path) -> Vec<String> {
+    let mut languages: Vec<String> = std::fs::read_dir(tessdata)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    entry.file_name().to_str()?.strip_suffix(".traineddata").map(|lang| lang.to_string())
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    languages.sort();
+    languages
+}
+
+/// Check a language spec (`eng`, or `eng+chi_sim` to combine) against the installed traineddata
+pub fn validate_language(spec: &str, tessdata: &Path) -> CaptchaResult<()> {
+    let installed = installed_languages(tessdata);
+
+    for lang in spec.split('+') {
+        let well_formed = !lang.is_empty()
+            && lang.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !well_formed {
+            return Err(CaptchaError::BadRequest(format!("Invalid OCR language '{}'", spec)));
+        }
+
+        if !installed.iter().any(|installed| installed == lang) {
+            let available = if installed.is_empty() {
+                format!("none installed in {}", tessdata.display())
+            } else {
+                installed.join(", ")
+            };
+            return Err(CaptchaError::BadRequest(format!(
+                "Unknown OCR language '{}'; available: {}", lang, available
+            )));
+        }
+    }
+
+    Ok(())
+}
+
 /// OCR-based captcha solver using Tesseract
 pub struct OcrSolver {
     ready: AtomicBool,
     models_path: String,
+    /// Stroke width preprocessing aims for when the request sets none (0 disables)
+    stroke_width_target: f32,
 }
 
 impl OcrSolver {
     /// Create a new OCR solver
-    pub async fn new(models_path: &str) -> CaptchaResult<Self> {
+    pub async fn new(models_path: &str, stroke_width_target: f32) -> CaptchaResult<Self> {
         // Verify Tesseract is available
         let solver = Self {
             ready: AtomicBool::new(false),
             models_path: models_path.to_string(),
+            stroke_width_target,
         };
 
         // Try to initialize Tesseract
@@ -40,19 +97,28 @@ impl OcrSolver {
         // For now, we'll just check if the library is available
         
         // Check if tesseract data path exists
-        let tessdata_path = std::env::var("TESSDATA_PREFIX")
-            .unwrap_or_else(|_| "/usr/share/tesseract-ocr/4.00/tessdata".to_string());
+        let tessdata_path = tessdata_path();
 
-        if !std::path::Path::new(&tessdata_path).exists() {
-            tracing::warn!("Tesseract data path not found: {}", tessdata_path);
+        if !tessdata_path.exists() {
+            tracing::warn!("Tesseract data path not found: {}", tessdata_path.display());
             // Don't fail, just warn - we'll use mock for development
+        } else {
+            tracing::info!("Tesseract languages: {}", installed_languages(&tessdata_path).join(", "));
         }
 
         Ok(())
     }
 
-    /// Perform OCR on an image
-    fn perform_ocr(&self, image: &DynamicImage) -> CaptchaResult<(String, f32)> {
+    /// Perform OCR on an image with the given Tesseract language spec,
+    /// recognizing only the characters of `whitelist` when given.
+    /// `single_char` reads the image as one character (page segmentation mode 10).
+    fn perform_ocr(
+        &self,
+        image: &DynamicImage,
+        language: &str,
+        whitelist: Option<&str>,
+        single_char: bool,
+    ) -> CaptchaResult<(String, f32)> {
         // Convert image to grayscale
         let gray = image.to_luma8();
         
@@ -66,8 +132,18 @@ impl OcrSolver {
         {
             use tesseract::Tesseract;
             
-            let tess = Tesseract::new(None, Some("eng"))
+            let mut tess = Tesseract::new(None, Some(language))
                 .map_err(|e| CaptchaError::ModelLoadError(e.to_string()))?;
+            if let Some(whitelist) = whitelist {
+                tess = tess
+                    .set_variable("tessedit_char_whitelist", whitelist)
+                    .map_err(|e| CaptchaError::ProcessingError(e.to_string()))?;
+            }
+            if single_char {
+                tess = tess
+                    .set_variable("tessedit_pageseg_mode", "10")
+                    .map_err(|e| CaptchaError::ProcessingError(e.to_string()))?;
+            }
             
             // Set image data
             let result = tess
@@ -85,13 +161,46 @@ impl OcrSolver {
         #[cfg(not(feature = "tesseract"))]
         {
             // Simulate OCR by analyzing image characteristics
-            let text = self.mock_ocr(&gray);
+            let _ = (language, single_char);
+            let mut text = self.mock_ocr(&gray);
+            if let Some(whitelist) = whitelist {
+                text.retain(|c| whitelist.contains(c));
+            }
             let confidence = 0.85; // Mock confidence
             
             Ok((text, confidence))
         }
     }
 
+    /// Split the image into `count` character segments and OCR each as a single character.
+    /// `None` when the image can't be split that far or a segment reads nothing, so the
+    /// caller falls back to reading the whole image.
+    fn perform_segmented_ocr(
+        &self,
+        image: &DynamicImage,
+        count: usize,
+        language: &str,
+        whitelist: Option<&str>,
+    ) -> CaptchaResult<Option<(String, f32)>> {
+        let segments = ImagePreprocessor::segment_characters_into(image, count)?;
+        if segments.len() != count {
+            return Ok(None);
+        }
+
+        let mut text = String::with_capacity(count);
+        let mut confidence = 0.0;
+        for segment in &segments {
+            let (reading, segment_confidence) = self.perform_ocr(segment, language, whitelist, true)?;
+            let Some(c) = reading.chars().find(|c| !c.is_whitespace()) else {
+                return Ok(None);
+            };
+            text.push(c);
+            confidence += segment_confidence;
+        }
+
+        Ok(Some((text, confidence / count as f32)))
+    }
+
     /// Mock OCR for development/testing
     fn mock_ocr(&self, image: &image::GrayImage) -> String {
         // This is a placeholder that returns mock text
@@ -116,27 +225,54 @@ impl OcrSolver {
 
 #[async_trait::async_trait]
 impl CaptchaSolver for OcrSolver {
-    async fn solve(&self, image: &DynamicImage, options: Option<&PreprocessOptions>) -> CaptchaResult<SolveResult> {
+    async fn solve(
+        &self,
+        image: &DynamicImage,
+        options: Option<&PreprocessOptions>,
+        hints: &SolveHints,
+    ) -> CaptchaResult<SolveResult> {
         if !self.is_ready() {
             return Err(CaptchaError::ModelLoadError("OCR solver not ready".to_string()));
         }
 
         // Preprocess image
-        let processed = match options {
-            Some(opts) => ImagePreprocessor::preprocess(image, opts)?,
-            None => ImagePreprocessor::preprocess(image, &PreprocessOptions::default())?,
-        };
+        let preprocess_start = Instant::now();
+        let preprocess_opts = ImagePreprocessor::with_stroke_width(
+            options.cloned().unwrap_or_default(),
+            self.stroke_width_target,
+        );
+        let processed = ImagePreprocessor::preprocess(image, &preprocess_opts)?;
+        let preprocess_ms = preprocess_start.elapsed().as_millis() as u64;
 
         // Perform OCR
-        let (text, confidence) = self.perform_ocr(&processed)?;
+        let inference_start = Instant::now();
+        let language = hints.language.as_deref().unwrap_or(DEFAULT_LANGUAGE);
+        let whitelist = hints.digits_only().then_some(DIGIT_CHARSET);
+        // A known length lets each character be read on its own
+        let segmented = match hints.expected_length.filter(|&n| n > 0) {
+            Some(count) => self.perform_segmented_ocr(&processed, count, language, whitelist)?,
+            None => None,
+        };
+        let (text, confidence) = match segmented {
+            Some(reading) => reading,
+            None => self.perform_ocr(&processed, language, whitelist, false)?,
+        };
+        let inference_ms = inference_start.elapsed().as_millis() as u64;
 
-        // Post-process result
-        let cleaned_text = self.post_process(&text);
+        // Tesseract yields a single reading
+        let candidates = vec![Candidate { text: text.clone(), confidence }];
 
         Ok(SolveResult {
-            text: cleaned_text,
+            text,
             confidence,
             solver_name: self.name().to_string(),
+            length_mismatch: None,
+            preprocess_ms,
+            inference_ms,
+            candidates,
+            preprocess_variant: None,
+            processed_image: hints.include_processed_image.then_some(processed),
+            char_positions: None,
         })
     }
 
@@ -147,15 +283,22 @@ impl CaptchaSolver for OcrSolver {
     fn is_ready(&self) -> bool {
         self.ready.load(Ordering::SeqCst)
     }
-}
 
-impl OcrSolver {
-    /// Post-process OCR result
-    fn post_process(&self, text: &str) -> String {
-        text.chars()
-            .filter(|c| c.is_alphanumeric())
-            .map(|c| c.to_ascii_uppercase())
-            .collect()
+    fn is_mock(&self) -> bool {
+        cfg!(not(feature = "tesseract"))
+    }
+
+    fn capabilities(&self) -> SolverCapabilities {
+        SolverCapabilities {
+            // Tesseract reads any character of the language; images keep their own size
+            charset: None,
+            input_size: None,
+            top_k: false,
+            expected_length_hint: true,
+            per_char_confidence: false,
+            char_positions: false,
+            languages: installed_languages(&tessdata_path()),
+        }
     }
 }
 
@@ -163,15 +306,37 @@ impl OcrSolver {
 mod tests {
     use super::*;
 
+    fn tessdata_dir(languages: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("captcha-tessdata-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for lang in languages {
+            std::fs::write(dir.join(format!("{}.traineddata", lang)), b"").unwrap();
+        }
+        std::fs::write(dir.join("configs"), b"").unwrap();
+        dir
+    }
+
     #[test]
-    fn test_post_process() {
-        let solver = OcrSolver {
-            ready: AtomicBool::new(true),
-            models_path: "/tmp".to_string(),
-        };
+    fn test_installed_languages() {
+        let dir = tessdata_dir(&["eng", "chi_sim", "ara"]);
+        let languages = installed_languages(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(languages, vec!["ara", "chi_sim", "eng"]);
+    }
 
-        assert_eq!(solver.post_process("abc123"), "ABC123");
-        assert_eq!(solver.post_process("a b c"), "ABC");
-        assert_eq!(solver.post_process("AB-CD_12"), "ABCD12");
+    #[test]
+    fn test_validate_language() {
+        let dir = tessdata_dir(&["eng", "chi_sim"]);
+
+        assert!(validate_language("eng", &dir).is_ok());
+        assert!(validate_language("eng+chi_sim", &dir).is_ok());
+
+        let unknown = validate_language("jpn", &dir).unwrap_err().to_string();
+        assert!(unknown.contains("jpn") && unknown.contains("chi_sim, eng"), "{}", unknown);
+        assert!(matches!(validate_language("../eng", &dir), Err(CaptchaError::BadRequest(_))));
+        assert!(matches!(validate_language("eng+", &dir), Err(CaptchaError::BadRequest(_))));
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,182 @@
+//! Inference Thread Pool
+//!
+//! Solving is CPU-bound (preprocessing, Tesseract, ONNX inference). Run on the
+//! async workers it stalls every other request scheduled on the same thread, and
+//! tokio's blocking pool (up to 512 threads, shared with file and database work)
+//! would oversubscribe the CPU. Solves run instead on a dedicated pool with a
+//! fixed number of threads, sized to the CPU count by default.
+
+use std::sync::Arc;
+
+use image::DynamicImage;
+use tokio::sync::OwnedSemaphorePermit;
+
+use super::{CaptchaSolver, SolveHints, SolveResult};
+use crate::error::{CaptchaError, CaptchaResult};
+use crate::models::PreprocessOptions;
+
+/// Solve slot held by a request. Each job it runs on the pool keeps a share, so the
+/// slot is released only once the request and all of its inference are done.
+pub type SolvePermit = Arc<OwnedSemaphorePermit>;
+
+/// Fixed-size thread pool running solver inference
+pub struct InferencePool {
+    pool: rayon::ThreadPool,
+}
+
+impl InferencePool {
+    pub fn new(threads: usize) -> CaptchaResult<Self> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("inference-{}", i))
+            // Without a handler a panicking solve would abort the process
+            .panic_handler(|_| tracing::error!("Solver panicked on an inference thread"))
+            .build()
+            .map_err(|e| CaptchaError::ModelLoadError(format!("Failed to start inference pool: {}", e)))?;
+
+        Ok(Self { pool })
+    }
+
+    /// Number of inference threads
+    pub fn threads(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    /// Run a solve on the pool and wait for it without blocking the async runtime.
+    /// A solve its caller stopped waiting for (e.g. on timeout) still runs to completion on
+    /// its thread, holding its share of `permit` until then.
+    pub async fn solve(
+        &self,
+        solver: Arc<dyn CaptchaSolver>,
+        image: DynamicImage,
+        options: Option<PreprocessOptions>,
+        hints: SolveHints,
+        permit: SolvePermit,
+    ) -> CaptchaResult<SolveResult> {
+        let runtime = tokio::runtime::Handle::current();
+        let span = tracing::Span::current();
+        let (tx, rx) = tokio::sync::oneshot::channel();
+
+        self.pool.spawn(move || {
+            let _entered = span.enter();
+            let result = runtime.block_on(solver.solve(&image, options.as_ref(), &hints));
+            drop(permit);
+            let _ = tx.send(result);
+        });
+
+        rx.await.map_err(|_| CaptchaError::ProcessingError(
+            "Solver panicked during inference".to_string()
+        ))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::sync::mpsc::{self, Receiver};
+    use std::time::Duration;
+    use tokio::sync::Semaphore;
+
+    fn permit(permits: &Arc<Semaphore>) -> SolvePermit {
+        Arc::new(Arc::clone(permits).try_acquire_owned().unwrap())
+    }
+
+    /// Solver answering with the name of the thread it runs on, or panicking
+    struct ThreadNameSolver {
+        panic: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl CaptchaSolver for ThreadNameSolver {
+        async fn solve(
+            &self,
+            _image: &DynamicImage,
+            _options: Option<&PreprocessOptions>,
+            _hints: &SolveHints,
+        ) -> CaptchaResult<SolveResult> {
+            assert!(!self.panic, "solver bug");
+            Ok(SolveResult {
+                text: std::thread::current().name().unwrap_or_default().to_string(),
+                confidence: 0.9,
+                solver_name: "thread".to_string(),
+                length_mismatch: None,
+                preprocess_ms: 0,
+                inference_ms: 0,
+                candidates: Vec::new(),
+                preprocess_variant: None,
+                processed_image: None,
+                char_positions: None,
+            })
+        }
+
+        fn name(&self) -> &str {
+            "thread"
+        }
+
+        fn is_ready(&self) -> bool {
+            true
+        }
+    }
+
+    #[tokio::test]
+    async fn test_solves_run_on_inference_threads() {
+        let pool = InferencePool::new(2).unwrap();
+        assert_eq!(pool.threads(), 2);
+        let image = DynamicImage::new_luma8(10, 10);
+
+        let solver = Arc::new(ThreadNameSolver { panic: false });
+        let permits = Arc::new(Semaphore::new(2));
+        let result = pool.solve(solver, image.clone(), None, SolveHints::default(), permit(&permits)).await.unwrap();
+        assert!(result.text.starts_with("inference-"), "{}", result.text);
+
+        // A panicking solver fails its request, not the process
+        let panicking = Arc::new(ThreadNameSolver { panic: true });
+        let result = pool.solve(panicking, image, None, SolveHints::default(), permit(&permits)).await;
+        assert!(matches!(result, Err(CaptchaError::ProcessingError(_))));
+        assert_eq!(permits.available_permits(), 2);
+    }
+
+    /// Solver blocking its inference thread until released
+    struct BlockingSolver {
+        release: Mutex<Receiver<()>>,
+    }
+
+    #[async_trait::async_trait]
+    impl CaptchaSolver for BlockingSolver {
+        async fn solve(
+            &self,
+            _image: &DynamicImage,
+            _options: Option<&PreprocessOptions>,
+            _hints: &SolveHints,
+        ) -> CaptchaResult<SolveResult> {
+            let _ = self.release.lock().unwrap().recv();
+            Err(CaptchaError::ProcessingError("released".to_string()))
+        }
+
+        fn name(&self) -> &str {
+            "blocking"
+        }
+
+        fn is_ready(&self) -> bool {
+            true
+        }
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_solve_holds_permit_until_it_finishes() {
+        let pool = InferencePool::new(1).unwrap();
+        let permits = Arc::new(Semaphore::new(1));
+        let (release, wait) = mpsc::channel();
+        let solver = Arc::new(BlockingSolver { release: Mutex::new(wait) });
+
+        // The caller stops waiting, as on a solve timeout, and lets go of its share
+        let solve = pool.solve(solver, DynamicImage::new_luma8(10, 10), None, SolveHints::default(), permit(&permits));
+        assert!(tokio::time::timeout(Duration::from_millis(20), solve).await.is_err());
+        assert_eq!(permits.available_permits(), 0);
+
+        release.send(()).unwrap();
+        let freed = tokio::time::timeout(Duration::from_secs(5), Arc::clone(&permits).acquire_owned()).await;
+        assert!(freed.is_ok());
+    }
+}
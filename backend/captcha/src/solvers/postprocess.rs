@@ -0,0 +1,157 @@
+//! Solver Output Post-processing
+//!
+//! This module normalizes raw solver output according to the alphabet the
+//! caller expects, fixing common OCR confusions (O/0, I/1, S/5, ...).
+//! Whitespace is removed by default; captchas whose answer contains spaces can
+//! keep it, as is or collapsed to single spaces (see [`WhitespaceMode`]).
+
+use std::collections::HashMap;
+
+use crate::models::{CaseMode, NormalizeMode, WhitespaceMode};
+
+/// Default letter → digit substitutions used for digits-only captchas
+pub const DEFAULT_DIGIT_SUBSTITUTIONS: &str = "O0,Q0,D0,I1,L1,Z2,S5,G6,T7,B8";
+
+/// Normalizes solver output text
+#[derive(Debug, Clone)]
+pub struct TextNormalizer {
+    digit_substitutions: HashMap<char, char>,
+}
+
+impl TextNormalizer {
+    /// Create a normalizer from a substitution spec like `"O0,I1,S5"`.
+    /// Each comma-separated entry is a source character followed by its replacement.
+    pub fn from_spec(spec: &str) -> Self {
+        let digit_substitutions = spec
+            .split(',')
+            .filter_map(|pair| {
+                let mut chars = pair.trim().chars();
+                match (chars.next(), chars.next(), chars.next()) {
+                    (Some(from), Some(to), None) => Some((from.to_ascii_uppercase(), to)),
+                    _ => {
+                        if !pair.trim().is_empty() {
+                            tracing::warn!("Ignoring invalid substitution entry: {:?}", pair);
+                        }
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        Self { digit_substitutions }
+    }
+
+    /// Normalize text according to the given mode; `case` applies to `alnum` only and
+    /// `whitespace` to every mode but `none`
+    pub fn normalize(&self, text: &str, mode: NormalizeMode, case: CaseMode, whitespace: WhitespaceMode) -> String {
+        let keep_space = whitespace != WhitespaceMode::Strip;
+        let text: String = match mode {
+            NormalizeMode::None => return text.to_string(),
+            NormalizeMode::Alnum => {
+                let alnum = text.chars().filter(|c| c.is_alphanumeric() || (keep_space && c.is_whitespace()));
+                match case {
+                    CaseMode::Upper => alnum.map(|c| c.to_ascii_uppercase()).collect(),
+                    CaseMode::Lower => alnum.map(|c| c.to_ascii_lowercase()).collect(),
+                    CaseMode::Preserve => alnum.collect(),
+                }
+            }
+            NormalizeMode::UpperAlnum => return self.normalize(text, NormalizeMode::Alnum, CaseMode::Upper, whitespace),
+            NormalizeMode::Digits => text
+                .chars()
+                .map(|c| c.to_ascii_uppercase())
+                .map(|c| self.digit_substitutions.get(&c).copied().unwrap_or(c))
+                .filter(|c| c.is_ascii_digit() || (keep_space && c.is_whitespace()))
+                .collect(),
+        };
+
+        match whitespace {
+            WhitespaceMode::Collapse => text.split_whitespace().collect::<Vec<_>>().join(" "),
+            WhitespaceMode::Strip | WhitespaceMode::Preserve => text,
+        }
+    }
+
+    /// Whether a prediction matches the actual text once both are normalized.
+    /// Whitespace is ignored, so labels need not reproduce the spacing of the answer.
+    pub fn matches(&self, predicted: &str, actual: &str, mode: NormalizeMode, case: CaseMode) -> bool {
+        let normalize = |text| self.normalize(text, mode, case, WhitespaceMode::Strip);
+        normalize(predicted) == normalize(actual)
+    }
+}
+
+impl Default for TextNormalizer {
+    fn default() -> Self {
+        Self::from_spec(DEFAULT_DIGIT_SUBSTITUTIONS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upper_alnum() {
+        let normalizer = TextNormalizer::default();
+
+        assert_eq!(normalizer.normalize("abc123", NormalizeMode::UpperAlnum, CaseMode::Upper, WhitespaceMode::Strip), "ABC123");
+        assert_eq!(normalizer.normalize("a b c", NormalizeMode::UpperAlnum, CaseMode::Upper, WhitespaceMode::Strip), "ABC");
+        assert_eq!(normalizer.normalize("AB-CD_12", NormalizeMode::UpperAlnum, CaseMode::Upper, WhitespaceMode::Strip), "ABCD12");
+    }
+
+    #[test]
+    fn test_alnum_case() {
+        let normalizer = TextNormalizer::default();
+
+        assert_eq!(normalizer.normalize("aB-c1", NormalizeMode::Alnum, CaseMode::Upper, WhitespaceMode::Strip), "ABC1");
+        assert_eq!(normalizer.normalize("aB-c1", NormalizeMode::Alnum, CaseMode::Lower, WhitespaceMode::Strip), "abc1");
+        assert_eq!(normalizer.normalize("aB-c1", NormalizeMode::Alnum, CaseMode::Preserve, WhitespaceMode::Strip), "aBc1");
+        assert_eq!(normalizer.normalize("aB-c1", NormalizeMode::UpperAlnum, CaseMode::Preserve, WhitespaceMode::Strip), "ABC1");
+        assert_eq!(normalizer.normalize("a-O1", NormalizeMode::Digits, CaseMode::Preserve, WhitespaceMode::Strip), "01");
+    }
+
+    #[test]
+    fn test_digits() {
+        let normalizer = TextNormalizer::default();
+
+        assert_eq!(normalizer.normalize("1O2l3S", NormalizeMode::Digits, CaseMode::Upper, WhitespaceMode::Strip), "102135");
+        assert_eq!(normalizer.normalize("4-x-7", NormalizeMode::Digits, CaseMode::Upper, WhitespaceMode::Strip), "47");
+    }
+
+    #[test]
+    fn test_none_keeps_raw_text() {
+        let normalizer = TextNormalizer::default();
+
+        assert_eq!(normalizer.normalize(" aB-1 ", NormalizeMode::None, CaseMode::Upper, WhitespaceMode::Strip), " aB-1 ");
+    }
+
+    #[test]
+    fn test_whitespace_modes() {
+        let normalizer = TextNormalizer::default();
+        let text = " ab -  cd\t1 ";
+
+        assert_eq!(normalizer.normalize(text, NormalizeMode::Alnum, CaseMode::Upper, WhitespaceMode::Strip), "ABCD1");
+        assert_eq!(normalizer.normalize(text, NormalizeMode::Alnum, CaseMode::Upper, WhitespaceMode::Collapse), "AB CD 1");
+        assert_eq!(normalizer.normalize(text, NormalizeMode::Alnum, CaseMode::Upper, WhitespaceMode::Preserve), " AB   CD\t1 ");
+        assert_eq!(normalizer.normalize("1O 2-3", NormalizeMode::Digits, CaseMode::Upper, WhitespaceMode::Collapse), "10 23");
+        // `none` keeps the text unchanged whatever the whitespace mode
+        assert_eq!(normalizer.normalize(" a  b ", NormalizeMode::None, CaseMode::Upper, WhitespaceMode::Collapse), " a  b ");
+        // Matching ignores spacing
+        assert!(normalizer.matches("AB CD", "abcd", NormalizeMode::Alnum, CaseMode::Upper));
+    }
+
+    #[test]
+    fn test_custom_spec() {
+        let normalizer = TextNormalizer::from_spec("A4, bad ,E3");
+
+        assert_eq!(normalizer.normalize("AEO", NormalizeMode::Digits, CaseMode::Upper, WhitespaceMode::Strip), "43");
+    }
+
+    #[test]
+    fn test_matches() {
+        let normalizer = TextNormalizer::default();
+
+        assert!(normalizer.matches("ab-12", "AB12", NormalizeMode::UpperAlnum, CaseMode::Upper));
+        assert!(!normalizer.matches("ab-12", "AB12", NormalizeMode::None, CaseMode::Upper));
+        assert!(normalizer.matches("1O5", "105", NormalizeMode::Digits, CaseMode::Upper));
+        assert!(!normalizer.matches("aB12", "AB12", NormalizeMode::Alnum, CaseMode::Preserve));
+    }
+}
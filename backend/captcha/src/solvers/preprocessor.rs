@@ -2,128 +2,797 @@
 //!
 //! This module provides image preprocessing utilities for captcha solving.
 
-use image::{DynamicImage, GrayImage, ImageBuffer, Luma, Rgb};
-use imageproc::contrast::{adaptive_threshold, threshold};
+use std::borrow::Cow;
+
+use image::{DynamicImage, GrayImage, ImageBuffer, ImageFormat, Luma, Pixel, Rgb, RgbImage};
+use imageproc::contrast::{adaptive_threshold, otsu_level, threshold};
 use imageproc::filter::{gaussian_blur_f32, median_filter};
 use imageproc::morphology::{dilate, erode};
-use imageproc::distance_transform::Norm;
+use imageproc::distance_transform::{distance_transform, Norm};
 
 use crate::error::{CaptchaError, CaptchaResult};
-use crate::models::PreprocessOptions;
+use crate::models::{ColorSpace, ComponentBox, DenoiseMethod, Difficulty, DifficultyEstimate, PreprocessOptions};
+use crate::validation::Validator;
+
+/// Preprocessing variants tried by the `auto_preprocess` strategy, in order
+pub const DEFAULT_AUTO_PREPROCESS_VARIANTS: &str = "raw,threshold,adaptive,denoise_threshold";
+
+/// Upload formats accepted by default: the usual captcha formats, all decodable in this build
+pub const DEFAULT_ALLOWED_IMAGE_FORMATS: &str = "png,jpeg,gif,bmp,webp";
+
+/// Preprocessing variants used by the `by_difficulty` strategy for easy, medium and hard captchas
+pub const DEFAULT_DIFFICULTY_PRESETS: &str = "raw,threshold,denoise_threshold";
+
+/// Dark components of at most this many pixels count as noise specks in difficulty estimates
+const SPECK_MAX_AREA: u32 = 4;
+
+/// Character-sized component counts that [`ImagePreprocessor::quality`] finds fully plausible
+const QUALITY_COMPONENTS: std::ops::RangeInclusive<usize> = 3..=10;
+
+/// Images are scaled down to this larger side before [`ImagePreprocessor::quality`] scores them
+const QUALITY_MAX_DIMENSION: u32 = 400;
+
+/// Step names accepted in `PreprocessOptions::steps`
+pub const PREPROCESS_STEPS: &[&str] = &[
+    "grayscale",
+    "color_filter",
+    "denoise",
+    "median",
+    "contrast",
+    "threshold",
+    "adaptive_threshold",
+    "remove_lines",
+    "erode",
+    "dilate",
+    "normalize_stroke",
+    "thin",
+];
+
+/// Character segmentation parameters
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Segmentation {
+    /// Narrower ink columns are dropped as noise
+    pub min_width: u32,
+    /// Segments whose ink spans fewer rows are dropped (stray dots, speckles)
+    pub min_height: u32,
+    /// Segments with fewer dark pixels are dropped
+    pub min_pixels: u32,
+    /// Segments wider than this multiple of the median segment width are split
+    pub split_width_ratio: f32,
+}
+
+/// Adjustments applied once to every decoded input image, before any preprocessing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputOptions {
+    /// Color transparent pixels are composited over
+    pub alpha_background: [u8; 3],
+    /// Inputs whose larger side exceeds this are downscaled to it (0 disables)
+    pub max_dimension: u32,
+    /// Formats an upload may be in; others are rejected before decoding
+    pub allowed_formats: Vec<ImageFormat>,
+}
+
+impl Default for InputOptions {
+    fn default() -> Self {
+        Self {
+            alpha_background: [255, 255, 255],
+            max_dimension: ImagePreprocessor::DEFAULT_MAX_INPUT_DIMENSION,
+            allowed_formats: parse_image_formats(DEFAULT_ALLOWED_IMAGE_FORMATS).unwrap_or_default(),
+        }
+    }
+}
+
+/// Parse a comma-separated list of image formats named by file extension (`png,jpg,webp`)
+pub fn parse_image_formats(spec: &str) -> Result<Vec<ImageFormat>, String> {
+    let mut formats = Vec::new();
+    for name in spec.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        let format = ImageFormat::from_extension(name)
+            .ok_or_else(|| format!("Unknown image format '{}'", name))?;
+        if !formats.contains(&format) {
+            formats.push(format);
+        }
+    }
+    if formats.is_empty() {
+        return Err("At least one image format must be allowed".to_string());
+    }
+    Ok(formats)
+}
+
+/// Lowercase name of an image format, as written in `processing.allowed_image_formats`
+pub fn image_format_name(format: ImageFormat) -> String {
+    format!("{:?}", format).to_lowercase()
+}
+
+impl Default for Segmentation {
+    fn default() -> Self {
+        Self {
+            min_width: ImagePreprocessor::DEFAULT_MIN_CHAR_WIDTH,
+            min_height: ImagePreprocessor::DEFAULT_MIN_CHAR_HEIGHT,
+            min_pixels: ImagePreprocessor::DEFAULT_MIN_CHAR_PIXELS,
+            split_width_ratio: ImagePreprocessor::DEFAULT_SPLIT_WIDTH_RATIO,
+        }
+    }
+}
+
+impl Segmentation {
+    pub fn validate(&self) -> CaptchaResult<()> {
+        if self.min_width == 0 || self.min_height == 0 {
+            return Err(CaptchaError::BadRequest("Minimum character width and height must be positive".to_string()));
+        }
+        if self.split_width_ratio.is_nan() || self.split_width_ratio <= 1.0 {
+            return Err(CaptchaError::BadRequest("Split width ratio must be greater than 1".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Grayscale intensity distribution of an image
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    /// Number of pixels at each intensity, 0 (black) to 255 (white)
+    pub bins: [u32; 256],
+    /// Otsu's binarization level
+    pub otsu_threshold: u8,
+    /// Mean intensity
+    pub mean: f64,
+    /// Population standard deviation of the intensity
+    pub stddev: f64,
+}
+
+/// Limits separating easy, medium and hard captchas in [`ImagePreprocessor::estimate_difficulty`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DifficultyThresholds {
+    /// Easy captchas have at most this noise ratio...
+    pub easy_max_noise: f32,
+    /// ...and at most this many components
+    pub easy_max_components: usize,
+    /// Captchas with at least this noise ratio, or at least this many components, are hard
+    pub hard_min_noise: f32,
+    pub hard_min_components: usize,
+}
+
+impl Default for DifficultyThresholds {
+    fn default() -> Self {
+        Self { easy_max_noise: 0.02, easy_max_components: 8, hard_min_noise: 0.1, hard_min_components: 16 }
+    }
+}
+
+impl DifficultyThresholds {
+    pub fn classify(&self, noise: f32, components: usize) -> Difficulty {
+        if noise >= self.hard_min_noise || components >= self.hard_min_components {
+            Difficulty::Hard
+        } else if noise <= self.easy_max_noise && components <= self.easy_max_components {
+            Difficulty::Easy
+        } else {
+            Difficulty::Medium
+        }
+    }
+}
 
 /// Image preprocessor for captcha images
 pub struct ImagePreprocessor;
 
 impl ImagePreprocessor {
-    /// Preprocess an image according to the given options
+    /// Default color distance used when a color filter has no explicit tolerance
+    const DEFAULT_COLOR_TOLERANCE: f32 = 60.0;
+
+    /// Saturation below which a pixel is considered gray and has no meaningful hue
+    const MIN_HUE_SATURATION: f32 = 0.15;
+
+    /// Segments wider than this multiple of the median segment width are treated as touching characters
+    pub const DEFAULT_SPLIT_WIDTH_RATIO: f32 = 1.8;
+
+    /// Default minimum segment width; low enough to keep thin glyphs like `I` and `1`
+    pub const DEFAULT_MIN_CHAR_WIDTH: u32 = 1;
+
+    /// Default minimum height of a segment's ink
+    pub const DEFAULT_MIN_CHAR_HEIGHT: u32 = 4;
+
+    /// Default minimum number of dark pixels in a segment
+    pub const DEFAULT_MIN_CHAR_PIXELS: u32 = 6;
+
+    /// Default threshold level for a `threshold` step without an explicit `threshold`
+    const DEFAULT_THRESHOLD: u8 = 128;
+
+    /// Default block radius for an `adaptive_threshold` step without an explicit value
+    const DEFAULT_ADAPTIVE_RADIUS: u32 = 7;
+
+    /// Default cap on the larger side of input images; captchas are far smaller, so
+    /// only oversized uploads (photos, full screenshots) are downscaled
+    pub const DEFAULT_MAX_INPUT_DIMENSION: u32 = 2048;
+
+    /// Largest accepted `resize_width` / `resize_height`
+    pub const MAX_RESIZE_DIMENSION: u32 = 4096;
+
+    /// Largest accepted adaptive threshold block radius
+    pub const MAX_ADAPTIVE_RADIUS: u32 = 64;
+
+    /// Largest RGB distance between two colors (black to white)
+    const MAX_RGB_TOLERANCE: f32 = 441.7;
+
+    /// Largest hue distance in degrees
+    const MAX_HUE_TOLERANCE: f32 = 180.0;
+
+    /// Default target for a `normalize_stroke` step without an explicit `stroke_width`
+    pub const DEFAULT_STROKE_WIDTH: f32 = 3.0;
+
+    /// Largest accepted `stroke_width` target
+    pub const MAX_STROKE_WIDTH: f32 = 16.0;
+
+    /// Default `denoise_strength`: the sigma of the original fixed Gaussian blur
+    const DEFAULT_DENOISE_STRENGTH: f32 = 1.0;
+
+    /// Largest accepted `denoise_strength`
+    pub const MAX_DENOISE_STRENGTH: f32 = 8.0;
+
+    /// Intensity difference scale of the bilateral filter: neighbors differing by much
+    /// more (ink next to background) barely contribute, so stroke edges stay sharp
+    const BILATERAL_SIGMA_COLOR: f32 = 40.0;
+
+    /// Largest bilateral neighborhood radius (11x11 taps per pixel), however large the
+    /// spatial sigma; the filter is quadratic in it and runs on the solve path
+    pub const MAX_BILATERAL_RADIUS: u32 = 5;
+
+    /// Preprocess an image according to the given options.
+    /// With `steps`, the named steps run in the given order after resizing;
+    /// otherwise the fixed pipeline driven by the individual flags runs.
+    /// The operations applied are logged at debug level.
     pub fn preprocess(image: &DynamicImage, options: &PreprocessOptions) -> CaptchaResult<DynamicImage> {
+        let (result, applied) = Self::preprocess_traced(image, options)?;
+        tracing::debug!(
+            steps = %applied.join(" -> "),
+            custom_order = options.steps.is_some(),
+            "Preprocessing applied"
+        );
+        Ok(result)
+    }
+
+    /// Preprocess like [`Self::preprocess`], also returning each operation applied
+    /// with its effective parameters, e.g. `threshold(level=128)`
+    pub fn preprocess_traced(image: &DynamicImage, options: &PreprocessOptions) -> CaptchaResult<(DynamicImage, Vec<String>)> {
+        Self::validate(options)?;
         let mut result = image.clone();
+        let mut applied = Vec::new();
 
         // Resize if dimensions specified
         if let (Some(width), Some(height)) = (options.resize_width, options.resize_height) {
             result = result.resize_exact(width, height, image::imageops::FilterType::Lanczos3);
+            applied.push(format!("resize(width={}, height={}, filter=lanczos3)", width, height));
+        }
+
+        if let Some(steps) = &options.steps {
+            for step in steps {
+                result = Self::apply_step(&result, step, options)?;
+                applied.push(Self::describe_step(step, options));
+            }
+            return Ok((result, applied));
         }
 
-        // Convert to grayscale if requested
-        if options.grayscale.unwrap_or(true) {
+        // Isolate foreground by color (must run before grayscale conversion)
+        if let Some(filter) = &options.color_filter {
+            let tolerance = filter.tolerance.unwrap_or(Self::DEFAULT_COLOR_TOLERANCE);
+            result = match filter.space.unwrap_or_default() {
+                ColorSpace::Rgb => Self::extract_by_color(&result, filter.target, tolerance)?,
+                ColorSpace::Hsv => Self::extract_by_hue(&result, filter.target, tolerance)?,
+            };
+            applied.push(Self::describe_step("color_filter", options));
+        }
+
+        // Convert to grayscale if requested and not already single-channel
+        if options.grayscale.unwrap_or(true) && !matches!(result, DynamicImage::ImageLuma8(_)) {
             result = DynamicImage::ImageLuma8(result.to_luma8());
+            applied.push(Self::describe_step("grayscale", options));
         }
 
         // Apply denoising if requested
         if options.denoise.unwrap_or(false) {
-            result = Self::denoise(&result)?;
+            result = Self::denoise(&result, options)?;
+            applied.push(Self::describe_step("denoise", options));
+        }
+
+        // Apply adaptive threshold if specified
+        if let Some(block_radius) = options.adaptive_threshold {
+            result = Self::adaptive_threshold(&result, block_radius)?;
+            applied.push(Self::describe_step("adaptive_threshold", options));
         }
 
         // Apply threshold if specified
         if let Some(thresh_value) = options.threshold {
             result = Self::apply_threshold(&result, thresh_value)?;
+            applied.push(Self::describe_step("threshold", options));
         }
 
-        Ok(result)
+        // Push the average stroke width toward the target
+        if let Some(target) = options.stroke_width.filter(|&target| target > 0.0) {
+            result = Self::normalize_stroke_width(&result, target)?;
+            applied.push(Self::describe_step("normalize_stroke", options));
+        }
+
+        // Thin strokes to a 1px skeleton if requested
+        if options.thin.unwrap_or(false) {
+            result = Self::thin(&result)?;
+            applied.push(Self::describe_step("thin", options));
+        }
+
+        Ok((result, applied))
+    }
+
+    /// A named step with the parameters it runs with under `options`
+    fn describe_step(step: &str, options: &PreprocessOptions) -> String {
+        match step {
+            "color_filter" => match &options.color_filter {
+                Some(filter) => format!(
+                    "color_filter(space={}, target={:?}, tolerance={})",
+                    match filter.space.unwrap_or_default() {
+                        ColorSpace::Rgb => "rgb",
+                        ColorSpace::Hsv => "hsv",
+                    },
+                    filter.target,
+                    filter.tolerance.unwrap_or(Self::DEFAULT_COLOR_TOLERANCE),
+                ),
+                None => step.to_string(),
+            },
+            "denoise" => {
+                let strength = Self::denoise_strength(options);
+                match options.denoise_method.unwrap_or_default() {
+                    DenoiseMethod::Gaussian => format!("denoise(gaussian_sigma={})", strength),
+                    DenoiseMethod::Median => format!("denoise(median_radius={})", Self::median_radius(strength)),
+                    DenoiseMethod::Bilateral => format!(
+                        "denoise(bilateral_sigma_space={}, sigma_color={})",
+                        strength, Self::BILATERAL_SIGMA_COLOR,
+                    ),
+                }
+            }
+            "median" | "erode" | "dilate" => format!("{}(radius=1)", step),
+            "threshold" => format!("threshold(level={})", options.threshold.unwrap_or(Self::DEFAULT_THRESHOLD)),
+            "adaptive_threshold" => format!(
+                "adaptive_threshold(block_radius={})",
+                options.adaptive_threshold.unwrap_or(Self::DEFAULT_ADAPTIVE_RADIUS),
+            ),
+            "normalize_stroke" => format!("normalize_stroke(target={})", Self::stroke_width_target(options)),
+            _ => step.to_string(),
+        }
+    }
+
+    /// Reject options that can't run: unknown step names, a `color_filter` step without a filter,
+    /// resize dimensions outside 1..=[`Self::MAX_RESIZE_DIMENSION`] or only one of them set,
+    /// and out-of-range adaptive threshold radius, color tolerance, stroke width or denoise strength
+    pub fn validate(options: &PreprocessOptions) -> CaptchaResult<()> {
+        let mut v = Validator::new();
+        Self::check(options, &mut v);
+        v.finish()
+    }
+
+    /// Record every invalid field of `options` in `v`, see [`Self::validate`]
+    pub fn check(options: &PreprocessOptions, v: &mut Validator) {
+        match (options.resize_width, options.resize_height) {
+            (Some(width), Some(height)) => {
+                for (name, value) in [("resize_width", width), ("resize_height", height)] {
+                    v.check(
+                        (1..=Self::MAX_RESIZE_DIMENSION).contains(&value),
+                        name,
+                        format!("must be between 1 and {}, got {}", Self::MAX_RESIZE_DIMENSION, value),
+                    );
+                }
+            }
+            (None, None) => {}
+            (None, Some(_)) => v.add("resize_width", "must be set together with resize_height"),
+            (Some(_), None) => v.add("resize_height", "must be set together with resize_width"),
+        }
+
+        if let Some(radius) = options.adaptive_threshold {
+            v.check(
+                (1..=Self::MAX_ADAPTIVE_RADIUS).contains(&radius),
+                "adaptive_threshold",
+                format!("must be between 1 and {}, got {}", Self::MAX_ADAPTIVE_RADIUS, radius),
+            );
+        }
+
+        if let Some(filter) = &options.color_filter {
+            let max = match filter.space.unwrap_or_default() {
+                ColorSpace::Rgb => Self::MAX_RGB_TOLERANCE,
+                ColorSpace::Hsv => Self::MAX_HUE_TOLERANCE,
+            };
+            if let Some(tolerance) = filter.tolerance {
+                v.check(
+                    (0.0..=max).contains(&tolerance),
+                    "color_filter.tolerance",
+                    format!("must be between 0 and {}, got {}", max, tolerance),
+                );
+            }
+        }
+
+        if let Some(width) = options.stroke_width {
+            v.check(
+                (0.0..=Self::MAX_STROKE_WIDTH).contains(&width),
+                "stroke_width",
+                format!("must be between 0 and {}, got {}", Self::MAX_STROKE_WIDTH, width),
+            );
+        }
+
+        if let Some(strength) = options.denoise_strength {
+            v.check(
+                strength > 0.0 && strength <= Self::MAX_DENOISE_STRENGTH,
+                "denoise_strength",
+                format!("must be greater than 0 and at most {}, got {}", Self::MAX_DENOISE_STRENGTH, strength),
+            );
+        }
+
+        for (i, step) in options.steps.iter().flatten().enumerate() {
+            let field = format!("steps[{}]", i);
+            if !PREPROCESS_STEPS.contains(&step.as_str()) {
+                v.add(field, format!(
+                    "unknown step '{}'; expected one of: {}", step, PREPROCESS_STEPS.join(", ")
+                ));
+            } else if step == "color_filter" && options.color_filter.is_none() {
+                v.add(field, "step 'color_filter' requires `color_filter` options");
+            }
+        }
+    }
+
+    /// Run one named step, taking its parameters from `options`
+    fn apply_step(image: &DynamicImage, step: &str, options: &PreprocessOptions) -> CaptchaResult<DynamicImage> {
+        match step {
+            "grayscale" => Ok(DynamicImage::ImageLuma8(luma(image).into_owned())),
+            "color_filter" => {
+                let filter = options.color_filter.as_ref().ok_or_else(|| {
+                    CaptchaError::BadRequest("Preprocessing step 'color_filter' requires `color_filter` options".to_string())
+                })?;
+                let tolerance = filter.tolerance.unwrap_or(Self::DEFAULT_COLOR_TOLERANCE);
+                match filter.space.unwrap_or_default() {
+                    ColorSpace::Rgb => Self::extract_by_color(image, filter.target, tolerance),
+                    ColorSpace::Hsv => Self::extract_by_hue(image, filter.target, tolerance),
+                }
+            }
+            "denoise" => Self::denoise(image, options),
+            "median" => Self::median_denoise(image, 1),
+            "contrast" => Self::enhance_contrast(image),
+            "threshold" => Self::apply_threshold(image, options.threshold.unwrap_or(Self::DEFAULT_THRESHOLD)),
+            "adaptive_threshold" => Self::adaptive_threshold(
+                image,
+                options.adaptive_threshold.unwrap_or(Self::DEFAULT_ADAPTIVE_RADIUS),
+            ),
+            "remove_lines" => Self::remove_lines(image),
+            "erode" => Self::erode_image(image, 1),
+            "dilate" => Self::dilate_image(image, 1),
+            "normalize_stroke" => Self::normalize_stroke_width(image, Self::stroke_width_target(options)),
+            "thin" => Self::thin(image),
+            _ => Err(CaptchaError::BadRequest(format!("Unknown preprocessing step '{}'", step))),
+        }
+    }
+
+    /// Strength of the `denoise` step: the options' `denoise_strength`, else the default
+    fn denoise_strength(options: &PreprocessOptions) -> f32 {
+        options.denoise_strength.unwrap_or(Self::DEFAULT_DENOISE_STRENGTH)
+    }
+
+    /// Median filter radius for a denoise strength, at least 1
+    fn median_radius(strength: f32) -> u32 {
+        (strength.round() as u32).max(1)
+    }
+
+    /// Bilateral filter radius for a spatial sigma: `2 * sigma_space`, capped at
+    /// [`Self::MAX_BILATERAL_RADIUS`]
+    fn bilateral_radius(sigma_space: f32) -> u32 {
+        ((2.0 * sigma_space).ceil() as u32).min(Self::MAX_BILATERAL_RADIUS)
+    }
+
+    /// Stroke width a `normalize_stroke` step aims for: the options' `stroke_width`, else the default
+    fn stroke_width_target(options: &PreprocessOptions) -> f32 {
+        options.stroke_width.filter(|&target| target > 0.0).unwrap_or(Self::DEFAULT_STROKE_WIDTH)
+    }
+
+    /// Fill in `target` as the stroke width of options that don't set one.
+    /// A zero target leaves the options unchanged.
+    pub fn with_stroke_width(mut options: PreprocessOptions, target: f32) -> PreprocessOptions {
+        if target > 0.0 && options.stroke_width.is_none() {
+            options.stroke_width = Some(target);
+        }
+        options
+    }
+
+    /// Convert a high-bit-depth image (16-bit or float samples) to 8 bits per channel,
+    /// stretching the range of color values actually used to 0-255. Scanner output often
+    /// spans a narrow band of the 16-bit range that plain conversion squashes into a few
+    /// gray levels. Alpha is scaled, not stretched; 8-bit images are returned unchanged.
+    pub fn to_eight_bit(image: DynamicImage) -> DynamicImage {
+        let (width, height) = (image.width(), image.height());
+        let converted = match &image {
+            DynamicImage::ImageLuma16(buffer) => {
+                ImageBuffer::from_raw(width, height, stretch_to_u8(buffer.as_raw(), 1, false)).map(DynamicImage::ImageLuma8)
+            }
+            DynamicImage::ImageLumaA16(buffer) => {
+                ImageBuffer::from_raw(width, height, stretch_to_u8(buffer.as_raw(), 2, true)).map(DynamicImage::ImageLumaA8)
+            }
+            DynamicImage::ImageRgb16(buffer) => {
+                ImageBuffer::from_raw(width, height, stretch_to_u8(buffer.as_raw(), 3, false)).map(DynamicImage::ImageRgb8)
+            }
+            DynamicImage::ImageRgba16(buffer) => {
+                ImageBuffer::from_raw(width, height, stretch_to_u8(buffer.as_raw(), 4, true)).map(DynamicImage::ImageRgba8)
+            }
+            DynamicImage::ImageRgb32F(_) => Some(Self::to_eight_bit(DynamicImage::ImageRgb16(image.to_rgb16()))),
+            DynamicImage::ImageRgba32F(_) => Some(Self::to_eight_bit(DynamicImage::ImageRgba16(image.to_rgba16()))),
+            _ => return image,
+        };
+
+        tracing::debug!(color = ?image.color(), "Converted high-bit-depth image to 8 bits");
+        converted.unwrap_or_else(|| DynamicImage::ImageRgba8(image.to_rgba8()))
+    }
+
+    /// Whether every pixel of an 8-bit image is opaque pure black or pure white, as after
+    /// thresholding. High-bit-depth images are never reported binary.
+    pub fn is_binary(image: &DynamicImage) -> bool {
+        let binary = |v: u8| v == 0 || v == 255;
+        match image {
+            DynamicImage::ImageLuma8(buffer) => buffer.pixels().all(|p| binary(p[0])),
+            DynamicImage::ImageLumaA8(buffer) => buffer.pixels().all(|p| binary(p[0]) && p[1] == 255),
+            DynamicImage::ImageRgb8(buffer) => buffer.pixels().all(|p| binary(p[0]) && p[0] == p[1] && p[1] == p[2]),
+            DynamicImage::ImageRgba8(buffer) => {
+                buffer.pixels().all(|p| binary(p[0]) && p[0] == p[1] && p[1] == p[2] && p[3] == 255)
+            }
+            _ => false,
+        }
+    }
+
+    /// Apply the [`InputOptions`] to a decoded image: flatten transparency, then cap its size
+    pub fn prepare_input(image: DynamicImage, options: &InputOptions) -> DynamicImage {
+        Self::cap_dimension(Self::flatten_alpha(image, options.alpha_background), options.max_dimension)
+    }
+
+    /// Downscale an image so its larger side is at most `max_dimension`, keeping the aspect
+    /// ratio. Smaller images, and every image when `max_dimension` is 0, are returned unchanged.
+    pub fn cap_dimension(image: DynamicImage, max_dimension: u32) -> DynamicImage {
+        if max_dimension == 0 || image.width().max(image.height()) <= max_dimension {
+            return image;
+        }
+
+        tracing::debug!(
+            width = image.width(),
+            height = image.height(),
+            max_dimension,
+            "Downscaling oversized input image"
+        );
+        image.resize(max_dimension, max_dimension, image::imageops::FilterType::Triangle)
+    }
+
+    /// Composite an image with an alpha channel over an opaque `background` color.
+    /// Grayscale conversion drops alpha, so transparent pixels would otherwise read as
+    /// whatever color they store, often black. Opaque images are returned unchanged.
+    pub fn flatten_alpha(image: DynamicImage, background: [u8; 3]) -> DynamicImage {
+        fn blend(value: u8, background: u8, alpha: u8) -> u8 {
+            let alpha = alpha as u32;
+            ((value as u32 * alpha + background as u32 * (255 - alpha) + 127) / 255) as u8
+        }
+
+        match image {
+            DynamicImage::ImageLumaA8(buffer) => {
+                let gray = Rgb(background).to_luma()[0];
+                DynamicImage::ImageLuma8(GrayImage::from_fn(buffer.width(), buffer.height(), |x, y| {
+                    let [value, alpha] = buffer.get_pixel(x, y).0;
+                    Luma([blend(value, gray, alpha)])
+                }))
+            }
+            DynamicImage::ImageRgba8(buffer) => {
+                DynamicImage::ImageRgb8(RgbImage::from_fn(buffer.width(), buffer.height(), |x, y| {
+                    let [r, g, b, alpha] = buffer.get_pixel(x, y).0;
+                    Rgb([blend(r, background[0], alpha), blend(g, background[1], alpha), blend(b, background[2], alpha)])
+                }))
+            }
+            image if image.color().has_alpha() => {
+                Self::flatten_alpha(DynamicImage::ImageRgba8(image.to_rgba8()), background)
+            }
+            image => image,
+        }
+    }
+
+    /// Preprocessing options for a named variant, or `None` if the name is unknown
+    pub fn variant(name: &str) -> Option<PreprocessOptions> {
+        let options = match name {
+            "raw" => PreprocessOptions { grayscale: Some(false), ..Default::default() },
+            "threshold" => PreprocessOptions { threshold: Some(128), ..Default::default() },
+            "adaptive" => PreprocessOptions { adaptive_threshold: Some(7), ..Default::default() },
+            "denoise_threshold" => PreprocessOptions {
+                denoise: Some(true),
+                threshold: Some(128),
+                ..Default::default()
+            },
+            _ => return None,
+        };
+        Some(options)
+    }
+
+    /// Apply a variant on top of the caller's options, keeping their geometry and color filter
+    pub fn with_variant(base: Option<&PreprocessOptions>, variant: &PreprocessOptions) -> PreprocessOptions {
+        let base = base.cloned().unwrap_or_default();
+        PreprocessOptions {
+            resize_width: base.resize_width,
+            resize_height: base.resize_height,
+            color_filter: base.color_filter,
+            stroke_width: base.stroke_width,
+            denoise_method: base.denoise_method,
+            denoise_strength: base.denoise_strength,
+            ..variant.clone()
+        }
+    }
+
+    /// Denoise with the method and strength set in `options`
+    fn denoise(image: &DynamicImage, options: &PreprocessOptions) -> CaptchaResult<DynamicImage> {
+        let strength = Self::denoise_strength(options);
+        match options.denoise_method.unwrap_or_default() {
+            DenoiseMethod::Gaussian => {
+                let gray = luma(image);
+                Ok(DynamicImage::ImageLuma8(gaussian_blur_f32(&gray, strength)))
+            }
+            DenoiseMethod::Median => Self::median_denoise(image, Self::median_radius(strength)),
+            DenoiseMethod::Bilateral => Self::bilateral_denoise(image, strength, Self::BILATERAL_SIGMA_COLOR),
+        }
     }
 
-    /// Apply Gaussian blur for denoising
-    fn denoise(image: &DynamicImage) -> CaptchaResult<DynamicImage> {
-        let gray = image.to_luma8();
-        let blurred = gaussian_blur_f32(&gray, 1.0);
-        Ok(DynamicImage::ImageLuma8(blurred))
+    /// Edge-preserving smoothing: each pixel becomes the average of its neighbors within
+    /// `2 * sigma_space` (at most [`Self::MAX_BILATERAL_RADIUS`]), weighted by distance and
+    /// by how close their intensity is to its own
+    pub fn bilateral_denoise(image: &DynamicImage, sigma_space: f32, sigma_color: f32) -> CaptchaResult<DynamicImage> {
+        if sigma_space <= 0.0 || sigma_color <= 0.0 {
+            return Err(CaptchaError::BadRequest("Bilateral filter sigmas must be positive".to_string()));
+        }
+
+        let gray = luma(image);
+        let (width, height) = gray.dimensions();
+        let radius = Self::bilateral_radius(sigma_space) as i64;
+        let side = (2 * radius + 1) as usize;
+
+        let spatial: Vec<f32> = (-radius..=radius)
+            .flat_map(|dy| (-radius..=radius).map(move |dx| (dx * dx + dy * dy) as f32))
+            .map(|d2| (-d2 / (2.0 * sigma_space * sigma_space)).exp())
+            .collect();
+        let range: Vec<f32> = (0..256)
+            .map(|d| (-((d * d) as f32) / (2.0 * sigma_color * sigma_color)).exp())
+            .collect();
+
+        let src: &[u8] = &gray;
+        let mut output = GrayImage::new(width, height);
+        let dst: &mut [u8] = &mut output;
+        let (w, h) = (width as i64, height as i64);
+
+        for y in 0..h {
+            for x in 0..w {
+                let center = src[(y * w + x) as usize];
+                let (mut sum, mut weights) = (0.0f32, 0.0f32);
+                for ny in (y - radius).max(0)..=(y + radius).min(h - 1) {
+                    let kernel_row = (ny - y + radius) as usize * side;
+                    for nx in (x - radius).max(0)..=(x + radius).min(w - 1) {
+                        let value = src[(ny * w + nx) as usize];
+                        let weight = spatial[kernel_row + (nx - x + radius) as usize]
+                            * range[center.abs_diff(value) as usize];
+                        sum += weight * value as f32;
+                        weights += weight;
+                    }
+                }
+                // The center pixel always contributes with weight 1
+                dst[(y * w + x) as usize] = (sum / weights).round() as u8;
+            }
+        }
+
+        Ok(DynamicImage::ImageLuma8(output))
     }
 
     /// Apply binary threshold
     fn apply_threshold(image: &DynamicImage, thresh_value: u8) -> CaptchaResult<DynamicImage> {
-        let gray = image.to_luma8();
+        let gray = luma(image);
         let thresholded = threshold(&gray, thresh_value);
         Ok(DynamicImage::ImageLuma8(thresholded))
     }
 
+    /// Keep pixels within `tolerance` (RGB Euclidean distance) of `target` and whiten the rest.
+    /// Images without color are returned unchanged.
+    pub fn extract_by_color(image: &DynamicImage, target: [u8; 3], tolerance: f32) -> CaptchaResult<DynamicImage> {
+        if tolerance < 0.0 {
+            return Err(CaptchaError::BadRequest("Color tolerance must be non-negative".to_string()));
+        }
+        if !image.color().has_color() {
+            return Ok(image.clone());
+        }
+
+        let mut rgb = image.to_rgb8();
+        for pixel in rgb.pixels_mut() {
+            let distance = pixel.0.iter()
+                .zip(target.iter())
+                .map(|(&a, &b)| (a as f32 - b as f32).powi(2))
+                .sum::<f32>()
+                .sqrt();
+
+            if distance > tolerance {
+                *pixel = Rgb([255, 255, 255]);
+            }
+        }
+
+        Ok(DynamicImage::ImageRgb8(rgb))
+    }
+
+    /// Keep pixels whose hue is within `tolerance` degrees of the hue of `target` and whiten the rest.
+    /// Near-gray pixels have no meaningful hue and are always whitened.
+    /// Images without color are returned unchanged.
+    pub fn extract_by_hue(image: &DynamicImage, target: [u8; 3], tolerance: f32) -> CaptchaResult<DynamicImage> {
+        if tolerance < 0.0 {
+            return Err(CaptchaError::BadRequest("Color tolerance must be non-negative".to_string()));
+        }
+        if !image.color().has_color() {
+            return Ok(image.clone());
+        }
+
+        let (target_hue, _, _) = rgb_to_hsv(target);
+        let mut rgb = image.to_rgb8();
+        for pixel in rgb.pixels_mut() {
+            let (hue, saturation, _) = rgb_to_hsv(pixel.0);
+            let diff = (hue - target_hue).abs();
+            let distance = diff.min(360.0 - diff);
+
+            if saturation < Self::MIN_HUE_SATURATION || distance > tolerance {
+                *pixel = Rgb([255, 255, 255]);
+            }
+        }
+
+        Ok(DynamicImage::ImageRgb8(rgb))
+    }
+
     /// Apply adaptive threshold for varying lighting conditions
     pub fn adaptive_threshold(image: &DynamicImage, block_radius: u32) -> CaptchaResult<DynamicImage> {
-        let gray = image.to_luma8();
+        let gray = luma(image);
         let thresholded = adaptive_threshold(&gray, block_radius);
         Ok(DynamicImage::ImageLuma8(thresholded))
     }
 
     /// Apply median filter to remove salt-and-pepper noise
     pub fn median_denoise(image: &DynamicImage, radius: u32) -> CaptchaResult<DynamicImage> {
-        let gray = image.to_luma8();
+        let gray = luma(image);
         let filtered = median_filter(&gray, radius, radius);
         Ok(DynamicImage::ImageLuma8(filtered))
     }
 
     /// Apply morphological erosion
     pub fn erode_image(image: &DynamicImage, radius: u8) -> CaptchaResult<DynamicImage> {
-        let gray = image.to_luma8();
+        let gray = luma(image);
         let eroded = erode(&gray, Norm::LInf, radius);
         Ok(DynamicImage::ImageLuma8(eroded))
     }
 
     /// Apply morphological dilation
     pub fn dilate_image(image: &DynamicImage, radius: u8) -> CaptchaResult<DynamicImage> {
-        let gray = image.to_luma8();
+        let gray = luma(image);
         let dilated = dilate(&gray, Norm::LInf, radius);
         Ok(DynamicImage::ImageLuma8(dilated))
     }
 
-    /// Remove noise lines by analyzing connected components
+    /// Remove noise lines by analyzing connected components.
+    ///
+    /// A dark pixel is whitened when it is part of a one-pixel-wide vertical line
+    /// (light left and right, dark above and below) or has at most one dark
+    /// 4-neighbor; a one-pixel-wide horizontal stroke has two and is kept. Border
+    /// pixels are left as they are. The loop runs over whole rows without branches so
+    /// the compiler can vectorize it; `preprocessor_scalar.rs` keeps the per-pixel
+    /// version it must match.
     pub fn remove_lines(image: &DynamicImage) -> CaptchaResult<DynamicImage> {
-        let gray = image.to_luma8();
-        let (width, height) = gray.dimensions();
-        
-        // Create output image
-        let mut output = gray.clone();
+        let gray = luma(image);
+        let (width, height) = (gray.width() as usize, gray.height() as usize);
+        let mut output = (*gray).clone();
+        if width < 3 || height < 3 {
+            return Ok(DynamicImage::ImageLuma8(output));
+        }
 
-        // Simple line removal: remove very thin horizontal or vertical patterns
+        let src = gray.as_raw();
+        let dst: &mut [u8] = &mut output;
+        let inner = width - 2;
         for y in 1..height - 1 {
-            for x in 1..width - 1 {
-                let current = gray.get_pixel(x, y).0[0];
-                
-                // Check if this is a thin line (dark pixel with light neighbors)
-                if current < 128 {
-                    let top = gray.get_pixel(x, y - 1).0[0];
-                    let bottom = gray.get_pixel(x, y + 1).0[0];
-                    let left = gray.get_pixel(x - 1, y).0[0];
-                    let right = gray.get_pixel(x + 1, y).0[0];
-
-                    // Horizontal line detection
-                    if top > 200 && bottom > 200 && left < 128 && right < 128 {
-                        continue; // Keep character pixels
-                    }
-
-                    // Vertical line detection
-                    if left > 200 && right > 200 && top < 128 && bottom < 128 {
-                        output.put_pixel(x, y, Luma([255])); // Remove line
-                    }
-
-                    // Isolated noise detection
-                    let neighbor_dark_count = [top, bottom, left, right]
-                        .iter()
-                        .filter(|&&p| p < 128)
-                        .count();
+            let row = y * width;
+            let above = &src[row - width + 1..][..inner];
+            let below = &src[row + width + 1..][..inner];
+            let left = &src[row..][..inner];
+            let center = &src[row + 1..][..inner];
+            let right = &src[row + 2..][..inner];
+            let out = &mut dst[row + 1..][..inner];
 
-                    if neighbor_dark_count <= 1 {
-                        output.put_pixel(x, y, Luma([255])); // Remove isolated noise
-                    }
-                }
+            for i in 0..inner {
+                out[i] = line_pixel(center[i], above[i], below[i], left[i], right[i]);
             }
         }
 
@@ -132,14 +801,9 @@ impl ImagePreprocessor {
 
     /// Enhance contrast using histogram equalization
     pub fn enhance_contrast(image: &DynamicImage) -> CaptchaResult<DynamicImage> {
-        let gray = image.to_luma8();
+        let gray = luma(image);
         let (width, height) = gray.dimensions();
-
-        // Calculate histogram
-        let mut histogram = [0u32; 256];
-        for pixel in gray.pixels() {
-            histogram[pixel.0[0] as usize] += 1;
-        }
+        let histogram = gray_histogram(&gray);
 
         // Calculate cumulative distribution function
         let total_pixels = (width * height) as f32;
@@ -150,81 +814,584 @@ impl ImagePreprocessor {
             cdf[i] = sum as f32 / total_pixels;
         }
 
-        // Apply histogram equalization
-        let mut output = gray.clone();
-        for (x, y, pixel) in gray.enumerate_pixels() {
-            let new_value = (cdf[pixel.0[0] as usize] * 255.0) as u8;
-            output.put_pixel(x, y, Luma([new_value]));
+        // Apply histogram equalization through a lookup table over the raw buffer
+        let equalized: [u8; 256] = std::array::from_fn(|i| (cdf[i] * 255.0) as u8);
+        let mut output = (*gray).clone();
+        for value in output.iter_mut() {
+            *value = equalized[*value as usize];
         }
 
         Ok(DynamicImage::ImageLuma8(output))
     }
 
-    /// Segment characters from the image
-    pub fn segment_characters(image: &DynamicImage) -> CaptchaResult<Vec<DynamicImage>> {
-        let gray = image.to_luma8();
+    /// Grayscale histogram of an image with Otsu's level, mean and standard deviation
+    pub fn histogram(image: &DynamicImage) -> Histogram {
+        let gray = luma(image);
+        let bins = gray_histogram(&gray);
+
+        let total: f64 = bins.iter().map(|&count| count as f64).sum();
+        let (mean, stddev) = if total == 0.0 {
+            (0.0, 0.0)
+        } else {
+            let mean = bins.iter().enumerate().map(|(i, &count)| i as f64 * count as f64).sum::<f64>() / total;
+            let variance = bins.iter()
+                .enumerate()
+                .map(|(i, &count)| (i as f64 - mean).powi(2) * count as f64)
+                .sum::<f64>() / total;
+            (mean, variance.sqrt())
+        };
+
+        Histogram { bins, otsu_threshold: otsu_level(&gray), mean, stddev }
+    }
+
+    /// Thin dark strokes to 1px skeletons with the Zhang-Suen algorithm.
+    ///
+    /// The image is binarized with Otsu's level first; the result is black
+    /// skeletons on white. Thinning already-thin input leaves it unchanged.
+    pub fn thin(image: &DynamicImage) -> CaptchaResult<DynamicImage> {
+        let gray = luma(image);
         let (width, height) = gray.dimensions();
 
-        // Find vertical projections to locate character boundaries
-        let mut projection = vec![0u32; width as usize];
-        for x in 0..width {
-            for y in 0..height {
-                if gray.get_pixel(x, y).0[0] < 128 {
-                    projection[x as usize] += 1;
-                }
-            }
+        // A uniform image has no strokes to thin
+        let (min, max) = gray.pixels().fold((u8::MAX, u8::MIN), |(lo, hi), p| (lo.min(p[0]), hi.max(p[0])));
+        if min == max {
+            return Ok(DynamicImage::ImageLuma8(gray.into_owned()));
         }
 
-        // Find character boundaries
-        let mut segments = Vec::new();
-        let mut in_char = false;
-        let mut start = 0u32;
+        let level = otsu_level(&gray);
+        let mut foreground: Vec<bool> = gray.pixels().map(|p| p[0] <= level).collect();
 
-        for (x, &count) in projection.iter().enumerate() {
-            if count > 0 && !in_char {
-                in_char = true;
-                start = x as u32;
-            } else if count == 0 && in_char {
-                in_char = false;
-                if (x as u32) - start > 3 {
-                    // Minimum character width
-                    let char_image = image.crop_imm(start, 0, (x as u32) - start, height);
-                    segments.push(char_image);
+        let at = |fg: &[bool], x: i64, y: i64| -> bool {
+            x >= 0 && y >= 0 && x < width as i64 && y < height as i64
+                && fg[(y * width as i64 + x) as usize]
+        };
+
+        loop {
+            let mut changed = false;
+
+            for step in 0..2 {
+                let mut removals = Vec::new();
+
+                for y in 0..height as i64 {
+                    for x in 0..width as i64 {
+                        if !at(&foreground, x, y) {
+                            continue;
+                        }
+
+                        // Neighbours P2..P9, clockwise from north
+                        let p = [
+                            at(&foreground, x, y - 1),
+                            at(&foreground, x + 1, y - 1),
+                            at(&foreground, x + 1, y),
+                            at(&foreground, x + 1, y + 1),
+                            at(&foreground, x, y + 1),
+                            at(&foreground, x - 1, y + 1),
+                            at(&foreground, x - 1, y),
+                            at(&foreground, x - 1, y - 1),
+                        ];
+
+                        let neighbours = p.iter().filter(|&&v| v).count();
+                        let transitions = (0..8).filter(|&i| !p[i] && p[(i + 1) % 8]).count();
+                        let (first, second) = if step == 0 {
+                            (p[0] && p[2] && p[4], p[2] && p[4] && p[6])
+                        } else {
+                            (p[0] && p[2] && p[6], p[0] && p[4] && p[6])
+                        };
+
+                        if (2..=6).contains(&neighbours) && transitions == 1 && !first && !second {
+                            removals.push((y * width as i64 + x) as usize);
+                        }
+                    }
+                }
+
+                changed |= !removals.is_empty();
+                for idx in removals {
+                    foreground[idx] = false;
                 }
             }
-        }
 
-        // Handle last character
-        if in_char {
-            let char_image = image.crop_imm(start, 0, width - start, height);
-            segments.push(char_image);
+            if !changed {
+                break;
+            }
         }
 
-        Ok(segments)
+        let output = GrayImage::from_fn(width, height, |x, y| {
+            if foreground[(y * width + x) as usize] { Luma([0]) } else { Luma([255]) }
+        });
+
+        Ok(DynamicImage::ImageLuma8(output))
     }
 
-    /// Apply full preprocessing pipeline optimized for text captchas
-    pub fn full_pipeline(image: &DynamicImage) -> CaptchaResult<DynamicImage> {
-        let options = PreprocessOptions {
-            grayscale: Some(true),
-            denoise: Some(true),
-            threshold: Some(128),
-            resize_width: None,
-            resize_height: None,
+    /// Average width in pixels of the dark strokes after Otsu binarization, or `None`
+    /// when the image has no strokes. Estimated from the distance transform: the ridge
+    /// pixels along the middle of a stroke lie `d` pixels from the background, for a
+    /// width of `2d - 1` (even widths are underestimated by one).
+    pub fn stroke_width(image: &DynamicImage) -> Option<f32> {
+        stroke_mask(&luma(image)).and_then(|mask| mask_stroke_width(&mask))
+    }
+
+    /// Dilate or erode the dark strokes so their average width moves toward `target` pixels.
+    ///
+    /// Each step of radius 1 changes the width by 2px, so widths within 1px of the target
+    /// are left as they are. Strokes are never eroded below 1px. The result is binarized
+    /// like [`Self::thin`]: black strokes on white. Images without strokes are returned unchanged.
+    pub fn normalize_stroke_width(image: &DynamicImage, target: f32) -> CaptchaResult<DynamicImage> {
+        let gray = luma(image);
+        let Some(mask) = stroke_mask(&gray) else {
+            return Ok(DynamicImage::ImageLuma8(gray.into_owned()));
+        };
+        let Some(width) = mask_stroke_width(&mask) else {
+            return Ok(DynamicImage::ImageLuma8(gray.into_owned()));
         };
 
-        let result = Self::preprocess(image, &options)?;
-        let result = Self::enhance_contrast(&result)?;
-        let result = Self::remove_lines(&result)?;
-        
-        Ok(result)
+        let radius = ((target - width) / 2.0).round();
+        let adjusted = if radius > 0.0 {
+            dilate(&mask, Norm::LInf, radius.min(u8::MAX as f32) as u8)
+        } else {
+            let thinnest = ((width - 1.0) / 2.0).floor().max(0.0);
+            let radius = (-radius).min(thinnest).min(u8::MAX as f32) as u8;
+            if radius == 0 { mask } else { erode(&mask, Norm::LInf, radius) }
+        };
+
+        let output = GrayImage::from_fn(adjusted.width(), adjusted.height(), |x, y| {
+            if adjusted.get_pixel(x, y)[0] > 0 { Luma([0]) } else { Luma([255]) }
+        });
+
+        Ok(DynamicImage::ImageLuma8(output))
     }
-}
+
+    /// Bounding boxes of dark 8-connected components after binarization, ordered left to right.
+    /// Uses Otsu's level unless a threshold is given; returns the level used with the boxes.
+    pub fn component_boxes(image: &DynamicImage, threshold_value: Option<u8>) -> CaptchaResult<(u8, Vec<ComponentBox>)> {
+        use imageproc::region_labelling::{connected_components, Connectivity};
+
+        let gray = luma(image);
+        let level = threshold_value.unwrap_or_else(|| otsu_level(&gray));
+        let binary = threshold(&gray, level);
+        let labels = connected_components(&binary, Connectivity::Eight, Luma([255u8]));
+
+        // label -> (min_x, min_y, max_x, max_y, area)
+        let mut bounds: std::collections::HashMap<u32, (u32, u32, u32, u32, u32)> = std::collections::HashMap::new();
+        for (x, y, label) in labels.enumerate_pixels() {
+            let label = label[0];
+            if label == 0 {
+                continue;
+            }
+            let entry = bounds.entry(label).or_insert((x, y, x, y, 0));
+            entry.0 = entry.0.min(x);
+            entry.1 = entry.1.min(y);
+            entry.2 = entry.2.max(x);
+            entry.3 = entry.3.max(y);
+            entry.4 += 1;
+        }
+
+        let mut boxes: Vec<ComponentBox> = bounds
+            .into_values()
+            .map(|(min_x, min_y, max_x, max_y, area)| ComponentBox {
+                x: min_x,
+                y: min_y,
+                width: max_x - min_x + 1,
+                height: max_y - min_y + 1,
+                area,
+            })
+            .collect();
+        boxes.sort_by_key(|b| (b.x, b.y));
+
+        Ok((level, boxes))
+    }
+
+    /// Estimate how hard a captcha is from its Otsu-binarized dark components: the share
+    /// of dark pixels in specks of at most [`SPECK_MAX_AREA`] pixels, and the number of
+    /// larger components (characters, plus arcs and fragments left by noise lines)
+    pub fn estimate_difficulty(image: &DynamicImage, thresholds: &DifficultyThresholds) -> CaptchaResult<DifficultyEstimate> {
+        let (_, boxes) = Self::component_boxes(image, None)?;
+        let (noise, components) = speck_noise(&boxes);
+
+        Ok(DifficultyEstimate {
+            level: thresholds.classify(noise, components),
+            noise,
+            components,
+        })
+    }
+
+    /// 0-1 estimate of how solvable an image is, from the same cues as
+    /// [`Self::estimate_difficulty`]: the product of its contrast (gap between the mean
+    /// intensities of Otsu's dark and light classes), its cleanliness (one minus the speck
+    /// share of dark pixels), and how plausible its component count is for a line of
+    /// characters. Scored on a copy of at most [`QUALITY_MAX_DIMENSION`] pixels a side.
+    pub fn quality(image: &DynamicImage) -> CaptchaResult<f32> {
+        let scaled;
+        let image = if image.width().max(image.height()) > QUALITY_MAX_DIMENSION {
+            scaled = image.resize(QUALITY_MAX_DIMENSION, QUALITY_MAX_DIMENSION, image::imageops::FilterType::Triangle);
+            &scaled
+        } else {
+            image
+        };
+
+        let histogram = Self::histogram(image);
+        let level = histogram.otsu_threshold as usize;
+        let class_mean = |bins: &[u32], offset: usize| {
+            let count: f64 = bins.iter().map(|&n| n as f64).sum();
+            let sum: f64 = bins.iter().enumerate().map(|(i, &n)| (i + offset) as f64 * n as f64).sum();
+            (count > 0.0).then(|| sum / count)
+        };
+        let contrast = match (class_mean(&histogram.bins[..=level], 0), class_mean(&histogram.bins[level + 1..], level + 1)) {
+            (Some(dark), Some(light)) => ((light - dark) / 255.0) as f32,
+            _ => 0.0,
+        };
+
+        let (_, boxes) = Self::component_boxes(image, Some(histogram.otsu_threshold))?;
+        let (noise, components) = speck_noise(&boxes);
+        let plausibility = match components {
+            n if n < *QUALITY_COMPONENTS.start() => n as f32 / *QUALITY_COMPONENTS.start() as f32,
+            n if n > *QUALITY_COMPONENTS.end() => *QUALITY_COMPONENTS.end() as f32 / n as f32,
+            _ => 1.0,
+        };
+
+        let quality = (contrast * (1.0 - noise) * plausibility).clamp(0.0, 1.0);
+        Ok((quality * 1000.0).round() / 1000.0)
+    }
+
+    /// Segment characters from the image with the default [`Segmentation`]
+    pub fn segment_characters(image: &DynamicImage) -> CaptchaResult<Vec<DynamicImage>> {
+        Self::segment_characters_with(image, &Segmentation::default())
+    }
+
+    /// Segment characters from the image into full-height crops, see [`Self::segment_boxes`]
+    pub fn segment_characters_with(image: &DynamicImage, params: &Segmentation) -> CaptchaResult<Vec<DynamicImage>> {
+        let height = image.height();
+        Ok(Self::segment_boxes(image, params)?
+            .into_iter()
+            .map(|b| image.crop_imm(b.x, 0, b.width, height))
+            .collect())
+    }
+
+    /// Bounding boxes of the characters in the image, left to right. Column spans narrower than
+    /// `min_width`, or whose ink is shorter than `min_height` or has fewer than `min_pixels`
+    /// dark pixels, are dropped as noise. A segment wider than `split_width_ratio` × the median
+    /// segment width is assumed to hold several touching characters and is split at the local
+    /// minima of the vertical projection.
+    pub fn segment_boxes(image: &DynamicImage, params: &Segmentation) -> CaptchaResult<Vec<ComponentBox>> {
+        params.validate()?;
+
+        let gray = luma(image);
+        let (projection, spans) = Self::character_spans(&gray, params);
+        let spans = Self::split_wide_spans(&projection, spans, params.split_width_ratio);
+
+        Ok(spans
+            .into_iter()
+            .map(|(start, end)| {
+                let (top, bottom) = ink_rows(&gray, start, end).unwrap_or((0, 0));
+                ComponentBox {
+                    x: start,
+                    y: top,
+                    width: end - start,
+                    height: bottom - top + 1,
+                    area: projection[start as usize..end as usize].iter().sum(),
+                }
+            })
+            .collect())
+    }
+
+    /// Segment characters into exactly `count` regions when possible.
+    ///
+    /// Too few regions: the widest region is split at its thinnest column.
+    /// Too many regions: the two neighbours separated by the narrowest gap are merged.
+    pub fn segment_characters_into(image: &DynamicImage, count: usize) -> CaptchaResult<Vec<DynamicImage>> {
+        if count == 0 {
+            return Err(CaptchaError::BadRequest("Segment count must be positive".to_string()));
+        }
+
+        let height = image.height();
+        let (projection, mut spans) = Self::character_spans(&luma(image), &Segmentation::default());
+
+        while spans.len() < count {
+            let Some((idx, &(start, end))) = spans
+                .iter()
+                .enumerate()
+                .filter(|(_, (s, e))| e - s >= 2)
+                .max_by_key(|(_, (s, e))| e - s)
+            else {
+                break;
+            };
+
+            // Cut at the emptiest column within the middle half of the region
+            let quarter = (end - start) / 4;
+            let middle = start + (end - start) / 2;
+            let cut = (start + quarter.max(1)..end - quarter)
+                .min_by_key(|&x| (projection[x as usize], x.abs_diff(middle)))
+                .unwrap_or(middle);
+
+            spans[idx] = (start, cut);
+            spans.insert(idx + 1, (cut, end));
+        }
+
+        while spans.len() > count {
+            let idx = (0..spans.len() - 1)
+                .min_by_key(|&i| spans[i + 1].0 - spans[i].1)
+                .unwrap_or(0);
+            let merged_end = spans[idx + 1].1;
+            spans[idx].1 = merged_end;
+            spans.remove(idx + 1);
+        }
+
+        Ok(spans
+            .into_iter()
+            .map(|(start, end)| image.crop_imm(start, 0, end - start, height))
+            .collect())
+    }
+
+    /// Split spans much wider than the median span into `round(width / median)` pieces,
+    /// cutting each at the emptiest column near its evenly spaced position
+    fn split_wide_spans(projection: &[u32], spans: Vec<(u32, u32)>, ratio: f32) -> Vec<(u32, u32)> {
+        let mut widths: Vec<u32> = spans.iter().map(|(start, end)| end - start).collect();
+        widths.sort_unstable();
+        let Some(&median) = widths.get(widths.len() / 2) else {
+            return spans;
+        };
+
+        let mut result = Vec::with_capacity(spans.len());
+        for (start, end) in spans {
+            let width = end - start;
+            if (width as f32) <= median as f32 * ratio {
+                result.push((start, end));
+                continue;
+            }
+
+            let pieces = ((width as f32 / median as f32).round() as u32).max(2);
+            let window = width / (2 * pieces);
+            let mut piece_start = start;
+            for k in 1..pieces {
+                let ideal = start + k * width / pieces;
+                let cut = (ideal.saturating_sub(window).max(piece_start + 1)..=(ideal + window).min(end - 1))
+                    .min_by_key(|&x| (projection[x as usize], x.abs_diff(ideal)))
+                    .unwrap_or(ideal);
+                result.push((piece_start, cut));
+                piece_start = cut;
+            }
+            result.push((piece_start, end));
+        }
+
+        result
+    }
+
+    /// Compute the dark-pixel column projection and the `[start, end)` column spans of characters
+    /// that pass the size filters of `params`
+    fn character_spans(gray: &GrayImage, params: &Segmentation) -> (Vec<u32>, Vec<(u32, u32)>) {
+        let (width, height) = gray.dimensions();
+
+        // Find vertical projections to locate character boundaries
+        let mut projection = vec![0u32; width as usize];
+        for x in 0..width {
+            for y in 0..height {
+                if gray.get_pixel(x, y).0[0] < 128 {
+                    projection[x as usize] += 1;
+                }
+            }
+        }
+
+        // Find character boundaries
+        let mut spans = Vec::new();
+        let mut in_char = false;
+        let mut start = 0u32;
+
+        for (x, &count) in projection.iter().enumerate() {
+            if count > 0 && !in_char {
+                in_char = true;
+                start = x as u32;
+            } else if count == 0 && in_char {
+                in_char = false;
+                spans.push((start, x as u32));
+            }
+        }
+
+        // Handle last character
+        if in_char {
+            spans.push((start, width));
+        }
+
+        spans.retain(|&(start, end)| {
+            let pixels: u32 = projection[start as usize..end as usize].iter().sum();
+            let ink_height = ink_rows(gray, start, end).map_or(0, |(top, bottom)| bottom - top + 1);
+            end - start >= params.min_width && ink_height >= params.min_height && pixels >= params.min_pixels
+        });
+
+        (projection, spans)
+    }
+
+    /// Apply full preprocessing pipeline optimized for text captchas
+    pub fn full_pipeline(image: &DynamicImage) -> CaptchaResult<DynamicImage> {
+        let options = PreprocessOptions {
+            grayscale: Some(true),
+            denoise: Some(true),
+            threshold: Some(128),
+            resize_width: None,
+            resize_height: None,
+            ..Default::default()
+        };
+
+        let result = Self::preprocess(image, &options)?;
+        let result = Self::enhance_contrast(&result)?;
+        let result = Self::remove_lines(&result)?;
+        
+        Ok(result)
+    }
+}
+
+/// First and last rows with dark pixels within columns `[start, end)`
+fn ink_rows(gray: &GrayImage, start: u32, end: u32) -> Option<(u32, u32)> {
+    let has_ink = |y: u32| (start..end).any(|x| gray.get_pixel(x, y).0[0] < 128);
+    let top = (0..gray.height()).find(|&y| has_ink(y))?;
+    let bottom = (0..gray.height()).rev().find(|&y| has_ink(y))?;
+    Some((top, bottom))
+}
+
+/// Borrow the image as 8-bit grayscale, converting only when it is not already
+fn luma(image: &DynamicImage) -> Cow<'_, GrayImage> {
+    match image.as_luma8() {
+        Some(gray) => Cow::Borrowed(gray),
+        None => Cow::Owned(image.to_luma8()),
+    }
+}
+
+/// New value of an interior pixel in [`ImagePreprocessor::remove_lines`] given its
+/// 4-neighbors. Non-short-circuiting `&`/`|` keep it branch-free.
+#[inline(always)]
+fn line_pixel(current: u8, top: u8, bottom: u8, left: u8, right: u8) -> u8 {
+    let dark_neighbors = (top < 128) as u8 + (bottom < 128) as u8 + (left < 128) as u8 + (right < 128) as u8;
+    let vertical_line = (left > 200) & (right > 200) & (top < 128) & (bottom < 128);
+    let remove = (current < 128) & (vertical_line | (dark_neighbors <= 1));
+    if remove { 255 } else { current }
+}
+
+/// Number of pixels at each intensity
+fn gray_histogram(gray: &GrayImage) -> [u32; 256] {
+    let mut histogram = [0u32; 256];
+    for pixel in gray.pixels() {
+        histogram[pixel.0[0] as usize] += 1;
+    }
+    histogram
+}
+
+/// Dark pixels at or below Otsu's level as a white-on-black mask, or `None` for a uniform image
+fn stroke_mask(gray: &GrayImage) -> Option<GrayImage> {
+    let (min, max) = gray.pixels().fold((u8::MAX, u8::MIN), |(lo, hi), p| (lo.min(p[0]), hi.max(p[0])));
+    if min == max {
+        return None;
+    }
+
+    let level = otsu_level(gray);
+    Some(GrayImage::from_fn(gray.width(), gray.height(), |x, y| {
+        if gray.get_pixel(x, y)[0] <= level { Luma([255]) } else { Luma([0]) }
+    }))
+}
+
+/// Average stroke width of a white-on-black mask from the chessboard distance of its
+/// ridge pixels (local maxima) to the background, or `None` without strokes
+fn mask_stroke_width(mask: &GrayImage) -> Option<f32> {
+    // Distance of every stroke pixel to the nearest background pixel
+    let background = GrayImage::from_fn(mask.width(), mask.height(), |x, y| {
+        if mask.get_pixel(x, y)[0] > 0 { Luma([0]) } else { Luma([255]) }
+    });
+    let distances = distance_transform(&background, Norm::LInf);
+    let (width, height) = distances.dimensions();
+
+    let mut total = 0u64;
+    let mut ridges = 0u64;
+    for (x, y, pixel) in distances.enumerate_pixels() {
+        let d = pixel[0];
+        if mask.get_pixel(x, y)[0] == 0 {
+            continue;
+        }
+
+        let is_ridge = (-1i64..=1).all(|dy| (-1i64..=1).all(|dx| {
+            let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+            nx < 0 || ny < 0 || nx >= width as i64 || ny >= height as i64
+                || distances.get_pixel(nx as u32, ny as u32)[0] <= d
+        }));
+        if is_ridge {
+            total += u64::from(d);
+            ridges += 1;
+        }
+    }
+
+    (ridges > 0).then(|| 2.0 * total as f32 / ridges as f32 - 1.0)
+}
+
+/// Map interleaved 16-bit samples to 8 bits, stretching the min-max range of the color
+/// channels to 0-255. With `has_alpha` the last channel of each pixel is alpha and is
+/// scaled proportionally. A single-valued image is scaled proportionally as well.
+fn stretch_to_u8(samples: &[u16], channels: usize, has_alpha: bool) -> Vec<u8> {
+    let is_alpha = |i: usize| has_alpha && i % channels == channels - 1;
+    let scale = |v: u16| ((v as u32 + 128) / 257) as u8;
+
+    let (low, high) = samples
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !is_alpha(*i))
+        .fold((u16::MAX, u16::MIN), |(low, high), (_, &v)| (low.min(v), high.max(v)));
+
+    if low >= high {
+        return samples.iter().map(|&v| scale(v)).collect();
+    }
+
+    let range = (high - low) as f32;
+    samples
+        .iter()
+        .enumerate()
+        .map(|(i, &v)| {
+            if is_alpha(i) {
+                scale(v)
+            } else {
+                ((v - low) as f32 * 255.0 / range).round() as u8
+            }
+        })
+        .collect()
+}
+
+/// Convert an RGB triple to (hue in degrees, saturation 0-1, value 0-1)
+fn rgb_to_hsv(rgb: [u8; 3]) -> (f32, f32, f32) {
+    let r = rgb[0] as f32 / 255.0;
+    let g = rgb[1] as f32 / 255.0;
+    let b = rgb[2] as f32 / 255.0;
+
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * (((g - b) / delta).rem_euclid(6.0))
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+
+    (hue, saturation, max)
+}
+
+/// Share of the dark pixels of `boxes` in speck-sized components, and the number of larger ones
+fn speck_noise(boxes: &[ComponentBox]) -> (f32, usize) {
+    let (specks, components): (Vec<_>, Vec<_>) = boxes.iter().partition(|b| b.area <= SPECK_MAX_AREA);
+    let ink: u32 = boxes.iter().map(|b| b.area).sum();
+    let noise = if ink == 0 {
+        0.0
+    } else {
+        specks.iter().map(|b| b.area).sum::<u32>() as f32 / ink as f32
+    };
+    (noise, components.len())
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use image::RgbImage;
+    use image::{RgbImage, Rgba};
+    use crate::models::ColorFilterOptions;
+    use crate::solvers::preprocessor_scalar as scalar;
 
     fn create_test_image() -> DynamicImage {
         let img = RgbImage::from_fn(100, 50, |x, y| {
@@ -246,6 +1413,7 @@ mod tests {
             threshold: None,
             resize_width: None,
             resize_height: None,
+            ..Default::default()
         };
 
         let result = ImagePreprocessor::preprocess(&image, &options).unwrap();
@@ -261,6 +1429,7 @@ mod tests {
             threshold: None,
             resize_width: Some(200),
             resize_height: Some(100),
+            ..Default::default()
         };
 
         let result = ImagePreprocessor::preprocess(&image, &options).unwrap();
@@ -277,6 +1446,7 @@ mod tests {
             threshold: Some(128),
             resize_width: None,
             resize_height: None,
+            ..Default::default()
         };
 
         let result = ImagePreprocessor::preprocess(&image, &options).unwrap();
@@ -288,4 +1458,709 @@ mod tests {
             }
         }
     }
-}
\ No newline at end of file
+
+    fn steps(names: &[&str]) -> PreprocessOptions {
+        PreprocessOptions {
+            steps: Some(names.iter().map(|s| s.to_string()).collect()),
+            ..Default::default()
+        }
+    }
+
+    fn is_binary(image: &DynamicImage) -> bool {
+        image.to_luma8().pixels().all(|p| p.0[0] == 0 || p.0[0] == 255)
+    }
+
+    #[test]
+    fn test_preprocess_steps_run_in_order() {
+        let image = create_test_image();
+
+        let denoise_last = ImagePreprocessor::preprocess(&image, &steps(&["grayscale", "threshold", "denoise"])).unwrap();
+        assert!(!is_binary(&denoise_last));
+
+        let threshold_last = ImagePreprocessor::preprocess(&image, &steps(&["grayscale", "denoise", "threshold"])).unwrap();
+        assert!(is_binary(&threshold_last));
+
+        // Steps replace the default pipeline, so nothing runs for an empty list
+        let untouched = ImagePreprocessor::preprocess(&image, &steps(&[])).unwrap();
+        assert_eq!(untouched.to_rgb8(), image.to_rgb8());
+    }
+
+    #[test]
+    fn test_preprocess_traced_reports_effective_parameters() {
+        let image = create_test_image();
+
+        let options = PreprocessOptions {
+            resize_width: Some(50),
+            resize_height: Some(25),
+            denoise: Some(true),
+            threshold: Some(140),
+            ..Default::default()
+        };
+        let (_, applied) = ImagePreprocessor::preprocess_traced(&image, &options).unwrap();
+        assert_eq!(applied, vec![
+            "resize(width=50, height=25, filter=lanczos3)",
+            "grayscale",
+            "denoise(gaussian_sigma=1)",
+            "threshold(level=140)",
+        ]);
+
+        // Steps without explicit parameters report the defaults they ran with
+        let (_, applied) = ImagePreprocessor::preprocess_traced(&image, &steps(&["adaptive_threshold", "threshold"])).unwrap();
+        assert_eq!(applied, vec!["adaptive_threshold(block_radius=7)", "threshold(level=128)"]);
+    }
+
+    #[test]
+    fn test_preprocess_rejects_invalid_steps() {
+        let image = create_test_image();
+
+        let unknown = ImagePreprocessor::preprocess(&image, &steps(&["grayscale", "sharpen"]));
+        assert!(matches!(
+            unknown,
+            Err(CaptchaError::Validation(errors)) if errors[0].field == "steps[1]" && errors[0].error.contains("sharpen")
+        ));
+
+        let missing_filter = ImagePreprocessor::preprocess(&image, &steps(&["color_filter"]));
+        assert!(matches!(missing_filter, Err(CaptchaError::Validation(_))));
+    }
+
+    #[test]
+    fn test_validate_parameter_bounds() {
+        let valid = PreprocessOptions { resize_width: Some(200), resize_height: Some(50), adaptive_threshold: Some(7), ..Default::default() };
+        assert!(ImagePreprocessor::validate(&valid).is_ok());
+
+        let invalid = [
+            PreprocessOptions { resize_width: Some(0), resize_height: Some(50), ..Default::default() },
+            PreprocessOptions { resize_width: Some(200), resize_height: Some(100_000), ..Default::default() },
+            PreprocessOptions { resize_width: Some(200), ..Default::default() },
+            PreprocessOptions { adaptive_threshold: Some(0), ..Default::default() },
+            PreprocessOptions { adaptive_threshold: Some(1_000), ..Default::default() },
+            PreprocessOptions {
+                color_filter: Some(ColorFilterOptions { target: [200, 20, 20], tolerance: Some(f32::NAN), space: None }),
+                ..Default::default()
+            },
+            PreprocessOptions {
+                color_filter: Some(ColorFilterOptions { target: [200, 20, 20], tolerance: Some(300.0), space: Some(ColorSpace::Hsv) }),
+                ..Default::default()
+            },
+        ];
+        for options in &invalid {
+            assert!(
+                matches!(ImagePreprocessor::validate(options), Err(CaptchaError::Validation(_))),
+                "{:?}", options
+            );
+        }
+
+        // Every invalid field is reported, not just the first
+        let several = PreprocessOptions {
+            resize_width: Some(0),
+            resize_height: Some(50),
+            adaptive_threshold: Some(0),
+            steps: Some(vec!["grayscale".to_string(), "sharpen".to_string()]),
+            ..Default::default()
+        };
+        let Err(CaptchaError::Validation(errors)) = ImagePreprocessor::validate(&several) else {
+            panic!("expected a validation error");
+        };
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, vec!["resize_width", "adaptive_threshold", "steps[1]"]);
+    }
+
+    #[test]
+    fn test_extract_by_color() {
+        let img = RgbImage::from_fn(30, 10, |x, _| match x / 10 {
+            0 => Rgb([200, 20, 20]),
+            1 => Rgb([20, 200, 20]),
+            _ => Rgb([210, 30, 25]),
+        });
+        let image = DynamicImage::ImageRgb8(img);
+
+        let result = ImagePreprocessor::extract_by_color(&image, [200, 20, 20], 30.0)
+            .unwrap()
+            .to_rgb8();
+
+        assert_eq!(result.get_pixel(5, 5).0, [200, 20, 20]);
+        assert_eq!(result.get_pixel(15, 5).0, [255, 255, 255]);
+        assert_eq!(result.get_pixel(25, 5).0, [210, 30, 25]);
+    }
+
+    #[test]
+    fn test_extract_by_hue() {
+        let img = RgbImage::from_fn(30, 10, |x, _| match x / 10 {
+            0 => Rgb([120, 0, 0]),
+            1 => Rgb([0, 0, 255]),
+            _ => Rgb([128, 128, 128]),
+        });
+        let image = DynamicImage::ImageRgb8(img);
+
+        let result = ImagePreprocessor::extract_by_hue(&image, [255, 0, 0], 20.0)
+            .unwrap()
+            .to_rgb8();
+
+        // Dark red shares the hue of bright red
+        assert_eq!(result.get_pixel(5, 5).0, [120, 0, 0]);
+        assert_eq!(result.get_pixel(15, 5).0, [255, 255, 255]);
+        // Gray has no hue and is removed
+        assert_eq!(result.get_pixel(25, 5).0, [255, 255, 255]);
+    }
+
+    #[test]
+    fn test_segment_characters_into() {
+        // Two 10px-wide blobs separated by a 10px gap, followed by a third blob
+        let img = RgbImage::from_fn(70, 20, |x, _| {
+            if (5..15).contains(&x) || (25..35).contains(&x) || (50..60).contains(&x) {
+                Rgb([0, 0, 0])
+            } else {
+                Rgb([255, 255, 255])
+            }
+        });
+        let image = DynamicImage::ImageRgb8(img);
+
+        assert_eq!(ImagePreprocessor::segment_characters(&image).unwrap().len(), 3);
+        assert_eq!(ImagePreprocessor::segment_characters_into(&image, 3).unwrap().len(), 3);
+
+        let merged = ImagePreprocessor::segment_characters_into(&image, 2).unwrap();
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].width(), 30);
+
+        let split = ImagePreprocessor::segment_characters_into(&image, 5).unwrap();
+        assert_eq!(split.len(), 5);
+    }
+
+    #[test]
+    fn test_segment_splits_touching_characters() {
+        // Two separate 10px glyphs, then a 12px and a 6px glyph joined by a thin bridge at x = 52..54
+        let img = RgbImage::from_fn(80, 20, |x, y| {
+            let glyph = (5..15).contains(&x) || (20..30).contains(&x) || (40..52).contains(&x) || (54..60).contains(&x);
+            let bridge = (52..54).contains(&x) && y == 10;
+            if glyph || bridge { Rgb([0, 0, 0]) } else { Rgb([255, 255, 255]) }
+        });
+        let image = DynamicImage::ImageRgb8(img);
+
+        let segments = ImagePreprocessor::segment_characters(&image).unwrap();
+        let widths: Vec<u32> = segments.iter().map(|s| s.width()).collect();
+        // Cut at the bridge rather than the middle of the wide segment
+        assert_eq!(widths, vec![10, 10, 12, 8]);
+
+        // A generous ratio keeps the touching pair together
+        let generous = Segmentation { split_width_ratio: 2.5, ..Default::default() };
+        assert_eq!(ImagePreprocessor::segment_characters_with(&image, &generous).unwrap().len(), 3);
+        let invalid = Segmentation { split_width_ratio: 1.0, ..Default::default() };
+        assert!(ImagePreprocessor::segment_characters_with(&image, &invalid).is_err());
+    }
+
+    #[test]
+    fn test_segment_keeps_thin_glyphs_and_drops_dots() {
+        // A 2px-wide "I", a 2x2 speck, and a 10px glyph
+        let img = RgbImage::from_fn(50, 20, |x, y| {
+            let thin = (5..7).contains(&x) && (4..16).contains(&y);
+            let speck = (15..17).contains(&x) && (9..11).contains(&y);
+            let glyph = (25..35).contains(&x) && (4..16).contains(&y);
+            if thin || speck || glyph { Rgb([0, 0, 0]) } else { Rgb([255, 255, 255]) }
+        });
+        let image = DynamicImage::ImageRgb8(img);
+
+        let boxes = ImagePreprocessor::segment_boxes(&image, &Segmentation::default()).unwrap();
+        assert_eq!(boxes, vec![
+            ComponentBox { x: 5, y: 4, width: 2, height: 12, area: 24 },
+            ComponentBox { x: 25, y: 4, width: 10, height: 12, area: 120 },
+        ]);
+
+        // The old 4px minimum width drops the "I" too
+        let wide_only = Segmentation { min_width: 4, ..Default::default() };
+        assert_eq!(ImagePreprocessor::segment_boxes(&image, &wide_only).unwrap().len(), 1);
+
+        // Without the size filters the speck is kept (a large ratio stops it skewing the median into splits)
+        let keep_all = Segmentation { min_height: 1, min_pixels: 1, split_width_ratio: 10.0, ..Default::default() };
+        assert_eq!(ImagePreprocessor::segment_boxes(&image, &keep_all).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_auto_preprocess_variants() {
+        for name in DEFAULT_AUTO_PREPROCESS_VARIANTS.split(',') {
+            assert!(ImagePreprocessor::variant(name).is_some(), "missing variant {}", name);
+        }
+        assert!(ImagePreprocessor::variant("unknown").is_none());
+
+        let base = PreprocessOptions {
+            resize_width: Some(20),
+            resize_height: Some(10),
+            threshold: Some(50),
+            ..Default::default()
+        };
+        let variant = ImagePreprocessor::variant("adaptive").unwrap();
+        let merged = ImagePreprocessor::with_variant(Some(&base), &variant);
+        assert_eq!(merged.resize_width, Some(20));
+        assert_eq!(merged.threshold, None);
+        assert_eq!(merged.adaptive_threshold, Some(7));
+
+        let processed = ImagePreprocessor::preprocess(&create_test_image(), &merged).unwrap();
+        assert_eq!(processed.width(), 20);
+    }
+
+    fn dark_pixels(image: &DynamicImage) -> usize {
+        image.to_luma8().pixels().filter(|p| p[0] < 128).count()
+    }
+
+    fn component_count(image: &DynamicImage) -> u32 {
+        use imageproc::region_labelling::{connected_components, Connectivity};
+
+        let labels = connected_components(&image.to_luma8(), Connectivity::Eight, Luma([255u8]));
+        labels.pixels().map(|p| p[0]).max().unwrap_or(0)
+    }
+
+    #[test]
+    fn test_thin_bar_to_line() {
+        let thinned = ImagePreprocessor::thin(&create_test_image()).unwrap();
+
+        // A 59x29 block collapses to a skeleton far thinner than the original
+        assert!(dark_pixels(&thinned) > 0);
+        assert!(dark_pixels(&thinned) < 59 * 3);
+        assert_eq!(component_count(&thinned), 1);
+    }
+
+    /// Three vertical black bars `width` pixels wide on white
+    fn bars(width: u32) -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(80, 40, |x, y| {
+            let bar = (5..35).contains(&y) && [10, 35, 60].iter().any(|&left| (left..left + width).contains(&x));
+            if bar { Luma([0]) } else { Luma([255]) }
+        }))
+    }
+
+    #[test]
+    fn test_is_binary() {
+        assert!(ImagePreprocessor::is_binary(&create_test_image()));
+        assert!(ImagePreprocessor::is_binary(&DynamicImage::new_luma8(4, 4)));
+
+        let mut gray = create_test_image().to_luma8();
+        gray.put_pixel(0, 0, Luma([128]));
+        assert!(!ImagePreprocessor::is_binary(&DynamicImage::ImageLuma8(gray)));
+
+        // Colored or translucent pixels are not binary, nor are 16-bit images
+        let mut rgba = create_test_image().to_rgba8();
+        assert!(ImagePreprocessor::is_binary(&DynamicImage::ImageRgba8(rgba.clone())));
+        rgba.put_pixel(0, 0, Rgba([255, 255, 255, 0]));
+        assert!(!ImagePreprocessor::is_binary(&DynamicImage::ImageRgba8(rgba)));
+        let mut rgb = create_test_image().to_rgb8();
+        rgb.put_pixel(0, 0, Rgb([255, 0, 0]));
+        assert!(!ImagePreprocessor::is_binary(&DynamicImage::ImageRgb8(rgb)));
+        assert!(!ImagePreprocessor::is_binary(&DynamicImage::new_luma16(4, 4)));
+    }
+
+    #[test]
+    fn test_stroke_width_estimate() {
+        assert_eq!(ImagePreprocessor::stroke_width(&bars(1)), Some(1.0));
+        assert_eq!(ImagePreprocessor::stroke_width(&bars(5)), Some(5.0));
+        assert_eq!(ImagePreprocessor::stroke_width(&bars(9)), Some(9.0));
+        assert_eq!(ImagePreprocessor::stroke_width(&DynamicImage::new_luma8(10, 10)), None);
+    }
+
+    #[test]
+    fn test_normalize_stroke_width() {
+        let width = |image: &DynamicImage| ImagePreprocessor::stroke_width(image).unwrap();
+
+        // Thin strokes are dilated, thick ones eroded, toward the same target
+        let thickened = ImagePreprocessor::normalize_stroke_width(&bars(1), 5.0).unwrap();
+        assert_eq!(width(&thickened), 5.0);
+        let thinned = ImagePreprocessor::normalize_stroke_width(&bars(9), 5.0).unwrap();
+        assert_eq!(width(&thinned), 5.0);
+        assert_eq!(component_count(&thinned), 3);
+
+        // Erosion stops at 1px instead of removing strokes
+        let floor = ImagePreprocessor::normalize_stroke_width(&bars(3), 0.5).unwrap();
+        assert_eq!(width(&floor), 1.0);
+        assert_eq!(component_count(&floor), 3);
+    }
+
+    #[test]
+    fn test_histogram() {
+        // Three quarters at 40, one quarter at 200
+        let image = DynamicImage::ImageLuma8(GrayImage::from_fn(20, 10, |x, _| {
+            if x < 15 { Luma([40]) } else { Luma([200]) }
+        }));
+
+        let histogram = ImagePreprocessor::histogram(&image);
+        assert_eq!(histogram.bins[40], 150);
+        assert_eq!(histogram.bins[200], 50);
+        assert_eq!(histogram.bins.iter().sum::<u32>(), 200);
+        assert_eq!(histogram.mean, 80.0);
+        assert!((histogram.stddev - 69.282).abs() < 0.001, "{}", histogram.stddev);
+        assert!((40..200).contains(&histogram.otsu_threshold), "{}", histogram.otsu_threshold);
+
+        let uniform = ImagePreprocessor::histogram(&DynamicImage::new_luma8(4, 4));
+        assert_eq!(uniform.bins[0], 16);
+        assert_eq!((uniform.mean, uniform.stddev), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_stroke_width_option() {
+        let options = PreprocessOptions { stroke_width: Some(5.0), ..Default::default() };
+        let (processed, applied) = ImagePreprocessor::preprocess_traced(&bars(1), &options).unwrap();
+        assert_eq!(ImagePreprocessor::stroke_width(&processed), Some(5.0));
+        assert!(applied.contains(&"normalize_stroke(target=5)".to_string()));
+
+        // A configured target only fills options without one
+        let filled = ImagePreprocessor::with_stroke_width(PreprocessOptions::default(), 3.0);
+        assert_eq!(filled.stroke_width, Some(3.0));
+        let kept = ImagePreprocessor::with_stroke_width(PreprocessOptions { stroke_width: Some(0.0), ..Default::default() }, 3.0);
+        assert_eq!(kept.stroke_width, Some(0.0));
+
+        let too_wide = PreprocessOptions { stroke_width: Some(40.0), ..Default::default() };
+        assert!(ImagePreprocessor::validate(&too_wide).is_err());
+    }
+
+    #[test]
+    fn test_thin_preserves_connectivity() {
+        // Two thick rings
+        let img = GrayImage::from_fn(60, 30, |x, y| {
+            let ring = |cx: f32| {
+                let d = ((x as f32 - cx).powi(2) + (y as f32 - 15.0).powi(2)).sqrt();
+                (6.0..=11.0).contains(&d)
+            };
+            if ring(15.0) || ring(45.0) { Luma([0]) } else { Luma([255]) }
+        });
+        let image = DynamicImage::ImageLuma8(img);
+
+        let thinned = ImagePreprocessor::thin(&image).unwrap();
+        assert_eq!(component_count(&thinned), component_count(&image));
+        assert!(dark_pixels(&thinned) < dark_pixels(&image) / 3);
+    }
+
+    #[test]
+    fn test_thin_is_idempotent() {
+        let once = ImagePreprocessor::thin(&create_test_image()).unwrap();
+        let twice = ImagePreprocessor::thin(&once).unwrap();
+        assert_eq!(once.to_luma8(), twice.to_luma8());
+
+        let blank = DynamicImage::ImageLuma8(GrayImage::from_pixel(10, 10, Luma([255])));
+        assert_eq!(ImagePreprocessor::thin(&blank).unwrap().to_luma8(), blank.to_luma8());
+    }
+
+    #[test]
+    fn test_component_boxes() {
+        let img = GrayImage::from_fn(30, 10, |x, y| {
+            let first = (2..5).contains(&x) && (1..8).contains(&y);
+            let second = (10..20).contains(&x) && (4..6).contains(&y);
+            if first || second { Luma([0]) } else { Luma([255]) }
+        });
+
+        let (_, boxes) = ImagePreprocessor::component_boxes(&DynamicImage::ImageLuma8(img), Some(128)).unwrap();
+        assert_eq!(boxes, vec![
+            ComponentBox { x: 2, y: 1, width: 3, height: 7, area: 21 },
+            ComponentBox { x: 10, y: 4, width: 10, height: 2, area: 20 },
+        ]);
+
+        let blank = DynamicImage::ImageLuma8(GrayImage::from_pixel(10, 10, Luma([255])));
+        assert!(ImagePreprocessor::component_boxes(&blank, Some(128)).unwrap().1.is_empty());
+    }
+
+    #[test]
+    fn test_estimate_difficulty() {
+        // `bars` 5px-wide strokes, optionally sprinkled with isolated single-pixel specks
+        let captcha = |bars: u32, specks: bool| {
+            DynamicImage::ImageLuma8(GrayImage::from_fn(200, 40, |x, y| {
+                let bar = x % 12 < 5 && x / 12 < bars && (8..32).contains(&y);
+                let speck = specks && x % 12 == 8 && y % 4 == 1;
+                if bar || speck { Luma([0]) } else { Luma([255]) }
+            }))
+        };
+        let thresholds = DifficultyThresholds::default();
+
+        let easy = ImagePreprocessor::estimate_difficulty(&captcha(5, false), &thresholds).unwrap();
+        assert_eq!(easy, DifficultyEstimate { level: Difficulty::Easy, noise: 0.0, components: 5 });
+
+        let medium = ImagePreprocessor::estimate_difficulty(&captcha(12, false), &thresholds).unwrap();
+        assert_eq!(medium.level, Difficulty::Medium);
+
+        let hard = ImagePreprocessor::estimate_difficulty(&captcha(5, true), &thresholds).unwrap();
+        assert_eq!(hard.level, Difficulty::Hard);
+        assert_eq!(hard.components, 5);
+        assert!(hard.noise > 0.1, "noise {}", hard.noise);
+    }
+
+    #[test]
+    fn test_quality() {
+        // `bars` 5px-wide strokes of `ink` on white, optionally sprinkled with specks
+        let captcha = |bars: u32, ink: u8, specks: bool| {
+            DynamicImage::ImageLuma8(GrayImage::from_fn(200, 40, |x, y| {
+                let bar = x % 12 < 5 && x / 12 < bars && (8..32).contains(&y);
+                let speck = specks && x % 12 == 8 && y % 4 == 1;
+                if bar || speck { Luma([ink]) } else { Luma([255]) }
+            }))
+        };
+
+        let clean = ImagePreprocessor::quality(&captcha(5, 0, false)).unwrap();
+        assert_eq!(clean, 1.0);
+
+        for worse in [captcha(5, 0, true), captcha(5, 200, false), captcha(1, 0, false), captcha(16, 0, false)] {
+            let quality = ImagePreprocessor::quality(&worse).unwrap();
+            assert!((0.0..clean).contains(&quality), "quality {}", quality);
+        }
+
+        let blank = DynamicImage::ImageLuma8(GrayImage::from_pixel(200, 40, Luma([255])));
+        assert_eq!(ImagePreprocessor::quality(&blank).unwrap(), 0.0);
+
+        // Large uploads are scored on a downscaled copy
+        let large = DynamicImage::ImageLuma8(GrayImage::from_fn(2000, 400, |x, y| {
+            let bar = x % 120 < 50 && x / 120 < 5 && (80..320).contains(&y);
+            if bar { Luma([0]) } else { Luma([255]) }
+        }));
+        assert!(ImagePreprocessor::quality(&large).unwrap() > 0.9);
+    }
+
+    #[test]
+    fn test_sixteen_bit_png_keeps_detail() {
+        // Scanner-like input: dark text 300 levels below the background, out of 65535
+        let scan = ImageBuffer::<Luma<u16>, Vec<u16>>::from_fn(40, 20, |x, _| {
+            Luma([if (10..20).contains(&x) { 30_000 } else { 30_300 }])
+        });
+        let mut png = Vec::new();
+        DynamicImage::ImageLuma16(scan)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageOutputFormat::Png)
+            .unwrap();
+        let decoded = image::load_from_memory(&png).unwrap();
+        assert_eq!(decoded.color(), image::ColorType::L16);
+
+        // Plain conversion leaves text and background one gray level apart
+        let naive = decoded.to_luma8();
+        assert!(naive.get_pixel(15, 5).0[0].abs_diff(naive.get_pixel(30, 5).0[0]) <= 1);
+
+        let converted = ImagePreprocessor::to_eight_bit(decoded);
+        let gray = converted.as_luma8().expect("8-bit grayscale");
+        assert_eq!(gray.get_pixel(15, 5).0[0], 0);
+        assert_eq!(gray.get_pixel(30, 5).0[0], 255);
+
+        let processed = ImagePreprocessor::apply_threshold(&converted, 128).unwrap().to_luma8();
+        assert_eq!(processed.get_pixel(15, 5).0[0], 0);
+        assert_eq!(processed.get_pixel(30, 5).0[0], 255);
+    }
+
+    #[test]
+    fn test_cap_dimension() {
+        let wide = DynamicImage::new_luma8(4000, 1000);
+        let capped = ImagePreprocessor::cap_dimension(wide.clone(), 1000);
+        assert_eq!((capped.width(), capped.height()), (1000, 250));
+
+        let tall = DynamicImage::new_luma8(300, 900);
+        let capped = ImagePreprocessor::cap_dimension(tall, 600);
+        assert_eq!((capped.width(), capped.height()), (200, 600));
+
+        let small = DynamicImage::new_luma8(160, 50);
+        assert_eq!(ImagePreprocessor::cap_dimension(small.clone(), 1000), small);
+        assert_eq!(ImagePreprocessor::cap_dimension(wide, 0).width(), 4000);
+
+        // Segment boxes come out in the coordinates of the downscaled image
+        let mut glyph = GrayImage::from_pixel(400, 100, Luma([255]));
+        for x in 100..140 {
+            for y in 20..80 {
+                glyph.put_pixel(x, y, Luma([0]));
+            }
+        }
+        let capped = ImagePreprocessor::cap_dimension(DynamicImage::ImageLuma8(glyph), 200);
+        let boxes = ImagePreprocessor::segment_boxes(&capped, &Segmentation::default()).unwrap();
+        assert_eq!(boxes.len(), 1);
+        assert!((49..=51).contains(&boxes[0].x) && (19..=21).contains(&boxes[0].width), "{:?}", boxes[0]);
+    }
+
+    #[test]
+    fn test_flatten_alpha() {
+        // Transparent black on the left, opaque dark text on the right, half-transparent in between
+        let rgba = image::RgbaImage::from_fn(3, 1, |x, _| match x {
+            0 => image::Rgba([0, 0, 0, 0]),
+            1 => image::Rgba([0, 0, 0, 128]),
+            _ => image::Rgba([20, 20, 20, 255]),
+        });
+
+        let flattened = ImagePreprocessor::flatten_alpha(DynamicImage::ImageRgba8(rgba.clone()), [255, 255, 255]);
+        let gray = flattened.to_luma8();
+        assert_eq!(gray.get_pixel(0, 0).0[0], 255);
+        assert_eq!(gray.get_pixel(1, 0).0[0], 127);
+        assert_eq!(gray.get_pixel(2, 0).0[0], 20);
+        // Converting without flattening turns the transparent background black
+        assert_eq!(DynamicImage::ImageRgba8(rgba.clone()).to_luma8().get_pixel(0, 0).0[0], 0);
+
+        let on_red = ImagePreprocessor::flatten_alpha(DynamicImage::ImageRgba8(rgba), [200, 0, 0]).to_rgb8();
+        assert_eq!(on_red.get_pixel(0, 0).0, [200, 0, 0]);
+
+        let luma_alpha = image::GrayAlphaImage::from_pixel(2, 2, image::LumaA([0, 0]));
+        let flattened = ImagePreprocessor::flatten_alpha(DynamicImage::ImageLumaA8(luma_alpha), [255, 255, 255]);
+        assert_eq!(flattened.as_luma8().unwrap().get_pixel(1, 1).0[0], 255);
+
+        let opaque = DynamicImage::ImageLuma8(GrayImage::from_pixel(2, 2, Luma([0])));
+        assert_eq!(ImagePreprocessor::flatten_alpha(opaque.clone(), [255, 255, 255]), opaque);
+    }
+
+    #[test]
+    fn test_to_eight_bit_scales_alpha_and_flat_images() {
+        let rgba = ImageBuffer::<image::Rgba<u16>, Vec<u16>>::from_fn(2, 1, |x, _| {
+            image::Rgba(if x == 0 { [1_000, 1_000, 1_000, 65_535] } else { [2_000, 1_500, 1_000, 32_896] })
+        });
+        let converted = ImagePreprocessor::to_eight_bit(DynamicImage::ImageRgba16(rgba)).to_rgba8();
+        assert_eq!(converted.get_pixel(0, 0).0, [0, 0, 0, 255]);
+        assert_eq!(converted.get_pixel(1, 0).0, [255, 128, 0, 128]);
+
+        let flat = ImageBuffer::<Luma<u16>, Vec<u16>>::from_pixel(3, 3, Luma([65_535]));
+        let converted = ImagePreprocessor::to_eight_bit(DynamicImage::ImageLuma16(flat));
+        assert_eq!(converted.as_luma8().unwrap().get_pixel(1, 1).0, [255]);
+
+        let eight_bit = DynamicImage::ImageLuma8(GrayImage::from_pixel(2, 2, Luma([7])));
+        assert_eq!(ImagePreprocessor::to_eight_bit(eight_bit.clone()), eight_bit);
+    }
+
+    #[test]
+    fn test_grayscale_png_passes_through() {
+        let gray = GrayImage::from_fn(40, 20, |x, y| Luma([((x * 7 + y * 3) % 256) as u8]));
+        let mut png = Vec::new();
+        DynamicImage::ImageLuma8(gray.clone())
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageOutputFormat::Png)
+            .unwrap();
+        let decoded = image::load_from_memory(&png).unwrap();
+        assert!(decoded.as_luma8().is_some());
+
+        // Default options only convert to grayscale, which is a no-op here
+        let processed = ImagePreprocessor::preprocess(&decoded, &PreprocessOptions::default()).unwrap();
+        assert_eq!(processed.as_luma8(), Some(&gray));
+
+        // Color filters have nothing to select on a single channel
+        let options = PreprocessOptions {
+            color_filter: Some(crate::models::ColorFilterOptions {
+                target: [200, 20, 20],
+                tolerance: None,
+                space: Some(ColorSpace::Hsv),
+            }),
+            ..Default::default()
+        };
+        let filtered = ImagePreprocessor::preprocess(&decoded, &options).unwrap();
+        assert_eq!(filtered.as_luma8(), Some(&gray));
+        assert_eq!(ImagePreprocessor::extract_by_color(&decoded, [0, 0, 0], 10.0).unwrap().as_luma8(), Some(&gray));
+
+        assert!(ImagePreprocessor::full_pipeline(&decoded).is_ok());
+    }
+
+    /// Random grayscale images of random size, including degenerate ones, whose
+    /// pixels cluster around the thresholds the line and noise rules compare against
+    fn random_images(seed: u64) -> impl Iterator<Item = DynamicImage> {
+        use rand::{Rng, SeedableRng};
+
+        const EDGES: [u8; 8] = [0, 127, 128, 129, 200, 201, 254, 255];
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        (0..300).map(move |_| {
+            let (width, height) = (rng.gen_range(1..=48), rng.gen_range(1..=24));
+            let image = GrayImage::from_fn(width, height, |_, _| {
+                if rng.gen_bool(0.5) { Luma([EDGES[rng.gen_range(0..EDGES.len())]]) } else { Luma([rng.gen()]) }
+            });
+            DynamicImage::ImageLuma8(image)
+        })
+    }
+
+    /// Black left half, white right half, with a faint speck in each half
+    fn step_edge() -> DynamicImage {
+        let mut image = GrayImage::from_fn(20, 10, |x, _| Luma([if x < 10 { 0 } else { 255 }]));
+        image.put_pixel(4, 5, Luma([30]));
+        image.put_pixel(15, 5, Luma([225]));
+        DynamicImage::ImageLuma8(image)
+    }
+
+    fn denoise_with(method: DenoiseMethod, strength: Option<f32>) -> PreprocessOptions {
+        PreprocessOptions {
+            denoise: Some(true),
+            denoise_method: Some(method),
+            denoise_strength: strength,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_bilateral_radius_capped() {
+        assert_eq!(ImagePreprocessor::bilateral_radius(1.5), 3);
+        assert_eq!(ImagePreprocessor::bilateral_radius(2.5), 5);
+        assert_eq!(
+            ImagePreprocessor::bilateral_radius(ImagePreprocessor::MAX_DENOISE_STRENGTH),
+            ImagePreprocessor::MAX_BILATERAL_RADIUS
+        );
+
+        // The strongest accepted denoise still only smooths within the capped radius
+        let image = DynamicImage::ImageLuma8(GrayImage::from_fn(40, 1, |x, _| Luma([if x == 0 { 200 } else { 255 }])));
+        let options = denoise_with(DenoiseMethod::Bilateral, Some(ImagePreprocessor::MAX_DENOISE_STRENGTH));
+        let smoothed = ImagePreprocessor::preprocess(&image, &options).unwrap().to_luma8();
+        let reach = ImagePreprocessor::MAX_BILATERAL_RADIUS;
+        assert!(smoothed.get_pixel(reach, 0).0[0] < 255);
+        assert_eq!(smoothed.get_pixel(reach + 1, 0).0[0], 255);
+    }
+
+    #[test]
+    fn test_bilateral_denoise_preserves_edges() {
+        let image = step_edge();
+
+        let gaussian = ImagePreprocessor::preprocess(&image, &denoise_with(DenoiseMethod::Gaussian, Some(1.5))).unwrap().to_luma8();
+        let bilateral = ImagePreprocessor::preprocess(&image, &denoise_with(DenoiseMethod::Bilateral, Some(1.5))).unwrap().to_luma8();
+
+        // Gaussian blur bleeds across the edge, bilateral keeps both sides intact
+        assert!(gaussian.get_pixel(9, 2).0[0] > 20);
+        assert!(gaussian.get_pixel(10, 2).0[0] < 235);
+        assert!(bilateral.get_pixel(9, 2).0[0] < 5);
+        assert!(bilateral.get_pixel(10, 2).0[0] > 250);
+
+        // ...while still smoothing the specks toward their surroundings
+        assert!(bilateral.get_pixel(4, 5).0[0] < 30);
+        assert!(bilateral.get_pixel(15, 5).0[0] > 225);
+    }
+
+    #[test]
+    fn test_median_denoise_method_removes_specks() {
+        let mut image = GrayImage::from_pixel(9, 9, Luma([255]));
+        image.put_pixel(4, 4, Luma([0]));
+        let image = DynamicImage::ImageLuma8(image);
+
+        let result = ImagePreprocessor::preprocess(&image, &denoise_with(DenoiseMethod::Median, None)).unwrap();
+
+        assert!(result.to_luma8().pixels().all(|p| p.0[0] == 255));
+    }
+
+    #[test]
+    fn test_denoise_defaults_to_original_gaussian() {
+        let image = step_edge();
+        let legacy = DynamicImage::ImageLuma8(gaussian_blur_f32(&image.to_luma8(), 1.0));
+        let options = PreprocessOptions { denoise: Some(true), ..Default::default() };
+
+        let (result, applied) = ImagePreprocessor::preprocess_traced(&image, &options).unwrap();
+
+        assert_eq!(result.to_luma8(), legacy.to_luma8());
+        assert_eq!(applied, ["denoise(gaussian_sigma=1)"]);
+
+        let (_, applied) = ImagePreprocessor::preprocess_traced(&image, &denoise_with(DenoiseMethod::Median, Some(2.4))).unwrap();
+        assert_eq!(applied, ["denoise(median_radius=2)"]);
+    }
+
+    #[test]
+    fn test_denoise_strength_bounds() {
+        for strength in [0.0, -1.0, 9.0, f32::NAN] {
+            let err = ImagePreprocessor::validate(&denoise_with(DenoiseMethod::Bilateral, Some(strength))).unwrap_err();
+            assert!(matches!(err, CaptchaError::Validation(errors) if errors[0].field == "denoise_strength"));
+        }
+        assert!(ImagePreprocessor::validate(&denoise_with(DenoiseMethod::Bilateral, Some(8.0))).is_ok());
+    }
+
+    #[test]
+    fn test_remove_lines_matches_scalar() {
+        for image in random_images(7) {
+            let fast = ImagePreprocessor::remove_lines(&image).unwrap();
+            let scalar = scalar::remove_lines(&image);
+            assert_eq!(fast.as_luma8(), scalar.as_luma8(), "{}x{}", image.width(), image.height());
+        }
+
+        let color = create_test_image();
+        assert_eq!(
+            ImagePreprocessor::remove_lines(&color).unwrap().as_luma8(),
+            scalar::remove_lines(&color).as_luma8()
+        );
+    }
+
+    #[test]
+    fn test_enhance_contrast_matches_scalar() {
+        for image in random_images(11) {
+            let fast = ImagePreprocessor::enhance_contrast(&image).unwrap();
+            let scalar = scalar::enhance_contrast(&image);
+            assert_eq!(fast.as_luma8(), scalar.as_luma8(), "{}x{}", image.width(), image.height());
+        }
+    }
+}
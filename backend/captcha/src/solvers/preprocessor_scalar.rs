@@ -0,0 +1,79 @@
+//! Scalar Preprocessing Reference
+//!
+//! Per-pixel versions of the preprocessing loops that `ImagePreprocessor` runs
+//! vectorized. Not part of the service: the equivalence tests check the fast paths
+//! against them pixel for pixel, and `benches/preprocess.rs` measures the speedup.
+
+use image::{DynamicImage, GrayImage, Luma};
+
+/// Per-pixel [`ImagePreprocessor::remove_lines`](super::preprocessor::ImagePreprocessor::remove_lines)
+pub fn remove_lines(image: &DynamicImage) -> DynamicImage {
+    let gray = image.to_luma8();
+    let (width, height) = gray.dimensions();
+    let mut output = gray.clone();
+
+    for y in 1..height.saturating_sub(1) {
+        for x in 1..width.saturating_sub(1) {
+            let current = gray.get_pixel(x, y).0[0];
+
+            // Check if this is a thin line (dark pixel with light neighbors)
+            if current < 128 {
+                let top = gray.get_pixel(x, y - 1).0[0];
+                let bottom = gray.get_pixel(x, y + 1).0[0];
+                let left = gray.get_pixel(x - 1, y).0[0];
+                let right = gray.get_pixel(x + 1, y).0[0];
+
+                // Horizontal line detection
+                if top > 200 && bottom > 200 && left < 128 && right < 128 {
+                    continue; // Keep character pixels
+                }
+
+                // Vertical line detection
+                if left > 200 && right > 200 && top < 128 && bottom < 128 {
+                    output.put_pixel(x, y, Luma([255])); // Remove line
+                }
+
+                // Isolated noise detection
+                let neighbor_dark_count = [top, bottom, left, right]
+                    .iter()
+                    .filter(|&&p| p < 128)
+                    .count();
+
+                if neighbor_dark_count <= 1 {
+                    output.put_pixel(x, y, Luma([255])); // Remove isolated noise
+                }
+            }
+        }
+    }
+
+    DynamicImage::ImageLuma8(output)
+}
+
+/// Per-pixel [`ImagePreprocessor::enhance_contrast`](super::preprocessor::ImagePreprocessor::enhance_contrast)
+pub fn enhance_contrast(image: &DynamicImage) -> DynamicImage {
+    let gray = image.to_luma8();
+    let (width, height) = gray.dimensions();
+
+    let mut histogram = [0u32; 256];
+    for pixel in gray.pixels() {
+        histogram[pixel.0[0] as usize] += 1;
+    }
+
+    // Calculate cumulative distribution function
+    let total_pixels = (width * height) as f32;
+    let mut cdf = [0f32; 256];
+    let mut sum = 0u32;
+    for (i, &count) in histogram.iter().enumerate() {
+        sum += count;
+        cdf[i] = sum as f32 / total_pixels;
+    }
+
+    // Apply histogram equalization
+    let mut output: GrayImage = gray.clone();
+    for (x, y, pixel) in gray.enumerate_pixels() {
+        let new_value = (cdf[pixel.0[0] as usize] * 255.0) as u8;
+        output.put_pixel(x, y, Luma([new_value]));
+    }
+
+    DynamicImage::ImageLuma8(output)
+}
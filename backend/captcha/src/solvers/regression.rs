@@ -0,0 +1,190 @@
+//! Preprocessing Accuracy Regression Harness
+//!
+//! Runs the labeled mini-dataset in `assets/regression` (generated 5-character
+//! captchas with speckle noise and strike lines, in the training dataset layout)
+//! through each preprocessing variant and a reference recognizer, and fails when
+//! accuracy drops below the floor recorded for that variant. The recognizer matches
+//! segmented characters against a sheet of clean glyphs (`glyphs.png`), so a change
+//! in accuracy comes from preprocessing and segmentation rather than from a model.
+//!
+//! [`evaluate`] takes any [`CaptchaSolver`], so a real OCR or CNN solver can be
+//! measured on the same dataset.
+
+use std::path::PathBuf;
+
+use image::{DynamicImage, GrayImage, Luma};
+
+use super::preprocessor::{ImagePreprocessor, Segmentation};
+use super::{CaptchaSolver, SolveHints, SolveResult};
+use crate::dataset;
+use crate::error::CaptchaResult;
+use crate::models::{ComponentBox, PreprocessOptions};
+
+/// Characters of `glyphs.png`, left to right
+const GLYPHS: &str = "ABCDEFGHJKLMNPRSTUVWXYZ2345678";
+
+/// Size characters are scaled to before matching
+const GLYPH_WIDTH: u32 = 16;
+const GLYPH_HEIGHT: u32 = 24;
+
+fn dataset_root() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("assets/regression")
+}
+
+/// Character crop binarized at mid-gray and scaled to the glyph size
+fn normalize_glyph(image: &GrayImage, b: &ComponentBox) -> GrayImage {
+    let crop = image::imageops::crop_imm(image, b.x, b.y, b.width, b.height).to_image();
+    let scaled = image::imageops::resize(&crop, GLYPH_WIDTH, GLYPH_HEIGHT, image::imageops::FilterType::Triangle);
+    GrayImage::from_fn(GLYPH_WIDTH, GLYPH_HEIGHT, |x, y| {
+        if scaled.get_pixel(x, y)[0] < 128 { Luma([0]) } else { Luma([255]) }
+    })
+}
+
+/// Reference recognizer: nearest clean glyph by the number of differing pixels
+struct TemplateSolver {
+    templates: Vec<(char, GrayImage)>,
+}
+
+impl TemplateSolver {
+    fn load() -> Self {
+        let sheet = image::open(dataset_root().join("glyphs.png")).unwrap().to_luma8();
+        let boxes = ImagePreprocessor::segment_boxes(&DynamicImage::ImageLuma8(sheet.clone()), &Segmentation::default()).unwrap();
+        assert_eq!(boxes.len(), GLYPHS.len(), "glyphs.png must hold one glyph per character");
+
+        let templates = GLYPHS.chars().zip(&boxes).map(|(c, b)| (c, normalize_glyph(&sheet, b))).collect();
+        Self { templates }
+    }
+
+    /// Best matching character and the share of its pixels that differ
+    fn recognize(&self, glyph: &GrayImage) -> (char, f32) {
+        self.templates
+            .iter()
+            .map(|(c, template)| {
+                let differing = glyph.pixels().zip(template.pixels()).filter(|(a, b)| a != b).count();
+                (*c, differing as f32 / (GLYPH_WIDTH * GLYPH_HEIGHT) as f32)
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap_or(('?', 1.0))
+    }
+}
+
+#[async_trait::async_trait]
+impl CaptchaSolver for TemplateSolver {
+    async fn solve(
+        &self,
+        image: &DynamicImage,
+        options: Option<&PreprocessOptions>,
+        _hints: &SolveHints,
+    ) -> CaptchaResult<SolveResult> {
+        let processed = match options {
+            Some(options) => ImagePreprocessor::preprocess(image, options)?,
+            None => image.clone(),
+        };
+        let gray = processed.to_luma8();
+
+        let mut text = String::new();
+        let mut distance = 0.0;
+        let boxes = ImagePreprocessor::segment_boxes(&processed, &Segmentation::default())?;
+        for b in &boxes {
+            let (c, d) = self.recognize(&normalize_glyph(&gray, b));
+            text.push(c);
+            distance += d;
+        }
+
+        Ok(SolveResult {
+            confidence: if boxes.is_empty() { 0.0 } else { 1.0 - distance / boxes.len() as f32 },
+            text,
+            solver_name: "template".to_string(),
+            length_mismatch: None,
+            preprocess_ms: 0,
+            inference_ms: 0,
+            candidates: Vec::new(),
+            preprocess_variant: None,
+            processed_image: None,
+            char_positions: None,
+        })
+    }
+
+    fn name(&self) -> &str {
+        "template"
+    }
+
+    fn is_ready(&self) -> bool {
+        true
+    }
+}
+
+/// Share of captchas solved exactly and share of characters right at their position
+#[derive(Debug)]
+struct Accuracy {
+    text: f64,
+    chars: f64,
+}
+
+/// Solve every image of the regression dataset with `solver` and `options`
+async fn evaluate(solver: &dyn CaptchaSolver, options: Option<&PreprocessOptions>) -> Accuracy {
+    let samples = dataset::labeled_images(&dataset_root()).unwrap();
+    assert!(!samples.is_empty(), "regression dataset is empty");
+
+    let hints = SolveHints::default();
+    let (mut solved, mut chars_right, mut chars_total) = (0, 0, 0);
+    for (path, label) in &samples {
+        let image = image::open(path).unwrap();
+        let text = solver.solve(&image, options, &hints).await.map(|r| r.text).unwrap_or_default();
+
+        solved += usize::from(&text == label);
+        chars_right += text.chars().zip(label.chars()).filter(|(a, b)| a == b).count();
+        chars_total += label.chars().count();
+    }
+
+    Accuracy {
+        text: solved as f64 / samples.len() as f64,
+        chars: chars_right as f64 / chars_total as f64,
+    }
+}
+
+#[tokio::test]
+async fn test_reference_recognizer_reads_clean_glyphs() {
+    let solver = TemplateSolver::load();
+    let sheet = image::open(dataset_root().join("glyphs.png")).unwrap();
+    let result = solver.solve(&sheet, None, &SolveHints::default()).await.unwrap();
+    assert_eq!(result.text, GLYPHS);
+}
+
+/// Pipelines under test with their character accuracy floor, a little below the
+/// accuracy measured when the floor was set. Raise a floor when a change improves
+/// on it; lowering one needs a reason in the commit.
+fn pipelines() -> Vec<(&'static str, PreprocessOptions, f64)> {
+    let variant = |name| ImagePreprocessor::variant(name).unwrap();
+    vec![
+        // Specks split and merge characters without denoising
+        ("raw", variant("raw"), 0.2),
+        ("threshold", variant("threshold"), 0.2),
+        ("denoise_threshold", variant("denoise_threshold"), 0.85),
+        (
+            "median_remove_lines",
+            PreprocessOptions {
+                steps: Some(["grayscale", "median", "threshold", "remove_lines"].map(String::from).to_vec()),
+                ..Default::default()
+            },
+            0.92,
+        ),
+    ]
+}
+
+#[tokio::test]
+async fn test_preprocessing_accuracy_floors() {
+    let solver = TemplateSolver::load();
+
+    let mut regressions = Vec::new();
+    for (name, options, floor) in pipelines() {
+        let accuracy = evaluate(&solver, Some(&options)).await;
+        if accuracy.chars < floor {
+            regressions.push(format!(
+                "{} {:.3} < {} ({:.1}% of captchas fully solved)",
+                name, accuracy.chars, floor, accuracy.text * 100.0
+            ));
+        }
+    }
+    assert!(regressions.is_empty(), "character accuracy below floor: {}", regressions.join(", "));
+}
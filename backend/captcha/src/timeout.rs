@@ -0,0 +1,43 @@
+//! Request Deadlines
+//!
+//! Solver timeouts only bound inference. This middleware bounds the whole
+//! request, including database calls, decoding and preprocessing, so a slow
+//! dependency can't hold a connection open indefinitely. Each route class gets
+//! its own deadline (see [`ServerSettings::request_timeout`]); a request past it
+//! is answered with 504.
+//!
+//! Dropping the handler cancels its pending database and solver awaits. Inference
+//! already running on the solver threads finishes in the background.
+//!
+//! [`ServerSettings::request_timeout`]: crate::config::ServerSettings::request_timeout
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::web;
+
+use crate::AppState;
+use crate::error::CaptchaError;
+
+/// Fail requests that outlive the deadline of their route with [`CaptchaError::RequestTimeout`]
+pub async fn request_timeout(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let deadline = req.app_data::<web::Data<AppState>>()
+        .and_then(|state| state.config.server.request_timeout(req.path()));
+    let Some(deadline) = deadline else {
+        return next.call(req).await;
+    };
+
+    let method = req.method().clone();
+    let path = req.path().to_string();
+
+    match tokio::time::timeout(deadline, next.call(req)).await {
+        Ok(response) => response,
+        Err(_) => {
+            tracing::warn!("{} {} exceeded its {}s deadline", method, path, deadline.as_secs());
+            Err(CaptchaError::RequestTimeout(deadline.as_secs()).into())
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! Request Validation
+//!
+//! Handlers check a request field by field with a [`Validator`], which records
+//! every invalid field with its path (e.g. `config.epochs`, `preprocess.steps[1]`)
+//! instead of stopping at the first one. [`Validator::finish`] turns the findings
+//! into a single [`CaptchaError::Validation`], answered with 422 and the field list.
+
+use serde::Serialize;
+
+use crate::error::{CaptchaError, CaptchaResult};
+
+/// One invalid request field
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct FieldError {
+    /// Dotted path of the field in the request body, with `[i]` for array items
+    pub field: String,
+    pub error: String,
+}
+
+/// Collects invalid fields of a request
+#[derive(Debug, Default)]
+pub struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record `field` as invalid
+    pub fn add(&mut self, field: impl Into<String>, error: impl Into<String>) {
+        self.errors.push(FieldError { field: field.into(), error: error.into() });
+    }
+
+    /// Record `field` as invalid unless `valid` holds
+    pub fn check(&mut self, valid: bool, field: &str, error: impl Into<String>) {
+        if !valid {
+            self.add(field, error);
+        }
+    }
+
+    /// Run `validate` on a nested object, prefixing the paths it records with `prefix`
+    pub fn nested(&mut self, prefix: &str, validate: impl FnOnce(&mut Validator)) {
+        let mut inner = Validator::new();
+        validate(&mut inner);
+        self.errors.extend(inner.errors.into_iter().map(|e| FieldError {
+            field: format!("{}.{}", prefix, e.field),
+            error: e.error,
+        }));
+    }
+
+    /// `Ok` when no field was recorded, otherwise a validation error listing them all
+    pub fn finish(self) -> CaptchaResult<()> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(CaptchaError::Validation(self.errors))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validator_collects_nested_paths() {
+        let mut v = Validator::new();
+        v.check(true, "name", "is required");
+        v.check(false, "name", "is required");
+        v.nested("config", |v| {
+            v.add("epochs", "must be >= 1");
+            v.nested("steps[1]", |v| v.add("radius", "must be positive"));
+        });
+
+        let Err(CaptchaError::Validation(errors)) = v.finish() else {
+            panic!("expected a validation error");
+        };
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, vec!["name", "config.epochs", "config.steps[1].radius"]);
+        assert_eq!(errors[1].error, "must be >= 1");
+
+        assert!(Validator::new().finish().is_ok());
+    }
+}
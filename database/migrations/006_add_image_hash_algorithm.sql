@@ -0,0 +1,27 @@
+-- Migration: 006_add_image_hash_algorithm
+-- Description: Record which algorithm produced captcha_logs.image_hash
+-- Created: 2024
+
+-- Up Migration
+-- Existing rows were hashed with SHA256
+-- Guarded so it can run on a database where the column already exists
+-- (migrations run both as MySQL init scripts and at captcha service startup)
+SET @has_column := (
+    SELECT COUNT(*) FROM information_schema.COLUMNS
+    WHERE TABLE_SCHEMA = DATABASE()
+      AND TABLE_NAME = 'captcha_logs'
+      AND COLUMN_NAME = 'image_hash_algorithm'
+);
+SET @ddl := IF(@has_column = 0,
+    'ALTER TABLE captcha_logs
+        ADD COLUMN image_hash_algorithm VARCHAR(16) NOT NULL DEFAULT ''sha256''
+            COMMENT ''sha256 | blake3''
+            AFTER image_hash',
+    'DO 0'
+);
+PREPARE add_column FROM @ddl;
+EXECUTE add_column;
+DEALLOCATE PREPARE add_column;
+
+-- Down Migration (for rollback)
+-- ALTER TABLE captcha_logs DROP COLUMN image_hash_algorithm;
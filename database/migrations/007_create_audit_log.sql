@@ -0,0 +1,26 @@
+-- Migration: 007_create_audit_log
+-- Description: Create audit_log table recording admin mutations (who, what, when, before/after)
+-- Created: 2024
+
+-- Up Migration
+-- No foreign keys: entries must outlive the users and resources they mention
+CREATE TABLE IF NOT EXISTS audit_log (
+    id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+    user_id BIGINT UNSIGNED NULL COMMENT 'Actor, from X-User-ID or the API key owner',
+    api_key_id BIGINT UNSIGNED NULL COMMENT 'Set when the actor authenticated with an API key',
+    action VARCHAR(50) NOT NULL COMMENT 'e.g. model.update, training.cancel',
+    resource_type VARCHAR(50) NOT NULL,
+    resource_id BIGINT UNSIGNED NULL,
+    before_state JSON NULL,
+    after_state JSON NULL,
+    request_ip VARCHAR(45) NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    
+    INDEX idx_audit_log_user_id (user_id),
+    INDEX idx_audit_log_action (action),
+    INDEX idx_audit_log_resource (resource_type, resource_id),
+    INDEX idx_audit_log_created_at (created_at)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_unicode_ci;
+
+-- Down Migration (for rollback)
+-- DROP TABLE IF EXISTS audit_log;
@@ -0,0 +1,27 @@
+-- Migration: 008_add_model_checksum
+-- Description: Record the SHA256 of each model file, verified when the file is loaded
+-- Created: 2024
+
+-- Up Migration
+-- NULL for models uploaded without a file; their files are not verified
+-- Guarded so it can run on a database where the column already exists
+-- (migrations run both as MySQL init scripts and at captcha service startup)
+SET @has_column := (
+    SELECT COUNT(*) FROM information_schema.COLUMNS
+    WHERE TABLE_SCHEMA = DATABASE()
+      AND TABLE_NAME = 'captcha_models'
+      AND COLUMN_NAME = 'file_sha256'
+);
+SET @ddl := IF(@has_column = 0,
+    'ALTER TABLE captcha_models
+        ADD COLUMN file_sha256 CHAR(64) NULL
+            COMMENT ''Hex SHA256 of the model file''
+            AFTER file_size_bytes',
+    'DO 0'
+);
+PREPARE add_column FROM @ddl;
+EXECUTE add_column;
+DEALLOCATE PREPARE add_column;
+
+-- Down Migration (for rollback)
+-- ALTER TABLE captcha_models DROP COLUMN file_sha256;
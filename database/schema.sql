@@ -5,6 +5,7 @@
 -- Use migrations for actual database setup
 
 -- Drop tables in reverse order of creation (due to foreign keys)
+DROP TABLE IF EXISTS audit_log;
 DROP TABLE IF EXISTS training_jobs;
 DROP TABLE IF EXISTS api_keys;
 DROP TABLE IF EXISTS captcha_logs;
@@ -40,6 +41,7 @@ CREATE TABLE captcha_models (
     version VARCHAR(50) NOT NULL DEFAULT '1.0.0',
     file_path VARCHAR(500) NOT NULL,
     file_size_bytes BIGINT UNSIGNED NOT NULL DEFAULT 0,
+    file_sha256 CHAR(64) NULL COMMENT 'Hex SHA256 of the model file',
     accuracy DECIMAL(5, 4) NULL,
     is_active BOOLEAN NOT NULL DEFAULT FALSE,
     is_default BOOLEAN NOT NULL DEFAULT FALSE,
@@ -67,6 +69,7 @@ CREATE TABLE captcha_logs (
     user_id BIGINT UNSIGNED NULL,
     model_id BIGINT UNSIGNED NULL,
     image_hash VARCHAR(64) NOT NULL,
+    image_hash_algorithm VARCHAR(16) NOT NULL DEFAULT 'sha256',
     image_base64 LONGTEXT NULL,
     predicted_text VARCHAR(255) NULL,
     actual_text VARCHAR(255) NULL,
@@ -156,4 +159,25 @@ CREATE TABLE training_jobs (
     CONSTRAINT fk_training_jobs_output_model_id 
         FOREIGN KEY (output_model_id) REFERENCES captcha_models(id) 
         ON DELETE SET NULL ON UPDATE CASCADE
-) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_unicode_ci;
\ No newline at end of file
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_unicode_ci;
+
+-- ===========================================
+-- Table: audit_log
+-- ===========================================
+CREATE TABLE audit_log (
+    id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+    user_id BIGINT UNSIGNED NULL COMMENT 'Actor, from X-User-ID or the API key owner',
+    api_key_id BIGINT UNSIGNED NULL COMMENT 'Set when the actor authenticated with an API key',
+    action VARCHAR(50) NOT NULL COMMENT 'e.g. model.update, training.cancel',
+    resource_type VARCHAR(50) NOT NULL,
+    resource_id BIGINT UNSIGNED NULL,
+    before_state JSON NULL,
+    after_state JSON NULL,
+    request_ip VARCHAR(45) NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    
+    INDEX idx_audit_log_user_id (user_id),
+    INDEX idx_audit_log_action (action),
+    INDEX idx_audit_log_resource (resource_type, resource_id),
+    INDEX idx_audit_log_created_at (created_at)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_unicode_ci;
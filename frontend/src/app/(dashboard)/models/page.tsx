@@ -33,7 +33,7 @@ export default function ModelsPage() {
     queryFn: () => captchaApi.getModels(),
   });
 
-  const models = modelsData?.data || [];
+  const models = modelsData?.data.items || [];
 
   // Upload form state
   const [uploadForm, setUploadForm] = useState({
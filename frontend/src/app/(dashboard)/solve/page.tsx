@@ -25,7 +25,7 @@ export default function SolvePage() {
     queryFn: () => captchaApi.getModels(),
   });
 
-  const models = modelsData?.data || [];
+  const models = modelsData?.data.items || [];
 
   // Solve mutation
   const solveMutation = useMutation({
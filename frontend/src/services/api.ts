@@ -75,6 +75,14 @@ export interface ApiError {
   details?: string;
 }
 
+// Paginated list response
+export interface Page<T> {
+  items: T[];
+  total: number;
+  limit: number;
+  offset: number;
+}
+
 // Generic API response
 export interface ApiResponse<T> {
   data: T;
@@ -210,8 +218,8 @@ export const captchaApi = {
   solveBatch: (images: SolveRequest[]) =>
     api.post('/v1/captcha/solve/batch', { images }),
 
-  getModels: () =>
-    api.get<CaptchaModel[]>('/v1/captcha/models'),
+  getModels: (params?: { limit?: number; offset?: number }) =>
+    api.get<Page<CaptchaModel>>('/v1/captcha/models', { params }),
 
   uploadModel: (formData: FormData) =>
     api.post('/v1/captcha/models/upload', formData, {
@@ -221,7 +229,7 @@ export const captchaApi = {
   getStats: () =>
     api.get<Stats>('/v1/captcha/stats'),
 
-  getLogs: (params?: { page?: number; limit?: number; model_id?: number }) =>
+  getLogs: (params?: { limit?: number; offset?: number; model_id?: number }) =>
     api.get('/v1/captcha/logs', { params }),
 };
 
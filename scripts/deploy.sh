@@ -144,7 +144,8 @@ run_migrations() {
     sleep 5
     
     # Run migrations if migration tool exists
-    # For now, migrations are run on container startup via init scripts
+    # Migrations run via the MySQL init scripts on first start and are
+    # applied by the captcha service at startup (sqlx::migrate!)
     
     echo -e "${GREEN}✓ Migrations completed${NC}"
     echo ""